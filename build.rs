@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+// Compiles assets/battesty.rc plus a VERSIONINFO block generated from Cargo.toml and links
+// it into the exe: rc.exe on MSVC, windres on GNU. The resources are required, since
+// without the manifest the exe can't resolve TaskDialogIndirect and won't start.
+fn main() {
+    println!("cargo:rerun-if-changed=assets/battesty.rc");
+    println!("cargo:rerun-if-changed=assets/battesty.ico");
+    println!("cargo:rerun-if-changed=assets/battesty.manifest");
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("windows") {
+        return;
+    }
+
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    // Not battesty.rc, or its #include would find itself before the one in assets
+    let rc = out_dir.join("battesty_version.rc");
+    std::fs::write(&rc, resource_script()).expect("Cannot write the resource script");
+
+    let (status, res) = if std::env::var("CARGO_CFG_TARGET_ENV").as_deref() == Ok("msvc") {
+        let res = out_dir.join("battesty.res");
+        let status = Command::new("rc.exe")
+            .args(["/nologo", "/i", "assets", "/fo"])
+            .arg(&res)
+            .arg(&rc)
+            .status();
+        (status, res)
+    } else {
+        let res = out_dir.join("battesty_res.o");
+        let status = Command::new("windres")
+            .args(["--include-dir", "assets", "-O", "coff", "-i"])
+            .arg(&rc)
+            .arg("-o")
+            .arg(&res)
+            .status();
+        (status, res)
+    };
+
+    match status {
+        Ok(status) if status.success() => println!("cargo:rustc-link-arg-bins={}", res.display()),
+        Ok(status) => panic!("Resource compiler failed on {} ({})", rc.display(), status),
+        Err(e) => panic!("No resource compiler found (rc.exe or windres): {}", e),
+    }
+}
+
+fn resource_script() -> String {
+    let version = std::env::var("CARGO_PKG_VERSION").unwrap();
+    let part = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u16>().ok()).unwrap_or(0);
+    let numeric = format!("{},{},{},0", part("CARGO_PKG_VERSION_MAJOR"), part("CARGO_PKG_VERSION_MINOR"), part("CARGO_PKG_VERSION_PATCH"));
+
+    format!(
+        r#"#include "battesty.rc"
+
+1 VERSIONINFO
+FILEVERSION {numeric}
+PRODUCTVERSION {numeric}
+FILEOS 0x40004
+FILETYPE 0x1
+BEGIN
+    BLOCK "StringFileInfo"
+    BEGIN
+        BLOCK "040904B0"
+        BEGIN
+            VALUE "CompanyName", "ArsenijN"
+            VALUE "FileDescription", "Battesty battery monitor"
+            VALUE "FileVersion", "{version}"
+            VALUE "InternalName", "battesty"
+            VALUE "LegalCopyright", "Copyright (c) Arsenii Nochevnyi (ArsenijN), MIT License"
+            VALUE "OriginalFilename", "battesty.exe"
+            VALUE "ProductName", "Battesty"
+            VALUE "ProductVersion", "{version}"
+        END
+    END
+    BLOCK "VarFileInfo"
+    BEGIN
+        VALUE "Translation", 0x409, 1200
+    END
+END
+"#
+    )
+}
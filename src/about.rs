@@ -0,0 +1,145 @@
+use windows::Win32::Foundation::*;
+use windows::Win32::System::DataExchange::{CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData};
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+use windows::Win32::System::Ole::CF_UNICODETEXT;
+use windows::Win32::UI::Controls::*;
+use windows::Win32::UI::Shell::ShellExecuteW;
+use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+use windows::core::{HRESULT, PCWSTR};
+use crate::icon::APP_ICON_ID;
+use crate::versions;
+
+const REPOSITORY_URL: &str = "https://github.com/ArsenijN/battesty";
+
+const ID_COPY: i32 = 100;
+const ID_OPEN_FOLDER: i32 = 101;
+
+pub fn show_about(owner: HWND) {
+    let version = versions::app_version();
+    let content = format!(
+        "A Windows 11 battery monitor with accurate ETA estimation.\n\n<a href=\"{0}\">{0}</a>",
+        REPOSITORY_URL,
+    );
+    let copy = format!("Battesty v{}\n{}", version, REPOSITORY_URL);
+    show(
+        owner,
+        "About Battesty",
+        &format!("Battesty v{}", version),
+        &content,
+        Some("MIT License, see <a href=\"LICENSE.md\">LICENSE.md</a>"),
+        &copy,
+    );
+}
+
+// Statistics and other longer read-only text, with the same Copy / Open data folder buttons
+pub fn show_details(owner: HWND, title: &str, text: &str) {
+    show(owner, &format!("Battesty - {}", title), title, text, None, text);
+}
+
+fn show(owner: HWND, title: &str, instruction: &str, content: &str, footer: Option<&str>, copy: &str) {
+    let wide = |text: &str| text.encode_utf16().chain(std::iter::once(0)).collect::<Vec<u16>>();
+    let title_wide = wide(title);
+    let instruction_wide = wide(instruction);
+    let content_wide = wide(content);
+    let footer_wide = footer.map(wide);
+    let copy_label = wide("Copy");
+    let folder_label = wide("Open data folder");
+    let buttons = [
+        TASKDIALOG_BUTTON { nButtonID: ID_COPY, pszButtonText: PCWSTR(copy_label.as_ptr()) },
+        TASKDIALOG_BUTTON { nButtonID: ID_OPEN_FOLDER, pszButtonText: PCWSTR(folder_label.as_ptr()) },
+    ];
+    let copy = copy.to_string();
+
+    unsafe {
+        let config = TASKDIALOGCONFIG {
+            cbSize: std::mem::size_of::<TASKDIALOGCONFIG>() as u32,
+            hwndParent: owner,
+            hInstance: GetModuleHandleW(PCWSTR::null()).unwrap_or_default().into(),
+            dwFlags: TASKDIALOG_FLAGS(TDF_ENABLE_HYPERLINKS.0 | TDF_ALLOW_DIALOG_CANCELLATION.0 | TDF_SIZE_TO_CONTENT.0),
+            dwCommonButtons: TDCBF_CLOSE_BUTTON,
+            pszWindowTitle: PCWSTR(title_wide.as_ptr()),
+            Anonymous1: TASKDIALOGCONFIG_0 { pszMainIcon: PCWSTR(APP_ICON_ID as *const u16) },
+            pszMainInstruction: PCWSTR(instruction_wide.as_ptr()),
+            pszContent: PCWSTR(content_wide.as_ptr()),
+            cButtons: buttons.len() as u32,
+            pButtons: buttons.as_ptr(),
+            pszFooter: footer_wide.as_ref().map(|f| PCWSTR(f.as_ptr())).unwrap_or(PCWSTR::null()),
+            pfCallback: Some(dialog_callback),
+            lpCallbackData: &copy as *const String as isize,
+            ..std::mem::zeroed()
+        };
+        let _ = TaskDialogIndirect(&config, None, None, None);
+    }
+}
+
+unsafe extern "system" fn dialog_callback(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM, data: isize) -> HRESULT {
+    match TASKDIALOG_NOTIFICATIONS(msg as i32) {
+        TDN_HYPERLINK_CLICKED => {
+            let href = PCWSTR(lparam.0 as *const u16).to_string().unwrap_or_default();
+            // Anything that isn't a URL is a file shipped next to the exe
+            if href.contains("://") {
+                open(&href);
+            } else {
+                open(&data_dir().join(href).to_string_lossy());
+            }
+        }
+        TDN_BUTTON_CLICKED => {
+            let copy = &*(data as *const String);
+            match wparam.0 as i32 {
+                ID_COPY => {
+                    copy_to_clipboard(hwnd, copy);
+                    // S_FALSE keeps the dialog open
+                    return S_FALSE;
+                }
+                ID_OPEN_FOLDER => {
+                    open(&data_dir().to_string_lossy());
+                    return S_FALSE;
+                }
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+    S_OK
+}
+
+// Data files live next to the exe
+fn data_dir() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path
+}
+
+pub fn open(target: &str) {
+    let target_wide: Vec<u16> = target.encode_utf16().chain(std::iter::once(0)).collect();
+    let verb: Vec<u16> = "open\0".encode_utf16().collect();
+    unsafe {
+        ShellExecuteW(None, PCWSTR(verb.as_ptr()), PCWSTR(target_wide.as_ptr()), PCWSTR::null(), PCWSTR::null(), SW_SHOWNORMAL);
+    }
+}
+
+pub fn copy_to_clipboard(hwnd: HWND, text: &str) -> bool {
+    // CRLF so Notepad and friends show the line breaks
+    let text_wide: Vec<u16> = text.replace("\r\n", "\n").replace('\n', "\r\n").encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe {
+        if OpenClipboard(hwnd).is_err() {
+            return false;
+        }
+        let copied = (|| {
+            EmptyClipboard().ok()?;
+            let memory = GlobalAlloc(GMEM_MOVEABLE, text_wide.len() * 2).ok()?;
+            let target = GlobalLock(memory) as *mut u16;
+            if target.is_null() {
+                return None;
+            }
+            std::ptr::copy_nonoverlapping(text_wide.as_ptr(), target, text_wide.len());
+            let _ = GlobalUnlock(memory);
+            // The clipboard owns the memory once this succeeds
+            SetClipboardData(CF_UNICODETEXT.0 as u32, HANDLE(memory.0 as isize)).ok()
+        })()
+        .is_some();
+        let _ = CloseClipboard();
+        copied
+    }
+}
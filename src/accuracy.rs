@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+use chrono::Duration;
+use crate::battery::BatteryMeasurement;
+use crate::estimator;
+use crate::settings::{AppSettings, EtaAlgorithm};
+
+pub struct AccuracyReport {
+    pub battesty_errors: Vec<(EtaAlgorithm, f64)>,
+    pub windows_error: Option<f64>,
+    pub samples: usize,
+}
+
+// Projects each recorded ETA forward by `lookahead` and compares the implied
+// percentage with what was actually measured, in percentage points
+pub fn evaluate(measurements: &VecDeque<BatteryMeasurement>, lookahead: Duration) -> AccuracyReport {
+    let mut battesty: Vec<(EtaAlgorithm, f64, usize)> = Vec::new();
+    let mut windows = (0.0, 0usize);
+    let mut samples = 0;
+
+    for (i, start) in measurements.iter().enumerate() {
+        if start.is_charging || (start.eta_minutes.is_none() && start.os_eta_minutes.is_none()) {
+            continue;
+        }
+
+        let Some(end) = find_end(start, measurements.range(i + 1..), lookahead) else {
+            continue;
+        };
+
+        samples += 1;
+        if let Some(eta) = start.eta_minutes {
+            // Samples recorded before the estimator was selectable used the simple average
+            let algorithm = start.eta_algorithm.unwrap_or(EtaAlgorithm::SimpleAverage);
+            let error = projection_error(start, end, eta);
+            match battesty.iter_mut().find(|(a, _, _)| *a == algorithm) {
+                Some(entry) => {
+                    entry.1 += error;
+                    entry.2 += 1;
+                }
+                None => battesty.push((algorithm, error, 1)),
+            }
+        }
+        if let Some(eta) = start.os_eta_minutes {
+            windows.0 += projection_error(start, end, eta);
+            windows.1 += 1;
+        }
+    }
+
+    let mean = |(sum, count): (f64, usize)| if count > 0 { Some(sum / count as f64) } else { None };
+    AccuracyReport {
+        battesty_errors: battesty.into_iter().map(|(a, sum, count)| (a, sum / count as f64)).collect(),
+        windows_error: mean(windows),
+        samples,
+    }
+}
+
+// First sample at least `lookahead` after `start` within the same discharge run
+fn find_end<'a>(
+    start: &BatteryMeasurement,
+    following: impl Iterator<Item = &'a BatteryMeasurement>,
+    lookahead: Duration,
+) -> Option<&'a BatteryMeasurement> {
+    let target = start.timestamp + lookahead;
+    let end = following
+        .take_while(|m| !m.is_charging)
+        .find(|m| m.timestamp >= target)?;
+
+    // Skip pairs spanning sleep or app downtime
+    if end.timestamp - target > lookahead / 2 {
+        return None;
+    }
+    Some(end)
+}
+
+fn projection_error(start: &BatteryMeasurement, end: &BatteryMeasurement, eta_minutes: i32) -> f64 {
+    let elapsed = (end.timestamp - start.timestamp).num_seconds() as f64 / 60.0;
+    let eta = eta_minutes.max(1) as f64;
+    let projected = (start.percentage as f64 * (1.0 - elapsed / eta)).max(0.0);
+    (projected - end.percentage as f64).abs()
+}
+
+pub struct ReplayResult {
+    pub algorithm: EtaAlgorithm,
+    pub mean_error: Option<f64>,
+    pub mean_confidence: f64,
+    pub samples: usize,
+}
+
+// Re-runs every estimator over a recorded history as if it were live
+pub fn replay(measurements: &[BatteryMeasurement], lookahead: Duration, settings: &AppSettings) -> Vec<ReplayResult> {
+    estimator::all(settings)
+        .iter()
+        .map(|estimator| {
+            let mut total_error = 0.0;
+            let mut total_confidence = 0.0;
+            let mut samples = 0;
+            let mut window_start = 0;
+
+            for (i, start) in measurements.iter().enumerate() {
+                if start.is_charging {
+                    continue;
+                }
+                let Some(end) = find_end(start, measurements[i + 1..].iter(), lookahead) else {
+                    continue;
+                };
+
+                let cutoff = start.timestamp - Duration::hours(estimator::INPUT_WINDOW_HOURS);
+                while measurements[window_start].timestamp < cutoff {
+                    window_start += 1;
+                }
+
+                let estimate = estimator.estimate(&measurements[window_start..=i]);
+                if let Some(eta) = estimate.eta_minutes {
+                    total_error += projection_error(start, end, eta);
+                    total_confidence += estimate.confidence;
+                    samples += 1;
+                }
+            }
+
+            ReplayResult {
+                algorithm: estimator.algorithm(),
+                mean_error: if samples > 0 { Some(total_error / samples as f64) } else { None },
+                mean_confidence: if samples > 0 { total_confidence / samples as f64 } else { 0.0 },
+                samples,
+            }
+        })
+        .collect()
+}
+
+pub fn format_replay(results: &[ReplayResult], measurements: usize) -> String {
+    let mut text = format!("Replayed {} measurements, projecting 30 minutes ahead.\n\n", measurements);
+    for result in results {
+        match result.mean_error {
+            Some(error) => text.push_str(&format!(
+                "{}: ±{:.2}% mean error · {:.0}% avg confidence · {} checks\n",
+                result.algorithm.label(),
+                error,
+                result.mean_confidence * 100.0,
+                result.samples,
+            )),
+            None => text.push_str(&format!("{}: no usable estimates\n", result.algorithm.label())),
+        }
+    }
+    text
+}
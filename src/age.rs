@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use chrono::{Datelike, Local, NaiveDate};
+use crate::wmi::Wmi;
+
+#[derive(Serialize, Deserialize)]
+struct BatteryRecord {
+    first_seen: NaiveDate,
+}
+
+pub struct BatteryAge {
+    pub since: NaiveDate,
+    // False when only the date battesty first saw the battery is known
+    pub manufactured: bool,
+}
+
+impl BatteryAge {
+    pub fn months(&self) -> i32 {
+        let today = Local::now().date_naive();
+        let months = (today.year() - self.since.year()) * 12 + today.month() as i32 - self.since.month() as i32;
+        if today.day() < self.since.day() { months - 1 } else { months }.max(0)
+    }
+
+    pub fn summary(&self, health: Option<f64>) -> String {
+        let months = self.months();
+        let age = match months {
+            0..=11 => format!("{} months", months),
+            _ => format!("{}y {}m", months / 12, months % 12),
+        };
+        let since = if self.manufactured { "manufactured" } else { "first seen" };
+        let mut text = format!("{} ({} {})", age, since, self.since.format("%Y-%m-%d"));
+        if let Some(health) = health {
+            text.push_str(&format!(" · {:.1}% wear over {} months", (100.0 - health).max(0.0), months));
+        }
+        text
+    }
+}
+
+// Prefers the manufacture date the battery reports, falling back to when battesty first saw it
+pub fn battery_age() -> BatteryAge {
+    match read_manufacture_date() {
+        Some(date) => BatteryAge { since: date, manufactured: true },
+        None => BatteryAge { since: first_seen(), manufactured: false },
+    }
+}
+
+// CIM datetime, e.g. "20230415000000.000000+000"; many batteries leave it empty or zeroed
+fn read_manufacture_date() -> Option<NaiveDate> {
+    let wmi = Wmi::connect(r"root\wmi").ok()?;
+    let value = wmi
+        .query("SELECT ManufactureDate FROM BatteryStaticData", &["ManufactureDate"])
+        .ok()?
+        .into_iter()
+        .next()?
+        .into_iter()
+        .next()
+        .flatten()?;
+    NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d")
+        .ok()
+        .filter(|date| date.year() >= 2000 && *date <= Local::now().date_naive())
+}
+
+fn first_seen() -> NaiveDate {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_battery.json");
+
+    let record = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| serde_json::from_str::<BatteryRecord>(&s).ok());
+    match record {
+        Some(record) => record.first_seen,
+        None => {
+            let record = BatteryRecord { first_seen: Local::now().date_naive() };
+            if let Ok(json) = serde_json::to_string_pretty(&record) {
+                let _ = std::fs::write(&path, json);
+            }
+            record.first_seen
+        }
+    }
+}
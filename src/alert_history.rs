@@ -0,0 +1,150 @@
+use std::sync::Once;
+use std::sync::atomic::{AtomicIsize, AtomicUsize, Ordering};
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Gdi::*;
+use windows::Win32::UI::Input::KeyboardAndMouse::SetFocus;
+use windows::Win32::UI::WindowsAndMessaging::*;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::core::PCWSTR;
+
+use crate::MONITOR;
+use crate::icon::app_icon;
+
+static HISTORY_HWND: AtomicIsize = AtomicIsize::new(0);
+static LIST_HWND: AtomicIsize = AtomicIsize::new(0);
+// Alert count at the last fill, so the timer only refills on change
+static SHOWN_ALERTS: AtomicUsize = AtomicUsize::new(usize::MAX);
+static REGISTER: Once = Once::new();
+
+const ID_LIST: i32 = 100;
+
+pub fn show_alert_history(owner: HWND) {
+    unsafe {
+        let existing = HWND(HISTORY_HWND.load(Ordering::Relaxed));
+        if existing.0 != 0 && IsWindow(existing).as_bool() {
+            ShowWindow(existing, SW_SHOWNORMAL);
+            SetForegroundWindow(existing);
+            return;
+        }
+
+        let instance: HINSTANCE = GetModuleHandleW(PCWSTR::null()).unwrap().into();
+        let class_name = "BattestyAlertHistory\0".encode_utf16().collect::<Vec<u16>>();
+
+        REGISTER.call_once(|| {
+            let wc = WNDCLASSW {
+                lpfnWndProc: Some(history_proc),
+                hInstance: instance,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                hbrBackground: HBRUSH((COLOR_BTNFACE.0 + 1) as isize),
+                hCursor: LoadCursorW(None, IDC_ARROW).unwrap_or_default(),
+                hIcon: app_icon(),
+                ..std::mem::zeroed()
+            };
+            RegisterClassW(&wc);
+        });
+
+        let title = "Battesty - Notification History\0".encode_utf16().collect::<Vec<u16>>();
+        let hwnd = CreateWindowExW(
+            WS_EX_CONTROLPARENT,
+            PCWSTR(class_name.as_ptr()),
+            PCWSTR(title.as_ptr()),
+            WS_OVERLAPPEDWINDOW,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            560,
+            360,
+            owner,
+            None,
+            instance,
+            None,
+        );
+        HISTORY_HWND.store(hwnd.0, Ordering::Relaxed);
+
+        let font = GetStockObject(DEFAULT_GUI_FONT);
+        let child = |class: &str, text: &str, style: WINDOW_STYLE, x: i32, y: i32, w: i32, h: i32, id: i32| {
+            let class_wide: Vec<u16> = class.encode_utf16().chain(std::iter::once(0)).collect();
+            let text_wide: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+            let control = CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                PCWSTR(class_wide.as_ptr()),
+                PCWSTR(text_wide.as_ptr()),
+                WS_CHILD | WS_VISIBLE | style,
+                x, y, w, h,
+                hwnd,
+                HMENU(id as isize),
+                instance,
+                None,
+            );
+            SendMessageW(control, WM_SETFONT, WPARAM(font.0 as usize), LPARAM(1));
+            control
+        };
+
+        // The label names the list for screen readers
+        child("STATIC", "Alerts, newest first", WINDOW_STYLE(0), 8, 8, 200, 16, 0);
+        let list = child("LISTBOX", "", WS_BORDER | WS_VSCROLL | WS_TABSTOP | WINDOW_STYLE(LBS_NOINTEGRALHEIGHT as u32), 8, 26, 0, 0, ID_LIST);
+        LIST_HWND.store(list.0, Ordering::Relaxed);
+        layout(hwnd);
+        fill();
+
+        ShowWindow(hwnd, SW_SHOWNORMAL);
+        SetForegroundWindow(hwnd);
+        SetFocus(list);
+    }
+}
+
+// Picks up alerts fired since the window was filled, if it is open
+pub fn refresh() {
+    if HISTORY_HWND.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let count = MONITOR.get()
+        .and_then(|m| m.lock().ok())
+        .map(|mon| mon.alerts.len())
+        .unwrap_or(0);
+    if count != SHOWN_ALERTS.load(Ordering::Relaxed) {
+        fill();
+    }
+}
+
+unsafe extern "system" fn history_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_SIZE => {
+            layout(hwnd);
+            LRESULT(0)
+        }
+        WM_DESTROY => {
+            HISTORY_HWND.store(0, Ordering::Relaxed);
+            SHOWN_ALERTS.store(usize::MAX, Ordering::Relaxed);
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+unsafe fn layout(hwnd: HWND) {
+    let mut client = RECT::default();
+    let _ = GetClientRect(hwnd, &mut client);
+    let _ = MoveWindow(HWND(LIST_HWND.load(Ordering::Relaxed)), 8, 26, client.right - 16, client.bottom - 34, TRUE);
+}
+
+// Newest first
+fn fill() {
+    let Some(mon) = MONITOR.get().and_then(|m| m.lock().ok()) else { return };
+    let list = HWND(LIST_HWND.load(Ordering::Relaxed));
+
+    unsafe {
+        SendMessageW(list, WM_SETREDRAW, WPARAM(0), LPARAM(0));
+        SendMessageW(list, LB_RESETCONTENT, WPARAM(0), LPARAM(0));
+        if mon.alerts.is_empty() {
+            let line: Vec<u16> = "No alerts yet.\0".encode_utf16().collect();
+            SendMessageW(list, LB_ADDSTRING, WPARAM(0), LPARAM(line.as_ptr() as isize));
+        }
+        for alert in mon.alerts.iter().rev() {
+            let line: Vec<u16> = alert.summary().encode_utf16().chain(std::iter::once(0)).collect();
+            SendMessageW(list, LB_ADDSTRING, WPARAM(0), LPARAM(line.as_ptr() as isize));
+        }
+        SendMessageW(list, WM_SETREDRAW, WPARAM(1), LPARAM(0));
+        InvalidateRect(list, None, TRUE);
+    }
+    SHOWN_ALERTS.store(mon.alerts.len(), Ordering::Relaxed);
+}
@@ -0,0 +1,154 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate};
+use crate::battery::BatteryMeasurement;
+use crate::filelock::FileLock;
+
+// Weekly files for samples that aged out of the main history, so the main store stays small
+// while the chart can still reach back past the retention window
+
+// ISO year and week
+type Week = (i32, u32);
+
+// Archives read most recently, so repainting the chart doesn't reload them from disk; the
+// least recently used week is dropped once more than CACHE_WEEKS are held
+static CACHE: Mutex<VecDeque<(Week, Vec<BatteryMeasurement>)>> = Mutex::new(VecDeque::new());
+const CACHE_WEEKS: usize = 6;
+
+fn week_of(time: DateTime<Local>) -> Week {
+    let week = time.iso_week();
+    (week.year(), week.week())
+}
+
+fn archive_path((year, week): Week) -> PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push(format!("battesty_history_{}-W{:02}.json", year, week));
+    path
+}
+
+fn cached(week: Week) -> Option<Vec<BatteryMeasurement>> {
+    let mut cache = CACHE.lock().ok()?;
+    let index = cache.iter().position(|(w, _)| *w == week)?;
+    let entry = cache.remove(index)?;
+    let measurements = entry.1.clone();
+    cache.push_back(entry);
+    Some(measurements)
+}
+
+fn remember(week: Week, measurements: Vec<BatteryMeasurement>) {
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.retain(|(w, _)| *w != week);
+        cache.push_back((week, measurements));
+        while cache.len() > CACHE_WEEKS {
+            cache.pop_front();
+        }
+    }
+}
+
+fn read(week: Week) -> Vec<BatteryMeasurement> {
+    if let Some(cached) = cached(week) {
+        return cached;
+    }
+    let path = archive_path(week);
+    let _lock = FileLock::acquire(&path, false);
+    let measurements: Vec<BatteryMeasurement> = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    remember(week, measurements.clone());
+    measurements
+}
+
+// Moves everything older than `cutoff` off the front of `measurements` into the archive for its
+// week. Samples stay in memory if their archive can't be written.
+pub fn roll(measurements: &mut VecDeque<BatteryMeasurement>, cutoff: DateTime<Local>) -> usize {
+    let mut by_week: BTreeMap<Week, Vec<BatteryMeasurement>> = BTreeMap::new();
+    while measurements.front().is_some_and(|m| m.timestamp < cutoff) {
+        let m = measurements.pop_front().unwrap();
+        by_week.entry(week_of(m.timestamp)).or_default().push(m);
+    }
+
+    let mut archived = 0;
+    let mut failed = Vec::new();
+    for (week, samples) in by_week {
+        let count = samples.len();
+        match append(week, samples) {
+            Ok(()) => archived += count,
+            Err(samples) => failed.extend(samples),
+        }
+    }
+    for m in failed.into_iter().rev() {
+        measurements.push_front(m);
+    }
+    archived
+}
+
+// Merged with what the week's file already holds, which another instance may have written
+fn append(week: Week, samples: Vec<BatteryMeasurement>) -> Result<(), Vec<BatteryMeasurement>> {
+    let path = archive_path(week);
+    let Some(_lock) = FileLock::acquire(&path, true) else { return Err(samples) };
+    let mut all: Vec<BatteryMeasurement> = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    all.extend(samples.iter().cloned());
+    all.sort_by_key(|m| m.timestamp);
+    all.dedup_by_key(|m| m.timestamp);
+
+    let Ok(json) = serde_json::to_string(&all) else { return Err(samples) };
+    let temp = path.with_extension("json.tmp");
+    if std::fs::write(&temp, json).is_err() || std::fs::rename(&temp, &path).is_err() {
+        return Err(samples);
+    }
+    remember(week, all);
+    Ok(())
+}
+
+// Archived samples from `since` up to the start of the main history, oldest first
+pub fn before(since: DateTime<Local>, main_start: Option<DateTime<Local>>) -> Vec<BatteryMeasurement> {
+    let end = main_start.unwrap_or_else(Local::now);
+    if since >= end {
+        return Vec::new();
+    }
+    let mut day = monday(since.date_naive());
+    let mut found = Vec::new();
+    while day <= end.date_naive() {
+        let week = day.iso_week();
+        found.extend(
+            read((week.year(), week.week()))
+                .into_iter()
+                .filter(|m| m.timestamp >= since && m.timestamp < end),
+        );
+        day += Duration::days(7);
+    }
+    found
+}
+
+// The main history with archived samples from `since` in front of it
+pub fn measurements_since(measurements: &VecDeque<BatteryMeasurement>, since: DateTime<Local>) -> Vec<BatteryMeasurement> {
+    let mut all = before(since, measurements.front().map(|m| m.timestamp));
+    all.extend(measurements.iter().filter(|m| m.timestamp >= since).cloned());
+    all
+}
+
+// First archived sample, so exports can cover every archive
+pub fn oldest() -> Option<DateTime<Local>> {
+    let mut folder = std::env::current_exe().ok()?;
+    folder.pop();
+    let oldest_week = std::fs::read_dir(folder)
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|name| {
+            let week = name.strip_prefix("battesty_history_")?.strip_suffix(".json")?;
+            let (year, week) = week.split_once("-W")?;
+            Some((year.parse::<i32>().ok()?, week.parse::<u32>().ok()?))
+        })
+        .min()?;
+    read(oldest_week).first().map(|m| m.timestamp)
+}
+
+fn monday(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+}
@@ -1,36 +1,276 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration as StdDuration, Instant};
 use windows::Win32::System::Power::*;
 use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Local, Duration};
-use crate::settings::AppSettings;
+use chrono::{DateTime, Local, Duration, NaiveDate, NaiveTime};
+use windows::core::GUID;
+use crate::settings::{AppSettings, EtaAlgorithm};
+use crate::accuracy;
+use crate::activity;
+use crate::boot::{self, BootType};
+use crate::clock::{Clock, SystemClock};
+use crate::display::DisplayFilter;
+use crate::age::{self, BatteryAge};
+use crate::estimator::{self, Estimate};
+use crate::footprint;
+use crate::forecast::{self, Verdict};
+use crate::notify::{self, AlertKind, AlertRecord, QueuedAlert};
+use crate::patterns;
+use crate::plan_profiles::{self, PlanProfiles};
+use crate::brightness;
+use crate::benchmark::{self, Benchmark, BenchmarkResult, ChargeTest, ChargeTestResult, Workload};
+use crate::drain_curve::{self, DrainCurve};
+use crate::drain_test::{DrainTest, Phase};
+use crate::engine;
+use crate::charge_curve::{self, ChargeCurve};
+use crate::compaction;
+use crate::cycles::{self, CycleLog};
+use crate::events::{self, Event, EventKind};
+use crate::archive;
+use crate::filelock::FileLock;
+use crate::ioctl;
+use crate::journal;
+use crate::power::{self, Capacity};
+use crate::power_plan;
+use crate::rollup::{self, DailyRollup};
+use crate::score::{self, ScoreInputs};
+use crate::sessions::{self, Session, SessionKind};
+use crate::suspend::{self, SuspendPeriod};
+use crate::usage_model::{self, UsageModel};
+use crate::vendor::{self, ChargeLimit, LimitControl, Vendor};
+use crate::versions;
+use crate::wear;
 
 pub const DEBUG_MODE: bool = true;
 
+// Assumed charging speed until the charge curve has learned a band
+const DEFAULT_MINUTES_PER_PERCENT: f64 = 1.0 / 1.5;
+
+// Samples serialized to estimate the history's size on disk
+const SIZE_ESTIMATE_SAMPLES: usize = 200;
+
+// Longest gap between two samples still counted as continuous discharge
+pub const MAX_SAMPLE_GAP_MINUTES: i64 = 15;
+
+// Weight of the newest reading in the smoothed power draw
+const DRAW_SMOOTHING: f64 = 0.3;
+
+// On AC at the same level for this long with no real charge rate means the firmware is holding it
+const PLATEAU_MINUTES: i64 = 15;
+const PLATEAU_MAX_RATE_MW: i32 = 500;
+
+// Health trend in the details needs at least this much history to mean anything
+const HEALTH_TREND_MIN_DAYS: i64 = 30;
+// Full-charge capacity moving more than this between snapshots isn't normal wear
+const CAPACITY_JUMP_PERCENT: f64 = 3.0;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct BatteryMeasurement {
     pub timestamp: DateTime<Local>,
     pub percentage: u8,
     pub is_charging: bool,
     pub discharge_rate: i32,
+    pub eta_minutes: Option<i32>,
+    pub os_eta_minutes: Option<i32>,
+    pub eta_algorithm: Option<EtaAlgorithm>,
+    // Per-pack levels, only recorded on machines with more than one battery
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packs: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub screen_on: Option<bool>,
+    // No keyboard or mouse input for idle_after_minutes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_idle: Option<bool>,
+    // Battery driver's rate, positive while charging; with the full-charge capacity it turns
+    // into %/h without waiting for the percentage to tick
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_mw: Option<i32>,
+    // Capacity pair at the time, so health can be followed at sample resolution
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_charge_mwh: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub design_mwh: Option<u32>,
+    // Energy left in all packs, for the mWh-based ETA
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remaining_mwh: Option<u32>,
+    // Pack voltage and temperature (tenths of a kelvin), for spotting a swelling or overheating pack
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voltage_mv: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature_dk: Option<u32>,
+    // On AC but not taking charge (charge threshold, firmware hold); is_charging stays true
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub not_charging: bool,
+    // Pack serial(s), so a replaced battery starts a new chapter instead of looking like wear
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    // Active power scheme GUID, so drain can be told apart per plan
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power_plan: Option<String>,
+}
+
+impl BatteryMeasurement {
+    pub fn capacity(&self) -> Option<Capacity> {
+        Some(Capacity { design_mwh: self.design_mwh?, full_charge_mwh: self.full_charge_mwh? })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ChargeState {
+    Full,
+    Charging,
+    Discharging,
+    // On AC, but the battery isn't taking charge (charge limit, firmware hold)
+    NotCharging,
+    // The battery was removed or the tablet detached from its base; running on AC alone
+    NoBattery,
+    Unknown,
 }
 
 pub struct BatteryMonitor {
     pub measurements: VecDeque<BatteryMeasurement>,
     pub settings: AppSettings,
     pub last_icon: Option<windows::Win32::UI::WindowsAndMessaging::HICON>,
+    // What the current tray icon was drawn for, so unchanged icons aren't re-rendered
+    pub icon_key: Option<(u8, u8, ChargeState, Option<String>, bool)>,
+    pub display: DisplayFilter,
+    pub benchmark: Option<Benchmark>,
+    pub benchmark_results: Vec<BenchmarkResult>,
+    pub charge_test: Option<ChargeTest>,
+    pub charge_test_results: Vec<ChargeTestResult>,
+    pub drain_test: Option<DrainTest>,
+    pub drain_test_summary: Option<String>,
+    // Results of a benchmark that ended by itself, until the UI shows them
+    pub benchmark_summary: Option<String>,
+    // Set when the firmware-reported capacity jumps, until the UI shows it
+    pub capacity_alert: Option<String>,
+    pub vendor: Vendor,
+    pub charge_limit: Option<ChargeLimit>,
+    pub sessions: Vec<Session>,
+    pub events: Vec<Event>,
+    pub alerts: Vec<AlertRecord>,
+    // Alert types held back until the given time
+    pub snoozes: BTreeMap<AlertKind, DateTime<Local>>,
+    pub queued_alerts: Vec<QueuedAlert>,
+    pub daily: Vec<DailyRollup>,
+    pub suspend_periods: Vec<SuspendPeriod>,
+    // Display state from power notifications; false while asleep
+    pub screen_on: bool,
+    // Registered for percentage and power source notifications, so polling can be slow
+    pub power_notifications: bool,
+    // Every battery the driver lists is a UPS
+    pub ups_detected: bool,
+    // None until the first reading, so starting on a machine without a battery logs nothing
+    pub battery_present: Option<bool>,
+    pub input_watts: Option<f64>,
+    pub draw_watts: Option<f64>,
+    pub capacity: Option<Capacity>,
+    pub cycle_count: Option<u32>,
+    // Battesty's own count, for firmware that doesn't report one or reports it wrong
+    pub cycles: CycleLog,
+    pub charge_curve: ChargeCurve,
+    pub drain_curve: DrainCurve,
+    pub plan_profiles: PlanProfiles,
+    pub age: BatteryAge,
+    pub clock: Box<dyn Clock>,
+    capacity_read_at: Option<Instant>,
+    // Measurements taken since the history file was last written
+    history_dirty: bool,
+    history_saved_at: Option<Instant>,
+    versions_checked_at: Option<Instant>,
+    charge_rate_mw: Option<i32>,
+    system_draw_mw: Option<i32>,
+    battery_flag: Option<u8>,
+    learned_departure: Option<NaiveTime>,
+    departure_learned_at: Option<Instant>,
+    // Hour-of-day drain, rebuilt hourly like the departure time
+    usage_model: Option<UsageModel>,
+    usage_model_at: Option<Instant>,
+    last_charge_reminder: Option<NaiveDate>,
+    low_battery_alerted: bool,
+    critical_battery_alerted: bool,
+    // When the machine went to sleep and the last reading before it
+    suspended: Option<(DateTime<Local>, u8, bool)>,
+    // A sample was taken by this process, so a long gap before the next one was spent asleep
+    sampled: bool,
+    // Threshold of the power-plan rule applied during this discharge, and the plan it replaced
+    power_plan_threshold: Option<u8>,
+    plan_before_switch: Option<GUID>,
+    // Same for brightness rules, with the levels from before the first step-down
+    brightness_threshold: Option<u8>,
+    brightness_before: Option<brightness::Levels>,
     debug_percentage: u8,
     debug_charging: bool,
 }
 
 impl BatteryMonitor {
     pub fn new() -> Self {
-        Self {
+        let vendor = vendor::detect_vendor();
+        let mut monitor = Self {
             measurements: Self::load_history(),
             settings: AppSettings::load(),
             last_icon: None,
+            icon_key: None,
+            display: DisplayFilter::default(),
+            benchmark: None,
+            benchmark_results: benchmark::load_results(),
+            charge_test: None,
+            charge_test_results: benchmark::load_charge_results(),
+            drain_test: None,
+            drain_test_summary: None,
+            benchmark_summary: None,
+            capacity_alert: None,
+            vendor,
+            charge_limit: vendor::read_charge_limit(vendor),
+            sessions: sessions::load_sessions(),
+            events: events::load_events(),
+            alerts: notify::load_alerts(),
+            snoozes: notify::load_snoozes(),
+            queued_alerts: Vec::new(),
+            daily: rollup::load_rollups(),
+            suspend_periods: suspend::load_periods(),
+            screen_on: true,
+            power_notifications: false,
+            ups_detected: false,
+            battery_present: None,
+            input_watts: None,
+            draw_watts: None,
+            capacity: None,
+            cycle_count: None,
+            cycles: cycles::load_cycles(),
+            charge_curve: charge_curve::load_curve(),
+            drain_curve: drain_curve::load_curve(),
+            plan_profiles: plan_profiles::load_profiles(),
+            age: age::battery_age(),
+            clock: Box::new(SystemClock),
+            capacity_read_at: None,
+            history_dirty: false,
+            history_saved_at: None,
+            versions_checked_at: None,
+            charge_rate_mw: None,
+            system_draw_mw: None,
+            battery_flag: None,
+            learned_departure: None,
+            departure_learned_at: None,
+            usage_model: None,
+            usage_model_at: None,
+            last_charge_reminder: None,
+            low_battery_alerted: false,
+            critical_battery_alerted: false,
+            suspended: None,
+            sampled: false,
+            power_plan_threshold: None,
+            plan_before_switch: None,
+            brightness_threshold: None,
+            brightness_before: None,
             debug_percentage: 100,
             debug_charging: false,
+        };
+        
+        if let (Some(limit), Some(LimitControl::AsusLimit)) = (monitor.settings.charge_limit, vendor::limit_control(vendor)) {
+            let _ = monitor.apply_charge_limit(LimitControl::AsusLimit, Some(limit));
         }
+        monitor
     }
 
     fn load_history() -> VecDeque<BatteryMeasurement> {
@@ -38,24 +278,160 @@ impl BatteryMonitor {
         path.pop();
         path.push("battesty_history.json");
         
-        std::fs::read_to_string(&path)
+        // Whatever was measured after the last save before a crash or power loss
+        let mut measurements = Self::load_history_from(&path);
+        journal::replay(&mut measurements);
+        measurements
+    }
+
+    pub fn load_history_from(path: &std::path::Path) -> VecDeque<BatteryMeasurement> {
+        // Saves replace the file by rename, so reading without the lock is still safe
+        let _lock = FileLock::acquire(path, false);
+        std::fs::read_to_string(path)
             .ok()
             .and_then(|s| serde_json::from_str(&s).ok())
             .unwrap_or_default()
     }
 
-    pub fn save_history(&self) {
+    // A sample the background engine took; it saves it, so this copy never makes the history dirty
+    pub fn mirror_measurement(&mut self, measurement: BatteryMeasurement) {
+        if self.measurements.back().is_none_or(|m| m.timestamp < measurement.timestamp) {
+            self.measurements.push_back(measurement);
+        }
+    }
+
+    // Feeds a sample the background engine took to the tests running in this tray, once
+    pub fn track_tests(&mut self, measurement: &BatteryMeasurement) {
+        if self.measurements.back().is_some_and(|m| m.timestamp >= measurement.timestamp) {
+            return;
+        }
+        let (percentage, is_charging) = (measurement.percentage, measurement.is_charging);
+        self.charge_rate_mw = measurement.rate_mw.filter(|r| *r > 0);
+        self.check_benchmark(percentage, is_charging);
+        self.track_drain_test(percentage, is_charging, None);
+        self.track_charge_test(percentage, is_charging);
+    }
+
+    // Picks up what the background engine saved, along with its rollups
+    pub fn reload_history(&mut self) {
+        merge_measurements(&mut self.measurements, Self::load_history());
+        self.daily = rollup::load_rollups();
+        self.cycles = cycles::load_cycles();
+        self.charge_curve = charge_curve::load_curve();
+        self.drain_curve = drain_curve::load_curve();
+        self.plan_profiles = plan_profiles::load_profiles();
+    }
+
+    // The journal keeps new samples safe in between, so the full store is rewritten rarely,
+    // and less often still on battery
+    pub fn save_history_if_due(&mut self) {
+        let on_battery = self.measurements.back().is_some_and(|m| !m.is_charging);
+        let minutes = if on_battery {
+            self.settings.save_interval_on_battery_minutes
+        } else {
+            self.settings.save_interval_minutes
+        };
+        // Slack for the save timer firing a moment early
+        let due = StdDuration::from_secs(minutes as u64 * 60).saturating_sub(StdDuration::from_secs(30));
+        let now = self.clock.monotonic();
+        if self.history_saved_at.is_none_or(|t| now.duration_since(t) >= due) {
+            self.save_history();
+        }
+    }
+
+    // Skips the write when nothing was measured since the last one
+    pub fn save_history(&mut self) {
+        if !self.history_dirty {
+            return;
+        }
         let mut path = std::env::current_exe().unwrap();
         path.pop();
         path.push("battesty_history.json");
         
+        // Still dirty if another instance holds the lock; the next save tries again
+        let Some(_lock) = FileLock::acquire(&path, true) else { return };
+        
+        // Another instance sharing the directory may have saved or journaled samples this one
+        // never saw; they are merged in rather than overwritten
+        let on_disk = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<Vec<BatteryMeasurement>>(&s).ok())
+            .unwrap_or_default();
+        merge_measurements(&mut self.measurements, on_disk.into_iter().chain(journal::read()));
+        self.cleanup_old_measurements();
+        self.enforce_history_size(&path);
+        
+        // Written aside and renamed over, so a crash mid-save can't leave a truncated store
+        let temp = path.with_extension("json.tmp");
         if let Ok(json) = serde_json::to_string(&self.measurements) {
-            let _ = std::fs::write(&path, json);
+            if std::fs::write(&temp, json).is_ok() && std::fs::rename(&temp, &path).is_ok() {
+                journal::clear();
+                self.history_dirty = false;
+                self.history_saved_at = Some(self.clock.monotonic());
+            }
+        }
+    }
+
+    // Downsamples old history instead of letting the file grow past the configured size,
+    // or past half of it when the disk is nearly full
+    fn enforce_history_size(&mut self, path: &std::path::Path) {
+        // 0 turns the cap off
+        if self.settings.history_max_mb == 0 {
+            return;
+        }
+        let limit = self.settings.history_max_mb as usize * 1_048_576;
+        let low_disk = compaction::free_disk_bytes(path).is_some_and(|free| free < compaction::LOW_DISK_BYTES);
+        let (target, reason) = if low_disk {
+            (limit / 2, "low disk space")
+        } else {
+            (limit, "size limit")
+        };
+        
+        // Estimated from the newest samples instead of serializing the whole history per check;
+        // they carry every field the older ones do, so this errs on the large side
+        let newest: Vec<&BatteryMeasurement> = self.measurements.iter().rev().take(SIZE_ESTIMATE_SAMPLES).collect();
+        let Ok(json) = serde_json::to_string(&newest) else { return };
+        let per_sample = json.len() / newest.len().max(1);
+        let size = |m: &VecDeque<BatteryMeasurement>| m.len() * per_sample;
+        if let Some(result) = compaction::compact(&mut self.measurements, target, size, self.clock.now()) {
+            let bytes = size(&self.measurements);
+            self.log_event(EventKind::Note, &result.summary(bytes, reason));
+        }
+    }
+
+    pub fn update_rollups(&mut self) {
+        if rollup::update(&mut self.daily, &self.measurements, self.capacity, self.clock.now().date_naive()) {
+            rollup::save_rollups(&self.daily);
+        }
+    }
+
+    // Updates land on reboot, so a check every few hours catches them close to the boundary
+    pub fn check_versions(&mut self) {
+        let now = self.clock.monotonic();
+        if self.versions_checked_at.is_some_and(|t| now.duration_since(t) < StdDuration::from_secs(6 * 3600)) {
+            return;
+        }
+        self.versions_checked_at = Some(now);
+        
+        let current = versions::read_versions();
+        match versions::load_versions() {
+            Some(previous) if previous == current => return,
+            Some(previous) => {
+                for change in current.changes_from(&previous) {
+                    self.log_event(EventKind::SystemUpdate, &change);
+                }
+            }
+            None => {}
         }
+        versions::save_versions(&current);
     }
 
     fn cleanup_old_measurements(&mut self) {
-        let cutoff = Local::now() - Duration::hours(self.settings.history_retention_hours as i64);
+        let cutoff = self.clock.now() - Duration::hours(self.settings.history_retention_hours as i64);
+        if self.settings.archive_weekly {
+            archive::roll(&mut self.measurements, cutoff);
+            return;
+        }
         while let Some(m) = self.measurements.front() {
             if m.timestamp < cutoff {
                 self.measurements.pop_front();
@@ -91,20 +467,92 @@ impl BatteryMonitor {
             if GetSystemPowerStatus(&mut status).is_ok() {
                 let percentage = status.BatteryLifePercent;
                 let is_charging = status.ACLineStatus == 1;
+                self.battery_flag = Some(status.BatteryFlag);
+                // 128 is "no system battery"; 255 (unknown) still counts as present
+                let present = status.BatteryFlag == 255 || status.BatteryFlag & 128 == 0;
+                if self.battery_present.is_some_and(|was| was != present) {
+                    self.log_event(EventKind::Note, if present { "Battery back, monitoring resumed" } else { "Battery removed, running on AC" });
+                    self.battery_changed();
+                }
+                self.battery_present = Some(present);
+                if !present {
+                    return None;
+                }
+                // BatteryLifeTime is u32::MAX while Windows has no estimate (e.g. on AC)
+                let os_eta_minutes = if status.BatteryLifeTime != u32::MAX {
+                    Some((status.BatteryLifeTime / 60) as i32)
+                } else {
+                    None
+                };
+                let rate_mw = self.update_power(is_charging);
+                self.update_capacity();
+                // The first pack's voltage; the hottest pack's temperature
+                let batteries = ioctl::read_batteries();
+                self.ups_detected = !batteries.is_empty() && batteries.iter().all(|b| b.short_term);
+                let voltage_mv = batteries.iter().find_map(|b| b.voltage_mv);
+                let temperature_dk = batteries.iter().filter_map(|b| b.temperature_dk).max();
+                let remaining_mwh = batteries.iter().map(|b| b.remaining_mwh).sum::<Option<u32>>().filter(|_| !batteries.is_empty());
+                // Every pack idle, as the driver sees it; short of 100%, or it's simply full
+                let not_charging = is_charging && percentage < 100
+                    && !batteries.is_empty() && batteries.iter().all(|b| b.idle_on_ac());
                 
                 let measurement = BatteryMeasurement {
-                    timestamp: Local::now(),
+                    timestamp: self.clock.now(),
                     percentage,
                     is_charging,
                     discharge_rate: self.estimate_discharge_rate(),
+                    eta_minutes: None,
+                    os_eta_minutes,
+                    eta_algorithm: None,
+                    packs: Some(power::read_pack_levels()).filter(|p| p.len() > 1).unwrap_or_default(),
+                    screen_on: Some(self.screen_on),
+                    user_idle: activity::idle_time().map(|idle| idle.as_secs() >= self.settings.idle_after_minutes as u64 * 60),
+                    rate_mw,
+                    full_charge_mwh: self.capacity.map(|c| c.full_charge_mwh),
+                    design_mwh: self.capacity.map(|c| c.design_mwh),
+                    remaining_mwh,
+                    voltage_mv,
+                    temperature_dk,
+                    not_charging,
+                    serial: ioctl::serials(&batteries),
+                    power_plan: power_plan::active_guid().map(|guid| format!("{:?}", guid)),
                 };
                 
+                // Sleep that came without a suspend notification (the tray was busy, or the system
+                // crashed out of it) is still logged as standby, so the drain isn't lost
+                let max_gap = Duration::minutes(MAX_SAMPLE_GAP_MINUTES).max(Duration::milliseconds(3 * self.update_interval() as i64));
+                let unnoticed_sleep = self.measurements.back()
+                    .filter(|_| self.sampled && self.suspended.is_none())
+                    .filter(|last| measurement.timestamp - last.timestamp > max_gap)
+                    .map(|last| (last.timestamp, last.percentage, last.is_charging));
                 self.measurements.push_back(measurement);
+                self.sampled = true;
+                if unnoticed_sleep.is_some() {
+                    self.suspended = unnoticed_sleep;
+                    self.end_suspend();
+                }
+                self.check_pack_swap();
+                self.track_cycles();
+                self.track_charge_curve();
+                self.track_plan_profiles();
+                self.refresh_usage_model();
                 
                 if self.measurements.len() % 100 == 0 {
                     self.cleanup_old_measurements();
                 }
                 
+                let estimator = estimator::for_algorithm(self.settings.eta_algorithm, &self.settings);
+                let eta_minutes = if is_charging { None } else { estimator.estimate(&self.recent_samples()).eta_minutes };
+                if let Some(last) = self.measurements.back_mut() {
+                    last.eta_minutes = eta_minutes;
+                    last.eta_algorithm = eta_minutes.map(|_| estimator.algorithm());
+                }
+                
+                if let Some(last) = self.measurements.back() {
+                    journal::append(last);
+                }
+                self.history_dirty = true;
+                
                 let eta = self.calculate_eta(percentage, is_charging);
                 return Some((percentage, eta, is_charging));
             }
@@ -112,54 +560,171 @@ impl BatteryMonitor {
         None
     }
 
-    fn estimate_discharge_rate(&self) -> i32 {
-        if self.measurements.len() < 2 {
-            return 0;
-        }
-        
-        let recent: Vec<_> = self.measurements.iter().rev().take(10).collect();
-        if recent.len() < 2 {
-            return 0;
+    // Input power is what goes into the battery plus what the system draws meanwhile;
+    // the draw can't be measured on AC, so the last reading on battery stands in for it
+    // Returns the signed battery rate for the measurement
+    fn update_power(&mut self, is_charging: bool) -> Option<i32> {
+        let reading = power::read_power();
+        let rate_mw = reading.as_ref().and_then(|r| r.charge_rate_mw.or(r.discharge_rate_mw.map(|mw| -mw)));
+        let draw = reading.as_ref().and_then(|r| r.discharge_rate_mw).filter(|_| !is_charging);
+        if let Some(draw) = draw {
+            self.system_draw_mw = Some(draw);
         }
+        let watts = draw.map(|mw| mw as f64 / 1000.0);
+        self.draw_watts = match (self.draw_watts, watts) {
+            (Some(smoothed), Some(watts)) => Some(smoothed + DRAW_SMOOTHING * (watts - smoothed)),
+            (_, watts) => watts,
+        };
         
-        let mut total_rate = 0.0;
-        let mut count = 0;
-        
-        for i in 0..recent.len() - 1 {
-            let time_diff = (recent[i].timestamp - recent[i + 1].timestamp).num_seconds() as f64;
-            if time_diff > 0.0 && !recent[i].is_charging {
-                let percentage_diff = recent[i + 1].percentage as f64 - recent[i].percentage as f64;
-                let rate = (percentage_diff / time_diff) * 3600.0;
-                total_rate += rate;
-                count += 1;
+        self.charge_rate_mw = reading.and_then(|r| r.charge_rate_mw).filter(|_| is_charging);
+        self.input_watts = self.charge_rate_mw
+            .map(|rate| (rate + self.system_draw_mw.unwrap_or(0)) as f64 / 1000.0);
+        rate_mw
+    }
+
+    // A pack came or went: its capacity and cycle count are read again on the next sample
+    pub fn battery_changed(&mut self) {
+        self.capacity_read_at = None;
+    }
+
+    // Capacity only moves over weeks, so it is re-read hourly
+    fn update_capacity(&mut self) {
+        let now = self.clock.monotonic();
+        if self.capacity_read_at.is_none_or(|t| now.duration_since(t) > StdDuration::from_secs(3600)) {
+            let previous = self.capacity.or_else(|| self.daily.last().and_then(|d| d.capacity));
+            self.capacity = power::read_capacity().or(self.capacity);
+            if let (Some(previous), Some(current)) = (previous, self.capacity) {
+                self.check_capacity_jump(previous, current);
             }
+            self.cycle_count = power::read_cycle_count().or(self.cycle_count);
+            self.capacity_read_at = Some(now);
+        }
+    }
+
+    // Recalibration, a firmware glitch or a swapped battery; trends across the jump aren't comparable
+    fn check_capacity_jump(&mut self, previous: Capacity, current: Capacity) {
+        let change = (current.full_charge_mwh as f64 - previous.full_charge_mwh as f64) / previous.full_charge_mwh as f64 * 100.0;
+        if change.abs() < CAPACITY_JUMP_PERCENT {
+            return;
         }
         
-        if count > 0 {
-            (total_rate / count as f64 * 100.0) as i32
-        } else {
-            0
+        let message = format!(
+            "Full-charge capacity changed {:+.1}% ({} → {} mWh); comparisons with earlier history are unreliable",
+            change,
+            previous.full_charge_mwh,
+            current.full_charge_mwh,
+        );
+        self.log_event(EventKind::Anomaly, &message);
+        self.capacity_alert = Some(message);
+    }
+
+    // Samples handed to the estimators, oldest first
+    fn recent_samples(&self) -> Vec<BatteryMeasurement> {
+        let Some(newest) = self.measurements.back() else {
+            return Vec::new();
+        };
+        let cutoff = newest.timestamp - Duration::hours(estimator::INPUT_WINDOW_HOURS);
+        let start = self.measurements.partition_point(|m| m.timestamp < cutoff);
+        self.measurements.range(start..).cloned().collect()
+    }
+
+    pub fn estimate(&self) -> Estimate {
+        let samples = self.recent_samples();
+        let estimate = estimator::for_algorithm(self.settings.eta_algorithm, &self.settings).estimate(&samples);
+        let estimate = match self.plan_profiles.switch_factor(&samples) {
+            Some(factor) => estimate.scaled(factor),
+            None => estimate,
+        };
+        let level = samples.last().filter(|m| !m.is_charging).map(|m| m.percentage);
+        match (level, estimate.eta_minutes) {
+            // Energy already accounts for what a percent holds at each level
+            (Some(level), Some(eta)) if self.settings.nonlinear_drain && self.settings.eta_algorithm != EtaAlgorithm::Energy => match self.drain_curve.minutes_until(level, 0, eta) {
+                Some(minutes) => Estimate { eta_minutes: Some(minutes as i32), ..estimate },
+                None => estimate,
+            },
+            _ => estimate,
         }
     }
 
+    fn refresh_usage_model(&mut self) {
+        let now = self.clock.monotonic();
+        if self.usage_model_at.is_none_or(|t| now.duration_since(t) > StdDuration::from_secs(3600)) {
+            self.usage_model = usage_model::build(&self.measurements, self.clock.now());
+            self.usage_model_at = Some(now);
+        }
+    }
+
+    // What the usual drain for the coming hours leaves, next to the ETA from the current drain
+    pub fn typical_eta(&self) -> Option<i32> {
+        let latest = self.measurements.back().filter(|m| !m.is_charging)?;
+        self.usage_model.as_ref()?.eta_minutes(latest.percentage, self.clock.now())
+    }
+
+    // Best and worst case around the ETA, widened to take the ETA in when the estimator disagrees
+    pub fn eta_range(&self) -> Option<(i32, i32)> {
+        let eta = self.estimate().eta_minutes?;
+        let (best, worst) = estimator::eta_range(&self.recent_samples())?;
+        Some((best.min(eta), worst.max(eta)))
+    }
+
+    // " (2h 40m – 3h 45m)" to follow the ETA, or nothing while there's no spread to go on
+    pub fn eta_range_text(&self) -> String {
+        match self.eta_range() {
+            Some((best, worst)) => format!(" ({} – {})", Self::format_time(best), Self::format_time(worst)),
+            None => String::new(),
+        }
+    }
+
+    // When the level is expected to reach eta_threshold_percentage, while discharging above it;
+    // the ETA is scaled rather than the rate used, so estimators that curve keep their shape
+    pub fn threshold_time(&self) -> Option<(u8, DateTime<Local>)> {
+        let threshold = self.settings.eta_threshold_percentage;
+        let latest = self.measurements.back().filter(|m| threshold > 0 && !m.is_charging && m.percentage > threshold)?;
+        let eta = self.estimate().eta_minutes?;
+        let share = self.drain_curve.share(latest.percentage, threshold)
+            .filter(|_| self.settings.nonlinear_drain)
+            .unwrap_or((latest.percentage - threshold) as f64 / latest.percentage as f64);
+        let minutes = eta as f64 * share;
+        Some((threshold, self.clock.now() + Duration::minutes(minutes as i64)))
+    }
+
+    // "Hits 20% at 16:42", with the weekday once it's past today
+    pub fn threshold_text(&self) -> Option<String> {
+        let (threshold, at) = self.threshold_time()?;
+        let format = if at.date_naive() == self.clock.now().date_naive() { "%H:%M" } else { "%a %H:%M" };
+        Some(format!("Hits {}% at {}", threshold, at.format(format)))
+    }
+
+    fn estimate_discharge_rate(&self) -> i32 {
+        self.estimate().rate
+    }
+
     fn calculate_eta(&self, percentage: u8, is_charging: bool) -> String {
         if is_charging {
             let remaining = 100 - percentage as i32;
             if remaining <= 0 {
                 return "Fully charged".to_string();
             }
+            if let Some(limit) = self.charge_limit_reached(percentage, is_charging) {
+                return format!("Charge limited ({}%)", limit);
+            }
+            if self.driver_not_charging(percentage, is_charging) {
+                return "Plugged in, not charging".to_string();
+            }
             
-            let minutes = (remaining as f64 / 1.5) as i32;
-            return format!("{} until full", Self::format_time(minutes));
+            // A charge limit is where charging ends, so that's what the time runs to
+            let limit = self.charge_limit.as_ref().and_then(|c| c.limit).filter(|l| *l > percentage && *l < 100);
+            let target = limit.unwrap_or(100);
+            let minutes = self.charge_curve.minutes_until(percentage, target, DEFAULT_MINUTES_PER_PERCENT) as i32;
+            return match limit {
+                Some(limit) => format!("{} until {}%", Self::format_time(minutes), limit),
+                None => format!("{} until full", Self::format_time(minutes)),
+            };
         }
         
-        let rate = self.estimate_discharge_rate();
-        if rate <= 0 {
+        let Some(minutes) = self.estimate().eta_minutes else {
             return "Calculating...".to_string();
-        }
-        
-        let hours_remaining = (percentage as f64 / rate.abs() as f64) * 100.0;
-        let minutes = (hours_remaining * 60.0) as i32;
+        };
         
         if minutes < 1 {
             return "< 1 min".to_string();
@@ -168,53 +733,195 @@ impl BatteryMonitor {
         Self::format_time(minutes)
     }
 
-    fn format_time(minutes: i32) -> String {
-        let hours = minutes / 60;
-        let mins = minutes % 60;
-        
-        if hours > 0 {
-            format!("{}h {}m", hours, mins)
+    // Time since the last AC→battery transition in the recorded history
+    pub fn time_on_battery(&self) -> Option<Duration> {
+        self.measurements.back().filter(|m| !m.is_charging)?;
+        let last_charging = self.measurements.iter().rposition(|m| m.is_charging)?;
+        let unplugged = self.measurements.get(last_charging + 1)?.timestamp;
+        Some(self.clock.now() - unplugged)
+    }
+
+    pub fn format_duration(duration: Duration) -> String {
+        Self::format_time(duration.num_minutes() as i32)
+    }
+
+    pub fn charge_state(&self, percentage: u8, is_charging: bool) -> ChargeState {
+        // BatteryFlag 255 is "unknown" and bit 128 "no system battery"; 255% is an unknown level
+        let flag = self.battery_flag.unwrap_or(0);
+        if percentage > 100 || flag == 255 || flag & 128 != 0 {
+            return ChargeState::Unknown;
+        }
+        if !is_charging {
+            ChargeState::Discharging
+        } else if percentage >= 100 {
+            ChargeState::Full
+        } else if self.charge_limit_reached(percentage, is_charging).is_some() || self.driver_not_charging(percentage, is_charging) {
+            ChargeState::NotCharging
         } else {
-            format!("{}m", mins)
+            ChargeState::Charging
         }
     }
 
-    fn calculate_annual_degradation(&self) -> f64 {
-        if self.measurements.len() < 100 {
-            return 0.0;
+    // The latest sample's driver flags; only trusted while the level matches the reading
+    pub fn driver_not_charging(&self, percentage: u8, is_charging: bool) -> bool {
+        is_charging && self.measurements.back().is_some_and(|m| m.not_charging && m.percentage == percentage)
+    }
+
+    // Level the battery is being held at, either by a known vendor limit or an observed plateau
+    pub fn charge_limit_reached(&self, percentage: u8, is_charging: bool) -> Option<u8> {
+        if !is_charging || percentage >= 100 {
+            return None;
+        }
+        if let Some(limit) = self.charge_limit.as_ref().and_then(|c| c.limit) {
+            if percentage >= limit {
+                return Some(limit);
+            }
+        }
+        if self.charge_rate_mw.is_some_and(|rate| rate > PLATEAU_MAX_RATE_MW) {
+            return None;
         }
         
-        let full_charges: Vec<_> = self.measurements
+        let plateau_start = self.measurements
             .iter()
-            .filter(|m| m.percentage == 100)
-            .collect();
+            .rev()
+            .take_while(|m| m.is_charging && m.percentage == percentage)
+            .last()?
+            .timestamp;
+        (self.clock.now() - plateau_start >= Duration::minutes(PLATEAU_MINUTES)).then_some(percentage)
+    }
+
+    pub fn format_time(minutes: i32) -> String {
+        let hours = minutes / 60;
+        let mins = minutes % 60;
         
-        if full_charges.len() < 2 {
-            return 0.0;
+        if hours > 0 {
+            format!("{}h {}m", hours, mins)
+        } else {
+            format!("{}m", mins)
         }
-        
-        2.5
     }
 
     pub fn get_detailed_info(&self, percentage: u8, is_charging: bool) -> String {
         let discharge_rate = self.estimate_discharge_rate();
         let measurements_count = self.measurements.len();
-        let degradation = self.calculate_annual_degradation();
+        
+        let format_eta = |eta: Option<i32>| match eta {
+            Some(minutes) if !is_charging => Self::format_time(minutes),
+            _ => "N/A".to_string(),
+        };
+        let last = self.measurements.back();
+        let battesty_eta = format_eta(last.and_then(|m| m.eta_minutes));
+        let windows_eta = format_eta(last.and_then(|m| m.os_eta_minutes));
+        
+        let accuracy = if self.settings.track_eta_accuracy {
+            let report = accuracy::evaluate(&self.measurements, Duration::minutes(30));
+            let format_error = |error: Option<f64>| match error {
+                Some(e) => format!("±{:.1}%", e),
+                None => "N/A".to_string(),
+            };
+            let battesty_errors: Vec<String> = report.battesty_errors
+                .iter()
+                .map(|(algorithm, error)| format!("{} {}", algorithm.label(), format_error(Some(*error))))
+                .collect();
+            format!(
+                "ETA Error (30 min ahead, {} checks): {} · Windows {}\n",
+                report.samples,
+                if battesty_errors.is_empty() { "Battesty N/A".to_string() } else { battesty_errors.join(" · ") },
+                format_error(report.windows_error),
+            )
+        } else {
+            String::new()
+        };
         
         format!(
             "Battery Status: {}%\n\
              State: {}\n\
              Discharge Rate: ~{:.1}% per hour\n\
+             Battesty ETA: {} ({}, {:.0}% confidence)\n\
+             Windows ETA: {}\n\
+             {}\
+             {}\
+             {}\
+             {}\
+             {}\
+             {}\
+             {}\
+             {}\
+             {}\
+             Battery Health: {}\n\
+             Charge Cycles: {}\n\
+             Battery Age: {}\n\
              Measurements Recorded: {}\n\
-             Estimated Annual Degradation: {:.1}%\n\
+             {}\n\
+             {}\
              {}\
              \n\
              Monitoring since: {}",
             percentage,
-            if is_charging { "Charging" } else { "Discharging" },
+            match self.charge_state(percentage, is_charging) {
+                ChargeState::NotCharging => "Plugged in, not charging",
+                ChargeState::Full => "Fully charged",
+                _ if is_charging => "Charging",
+                _ => "Discharging",
+            },
             discharge_rate.abs() as f64 / 100.0,
+            battesty_eta,
+            self.settings.eta_algorithm.label(),
+            self.estimate().confidence * 100.0,
+            windows_eta,
+            match self.typical_eta().filter(|_| !is_charging) {
+                Some(minutes) => format!("Typical-Usage ETA: {}\n", Self::format_time(minutes)),
+                None => String::new(),
+            },
+            match self.time_on_battery() {
+                Some(duration) if !is_charging => format!("On Battery For: {}\n", Self::format_duration(duration)),
+                _ => String::new(),
+            },
+            match self.target_verdict(percentage, is_charging) {
+                Some(verdict) => format!("{}\n", verdict.summary()),
+                None => String::new(),
+            },
+            match self.threshold_text() {
+                Some(text) => format!("{}\n", text),
+                None => String::new(),
+            },
+            match self.activity_text().filter(|_| !is_charging) {
+                Some(text) => format!("{}\n", text),
+                None => String::new(),
+            },
+            accuracy,
+            match self.plan_profiles.summary() {
+                plans if plans.is_empty() => String::new(),
+                plans => format!("Drain by Power Plan: {}\n", plans.join(" · ")),
+            },
+            match &self.charge_limit {
+                Some(limit) => format!("{}\n", limit.summary()),
+                None => String::new(),
+            },
+            match last.map(|m| (m.voltage_mv, m.temperature_dk)) {
+                Some((Some(voltage), Some(temperature))) => format!("Voltage: {:.2} V · Temperature: {:.1} °C\n", voltage as f64 / 1000.0, ioctl::celsius(temperature)),
+                Some((Some(voltage), None)) => format!("Voltage: {:.2} V\n", voltage as f64 / 1000.0),
+                Some((None, Some(temperature))) => format!("Temperature: {:.1} °C\n", ioctl::celsius(temperature)),
+                _ => String::new(),
+            },
+            match self.capacity {
+                Some(capacity) => format!("{:.0}%{}", capacity.health(), self.health_trend(capacity)),
+                None => "N/A".to_string(),
+            },
+            match self.cycle_count {
+                Some(reported) => format!("{} · {} reported by the battery", self.cycles.summary(), reported),
+                None => self.cycles.summary(),
+            },
+            self.age.summary(self.capacity.map(|c| c.health())),
             measurements_count,
-            degradation,
+            match self.capacity {
+                Some(capacity) => capacity.summary(),
+                None => "Design/Full charge capacity: N/A".to_string(),
+            },
+            match ioctl::hardware(&ioctl::read_batteries()) {
+                lines if lines.is_empty() => String::new(),
+                lines => format!("\nHardware\n{}\n", lines.join("\n")),
+            },
             if DEBUG_MODE { "\n[DEBUG MODE ACTIVE]\n" } else { "" },
             if let Some(first) = self.measurements.front() {
                 first.timestamp.format("%Y-%m-%d %H:%M").to_string()
@@ -224,6 +931,584 @@ impl BatteryMonitor {
         )
     }
 
+    // " (−1.8 points since 2026-03-01)", from the oldest rollup that recorded a capacity
+    fn health_trend(&self, current: Capacity) -> String {
+        let Some((date, oldest)) = self.daily.iter().find_map(|d| Some((d.date, d.capacity?))) else {
+            return String::new();
+        };
+        if date >= self.clock.now().date_naive() - Duration::days(HEALTH_TREND_MIN_DAYS) {
+            return String::new();
+        }
+        format!(" ({:+.1} points since {})", current.health() - oldest.health(), date.format("%Y-%m-%d"))
+    }
+
+    pub fn start_benchmark(&mut self, workload: Workload) -> Result<(), String> {
+        if self.benchmark.is_some() {
+            return Err("A benchmark is already running".to_string());
+        }
+
+        // The last sample is at most one update old; sampling here would journal an extra reading
+        let (percentage, is_charging) = self.measurements.back().map(|m| (m.percentage, m.is_charging)).ok_or("Battery status unavailable")?;
+        if is_charging {
+            return Err("Unplug the charger before starting a rundown benchmark".to_string());
+        }
+
+        let video_path = self.settings.benchmark_video_path.clone();
+        self.benchmark = Some(Benchmark::start(workload, percentage, video_path.as_deref())?);
+        Ok(())
+    }
+
+    pub fn stop_benchmark(&mut self, end_percentage: u8) -> Option<BenchmarkResult> {
+        let result = self.benchmark.take()?.finish(end_percentage);
+        self.benchmark_results.push(result.clone());
+        benchmark::save_results(&self.benchmark_results);
+        Some(result)
+    }
+
+    // Ends a running benchmark once the charger is connected or the stop level is reached
+    pub fn check_benchmark(&mut self, percentage: u8, is_charging: bool) {
+        if self.benchmark.is_some() && (is_charging || percentage <= self.settings.benchmark_stop_percentage) {
+            if let Some(result) = self.stop_benchmark(percentage) {
+                self.benchmark_summary = Some(benchmark::format_results(&[result]));
+            }
+        }
+    }
+
+    pub fn track_charge_test(&mut self, percentage: u8, is_charging: bool) {
+        let Some(test) = self.charge_test.as_mut() else { return };
+        if let Some(result) = test.record(percentage, is_charging, self.charge_rate_mw) {
+            self.charge_test = None;
+            self.charge_test_results.push(result);
+            benchmark::save_charge_results(&self.charge_test_results);
+        }
+    }
+
+    // Keeps one open session per stretch on AC or on battery, closing it on the transition
+    pub fn track_session(&mut self, percentage: u8, is_charging: bool) {
+        let kind = if is_charging { SessionKind::Charge } else { SessionKind::Discharge };
+        let watts = if is_charging { self.input_watts } else { self.draw_watts };
+        let limited = self.charge_limit_reached(percentage, is_charging);
+        
+        if let Some(session) = self.sessions.last_mut().filter(|s| s.is_open()) {
+            if session.kind == kind {
+                session.record(percentage, watts);
+                session.limited_at = session.limited_at.or(limited);
+                return;
+            }
+            session.ended = Some(self.clock.now());
+            self.log_event(if is_charging { EventKind::AcConnected } else { EventKind::AcDisconnected }, "");
+        }
+        
+        let mut session = Session::start(kind, percentage, self.clock.now());
+        session.record(percentage, watts);
+        self.sessions.push(session);
+        sessions::save_sessions(&self.sessions);
+    }
+
+    // Compared with the last sample that had a serial; packs without one can't be told apart
+    fn check_pack_swap(&mut self) {
+        let Some(current) = self.measurements.back().and_then(|m| m.serial.clone()) else { return };
+        let previous = self.measurements.iter().rev().skip(1).find_map(|m| m.serial.clone());
+        if let Some(previous) = previous.filter(|p| *p != current) {
+            let message = format!("Battery pack changed (serial {} → {})", previous, current);
+            self.log_event(EventKind::Note, &message);
+        }
+    }
+
+    // Saved on every counted drop, which is at most once per percentage point
+    fn track_cycles(&mut self) {
+        let len = self.measurements.len();
+        if len < 2 {
+            return;
+        }
+        let (previous, current) = (&self.measurements[len - 2], &self.measurements[len - 1]);
+        let before = self.cycles.partial_percent;
+        let completed = self.cycles.record(previous, current);
+        if completed.is_some() || self.cycles.partial_percent != before {
+            cycles::save_cycles(&mut self.cycles);
+        }
+        if completed.is_some() {
+            let message = format!("Charge cycle {} completed", self.cycles.total);
+            self.log_event(EventKind::Note, &message);
+        }
+    }
+
+    fn track_charge_curve(&mut self) {
+        let len = self.measurements.len();
+        if len >= 2 && self.charge_curve.record(&self.measurements[len - 2], &self.measurements[len - 1]) {
+            charge_curve::save_curve(&self.charge_curve);
+        }
+        if len >= 2 && self.drain_curve.record(&self.measurements[len - 2], &self.measurements[len - 1]) {
+            drain_curve::save_curve(&self.drain_curve);
+        }
+    }
+
+    fn track_plan_profiles(&mut self) {
+        let len = self.measurements.len();
+        if len < 2 || !self.plan_profiles.record(&self.measurements[len - 2], &self.measurements[len - 1]) {
+            return;
+        }
+        // Named once, while the scheme still exists to be asked
+        for (guid, drain) in self.plan_profiles.plans.iter_mut().filter(|(_, d)| d.name.is_empty()) {
+            if let Some(name) = power_plan::resolve(guid).and_then(|g| power_plan::plan_name(&g)) {
+                drain.name = name;
+            }
+        }
+        plan_profiles::save_profiles(&self.plan_profiles);
+    }
+
+    pub fn log_event(&mut self, kind: EventKind, message: &str) {
+        self.events.push(Event {
+            timestamp: self.clock.now(),
+            kind,
+            percentage: self.measurements.back().map(|m| m.percentage),
+            message: message.to_string(),
+        });
+        events::save_events(&self.events);
+    }
+
+    pub fn target_verdict(&self, percentage: u8, is_charging: bool) -> Option<Verdict> {
+        if is_charging {
+            return None;
+        }
+        let time = forecast::parse_target(self.settings.target_time.as_deref()?)?;
+        let now = self.clock.now();
+        forecast::will_it_last(percentage, &self.estimate(), forecast::next_occurrence(time, now), now)
+    }
+
+    // Warns ahead of the usual unplug time if the battery won't reach the target level by then
+    pub fn check_charge_reminder(&mut self, percentage: u8, is_charging: bool) -> Option<String> {
+        if !self.settings.charge_reminder_enabled {
+            return None;
+        }
+        
+        let learned_at = self.clock.monotonic();
+        let stale = self.departure_learned_at.is_none_or(|t| learned_at.duration_since(t) > StdDuration::from_secs(3600));
+        if stale {
+            self.learned_departure = patterns::analyze(&self.measurements).and_then(|p| p.typical_unplug);
+            self.departure_learned_at = Some(learned_at);
+        }
+        let now = self.clock.now();
+        
+        let departure = forecast::next_occurrence(self.learned_departure?, now);
+        let minutes_left = (departure - now).num_minutes();
+        if minutes_left > self.settings.charge_reminder_lead_minutes as i64
+            || self.last_charge_reminder == Some(departure.date_naive())
+        {
+            return None;
+        }
+        
+        let expected = if is_charging {
+            self.charge_curve.level_after(percentage, minutes_left as f64, DEFAULT_MINUTES_PER_PERCENT)
+        } else {
+            percentage as f64 - self.estimate().rate.max(0) as f64 / 100.0 / 60.0 * minutes_left as f64
+        }
+        .clamp(0.0, 100.0);
+        
+        if expected >= self.settings.charge_reminder_target_percentage as f64 {
+            return None;
+        }
+        
+        self.last_charge_reminder = Some(departure.date_naive());
+        Some(format!(
+            "Only {:.0}% by {} at current rate — {}",
+            expected,
+            departure.format("%H:%M"),
+            if is_charging { "use a faster charger if you can." } else { "plug in now." },
+        ))
+    }
+
+    pub fn is_ups(&self) -> bool {
+        self.settings.ups_mode.unwrap_or(self.ups_detected)
+    }
+
+    // Low and critical levels, raised in UPS mode
+    fn alert_levels(&self) -> (u8, u8) {
+        if self.is_ups() {
+            (self.settings.ups_low_battery_percentage, self.settings.ups_critical_battery_percentage)
+        } else {
+            (self.settings.low_battery_percentage, self.settings.critical_battery_percentage)
+        }
+    }
+
+    // Each level fires once per discharge, re-armed by plugging in
+    pub fn check_low_battery(&mut self, percentage: u8, is_charging: bool) -> Option<(AlertKind, String)> {
+        if is_charging {
+            self.low_battery_alerted = false;
+            self.critical_battery_alerted = false;
+            return None;
+        }
+        
+        let remaining = || self.estimate().eta_minutes.map(|m| format!(", about {} left", Self::format_time(m))).unwrap_or_default();
+        let (low, critical) = self.alert_levels();
+        if !self.critical_battery_alerted && percentage <= critical {
+            let text = if self.is_ups() {
+                format!("UPS at {}%{} — save your work and shut down.", percentage, remaining())
+            } else {
+                format!("Battery at {}%{} — plug in now or save your work.", percentage, remaining())
+            };
+            self.critical_battery_alerted = true;
+            self.low_battery_alerted = true;
+            return Some((AlertKind::CriticalBattery, text));
+        }
+        if !self.low_battery_alerted && percentage <= low {
+            let text = if self.is_ups() {
+                format!("Running on UPS, {}% left{} — mains power is out.", percentage, remaining())
+            } else {
+                format!("Battery at {}%{} — plug in soon.", percentage, remaining())
+            };
+            self.low_battery_alerted = true;
+            return Some((AlertKind::LowBattery, text));
+        }
+        None
+    }
+
+    // Like the low battery alerts, each rule fires once per discharge and plugging in re-arms them
+    pub fn check_power_plan(&mut self, percentage: u8, is_charging: bool) {
+        if is_charging {
+            if self.power_plan_threshold.take().is_none() {
+                return;
+            }
+            let previous = self.plan_before_switch.take();
+            let target = match &self.settings.power_plan_on_ac {
+                Some(plan) => power_plan::resolve(plan),
+                None => previous,
+            };
+            if let Some(target) = target {
+                self.switch_power_plan(&target, "plugged in");
+            }
+            return;
+        }
+
+        // The lowest threshold reached wins, so "Power Saver below 30%" can be followed by a stricter plan below 10%
+        let Some(rule) = self.settings.power_plan_rules.iter()
+            .filter(|r| percentage <= r.below_percentage)
+            .min_by_key(|r| r.below_percentage)
+            .cloned()
+        else {
+            return;
+        };
+        if self.power_plan_threshold.is_some_and(|t| t <= rule.below_percentage) {
+            return;
+        }
+        self.power_plan_threshold = Some(rule.below_percentage);
+
+        let Some(target) = power_plan::resolve(&rule.plan) else {
+            self.log_event(EventKind::PowerPlan, &format!("Unknown power plan \"{}\" in the rule for {}%", rule.plan, rule.below_percentage));
+            return;
+        };
+        let current = power_plan::active_plan();
+        if self.plan_before_switch.is_none() {
+            self.plan_before_switch = current.as_ref().map(|p| p.guid);
+        }
+        if current.is_some_and(|p| p.guid == target) {
+            return;
+        }
+        self.switch_power_plan(&target, &format!("below {}%", rule.below_percentage));
+    }
+
+    fn switch_power_plan(&mut self, target: &GUID, reason: &str) {
+        let name = power_plan::plan_name(target).unwrap_or_else(|| format!("{:?}", target));
+        match power_plan::set_active(target) {
+            Ok(()) => self.log_event(EventKind::PowerPlan, &format!("Switched to {} ({})", name, reason)),
+            Err(e) => self.log_event(EventKind::PowerPlan, &e),
+        }
+    }
+
+    // Works like the power-plan rules; displays already dimmer than a rule are left alone
+    pub fn check_brightness(&mut self, percentage: u8, is_charging: bool) {
+        if is_charging {
+            if self.brightness_threshold.take().is_none() {
+                return;
+            }
+            let Some(before) = self.brightness_before.take() else { return };
+            if self.settings.restore_brightness_on_ac {
+                match brightness::restore(&before) {
+                    Ok(()) => self.log_event(EventKind::Note, "Restored display brightness (plugged in)"),
+                    Err(e) => self.log_event(EventKind::Note, &format!("Cannot restore brightness: {}", e)),
+                }
+            }
+            return;
+        }
+
+        let Some(rule) = self.settings.brightness_rules.iter()
+            .filter(|r| percentage <= r.below_percentage)
+            .min_by_key(|r| r.below_percentage)
+            .cloned()
+        else {
+            return;
+        };
+        if self.brightness_threshold.is_some_and(|t| t <= rule.below_percentage) {
+            return;
+        }
+        self.brightness_threshold = Some(rule.below_percentage);
+        if self.brightness_before.is_none() {
+            self.brightness_before = Some(brightness::read());
+        }
+
+        match brightness::dim_to(rule.brightness.min(100)) {
+            Ok(0) => {}
+            Ok(_) => self.log_event(EventKind::Note, &format!("Dimmed displays to {}% (below {}%)", rule.brightness, rule.below_percentage)),
+            Err(e) => self.log_event(EventKind::Note, &format!("Cannot dim displays: {}", e)),
+        }
+    }
+
+    // None removes the limit (charge to 100%)
+    pub fn apply_charge_limit(&mut self, control: LimitControl, limit: Option<u8>) -> Result<(), String> {
+        match control {
+            LimitControl::LenovoConservation => {
+                vendor::set_lenovo_conservation(limit.is_some())?;
+                self.charge_limit = vendor::read_charge_limit(self.vendor);
+            }
+            LimitControl::AsusLimit => {
+                vendor::set_asus_limit(limit.unwrap_or(100))?;
+                self.charge_limit = limit.map(|limit| ChargeLimit {
+                    vendor: Vendor::Asus,
+                    limit: Some(limit),
+                    mode: "set by Battesty".to_string(),
+                });
+            }
+        }
+        
+        self.settings.charge_limit = limit;
+        self.settings.save();
+        Ok(())
+    }
+
+    pub fn update_interval(&self) -> u32 {
+        if DEBUG_MODE {
+            2000
+        } else if self.drain_test.is_some() {
+            self.settings.drain_test_interval_ms
+        } else if self.power_notifications && self.settings.power_notifications && !engine::is_client() {
+            // Still polled now and then, since the rate and ETA drift between percentage ticks
+            self.settings.update_interval_ms.max(self.settings.fallback_update_interval_ms)
+        } else {
+            self.settings.update_interval_ms
+        }
+    }
+
+    pub fn arm_drain_test(&mut self) -> Result<(), String> {
+        // The last sample is at most one update old; sampling here would journal an extra reading
+        let (percentage, is_charging) = self.measurements.back().map(|m| (m.percentage, m.is_charging)).ok_or("Battery status unavailable")?;
+        if is_charging {
+            return Err("Unplug the charger before arming the drain test".to_string());
+        }
+        self.drain_test = Some(DrainTest::arm(percentage, self.clock.now()));
+        Ok(())
+    }
+
+    pub fn end_drain_test(&mut self) -> Option<String> {
+        let test = self.drain_test.take()?;
+        Some(test.summary(self.clock.now()))
+    }
+
+    // Records the drain test phase; the test finishes by itself once the charger is connected
+    pub fn track_drain_test(&mut self, percentage: u8, is_charging: bool, phase: Option<Phase>) {
+        if is_charging {
+            if let Some(summary) = self.end_drain_test() {
+                self.drain_test_summary = Some(summary);
+            }
+            return;
+        }
+        let now = self.clock.now();
+        if let Some(test) = self.drain_test.as_mut() {
+            match phase {
+                Some(phase) => test.set_phase(phase, percentage, now),
+                None => test.record(percentage),
+            }
+        }
+    }
+
+    // Average drain in percent per hour over the last week of battery use
+    pub fn average_drain_per_hour(&self) -> Option<f64> {
+        self.average_drain_where(|_| true)
+    }
+
+    // Same, over the stretches ending in a sample `include` accepts
+    fn average_drain_where(&self, include: impl Fn(&BatteryMeasurement) -> bool) -> Option<f64> {
+        let cutoff = self.clock.now() - Duration::days(7);
+        let mut drained = 0.0;
+        let mut seconds = 0.0;
+        for (current, next) in self.measurements.iter().zip(self.measurements.iter().skip(1)) {
+            let gap = next.timestamp - current.timestamp;
+            if current.timestamp < cutoff || current.is_charging || next.is_charging
+                || gap <= Duration::zero() || gap > Duration::minutes(MAX_SAMPLE_GAP_MINUTES)
+                || !include(next)
+            {
+                continue;
+            }
+            drained += current.percentage as f64 - next.percentage as f64;
+            seconds += gap.num_seconds() as f64;
+        }
+        
+        // Needs at least an hour on battery to say anything
+        (seconds >= 3600.0 && drained > 0.0).then(|| drained / seconds * 3600.0)
+    }
+
+    // "Active: 4h 10m / Idle: 9h 30m": how long the current level lasts if the user keeps
+    // working or leaves the machine be, from last week's drain in either state
+    pub fn activity_text(&self) -> Option<String> {
+        let percentage = self.measurements.back()?.percentage as f64;
+        let active = self.average_drain_where(|m| m.user_idle == Some(false))?;
+        let idle = self.average_drain_where(|m| m.user_idle == Some(true))?;
+        let minutes = |rate: f64| (percentage / rate * 60.0) as i32;
+        Some(format!("Active: {} / Idle: {}", Self::format_time(minutes(active)), Self::format_time(minutes(idle))))
+    }
+
+    pub fn begin_suspend(&mut self) {
+        self.suspended = self.measurements.back().map(|m| (self.clock.now(), m.percentage, m.is_charging));
+    }
+
+    // Called once a fresh sample was taken after resume; both resume notifications may arrive,
+    // but only the first finds the suspend to close
+    pub fn end_suspend(&mut self) {
+        let Some((started, start_percentage, charging_before)) = self.suspended.take() else { return };
+        let Some(latest) = self.measurements.back() else { return };
+        let period = SuspendPeriod {
+            started,
+            ended: latest.timestamp,
+            start_percentage,
+            end_percentage: latest.percentage,
+            on_ac: charging_before || latest.is_charging,
+        };
+        let summary = period.summary();
+        self.suspend_periods.push(period);
+        suspend::save_periods(&self.suspend_periods);
+        self.log_event(EventKind::Resume, &summary);
+    }
+
+    // Drain while asleep over the last month, from recorded suspend periods; history from before
+    // they were recorded falls back to long gaps between samples
+    pub fn standby_drain_per_hour(&self) -> Option<f64> {
+        let cutoff = self.clock.now() - Duration::days(30);
+        let (drained, seconds) = self.suspend_periods
+            .iter()
+            .filter(|p| p.started >= cutoff && !p.on_ac && p.duration() >= Duration::minutes(10))
+            .fold((0.0, 0.0), |(drained, seconds), p| {
+                (drained + p.lost().max(0) as f64, seconds + p.duration().num_seconds() as f64)
+            });
+        if seconds >= 2.0 * 3600.0 {
+            return Some(drained / seconds * 3600.0);
+        }
+        
+        let mut drained = 0.0;
+        let mut seconds = 0.0;
+        for (current, next) in self.measurements.iter().zip(self.measurements.iter().skip(1)) {
+            let gap = next.timestamp - current.timestamp;
+            if current.timestamp < cutoff || current.is_charging || next.is_charging || gap < Duration::hours(1) {
+                continue;
+            }
+            // A machine that was fully off isn't standby; Fast Startup's hibernate still counts
+            if self.cold_boot_between(current.timestamp, next.timestamp) {
+                continue;
+            }
+            drained += (current.percentage as f64 - next.percentage as f64).max(0.0);
+            seconds += gap.num_seconds() as f64;
+        }
+        
+        (seconds >= 2.0 * 3600.0).then(|| drained / seconds * 3600.0)
+    }
+
+    fn cold_boot_between(&self, from: DateTime<Local>, to: DateTime<Local>) -> bool {
+        self.events.iter().any(|e| {
+            e.kind == EventKind::Boot && e.timestamp > from && e.timestamp <= to
+                && e.message.starts_with(BootType::Cold.label())
+        })
+    }
+
+    // Called at launch, before the first sample: if Windows booted since the last one, the gap
+    // is labelled with how, so off-time drain isn't mistaken for missing data or for standby
+    pub fn annotate_boot(&mut self) {
+        let Some(last) = self.measurements.back().cloned() else { return };
+        let Some(boot) = boot::last_boot() else { return };
+        if boot.at <= last.timestamp {
+            // Only battesty restarted
+            return;
+        }
+        let off = boot.at - last.timestamp;
+        let mut status = SYSTEM_POWER_STATUS::default();
+        let now = unsafe { GetSystemPowerStatus(&mut status) }.ok()
+            .filter(|_| status.BatteryLifePercent <= 100)
+            .map(|_| (status.BatteryLifePercent, status.ACLineStatus == 1));
+
+        let mut message = format!("{} after {} off", boot.kind.label(), Self::format_duration(off));
+        match now {
+            Some((percentage, on_ac)) if !on_ac && !last.is_charging => {
+                let lost = last.percentage.saturating_sub(percentage);
+                message.push_str(&format!(", {}% → {}%", last.percentage, percentage));
+                let hours = off.num_minutes() as f64 / 60.0;
+                if boot.kind.kept_state() && hours >= 1.0 {
+                    message.push_str(&format!(" ({:.1}%/h hibernate drain)", lost as f64 / hours));
+                }
+            }
+            _ => message.push_str(" (plugged in, so no drain to attribute)"),
+        }
+        self.log_event(EventKind::Boot, &message);
+    }
+
+    pub fn score_inputs(&self) -> ScoreInputs {
+        ScoreInputs {
+            health: self.capacity.map(|c| c.health()),
+            cycle_count: self.cycle_count,
+            runtime_hours: self.average_drain_per_hour().map(|rate| 100.0 / rate),
+            full_charge_wh: self.capacity.map(|c| c.full_charge_mwh as f64 / 1000.0),
+            standby_drain_per_hour: self.standby_drain_per_hour(),
+        }
+    }
+
+    pub fn full_charge_runtime(&self) -> Option<String> {
+        let rate = self.average_drain_per_hour()?;
+        let minutes = (100.0 / rate * 60.0) as i32;
+        let basis = match self.capacity {
+            Some(capacity) => format!(
+                "{:.1} Wh at ~{:.1} W average",
+                capacity.full_charge_mwh as f64 / 1000.0,
+                capacity.full_charge_mwh as f64 / 1000.0 * rate / 100.0,
+            ),
+            None => format!("average drain {:.1}% per hour", rate),
+        };
+        Some(format!("A full charge currently lasts about {} ({})", Self::format_time(minutes), basis))
+    }
+
+    pub fn get_statistics(&self) -> String {
+        let patterns = match patterns::analyze(&self.measurements) {
+            Some(patterns) => patterns.summary(),
+            None => "Charging Patterns\nNot enough history yet (needs at least 3 days).\n".to_string(),
+        };
+        
+        let score = match score::compute(&self.score_inputs()) {
+            Some(score) => score.summary(),
+            None => "Battery Score\nNot enough data yet.\n".to_string(),
+        };
+        
+        let drain = match rollup::drain_percentiles(&self.daily, self.clock.now().date_naive()) {
+            Some(drain) => drain.summary(),
+            None => "Daily Drain\nNeeds at least 5 days on battery in the last month.\n".to_string(),
+        };
+        
+        let cycling = match rollup::cycling_stats(&self.daily, self.clock.now().date_naive()) {
+            Some(cycling) => cycling.summary(),
+            None => "Plug/Unplug Cycles\nNeeds at least 3 days of history.\n".to_string(),
+        };
+        
+        let own = footprint::measure().map(|f| f.summary()).unwrap_or_default();
+        let hardware = ioctl::summary(&ioctl::read_batteries());
+        
+        format!(
+            "{}\nMeasurements Recorded: {}\n{}\n\n{}\n{}\n{}\n{}\n{}\n\n{}",
+            score,
+            self.measurements.len(),
+            self.full_charge_runtime().unwrap_or_else(|| "Full-charge runtime: not enough time on battery yet".to_string()),
+            drain,
+            patterns,
+            cycling,
+            wear::summary(&self.daily),
+            hardware,
+            own,
+        )
+    }
+
     pub fn destroy_icon(&mut self) {
         if let Some(icon) = self.last_icon.take() {
             unsafe {
@@ -232,4 +1517,157 @@ impl BatteryMonitor {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+// Union ordered by time; a sample both sides have (same timestamp) is kept once
+fn merge_measurements(measurements: &mut VecDeque<BatteryMeasurement>, other: impl IntoIterator<Item = BatteryMeasurement>) {
+    let before = measurements.len();
+    measurements.extend(other);
+    if measurements.len() == before {
+        return;
+    }
+    measurements.make_contiguous().sort_by_key(|m| m.timestamp);
+    let mut merged: Vec<BatteryMeasurement> = Vec::with_capacity(measurements.len());
+    for measurement in measurements.drain(..) {
+        if merged.last().is_none_or(|last| last.timestamp != measurement.timestamp) {
+            merged.push(measurement);
+        }
+    }
+    measurements.extend(merged);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::{self, MockClock};
+
+    // Nothing loaded from disk or read from the machine; time comes from `clock`
+    fn monitor(clock: &MockClock) -> BatteryMonitor {
+        BatteryMonitor {
+            measurements: VecDeque::new(),
+            settings: AppSettings::default(),
+            last_icon: None,
+            icon_key: None,
+            display: DisplayFilter::default(),
+            benchmark: None,
+            benchmark_results: Vec::new(),
+            charge_test: None,
+            charge_test_results: Vec::new(),
+            drain_test: None,
+            drain_test_summary: None,
+            benchmark_summary: None,
+            capacity_alert: None,
+            vendor: Vendor::Other,
+            charge_limit: None,
+            sessions: Vec::new(),
+            events: Vec::new(),
+            alerts: Vec::new(),
+            snoozes: BTreeMap::new(),
+            queued_alerts: Vec::new(),
+            daily: Vec::new(),
+            suspend_periods: Vec::new(),
+            screen_on: true,
+            power_notifications: false,
+            ups_detected: false,
+            battery_present: Some(true),
+            input_watts: None,
+            draw_watts: None,
+            capacity: None,
+            cycle_count: None,
+            cycles: CycleLog::default(),
+            charge_curve: ChargeCurve::default(),
+            drain_curve: DrainCurve::default(),
+            plan_profiles: PlanProfiles::default(),
+            age: BatteryAge { since: trace::start().date_naive(), manufactured: false },
+            clock: Box::new(clock.clone()),
+            capacity_read_at: None,
+            history_dirty: false,
+            history_saved_at: None,
+            versions_checked_at: None,
+            charge_rate_mw: None,
+            system_draw_mw: None,
+            battery_flag: None,
+            learned_departure: None,
+            departure_learned_at: None,
+            usage_model: None,
+            usage_model_at: None,
+            last_charge_reminder: None,
+            low_battery_alerted: false,
+            critical_battery_alerted: false,
+            suspended: None,
+            sampled: false,
+            power_plan_threshold: None,
+            plan_before_switch: None,
+            brightness_threshold: None,
+            brightness_before: None,
+            debug_percentage: 100,
+            debug_charging: false,
+        }
+    }
+
+    // Feeds the trace through session tracking the way the update tick does
+    fn replay(mon: &mut BatteryMonitor, clock: &MockClock, samples: &[BatteryMeasurement]) {
+        for m in samples {
+            clock.set(m.timestamp);
+            mon.measurements.push_back(m.clone());
+            mon.track_session(m.percentage, m.is_charging);
+        }
+    }
+
+    #[test]
+    fn sessions_split_at_plug_and_unplug() {
+        let clock = MockClock::at(trace::start());
+        let mut mon = monitor(&clock);
+        let unplugged = trace::discharge(trace::start(), 90.0, 12.0, 120, 1);
+        let plugged_at = unplugged.last().unwrap().timestamp + Duration::minutes(1);
+        let charging = trace::charge(plugged_at, 66.0, 30.0, 60, 1);
+        let unplugged_again = charging.last().unwrap().timestamp + Duration::minutes(1);
+        let second = trace::discharge(unplugged_again, 96.0, 12.0, 30, 1);
+        replay(&mut mon, &clock, &unplugged);
+        replay(&mut mon, &clock, &charging);
+        replay(&mut mon, &clock, &second);
+
+        let kinds: Vec<SessionKind> = mon.sessions.iter().map(|s| s.kind).collect();
+        assert!(kinds == [SessionKind::Discharge, SessionKind::Charge, SessionKind::Discharge]);
+        let (first, charge, last) = (&mon.sessions[0], &mon.sessions[1], &mon.sessions[2]);
+        assert_eq!((first.start_percentage, first.end_percentage), (90, 66));
+        assert_eq!(first.ended, Some(plugged_at));
+        assert_eq!((charge.started, charge.start_percentage, charge.end_percentage), (plugged_at, 66, 96));
+        assert_eq!(charge.ended, Some(unplugged_again));
+        assert!(last.is_open());
+        assert_eq!(last.duration(clock.now()), Duration::minutes(30));
+
+        let transitions: Vec<EventKind> = mon.events.iter().map(|e| e.kind).collect();
+        assert!(transitions == [EventKind::AcConnected, EventKind::AcDisconnected]);
+    }
+
+    #[test]
+    fn retention_drops_samples_past_the_window() {
+        let clock = MockClock::at(trace::start());
+        let mut mon = monitor(&clock);
+        mon.settings.history_retention_hours = 24;
+        mon.measurements.extend(trace::discharge(trace::start(), 100.0, 1.0, 48 * 60, 10));
+        clock.set(mon.measurements.back().unwrap().timestamp);
+
+        mon.cleanup_old_measurements();
+        let cutoff = clock.now() - Duration::hours(24);
+        assert!(mon.measurements.front().unwrap().timestamp >= cutoff);
+        assert_eq!(mon.measurements.len(), 24 * 6 + 1);
+
+        // Nothing more goes until the clock moves on
+        mon.cleanup_old_measurements();
+        assert_eq!(mon.measurements.len(), 24 * 6 + 1);
+        clock.set(clock.now() + Duration::hours(1));
+        mon.cleanup_old_measurements();
+        assert_eq!(mon.measurements.len(), 23 * 6 + 1);
+    }
+
+    #[test]
+    fn merge_keeps_one_copy_per_timestamp() {
+        let samples = trace::discharge(trace::start(), 80.0, 10.0, 60, 5);
+        let mut measurements: VecDeque<BatteryMeasurement> = samples[..8].iter().cloned().collect();
+        merge_measurements(&mut measurements, samples[4..].iter().cloned());
+        assert_eq!(measurements.len(), samples.len());
+        assert!(measurements.iter().zip(measurements.iter().skip(1)).all(|(a, b)| a.timestamp < b.timestamp));
+    }
+}
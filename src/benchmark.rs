@@ -0,0 +1,344 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration as StdDuration, Instant};
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Local};
+use windows::Win32::Foundation::HWND;
+use windows::Win32::Media::Multimedia::mciSendStringW;
+use windows::core::PCWSTR;
+
+const VIDEO_ALIAS: &str = "battesty_bench";
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Workload {
+    Idle,
+    CpuSpin(u8),
+    VideoLoop,
+}
+
+impl Workload {
+    pub fn label(&self) -> String {
+        match self {
+            Workload::Idle => "Idle".to_string(),
+            Workload::CpuSpin(percent) => format!("CPU spin {}%", percent),
+            Workload::VideoLoop => "Video loop".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub workload: Workload,
+    pub started: DateTime<Local>,
+    pub ended: DateTime<Local>,
+    pub start_percentage: u8,
+    pub end_percentage: u8,
+}
+
+impl BenchmarkResult {
+    pub fn drain_per_hour(&self) -> f64 {
+        let hours = (self.ended - self.started).num_seconds() as f64 / 3600.0;
+        if hours <= 0.0 {
+            return 0.0;
+        }
+        (self.start_percentage as f64 - self.end_percentage as f64) / hours
+    }
+}
+
+pub struct Benchmark {
+    pub workload: Workload,
+    pub started: DateTime<Local>,
+    pub start_percentage: u8,
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Benchmark {
+    pub fn start(workload: Workload, start_percentage: u8, video_path: Option<&str>) -> Result<Self, String> {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let worker = match workload {
+            Workload::Idle => None,
+            Workload::CpuSpin(percent) => {
+                let stop = stop.clone();
+                Some(std::thread::spawn(move || spin(percent, &stop)))
+            }
+            Workload::VideoLoop => {
+                let path = video_path.ok_or("No benchmark video configured (benchmark_video_path)")?;
+                play_video_loop(path)?;
+                None
+            }
+        };
+
+        Ok(Self {
+            workload,
+            started: Local::now(),
+            start_percentage,
+            stop,
+            worker,
+        })
+    }
+
+    pub fn finish(self, end_percentage: u8) -> BenchmarkResult {
+        BenchmarkResult {
+            workload: self.workload,
+            started: self.started,
+            ended: Local::now(),
+            start_percentage: self.start_percentage,
+            end_percentage,
+        }
+    }
+}
+
+impl Drop for Benchmark {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+        if self.workload == Workload::VideoLoop {
+            let _ = mci(&format!("close {}", VIDEO_ALIAS));
+        }
+    }
+}
+
+// Busy-waits for `percent` of every 100ms slice on a single thread
+fn spin(percent: u8, stop: &AtomicBool) {
+    let period = StdDuration::from_millis(100);
+    let busy = period * percent.min(100) as u32 / 100;
+
+    while !stop.load(Ordering::Relaxed) {
+        let slice_start = Instant::now();
+        while slice_start.elapsed() < busy {
+            std::hint::spin_loop();
+        }
+        std::thread::sleep(period - busy);
+    }
+}
+
+fn play_video_loop(path: &str) -> Result<(), String> {
+    mci(&format!("open \"{}\" type mpegvideo alias {}", path, VIDEO_ALIAS))?;
+    if let Err(e) = mci(&format!("play {} repeat", VIDEO_ALIAS)) {
+        let _ = mci(&format!("close {}", VIDEO_ALIAS));
+        return Err(e);
+    }
+    Ok(())
+}
+
+fn mci(command: &str) -> Result<(), String> {
+    let command_wide: Vec<u16> = command.encode_utf16().chain(std::iter::once(0)).collect();
+    let err = unsafe { mciSendStringW(PCWSTR(command_wide.as_ptr()), None, HWND(0)) };
+    if err == 0 {
+        Ok(())
+    } else {
+        Err(format!("MCI error {} for \"{}\"", err, command))
+    }
+}
+
+pub fn load_results() -> Vec<BenchmarkResult> {
+    std::fs::read_to_string(results_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_results(results: &[BenchmarkResult]) {
+    if let Ok(json) = serde_json::to_string_pretty(results) {
+        let _ = std::fs::write(results_path(), json);
+    }
+}
+
+fn results_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_benchmarks.json");
+    path
+}
+
+pub fn format_results(results: &[BenchmarkResult]) -> String {
+    if results.is_empty() {
+        return "No benchmark runs recorded yet.".to_string();
+    }
+
+    let mut text = String::new();
+    for result in results.iter().rev().take(15) {
+        let minutes = (result.ended - result.started).num_minutes();
+        text.push_str(&format!(
+            "{} · {}\n    {}% → {}% in {}h {}m · {:.1}% per hour\n",
+            result.started.format("%Y-%m-%d %H:%M"),
+            result.workload.label(),
+            result.start_percentage,
+            result.end_percentage,
+            minutes / 60,
+            minutes % 60,
+            result.drain_per_hour(),
+        ));
+    }
+    text
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChargeBand {
+    pub from_percentage: u8,
+    pub to_percentage: u8,
+    pub seconds: i64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChargeTestResult {
+    pub started: DateTime<Local>,
+    pub ended: DateTime<Local>,
+    pub start_percentage: u8,
+    pub end_percentage: u8,
+    pub completed: bool,
+    pub bands: Vec<ChargeBand>,
+    pub average_watts: Option<f64>,
+    pub peak_watts: Option<f64>,
+}
+
+// Waits for the charger to be connected, then follows the charge curve up to 100%
+pub struct ChargeTest {
+    started: Option<DateTime<Local>>,
+    start_percentage: u8,
+    band_started: DateTime<Local>,
+    band_percentage: u8,
+    last_percentage: u8,
+    bands: Vec<ChargeBand>,
+    watt_samples: Vec<f64>,
+}
+
+impl ChargeTest {
+    pub fn new() -> Self {
+        Self {
+            started: None,
+            start_percentage: 0,
+            band_started: Local::now(),
+            band_percentage: 0,
+            last_percentage: 0,
+            bands: Vec::new(),
+            watt_samples: Vec::new(),
+        }
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.started.is_none()
+    }
+
+    // Returns the result once the battery is full or the charger was disconnected mid-test
+    pub fn record(&mut self, percentage: u8, is_charging: bool, rate_mw: Option<i32>) -> Option<ChargeTestResult> {
+        let now = Local::now();
+
+        let Some(started) = self.started else {
+            if is_charging {
+                self.started = Some(now);
+                self.start_percentage = percentage;
+                self.band_started = now;
+                self.band_percentage = percentage;
+                self.last_percentage = percentage;
+            }
+            return None;
+        };
+
+        if !is_charging {
+            return Some(self.result(started, false));
+        }
+
+        if let Some(rate) = rate_mw.filter(|r| *r > 0) {
+            self.watt_samples.push(rate as f64 / 1000.0);
+        }
+
+        // Close the band whenever a 10% boundary is crossed
+        if percentage / 10 > self.band_percentage / 10 || percentage >= 100 {
+            self.bands.push(ChargeBand {
+                from_percentage: self.band_percentage,
+                to_percentage: percentage,
+                seconds: (now - self.band_started).num_seconds(),
+            });
+            self.band_started = now;
+            self.band_percentage = percentage;
+        }
+        self.last_percentage = percentage;
+
+        if percentage >= 100 {
+            return Some(self.result(started, true));
+        }
+        None
+    }
+
+    fn result(&self, started: DateTime<Local>, completed: bool) -> ChargeTestResult {
+        let average_watts = if self.watt_samples.is_empty() {
+            None
+        } else {
+            Some(self.watt_samples.iter().sum::<f64>() / self.watt_samples.len() as f64)
+        };
+
+        ChargeTestResult {
+            started,
+            ended: Local::now(),
+            start_percentage: self.start_percentage,
+            end_percentage: self.last_percentage,
+            completed,
+            bands: self.bands.clone(),
+            average_watts,
+            peak_watts: self.watt_samples.iter().cloned().reduce(f64::max),
+        }
+    }
+}
+
+pub fn load_charge_results() -> Vec<ChargeTestResult> {
+    std::fs::read_to_string(charge_results_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_charge_results(results: &[ChargeTestResult]) {
+    if let Ok(json) = serde_json::to_string_pretty(results) {
+        let _ = std::fs::write(charge_results_path(), json);
+    }
+}
+
+fn charge_results_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_charge_tests.json");
+    path
+}
+
+pub fn format_charge_results(results: &[ChargeTestResult]) -> String {
+    if results.is_empty() {
+        return "No charge tests recorded yet.".to_string();
+    }
+
+    let watts = |w: Option<f64>| match w {
+        Some(w) => format!("{:.1} W", w),
+        None => "n/a".to_string(),
+    };
+
+    let mut text = String::new();
+    for result in results.iter().rev().take(5) {
+        let minutes = (result.ended - result.started).num_minutes();
+        text.push_str(&format!(
+            "{} · {}% → {}% in {}h {}m{}\n    Average {} · Peak {}\n",
+            result.started.format("%Y-%m-%d %H:%M"),
+            result.start_percentage,
+            result.end_percentage,
+            minutes / 60,
+            minutes % 60,
+            if result.completed { "" } else { " (unplugged early)" },
+            watts(result.average_watts),
+            watts(result.peak_watts),
+        ));
+        for band in &result.bands {
+            text.push_str(&format!(
+                "    {}–{}%: {}m {}s\n",
+                band.from_percentage,
+                band.to_percentage,
+                band.seconds / 60,
+                band.seconds % 60,
+            ));
+        }
+    }
+    text
+}
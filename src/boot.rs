@@ -0,0 +1,93 @@
+use chrono::{DateTime, Local};
+use windows::Win32::System::EventLog::*;
+use windows::core::w;
+
+// How Windows last started, from the Kernel-Boot event 27 it writes on every boot. With Fast
+// Startup on, "shut down" hibernates the kernel, so the time off is hibernate drain rather than a
+// machine that was fully off.
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BootType {
+    Cold,
+    FastStartup,
+    HibernateResume,
+}
+
+impl BootType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            BootType::Cold => "Cold boot",
+            BootType::FastStartup => "Fast startup",
+            BootType::HibernateResume => "Resume from hibernate",
+        }
+    }
+
+    // Whether the battery kept powering a saved session while the machine looked off
+    pub fn kept_state(&self) -> bool {
+        *self != BootType::Cold
+    }
+}
+
+pub struct Boot {
+    pub kind: BootType,
+    pub at: DateTime<Local>,
+}
+
+pub fn last_boot() -> Option<Boot> {
+    unsafe {
+        let query = EvtQuery(
+            None,
+            w!("System"),
+            w!("*[System[Provider[@Name='Microsoft-Windows-Kernel-Boot'] and EventID=27]]"),
+            EvtQueryChannelPath.0 | EvtQueryReverseDirection.0,
+        ).ok()?;
+        let mut events = [0isize; 1];
+        let mut returned = 0u32;
+        let xml = match EvtNext(query, &mut events, 5000, 0, &mut returned) {
+            Ok(()) if returned == 1 => {
+                let xml = render_xml(EVT_HANDLE(events[0]));
+                let _ = EvtClose(EVT_HANDLE(events[0]));
+                xml
+            }
+            _ => None,
+        };
+        let _ = EvtClose(query);
+        parse(&xml?)
+    }
+}
+
+unsafe fn render_xml(event: EVT_HANDLE) -> Option<String> {
+    let mut used = 0u32;
+    let mut properties = 0u32;
+    let _ = EvtRender(None, event, EvtRenderEventXml.0, 0, None, &mut used, &mut properties);
+    if used == 0 {
+        return None;
+    }
+    let mut buffer = vec![0u16; used as usize / 2 + 1];
+    EvtRender(None, event, EvtRenderEventXml.0, used, Some(buffer.as_mut_ptr() as *mut _), &mut used, &mut properties).ok()?;
+    let end = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Some(String::from_utf16_lossy(&buffer[..end]))
+}
+
+// <TimeCreated SystemTime='...'/> and <Data Name='BootType'>1</Data>, in either quote style
+fn parse(xml: &str) -> Option<Boot> {
+    let attribute = |name: &str| {
+        let start = xml.find(name)? + name.len();
+        let rest = &xml[start..];
+        let quote = rest.chars().next()?;
+        let rest = &rest[1..];
+        rest.find(quote).map(|end| &rest[..end])
+    };
+    let at = DateTime::parse_from_rfc3339(attribute("SystemTime=")?).ok()?.with_timezone(&Local);
+
+    let name = ["Name='BootType'>", "Name=\"BootType\">"].iter().find_map(|tag| xml.find(tag).map(|i| i + tag.len()))?;
+    let value = xml[name..].split('<').next()?.trim();
+    let value = u32::from_str_radix(value.trim_start_matches("0x"), if value.starts_with("0x") { 16 } else { 10 }).ok()?;
+    let kind = match value {
+        0 => BootType::Cold,
+        1 => BootType::FastStartup,
+        2 => BootType::HibernateResume,
+        _ => return None,
+    };
+    Some(Boot { kind, at })
+}
@@ -0,0 +1,119 @@
+use windows::Win32::Devices::Display::*;
+use windows::Win32::Foundation::{BOOL, HANDLE, LPARAM, RECT, TRUE};
+use windows::Win32::Graphics::Gdi::{EnumDisplayMonitors, HDC, HMONITOR};
+use crate::wmi::Wmi;
+
+// Built-in panels are driven through WMI, external monitors over DDC/CI
+
+// Brightness of every display in percent, so a step-down can be undone
+#[derive(Clone, Default)]
+pub struct Levels {
+    pub built_in: Option<u8>,
+    // In enumeration order; None for monitors without DDC/CI (including the built-in panel)
+    pub external: Vec<Option<u8>>,
+}
+
+pub fn read() -> Levels {
+    Levels {
+        built_in: read_built_in(),
+        external: with_external_monitors(ddc_percent),
+    }
+}
+
+// Only internal panels report brightness through WMI
+pub fn read_built_in() -> Option<u8> {
+    let wmi = Wmi::connect(r"root\wmi").ok()?;
+    wmi.query("SELECT CurrentBrightness FROM WmiMonitorBrightness", &["CurrentBrightness"])
+        .ok()?
+        .into_iter()
+        .next()?
+        .into_iter()
+        .next()
+        .flatten()?
+        .parse()
+        .ok()
+}
+
+// Lowers every display brighter than `percent`; dimmer ones are left alone. Returns how many changed.
+pub fn dim_to(percent: u8) -> Result<usize, String> {
+    let mut dimmed = 0;
+    if read_built_in().is_some_and(|current| current > percent) {
+        set_built_in(percent)?;
+        dimmed += 1;
+    }
+    dimmed += with_external_monitors(|monitor| ddc_percent(monitor).is_some_and(|current| current > percent) && ddc_set(monitor, percent))
+        .into_iter()
+        .filter(|changed| *changed)
+        .count();
+    Ok(dimmed)
+}
+
+pub fn restore(levels: &Levels) -> Result<(), String> {
+    if let Some(level) = levels.built_in {
+        set_built_in(level)?;
+    }
+    let mut index = 0;
+    with_external_monitors(|monitor| {
+        if let Some(Some(level)) = levels.external.get(index) {
+            ddc_set(monitor, *level);
+        }
+        index += 1;
+    });
+    Ok(())
+}
+
+fn set_built_in(percent: u8) -> Result<(), String> {
+    let wmi = Wmi::connect(r"root\wmi")?;
+    // A timeout of 0 keeps the level until something else changes it
+    match wmi.call_method("WmiMonitorBrightnessMethods", "WmiSetBrightness", &[("Timeout", 0), ("Brightness", percent as i32)])? {
+        0 => Err("The built-in display didn't accept the brightness change".to_string()),
+        _ => Ok(()),
+    }
+}
+
+// Monitors report their own range, usually but not always 0–100
+fn ddc_percent(monitor: HANDLE) -> Option<u8> {
+    let (mut minimum, mut current, mut maximum) = (0, 0, 0);
+    if unsafe { GetMonitorBrightness(monitor, &mut minimum, &mut current, &mut maximum) } == 0 || maximum <= minimum {
+        return None;
+    }
+    Some(((current.saturating_sub(minimum)) * 100 / (maximum - minimum)).min(100) as u8)
+}
+
+fn ddc_set(monitor: HANDLE, percent: u8) -> bool {
+    let (mut minimum, mut current, mut maximum) = (0, 0, 0);
+    unsafe {
+        if GetMonitorBrightness(monitor, &mut minimum, &mut current, &mut maximum) == 0 || maximum <= minimum {
+            return false;
+        }
+        SetMonitorBrightness(monitor, minimum + (maximum - minimum) * percent as u32 / 100) != 0
+    }
+}
+
+fn with_external_monitors<T>(mut f: impl FnMut(HANDLE) -> T) -> Vec<T> {
+    unsafe extern "system" fn collect(monitor: HMONITOR, _: HDC, _: *mut RECT, data: LPARAM) -> BOOL {
+        (*(data.0 as *mut Vec<HMONITOR>)).push(monitor);
+        TRUE
+    }
+
+    let mut results = Vec::new();
+    unsafe {
+        let mut monitors: Vec<HMONITOR> = Vec::new();
+        EnumDisplayMonitors(HDC::default(), None, Some(collect), LPARAM(&mut monitors as *mut _ as isize));
+        for monitor in monitors {
+            let mut count = 0;
+            if GetNumberOfPhysicalMonitorsFromHMONITOR(monitor, &mut count).is_err() || count == 0 {
+                continue;
+            }
+            let mut physical = vec![PHYSICAL_MONITOR::default(); count as usize];
+            if GetPhysicalMonitorsFromHMONITOR(monitor, &mut physical).is_err() {
+                continue;
+            }
+            for monitor in &physical {
+                results.push(f(monitor.hPhysicalMonitor));
+            }
+            let _ = DestroyPhysicalMonitors(&physical);
+        }
+    }
+    results
+}
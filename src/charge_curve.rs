@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Local};
+use crate::battery::{BatteryMeasurement, MAX_SAMPLE_GAP_MINUTES};
+
+// Minutes per percent while charging, learned per 5% band: lithium cells charge at constant
+// current up to ~80% and then taper, so one fixed speed is wrong at both ends
+
+const BUCKET_PERCENT: u8 = 5;
+const BUCKETS: usize = 20;
+// Newer ticks count for at least this share, so the curve follows a new charger or an ageing pack
+const MIN_WEIGHT: f64 = 0.05;
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Bucket {
+    pub minutes_per_percent: f64,
+    pub ticks: u32,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ChargeCurve {
+    pub buckets: Vec<Bucket>,
+    // Time and level of the last percentage tick in the ongoing charge
+    #[serde(skip)]
+    anchor: Option<(DateTime<Local>, u8)>,
+}
+
+impl ChargeCurve {
+    fn bucket(percentage: u8) -> usize {
+        (percentage / BUCKET_PERCENT) as usize % BUCKETS
+    }
+
+    // Measured from one tick to the next, since the first tick after plugging in comes at an
+    // unknown point within its percent. Returns true when the curve changed.
+    pub fn record(&mut self, previous: &BatteryMeasurement, current: &BatteryMeasurement) -> bool {
+        let charging = |m: &BatteryMeasurement| m.is_charging && !m.not_charging;
+        if !charging(previous) || !charging(current)
+            || current.timestamp - previous.timestamp > Duration::minutes(MAX_SAMPLE_GAP_MINUTES)
+            || current.percentage < previous.percentage
+        {
+            self.anchor = None;
+            return false;
+        }
+        if current.percentage == previous.percentage {
+            return false;
+        }
+
+        let mut learned = false;
+        if let Some((since, from)) = self.anchor.filter(|(_, from)| current.percentage > *from) {
+            let minutes = (current.timestamp - since).num_seconds() as f64 / 60.0;
+            let per_percent = minutes / (current.percentage - from) as f64;
+            if self.buckets.len() != BUCKETS {
+                self.buckets = vec![Bucket::default(); BUCKETS];
+            }
+            let bucket = &mut self.buckets[Self::bucket(from)];
+            bucket.ticks += 1;
+            let weight = (1.0 / bucket.ticks as f64).max(MIN_WEIGHT);
+            bucket.minutes_per_percent += weight * (per_percent - bucket.minutes_per_percent);
+            learned = true;
+        }
+        self.anchor = Some((current.timestamp, current.percentage));
+        learned
+    }
+
+    // Bands with nothing learned yet fall back to `default_minutes`
+    pub fn minutes_per_percent(&self, percentage: u8, default_minutes: f64) -> f64 {
+        match self.buckets.get(Self::bucket(percentage)) {
+            Some(bucket) if bucket.ticks > 0 => bucket.minutes_per_percent,
+            _ => default_minutes,
+        }
+    }
+
+    // Sum over every percent still to go
+    pub fn minutes_until(&self, from: u8, to: u8, default_minutes: f64) -> f64 {
+        (from..to.min(100)).map(|p| self.minutes_per_percent(p, default_minutes)).sum()
+    }
+
+    // Level reached after charging for `minutes`
+    pub fn level_after(&self, from: u8, minutes: f64, default_minutes: f64) -> f64 {
+        let mut left = minutes;
+        let mut level = from;
+        while level < 100 {
+            let step = self.minutes_per_percent(level, default_minutes);
+            if left < step {
+                return level as f64 + left / step;
+            }
+            left -= step;
+            level += 1;
+        }
+        100.0
+    }
+
+    // "80–84%: 1.4 min/% (12 ticks)" for every band learned so far
+    pub fn summary(&self) -> Vec<String> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.ticks > 0)
+            .map(|(i, b)| {
+                let start = i as u8 * BUCKET_PERCENT;
+                format!("{}–{}%: {:.1} min/% ({} ticks)", start, start + BUCKET_PERCENT - 1, b.minutes_per_percent, b.ticks)
+            })
+            .collect()
+    }
+}
+
+pub fn load_curve() -> ChargeCurve {
+    std::fs::read_to_string(curve_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_curve(curve: &ChargeCurve) {
+    if let Ok(json) = serde_json::to_string_pretty(curve) {
+        let _ = std::fs::write(curve_path(), json);
+    }
+}
+
+fn curve_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_charge_curve.json");
+    path
+}
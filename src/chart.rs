@@ -0,0 +1,603 @@
+use std::cell::{Cell, RefCell};
+use std::sync::Once;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use chrono::{DateTime, Duration, Local, Timelike};
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Gdi::*;
+use windows::Win32::UI::Input::KeyboardAndMouse::{TrackMouseEvent, TME_LEAVE, TRACKMOUSEEVENT};
+use windows::Win32::UI::WindowsAndMessaging::*;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::core::PCWSTR;
+
+use crate::archive;
+use crate::events::EventKind;
+use crate::rollup::DailyRollup;
+use crate::MONITOR;
+use crate::icon::app_icon;
+
+static CHART_HWND: AtomicIsize = AtomicIsize::new(0);
+static REGISTER: Once = Once::new();
+
+thread_local! {
+    // Mouse position for the crosshair, None when the cursor left the window
+    static HOVER: Cell<Option<POINT>> = const { Cell::new(None) };
+    // Series switched off in the legend: bit 0 is the total, bit n+1 pack n
+    static HIDDEN: Cell<u32> = const { Cell::new(0) };
+    // What's plotted, taken on refresh so hovering doesn't lock the monitor on every repaint
+    static DATA: RefCell<Option<ChartData>> = const { RefCell::new(None) };
+}
+
+const PACK_COLORS: [u32; 4] = [0x002080E0, 0x00A040A0, 0x0000A0A0, 0x00606060];
+const LEGEND_WIDTH: i32 = 84;
+
+// From the Controls headers; the feature isn't worth pulling in for one message
+const WM_MOUSELEAVE: u32 = 0x02A3;
+
+// Samples further apart than this weren't recorded continuously (app closed, sleep)
+const GAP_MINUTES: i64 = 15;
+
+const MARGIN_LEFT: i32 = 44;
+const MARGIN_RIGHT: i32 = 16;
+const MARGIN_TOP: i32 = 16;
+const MARGIN_BOTTOM: i32 = 28;
+
+struct ChartPoint {
+    timestamp: DateTime<Local>,
+    percentage: u8,
+    is_charging: bool,
+    // Hundredths of a percent per hour, as recorded with the sample
+    rate: i32,
+    packs: Vec<u8>,
+    // Nothing was recorded between the previous sample and this one
+    gap_before: bool,
+}
+
+struct Forecast {
+    percentage: u8,
+    // Hundredths of a percent per hour, as produced by the estimators
+    rate: i32,
+    confidence: f64,
+}
+
+struct ChartData {
+    points: Vec<ChartPoint>,
+    // OS and driver updates, marked so drain changes can be tied to them
+    updates: Vec<(DateTime<Local>, String)>,
+    // Days older than the raw history, for long spans
+    days: Vec<DailyRollup>,
+    forecast: Option<Forecast>,
+    span: Duration,
+}
+
+// Maps time and percentage onto the plot rectangle
+struct Plot {
+    area: RECT,
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+}
+
+impl Plot {
+    fn x(&self, t: DateTime<Local>) -> i32 {
+        let total = (self.end - self.start).num_seconds().max(1) as f64;
+        let offset = (t - self.start).num_seconds() as f64;
+        self.area.left + ((self.area.right - self.area.left) as f64 * offset / total).round() as i32
+    }
+
+    fn y(&self, percentage: f64) -> i32 {
+        let height = (self.area.bottom - self.area.top) as f64;
+        self.area.bottom - (height * percentage.clamp(0.0, 100.0) / 100.0).round() as i32
+    }
+}
+
+pub fn show_chart(owner: HWND) {
+    unsafe {
+        let existing = HWND(CHART_HWND.load(Ordering::Relaxed));
+        if existing.0 != 0 && IsWindow(existing).as_bool() {
+            ShowWindow(existing, SW_SHOWNORMAL);
+            SetForegroundWindow(existing);
+            return;
+        }
+
+        let instance: HINSTANCE = GetModuleHandleW(PCWSTR::null()).unwrap().into();
+        let class_name = "BattestyChart\0".encode_utf16().collect::<Vec<u16>>();
+
+        REGISTER.call_once(|| {
+            let wc = WNDCLASSW {
+                lpfnWndProc: Some(chart_proc),
+                hInstance: instance,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                hCursor: LoadCursorW(None, IDC_ARROW).unwrap_or_default(),
+                hIcon: app_icon(),
+                ..std::mem::zeroed()
+            };
+            RegisterClassW(&wc);
+        });
+
+        let title = "Battesty - Battery Graph\0".encode_utf16().collect::<Vec<u16>>();
+        let hwnd = CreateWindowExW(
+            WINDOW_EX_STYLE(0),
+            PCWSTR(class_name.as_ptr()),
+            PCWSTR(title.as_ptr()),
+            WS_OVERLAPPEDWINDOW,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            760,
+            420,
+            owner,
+            None,
+            instance,
+            None,
+        );
+
+        CHART_HWND.store(hwnd.0, Ordering::Relaxed);
+        reload(hwnd);
+        ShowWindow(hwnd, SW_SHOWNORMAL);
+        SetForegroundWindow(hwnd);
+    }
+}
+
+// Repaints the chart after new measurements, if it is open
+pub fn refresh() {
+    let hwnd = HWND(CHART_HWND.load(Ordering::Relaxed));
+    if hwnd.0 != 0 {
+        reload(hwnd);
+    }
+}
+
+fn reload(hwnd: HWND) {
+    DATA.with(|d| *d.borrow_mut() = snapshot());
+    unsafe {
+        InvalidateRect(hwnd, None, FALSE);
+    }
+}
+
+unsafe extern "system" fn chart_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_PAINT => {
+            let mut ps: PAINTSTRUCT = std::mem::zeroed();
+            let hdc = BeginPaint(hwnd, &mut ps);
+            paint(hwnd, hdc);
+            EndPaint(hwnd, &ps);
+            LRESULT(0)
+        }
+        WM_ERASEBKGND => LRESULT(1),
+        WM_MOUSEMOVE => {
+            if HOVER.with(|h| h.get()).is_none() {
+                let mut track = TRACKMOUSEEVENT {
+                    cbSize: std::mem::size_of::<TRACKMOUSEEVENT>() as u32,
+                    dwFlags: TME_LEAVE,
+                    hwndTrack: hwnd,
+                    dwHoverTime: 0,
+                };
+                let _ = TrackMouseEvent(&mut track);
+            }
+            let x = (lparam.0 & 0xFFFF) as i16 as i32;
+            let y = ((lparam.0 >> 16) & 0xFFFF) as i16 as i32;
+            HOVER.with(|h| h.set(Some(POINT { x, y })));
+            InvalidateRect(hwnd, None, FALSE);
+            LRESULT(0)
+        }
+        WM_LBUTTONDOWN => {
+            let x = (lparam.0 & 0xFFFF) as i16 as i32;
+            let y = ((lparam.0 >> 16) & 0xFFFF) as i16 as i32;
+            let mut client = RECT::default();
+            let _ = GetClientRect(hwnd, &mut client);
+            let pack_count = DATA.with(|d| d.borrow().as_ref().map_or(0, |d| pack_count(&d.points)));
+            for series in 0..legend_entries(pack_count) {
+                let rect = legend_rect(&client, series);
+                if x >= rect.left && x < rect.right && y >= rect.top && y < rect.bottom {
+                    HIDDEN.with(|h| h.set(h.get() ^ (1 << series)));
+                    InvalidateRect(hwnd, None, FALSE);
+                }
+            }
+            LRESULT(0)
+        }
+        WM_MOUSELEAVE => {
+            HOVER.with(|h| h.set(None));
+            InvalidateRect(hwnd, None, FALSE);
+            LRESULT(0)
+        }
+        WM_SIZE => {
+            reload(hwnd);
+            LRESULT(0)
+        }
+        WM_DESTROY => {
+            CHART_HWND.store(0, Ordering::Relaxed);
+            DATA.with(|d| *d.borrow_mut() = None);
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+fn snapshot() -> Option<ChartData> {
+    let mon = MONITOR.get()?.lock().ok()?;
+    let span = Duration::hours(mon.settings.chart_hours.max(1) as i64);
+    let cutoff = Local::now() - span;
+
+    // Past the retention window the chart reads on into the weekly archives
+    let archived = if mon.settings.archive_weekly {
+        archive::before(cutoff, mon.measurements.front().map(|m| m.timestamp))
+    } else {
+        Vec::new()
+    };
+    let raw_start = archived.first().or(mon.measurements.front()).map(|m| m.timestamp.date_naive());
+
+    let mut previous: Option<DateTime<Local>> = None;
+    let points = archived
+        .iter()
+        .chain(mon.measurements.iter())
+        .filter(|m| m.timestamp >= cutoff)
+        .map(|m| {
+            let gap_before = previous.is_some_and(|t| m.timestamp - t > Duration::minutes(GAP_MINUTES));
+            previous = Some(m.timestamp);
+            ChartPoint {
+                timestamp: m.timestamp,
+                percentage: m.percentage,
+                is_charging: m.is_charging,
+                rate: m.discharge_rate,
+                packs: m.packs.clone(),
+                gap_before,
+            }
+        })
+        .collect();
+
+    let forecast = match mon.measurements.back() {
+        Some(last) if !last.is_charging => {
+            let estimate = mon.estimate();
+            (estimate.rate > 0).then_some(Forecast {
+                percentage: last.percentage,
+                rate: estimate.rate,
+                confidence: estimate.confidence,
+            })
+        }
+        _ => None,
+    };
+
+    let days = mon.daily
+        .iter()
+        .filter(|d| d.date >= cutoff.date_naive() && raw_start.is_none_or(|start| d.date < start))
+        .cloned()
+        .collect();
+
+    let updates = mon.events
+        .iter()
+        .filter(|e| e.kind == EventKind::SystemUpdate && e.timestamp >= cutoff)
+        .map(|e| (e.timestamp, e.message.clone()))
+        .collect();
+
+    Some(ChartData { points, updates, days, forecast, span })
+}
+
+unsafe fn paint(hwnd: HWND, hdc: HDC) {
+    let mut client = RECT::default();
+    let _ = GetClientRect(hwnd, &mut client);
+    let width = client.right - client.left;
+    let height = client.bottom - client.top;
+
+    // Draw into a memory bitmap to avoid flicker on refresh
+    let hdc_mem = CreateCompatibleDC(hdc);
+    let hbm = CreateCompatibleBitmap(hdc, width, height);
+    let old_bitmap = SelectObject(hdc_mem, hbm);
+    let old_font = SelectObject(hdc_mem, GetStockObject(DEFAULT_GUI_FONT));
+
+    let background = CreateSolidBrush(COLORREF(0x00FFFFFF));
+    FillRect(hdc_mem, &client, background);
+    DeleteObject(background);
+    SetBkMode(hdc_mem, TRANSPARENT);
+
+    DATA.with(|d| {
+        let data = d.borrow();
+        let Some(data) = data.as_ref() else { return };
+        let now = Local::now();
+        // Leave a quarter of the span to the right for the forecast
+        let plot = Plot {
+            area: RECT {
+                left: MARGIN_LEFT,
+                top: MARGIN_TOP,
+                right: width - MARGIN_RIGHT,
+                bottom: height - MARGIN_BOTTOM,
+            },
+            start: now - data.span,
+            end: now + data.span / 4,
+        };
+
+        draw_grid(hdc_mem, &plot);
+        if let Some(forecast) = &data.forecast {
+            draw_forecast(hdc_mem, &plot, now, forecast);
+        }
+        draw_days(hdc_mem, &plot, &data.days);
+        draw_gaps(hdc_mem, &plot, &data.points);
+        draw_updates(hdc_mem, &plot, &data.updates);
+        let hidden = HIDDEN.with(|h| h.get());
+        if hidden & 1 == 0 {
+            draw_history(hdc_mem, &plot, &data.points);
+        }
+        let packs = pack_count(&data.points);
+        for pack in 0..packs {
+            if hidden & (1 << (pack + 1)) == 0 {
+                draw_pack(hdc_mem, &plot, &data.points, pack);
+            }
+        }
+        if packs > 0 {
+            draw_legend(hdc_mem, &client, packs, hidden);
+        }
+        if let Some(mouse) = HOVER.with(|h| h.get()) {
+            draw_crosshair(hdc_mem, &plot, &data.points, mouse);
+        }
+    });
+
+    let _ = BitBlt(hdc, 0, 0, width, height, hdc_mem, 0, 0, SRCCOPY);
+
+    SelectObject(hdc_mem, old_font);
+    SelectObject(hdc_mem, old_bitmap);
+    DeleteObject(hbm);
+    DeleteDC(hdc_mem);
+}
+
+unsafe fn draw_text(hdc: HDC, x: i32, y: i32, text: &str) {
+    let text_wide: Vec<u16> = text.encode_utf16().collect();
+    TextOutW(hdc, x, y, &text_wide);
+}
+
+unsafe fn draw_grid(hdc: HDC, plot: &Plot) {
+    let pen_grid = CreatePen(PS_SOLID, 1, COLORREF(0x00E0E0E0));
+    let old_pen = SelectObject(hdc, pen_grid);
+    SetTextColor(hdc, COLORREF(0x00606060));
+
+    for level in [0, 25, 50, 75, 100] {
+        let y = plot.y(level as f64);
+        MoveToEx(hdc, plot.area.left, y, None);
+        LineTo(hdc, plot.area.right, y);
+        draw_text(hdc, 8, y - 7, &format!("{}%", level));
+    }
+
+    // Vertical lines on whole hours, spaced so labels don't collide
+    let hours = (plot.end - plot.start).num_hours().max(1);
+    let step = match hours {
+        0..=6 => 1,
+        7..=24 => 3,
+        25..=72 => 12,
+        _ => 24,
+    };
+    let mut tick = plot.start
+        .with_minute(0)
+        .and_then(|t| t.with_second(0))
+        .unwrap_or(plot.start)
+        + Duration::hours(1);
+    while tick < plot.end {
+        if tick.hour() as i64 % step == 0 {
+            let x = plot.x(tick);
+            MoveToEx(hdc, x, plot.area.top, None);
+            LineTo(hdc, x, plot.area.bottom);
+            let label = if tick.hour() == 0 { tick.format("%d.%m") } else { tick.format("%H:%M") };
+            draw_text(hdc, x - 14, plot.area.bottom + 6, &label.to_string());
+        }
+        tick += Duration::hours(1);
+    }
+
+    SelectObject(hdc, old_pen);
+    DeleteObject(pen_grid);
+}
+
+// Min–max bar per day with a tick at the average
+unsafe fn draw_days(hdc: HDC, plot: &Plot, days: &[DailyRollup]) {
+    let pen_range = CreatePen(PS_SOLID, 3, COLORREF(0x00F0C8A0));
+    let pen_average = CreatePen(PS_SOLID, 1, COLORREF(0x00D07020));
+    let old_pen = SelectObject(hdc, pen_range);
+
+    for day in days {
+        let Some(noon) = day.date.and_hms_opt(12, 0, 0).and_then(|t| t.and_local_timezone(Local).earliest()) else {
+            continue;
+        };
+        let x = plot.x(noon);
+        SelectObject(hdc, pen_range);
+        MoveToEx(hdc, x, plot.y(day.min_percentage as f64), None);
+        LineTo(hdc, x, plot.y(day.max_percentage as f64));
+        SelectObject(hdc, pen_average);
+        let y = plot.y(day.avg_percentage);
+        MoveToEx(hdc, x - 3, y, None);
+        LineTo(hdc, x + 4, y);
+    }
+
+    SelectObject(hdc, old_pen);
+    DeleteObject(pen_range);
+    DeleteObject(pen_average);
+}
+
+// Vertical marker per update, labelled with what changed
+unsafe fn draw_updates(hdc: HDC, plot: &Plot, updates: &[(DateTime<Local>, String)]) {
+    let pen_update = CreatePen(PS_DASHDOT, 1, COLORREF(0x000080FF));
+    let old_pen = SelectObject(hdc, pen_update);
+    SetTextColor(hdc, COLORREF(0x000060C0));
+
+    for (i, (timestamp, message)) in updates.iter().enumerate() {
+        let x = plot.x(*timestamp);
+        MoveToEx(hdc, x, plot.area.top, None);
+        LineTo(hdc, x, plot.area.bottom);
+        // Stagger labels so neighbouring updates stay readable
+        draw_text(hdc, x + 3, plot.area.top + 2 + 14 * (i as i32 % 3), message);
+    }
+
+    SelectObject(hdc, old_pen);
+    DeleteObject(pen_update);
+}
+
+// Shaded band over each stretch with no samples
+unsafe fn draw_gaps(hdc: HDC, plot: &Plot, points: &[ChartPoint]) {
+    let brush_gap = CreateSolidBrush(COLORREF(0x00F2F2F2));
+    SetTextColor(hdc, COLORREF(0x00A0A0A0));
+
+    for pair in points.windows(2).filter(|pair| pair[1].gap_before) {
+        let band = RECT {
+            left: plot.x(pair[0].timestamp) + 1,
+            top: plot.area.top,
+            right: plot.x(pair[1].timestamp),
+            bottom: plot.area.bottom,
+        };
+        FillRect(hdc, &band, brush_gap);
+        if band.right - band.left > 40 {
+            draw_text(hdc, band.left + 4, plot.area.bottom - 16, "no data");
+        }
+    }
+
+    DeleteObject(brush_gap);
+}
+
+unsafe fn draw_history(hdc: HDC, plot: &Plot, points: &[ChartPoint]) {
+    let pen_discharge = CreatePen(PS_SOLID, 2, COLORREF(0x00D07020)); // Blue
+    let pen_charge = CreatePen(PS_SOLID, 2, COLORREF(0x0000A000)); // Green
+    let pen_bridge = CreatePen(PS_DOT, 1, COLORREF(0x00909090));
+    let old_pen = SelectObject(hdc, pen_discharge);
+
+    for pair in points.windows(2) {
+        // Across a gap the real curve is unknown, so only hint at where it went
+        SelectObject(hdc, match (pair[1].gap_before, pair[1].is_charging) {
+            (true, _) => pen_bridge,
+            (false, true) => pen_charge,
+            (false, false) => pen_discharge,
+        });
+        MoveToEx(hdc, plot.x(pair[0].timestamp), plot.y(pair[0].percentage as f64), None);
+        LineTo(hdc, plot.x(pair[1].timestamp), plot.y(pair[1].percentage as f64));
+    }
+
+    SelectObject(hdc, old_pen);
+    DeleteObject(pen_discharge);
+    DeleteObject(pen_charge);
+    DeleteObject(pen_bridge);
+}
+
+// Dashed projection from now, with a band widening as estimator confidence drops
+unsafe fn draw_forecast(hdc: HDC, plot: &Plot, now: DateTime<Local>, forecast: &Forecast) {
+    let rate = forecast.rate as f64 / 100.0;
+    let uncertainty = (1.0 - forecast.confidence).clamp(0.1, 0.9);
+
+    let project = |rate: f64| -> (DateTime<Local>, f64) {
+        let hours_to_empty = forecast.percentage as f64 / rate;
+        let hours_visible = (plot.end - now).num_seconds() as f64 / 3600.0;
+        let hours = hours_to_empty.min(hours_visible);
+        (now + Duration::seconds((hours * 3600.0) as i64), forecast.percentage as f64 - rate * hours)
+    };
+
+    let (fast_t, fast_p) = project(rate * (1.0 + uncertainty));
+    let (slow_t, slow_p) = project(rate * (1.0 - uncertainty));
+    let start = POINT { x: plot.x(now), y: plot.y(forecast.percentage as f64) };
+    let mut band = vec![start, POINT { x: plot.x(slow_t), y: plot.y(slow_p) }];
+    if fast_t < slow_t {
+        // The fast projection hit 0% early; follow the axis to close the band
+        band.push(POINT { x: plot.x(slow_t), y: plot.y(0.0) });
+    }
+    band.push(POINT { x: plot.x(fast_t), y: plot.y(fast_p) });
+
+    let brush_band = CreateSolidBrush(COLORREF(0x00F8E8D8));
+    let old_brush = SelectObject(hdc, brush_band);
+    let old_pen = SelectObject(hdc, GetStockObject(NULL_PEN));
+    Polygon(hdc, &band);
+
+    let (t, p) = project(rate);
+    let pen_forecast = CreatePen(PS_DASH, 1, COLORREF(0x00D07020));
+    SelectObject(hdc, pen_forecast);
+    MoveToEx(hdc, start.x, start.y, None);
+    LineTo(hdc, plot.x(t), plot.y(p));
+
+    SelectObject(hdc, old_pen);
+    SelectObject(hdc, old_brush);
+    DeleteObject(pen_forecast);
+    DeleteObject(brush_band);
+}
+
+// Snaps to the sample nearest the cursor and labels it
+unsafe fn draw_crosshair(hdc: HDC, plot: &Plot, points: &[ChartPoint], mouse: POINT) {
+    let area = plot.area;
+    if mouse.x < area.left || mouse.x > area.right || mouse.y < area.top || mouse.y > area.bottom {
+        return;
+    }
+    let Some(point) = points.iter().min_by_key(|p| (plot.x(p.timestamp) - mouse.x).abs()) else {
+        return;
+    };
+    let x = plot.x(point.timestamp);
+    let y = plot.y(point.percentage as f64);
+
+    let pen_cross = CreatePen(PS_DOT, 1, COLORREF(0x00808080));
+    let old_pen = SelectObject(hdc, pen_cross);
+    MoveToEx(hdc, x, area.top, None);
+    LineTo(hdc, x, area.bottom);
+    MoveToEx(hdc, area.left, y, None);
+    LineTo(hdc, area.right, y);
+    SelectObject(hdc, GetStockObject(BLACK_PEN));
+    Ellipse(hdc, x - 3, y - 3, x + 4, y + 4);
+
+    let lines = [
+        point.timestamp.format("%d.%m %H:%M:%S").to_string(),
+        format!("{}% · {}", point.percentage, if point.is_charging { "Charging" } else { "Discharging" }),
+        format!("Rate: {:.1}% per hour", point.rate as f64 / 100.0),
+    ];
+    let (width, height) = (150, 16 * lines.len() as i32 + 8);
+    // Keep the box inside the plot, flipping to the left of the cursor near the edge
+    let left = if x + 12 + width > area.right { x - 12 - width } else { x + 12 };
+    let top = (y - height / 2).clamp(area.top, (area.bottom - height).max(area.top));
+    let box_rect = RECT { left, top, right: left + width, bottom: top + height };
+
+    let brush_box = CreateSolidBrush(COLORREF(0x00F4F4F4));
+    FillRect(hdc, &box_rect, brush_box);
+    FrameRect(hdc, &box_rect, HBRUSH(GetStockObject(GRAY_BRUSH).0));
+    SetTextColor(hdc, COLORREF(0x00202020));
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(hdc, left + 6, top + 4 + 16 * i as i32, line);
+    }
+
+    SelectObject(hdc, old_pen);
+    DeleteObject(pen_cross);
+    DeleteObject(brush_box);
+}
+
+fn pack_count(points: &[ChartPoint]) -> usize {
+    points.iter().map(|p| p.packs.len()).max().unwrap_or(0).min(PACK_COLORS.len())
+}
+
+// Total plus one entry per pack; no legend on single-battery machines
+fn legend_entries(pack_count: usize) -> usize {
+    if pack_count > 0 { pack_count + 1 } else { 0 }
+}
+
+fn legend_rect(client: &RECT, series: usize) -> RECT {
+    let right = client.right - MARGIN_RIGHT - 4;
+    let top = MARGIN_TOP + 4 + 18 * series as i32;
+    RECT { left: right - LEGEND_WIDTH, top, right, bottom: top + 16 }
+}
+
+unsafe fn draw_pack(hdc: HDC, plot: &Plot, points: &[ChartPoint], pack: usize) {
+    let pen_pack = CreatePen(PS_SOLID, 1, COLORREF(PACK_COLORS[pack]));
+    let old_pen = SelectObject(hdc, pen_pack);
+
+    for pair in points.windows(2).filter(|pair| !pair[1].gap_before) {
+        let (Some(from), Some(to)) = (pair[0].packs.get(pack), pair[1].packs.get(pack)) else {
+            continue;
+        };
+        MoveToEx(hdc, plot.x(pair[0].timestamp), plot.y(*from as f64), None);
+        LineTo(hdc, plot.x(pair[1].timestamp), plot.y(*to as f64));
+    }
+
+    SelectObject(hdc, old_pen);
+    DeleteObject(pen_pack);
+}
+
+// Clickable entries; hidden series are greyed out
+unsafe fn draw_legend(hdc: HDC, client: &RECT, pack_count: usize, hidden: u32) {
+    for series in 0..legend_entries(pack_count) {
+        let rect = legend_rect(client, series);
+        let (label, color) = match series {
+            0 => ("Total".to_string(), 0x00D07020),
+            n => (format!("Battery {}", n), PACK_COLORS[n - 1]),
+        };
+        let visible = hidden & (1 << series) == 0;
+
+        let swatch = RECT { left: rect.left + 2, top: rect.top + 4, right: rect.left + 14, bottom: rect.top + 12 };
+        let brush = CreateSolidBrush(COLORREF(if visible { color } else { 0x00D0D0D0 }));
+        FillRect(hdc, &swatch, brush);
+        DeleteObject(brush);
+
+        SetTextColor(hdc, COLORREF(if visible { 0x00202020 } else { 0x00A0A0A0 }));
+        draw_text(hdc, rect.left + 18, rect.top, &label);
+    }
+}
@@ -0,0 +1,23 @@
+use std::time::Instant;
+use chrono::{DateTime, Local};
+
+// Where BatteryMonitor gets the time from, so gaps, retention and day boundaries can be driven
+// by something other than the wall clock (a replayed trace, a test)
+pub trait Clock: Send {
+    // Wall-clock time, for timestamps and calendar logic
+    fn now(&self) -> DateTime<Local>;
+    // For "has it been an hour" throttles, which shouldn't jump when the clock is changed
+    fn monotonic(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn monotonic(&self) -> Instant {
+        Instant::now()
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use chrono::{DateTime, Duration, Local};
+use windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+use windows::core::PCWSTR;
+use crate::battery::BatteryMeasurement;
+
+// Below this much free space the history is squeezed to half its size limit
+pub const LOW_DISK_BYTES: u64 = 1024 * 1024 * 1024;
+
+// The last day keeps full resolution; the charts and estimators mostly look there
+const FULL_RESOLUTION_HOURS: i64 = 24;
+
+// Coarser and coarser spacing for older samples, kept under the 15-minute gap the charts and
+// session tracking treat as missing data
+const STEP_MINUTES: [i64; 3] = [5, 10, 14];
+
+pub struct Compaction {
+    pub before: usize,
+    pub after: usize,
+    pub step_minutes: Option<i64>,
+    // Oldest samples dropped outright when thinning alone wasn't enough
+    pub dropped_oldest: usize,
+}
+
+impl Compaction {
+    pub fn summary(&self, bytes: usize, reason: &str) -> String {
+        let mut text = format!("History compacted ({}): {} → {} samples, {:.1} MB", reason, self.before, self.after, bytes as f64 / 1_048_576.0);
+        if let Some(step) = self.step_minutes {
+            text.push_str(&format!("; older than {}h thinned to one per {} min", FULL_RESOLUTION_HOURS, step));
+        }
+        if self.dropped_oldest > 0 {
+            text.push_str(&format!("; {} oldest samples removed", self.dropped_oldest));
+        }
+        text
+    }
+}
+
+// Shrinks `measurements` until `size` (the serialized length) fits in `target_bytes`.
+// None when nothing had to change.
+pub fn compact(
+    measurements: &mut VecDeque<BatteryMeasurement>,
+    target_bytes: usize,
+    size: impl Fn(&VecDeque<BatteryMeasurement>) -> usize,
+    now: DateTime<Local>,
+) -> Option<Compaction> {
+    if size(measurements) <= target_bytes {
+        return None;
+    }
+    let before = measurements.len();
+    let cutoff = now - Duration::hours(FULL_RESOLUTION_HOURS);
+
+    let mut step_minutes = None;
+    for step in STEP_MINUTES {
+        thin(measurements, cutoff, Duration::minutes(step));
+        step_minutes = Some(step);
+        if size(measurements) <= target_bytes {
+            return Some(Compaction { before, after: measurements.len(), step_minutes, dropped_oldest: 0 });
+        }
+    }
+
+    // Still too big: give up the oldest tenth at a time
+    let mut dropped_oldest = 0;
+    while size(measurements) > target_bytes && measurements.len() > 1 {
+        let count = (measurements.len() / 10).max(1);
+        measurements.drain(..count);
+        dropped_oldest += count;
+    }
+    Some(Compaction { before, after: measurements.len(), step_minutes, dropped_oldest })
+}
+
+// Keeps one sample per `step` before `cutoff`, plus every plug/unplug so sessions stay intact
+fn thin(measurements: &mut VecDeque<BatteryMeasurement>, cutoff: DateTime<Local>, step: Duration) {
+    let mut last_kept: Option<(DateTime<Local>, bool)> = None;
+    measurements.retain(|m| {
+        let keep = m.timestamp >= cutoff
+            || last_kept.is_none_or(|(time, charging)| m.timestamp - time >= step || m.is_charging != charging);
+        if keep {
+            last_kept = Some((m.timestamp, m.is_charging));
+        }
+        keep
+    });
+}
+
+pub fn free_disk_bytes(path: &Path) -> Option<u64> {
+    let dir = path.parent()?;
+    let dir_wide: Vec<u16> = dir.as_os_str().to_string_lossy().encode_utf16().chain(std::iter::once(0)).collect();
+    let mut free = 0u64;
+    unsafe {
+        GetDiskFreeSpaceExW(PCWSTR(dir_wide.as_ptr()), Some(&mut free), None, None).ok()?;
+    }
+    Some(free)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace;
+
+    // Ten days of one reading a minute, with a charge in the middle of the third day
+    fn history() -> VecDeque<BatteryMeasurement> {
+        let mut samples: VecDeque<BatteryMeasurement> = trace::discharge(trace::start(), 100.0, 0.5, 10 * 24 * 60, 1).into();
+        for m in samples.iter_mut().skip(2 * 24 * 60 + 12 * 60).take(90) {
+            m.is_charging = true;
+        }
+        samples
+    }
+
+    #[test]
+    fn under_the_limit_nothing_changes() {
+        let mut samples = history();
+        let before = samples.len();
+        assert!(compact(&mut samples, before, VecDeque::len, trace::start()).is_none());
+        assert_eq!(samples.len(), before);
+    }
+
+    #[test]
+    fn thinning_spares_the_last_day_and_plug_events() {
+        let mut samples = history();
+        let now = samples.back().unwrap().timestamp;
+        let target = samples.len() / 3;
+        let result = compact(&mut samples, target, VecDeque::len, now).unwrap();
+        assert_eq!(result.step_minutes, Some(5));
+        assert_eq!(result.dropped_oldest, 0);
+
+        let cutoff = now - Duration::hours(FULL_RESOLUTION_HOURS);
+        assert_eq!(samples.iter().filter(|m| m.timestamp >= cutoff).count(), 24 * 60 + 1);
+        // The first reading on AC and the first one back on battery both survive
+        let flips = samples.iter().zip(samples.iter().skip(1)).filter(|(a, b)| a.is_charging != b.is_charging).count();
+        assert_eq!(flips, 2);
+        assert!(samples.iter().zip(samples.iter().skip(1)).all(|(a, b)| b.timestamp - a.timestamp <= Duration::minutes(STEP_MINUTES[0])));
+    }
+
+    #[test]
+    fn oldest_go_when_thinning_is_not_enough() {
+        let mut samples = history();
+        let now = samples.back().unwrap().timestamp;
+        let result = compact(&mut samples, 1000, VecDeque::len, now).unwrap();
+        assert!(result.dropped_oldest > 0);
+        assert!(samples.len() <= 1000);
+        // What's left is the newest end of the history
+        assert_eq!(samples.back().unwrap().timestamp, now);
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use windows::Win32::Devices::Bluetooth::*;
+use windows::Win32::Devices::DeviceAndDriverInstallation::*;
+use windows::Win32::Foundation::*;
+use windows::Win32::Storage::FileSystem::*;
+use windows::core::{GUID, PCWSTR};
+use crate::ioctl;
+
+// Battery level of paired phones, earbuds and the like, read from the standard Battery Service
+// (0x180F) of Bluetooth LE devices. Windows publishes each paired device's GATT services as a
+// device interface named after the service UUID. Phones only show up when they expose the
+// service; Phone Link has no public API to read the level from.
+
+const BATTERY_SERVICE: GUID = GUID::from_u128(0x0000180F_0000_1000_8000_00805F9B34FB);
+const BATTERY_SERVICE_SHORT: u16 = 0x180F;
+const BATTERY_LEVEL: u16 = 0x2A19;
+// Reading wakes the device's radio, so this is deliberately much slower than the laptop's update
+const POLL_INTERVAL: Duration = Duration::from_secs(300);
+// A device has to climb this far above the threshold before it can alert again
+const REARM_MARGIN: u8 = 5;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Companion {
+    pub name: String,
+    pub percentage: u8,
+}
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+// Bumped on stop, so a thread still sleeping from before a restart exits instead of doubling up
+static GENERATION: AtomicU32 = AtomicU32::new(0);
+static READINGS: Mutex<Vec<Companion>> = Mutex::new(Vec::new());
+// Devices that already alerted this discharge
+static ALERTED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+pub fn latest() -> Vec<Companion> {
+    READINGS.lock().map(|r| r.clone()).unwrap_or_default()
+}
+
+// Readings passed on by the background engine, which does the polling in client mode
+pub fn set_latest(found: Vec<Companion>) {
+    if let Ok(mut readings) = READINGS.lock() {
+        *readings = found;
+    }
+}
+
+// GATT reads can block for seconds on an out-of-range device, so polling has its own thread
+pub fn start() {
+    if RUNNING.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let generation = GENERATION.load(Ordering::Relaxed);
+    std::thread::spawn(move || {
+        while GENERATION.load(Ordering::Relaxed) == generation {
+            let found = read_all();
+            if GENERATION.load(Ordering::Relaxed) != generation {
+                break;
+            }
+            if let Ok(mut readings) = READINGS.lock() {
+                *readings = found;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+pub fn stop() {
+    RUNNING.store(false, Ordering::Relaxed);
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+    if let Ok(mut readings) = READINGS.lock() {
+        readings.clear();
+    }
+}
+
+// Devices that just fell to the threshold; each alerts once until it's charged again
+pub fn check_low(threshold: u8) -> Vec<Companion> {
+    let Ok(mut alerted) = ALERTED.lock() else { return Vec::new() };
+    let mut newly_low = Vec::new();
+    for device in latest() {
+        if device.percentage <= threshold {
+            if alerted.insert(device.name.clone()) {
+                newly_low.push(device);
+            }
+        } else if device.percentage >= threshold.saturating_add(REARM_MARGIN) {
+            alerted.remove(&device.name);
+        }
+    }
+    newly_low
+}
+
+pub fn read_all() -> Vec<Companion> {
+    ioctl::device_interfaces(&BATTERY_SERVICE)
+        .iter()
+        .filter_map(|(path, device)| {
+            let percentage = unsafe { read_level(path) }?;
+            Some(Companion { name: unsafe { device_name(*device) }, percentage })
+        })
+        .collect()
+}
+
+unsafe fn read_level(path: &[u16]) -> Option<u8> {
+    let handle = CreateFileW(
+        PCWSTR(path.as_ptr()),
+        FILE_GENERIC_READ.0,
+        FILE_SHARE_READ | FILE_SHARE_WRITE,
+        None,
+        OPEN_EXISTING,
+        FILE_ATTRIBUTE_NORMAL,
+        None,
+    ).ok()?;
+    let level = read_level_from(handle);
+    let _ = CloseHandle(handle);
+    level
+}
+
+unsafe fn read_level_from(handle: HANDLE) -> Option<u8> {
+    // Each call is made once for the count and once for the data
+    let mut count = 0u16;
+    let _ = BluetoothGATTGetServices(handle, None, &mut count, BLUETOOTH_GATT_FLAG_NONE);
+    let mut services = vec![BTH_LE_GATT_SERVICE::default(); count as usize];
+    BluetoothGATTGetServices(handle, Some(&mut services), &mut count, BLUETOOTH_GATT_FLAG_NONE).ok()?;
+    let service = services.iter().find(|s| is_short_uuid(&s.ServiceUuid, BATTERY_SERVICE_SHORT))?;
+
+    let _ = BluetoothGATTGetCharacteristics(handle, Some(service), None, &mut count, BLUETOOTH_GATT_FLAG_NONE);
+    let mut characteristics = vec![BTH_LE_GATT_CHARACTERISTIC::default(); count as usize];
+    BluetoothGATTGetCharacteristics(handle, Some(service), Some(&mut characteristics), &mut count, BLUETOOTH_GATT_FLAG_NONE).ok()?;
+    let level = characteristics
+        .iter()
+        .find(|c| is_short_uuid(&c.CharacteristicUuid, BATTERY_LEVEL) && c.IsReadable.as_bool())?;
+
+    let mut size = 0u16;
+    let _ = BluetoothGATTGetCharacteristicValue(handle, level, 0, None, Some(&mut size), BLUETOOTH_GATT_FLAG_NONE);
+    if size == 0 {
+        return None;
+    }
+    let mut buffer = vec![0u32; size as usize / 4 + 1];
+    let value = buffer.as_mut_ptr() as *mut BTH_LE_GATT_CHARACTERISTIC_VALUE;
+    // The cached value only changes when someone subscribed, so the device is asked directly
+    BluetoothGATTGetCharacteristicValue(handle, level, size as u32, Some(value), None, BLUETOOTH_GATT_FLAG_FORCE_READ_FROM_DEVICE).ok()?;
+    ((*value).DataSize >= 1).then(|| (*value).Data[0].min(100))
+}
+
+fn is_short_uuid(uuid: &BTH_LE_UUID, short: u16) -> bool {
+    uuid.IsShortUuid.as_bool() && unsafe { uuid.Value.ShortUuid } == short
+}
+
+// The interface belongs to the service node; the device's own name is on its parent
+unsafe fn device_name(service_node: u32) -> String {
+    let mut parent = 0u32;
+    if CM_Get_Parent(&mut parent, service_node, 0) != CR_SUCCESS {
+        return "Bluetooth device".to_string();
+    }
+    let mut buffer = [0u16; 256];
+    let mut length = std::mem::size_of_val(&buffer) as u32;
+    let result = CM_Get_DevNode_Registry_PropertyW(parent, CM_DRP_FRIENDLYNAME, None, Some(buffer.as_mut_ptr() as *mut _), &mut length, 0);
+    if result != CR_SUCCESS {
+        return "Bluetooth device".to_string();
+    }
+    let end = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..end])
+}
@@ -0,0 +1,140 @@
+use std::cell::Cell;
+use std::sync::Once;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use chrono::{DateTime, Duration, Local};
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Gdi::*;
+use windows::Win32::UI::WindowsAndMessaging::*;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::core::PCWSTR;
+
+static OVERLAY_HWND: AtomicIsize = AtomicIsize::new(0);
+static REGISTER: Once = Once::new();
+
+const TIMER_TICK: usize = 1;
+const WIDTH: i32 = 132;
+const HEIGHT: i32 = 30;
+const MARGIN: i32 = 8;
+
+thread_local! {
+    // What the overlay shows; set on every battery update, read by the one-second repaint
+    static PERCENTAGE: Cell<u8> = const { Cell::new(0) };
+    static EMPTY_AT: Cell<Option<DateTime<Local>>> = const { Cell::new(None) };
+}
+
+// Called from the tray update with the monitor already locked, so the overlay never locks it itself
+pub fn update(owner: HWND, percentage: u8, eta_minutes: Option<i32>, visible: bool) {
+    let existing = HWND(OVERLAY_HWND.load(Ordering::Relaxed));
+    unsafe {
+        if !visible {
+            if existing.0 != 0 {
+                let _ = DestroyWindow(existing);
+            }
+            return;
+        }
+
+        PERCENTAGE.with(|p| p.set(percentage));
+        EMPTY_AT.with(|e| e.set(eta_minutes.map(|m| Local::now() + Duration::minutes(m as i64))));
+        if existing.0 != 0 && IsWindow(existing).as_bool() {
+            InvalidateRect(existing, None, FALSE);
+        } else {
+            create(owner);
+        }
+    }
+}
+
+unsafe fn create(owner: HWND) {
+    let instance: HINSTANCE = GetModuleHandleW(PCWSTR::null()).unwrap().into();
+    let class_name = "BattestyCountdown\0".encode_utf16().collect::<Vec<u16>>();
+
+    REGISTER.call_once(|| {
+        let wc = WNDCLASSW {
+            lpfnWndProc: Some(overlay_proc),
+            hInstance: instance,
+            lpszClassName: PCWSTR(class_name.as_ptr()),
+            ..std::mem::zeroed()
+        };
+        RegisterClassW(&wc);
+    });
+
+    // Bottom-right corner of the work area, just above the clock on a default taskbar
+    let mut work_area = RECT::default();
+    let _ = SystemParametersInfoW(SPI_GETWORKAREA, 0, Some(&mut work_area as *mut RECT as *mut _), SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0));
+
+    // Layered and transparent, so clicks go to whatever is underneath
+    let hwnd = CreateWindowExW(
+        WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOPMOST | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+        PCWSTR(class_name.as_ptr()),
+        PCWSTR::null(),
+        WS_POPUP,
+        work_area.right - WIDTH - MARGIN,
+        work_area.bottom - HEIGHT - MARGIN,
+        WIDTH,
+        HEIGHT,
+        owner,
+        None,
+        instance,
+        None,
+    );
+    OVERLAY_HWND.store(hwnd.0, Ordering::Relaxed);
+    let _ = SetLayeredWindowAttributes(hwnd, COLORREF(0), 220, LWA_ALPHA);
+    SetTimer(hwnd, TIMER_TICK, 1000, None);
+    ShowWindow(hwnd, SW_SHOWNOACTIVATE);
+}
+
+unsafe extern "system" fn overlay_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_TIMER if wparam.0 == TIMER_TICK => {
+            InvalidateRect(hwnd, None, FALSE);
+            LRESULT(0)
+        }
+        WM_PAINT => {
+            let mut ps: PAINTSTRUCT = std::mem::zeroed();
+            let hdc = BeginPaint(hwnd, &mut ps);
+            paint(hwnd, hdc);
+            EndPaint(hwnd, &ps);
+            LRESULT(0)
+        }
+        WM_ERASEBKGND => LRESULT(1),
+        WM_DESTROY => {
+            let _ = KillTimer(hwnd, TIMER_TICK);
+            OVERLAY_HWND.store(0, Ordering::Relaxed);
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+unsafe fn paint(hwnd: HWND, hdc: HDC) {
+    let mut rect = RECT::default();
+    let _ = GetClientRect(hwnd, &mut rect);
+    let background = CreateSolidBrush(COLORREF(0x00202020));
+    FillRect(hdc, &rect, background);
+    DeleteObject(background);
+
+    let percentage = PERCENTAGE.with(|p| p.get());
+    let text = match EMPTY_AT.with(|e| e.get()) {
+        Some(empty_at) => {
+            let seconds = (empty_at - Local::now()).num_seconds().max(0);
+            format!("{}% · {}:{:02}:{:02}", percentage, seconds / 3600, seconds / 60 % 60, seconds % 60)
+        }
+        None => format!("{}% · estimating", percentage),
+    };
+
+    let font_name: Vec<u16> = "Segoe UI\0".encode_utf16().collect();
+    let font = CreateFontW(
+        -16, 0, 0, 0, FW_SEMIBOLD.0 as i32, 0, 0, 0,
+        DEFAULT_CHARSET.0 as u32, OUT_TT_PRECIS.0 as u32, CLIP_DEFAULT_PRECIS.0 as u32,
+        CLEARTYPE_QUALITY.0 as u32, (DEFAULT_PITCH.0 | FF_SWISS.0) as u32,
+        PCWSTR(font_name.as_ptr()),
+    );
+    let old_font = SelectObject(hdc, font);
+    SetBkMode(hdc, TRANSPARENT);
+    // Red once under 10 minutes, amber before that
+    let urgent = EMPTY_AT.with(|e| e.get()).is_some_and(|t| t - Local::now() < Duration::minutes(10));
+    SetTextColor(hdc, if urgent { COLORREF(0x005050FF) } else { COLORREF(0x0040C0FF) });
+    let mut text_wide: Vec<u16> = text.encode_utf16().collect();
+    DrawTextW(hdc, &mut text_wide, &mut rect, DT_CENTER | DT_VCENTER | DT_SINGLELINE);
+    SelectObject(hdc, old_font);
+    DeleteObject(font);
+}
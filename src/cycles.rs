@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Local};
+use crate::battery::BatteryMeasurement;
+
+const MAX_CYCLES: usize = 5000;
+
+// One full cycle: 100 percentage points discharged, in however many pieces it took
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Cycle {
+    pub started: DateTime<Local>,
+    pub completed: DateTime<Local>,
+}
+
+// Kept apart from the history, which forgets old samples while the count has to keep growing
+#[derive(Default, Serialize, Deserialize)]
+pub struct CycleLog {
+    pub total: u32,
+    pub cycles: Vec<Cycle>,
+    // Discharge counted towards the cycle in progress, and when that cycle began
+    pub partial_percent: f64,
+    pub partial_since: Option<DateTime<Local>>,
+    // Last sample looked at, so samples merged in from another instance aren't counted twice
+    pub counted_until: Option<DateTime<Local>>,
+}
+
+impl CycleLog {
+    // Adds the drop between two consecutive samples; returns the cycle it completed, if any
+    pub fn record(&mut self, previous: &BatteryMeasurement, current: &BatteryMeasurement) -> Option<Cycle> {
+        if self.counted_until.is_some_and(|t| current.timestamp <= t) {
+            return None;
+        }
+        self.counted_until = Some(current.timestamp);
+        if previous.is_charging || current.is_charging || current.percentage >= previous.percentage {
+            return None;
+        }
+
+        self.partial_since.get_or_insert(previous.timestamp);
+        self.partial_percent += (previous.percentage - current.percentage) as f64;
+        if self.partial_percent < 100.0 {
+            return None;
+        }
+        self.partial_percent -= 100.0;
+        let cycle = Cycle { started: self.partial_since.take()?, completed: current.timestamp };
+        if self.partial_percent > 0.0 {
+            self.partial_since = Some(current.timestamp);
+        }
+        self.total += 1;
+        self.cycles.push(cycle.clone());
+        Some(cycle)
+    }
+
+    pub fn summary(&self) -> String {
+        let last = match self.cycles.last() {
+            Some(cycle) => format!(", last completed {}", cycle.completed.format("%Y-%m-%d")),
+            None => String::new(),
+        };
+        format!("{} counted by Battesty ({:.0}% into the next{})", self.total, self.partial_percent, last)
+    }
+}
+
+pub fn load_cycles() -> CycleLog {
+    std::fs::read_to_string(cycles_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_cycles(log: &mut CycleLog) {
+    let excess = log.cycles.len().saturating_sub(MAX_CYCLES);
+    log.cycles.drain(..excess);
+    if let Ok(json) = serde_json::to_string_pretty(log) {
+        let _ = std::fs::write(cycles_path(), json);
+    }
+}
+
+fn cycles_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_cycles.json");
+    path
+}
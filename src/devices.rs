@@ -0,0 +1,121 @@
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use crate::battery::BatteryMonitor;
+use crate::rollup;
+
+// Every instance pointed at the same synced folder (OneDrive, Dropbox, a network share) writes
+// <machine>.battesty.json there and reads everyone else's back for the devices view
+
+const SUFFIX: &str = ".battesty.json";
+// Older than this and a device is shown as last seen rather than current
+const STALE_MINUTES: i64 = 60;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DeviceStatus {
+    pub machine: String,
+    pub updated: DateTime<Local>,
+    pub percentage: u8,
+    pub charging: bool,
+    pub eta_minutes: Option<i32>,
+    pub draw_watts: Option<f64>,
+    pub health: Option<f64>,
+    pub cycle_count: Option<u32>,
+    pub used_today_percent: Option<f64>,
+    pub version: String,
+}
+
+pub fn machine_name() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_else(|_| "This PC".to_string())
+}
+
+fn file_name(machine: &str) -> String {
+    let safe: String = machine.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect();
+    format!("{}{}", safe, SUFFIX)
+}
+
+pub fn status(mon: &BatteryMonitor) -> Option<DeviceStatus> {
+    let latest = mon.measurements.back()?;
+    Some(DeviceStatus {
+        machine: machine_name(),
+        updated: latest.timestamp,
+        percentage: latest.percentage,
+        charging: latest.is_charging,
+        eta_minutes: latest.eta_minutes,
+        draw_watts: mon.draw_watts,
+        health: mon.capacity.map(|c| c.health()),
+        cycle_count: mon.cycle_count,
+        used_today_percent: rollup::today(&mon.measurements, mon.capacity, mon.clock.now().date_naive()).map(|r| r.discharge_percent),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    })
+}
+
+// Does nothing until a sync folder is set
+pub fn export(mon: &BatteryMonitor) -> Result<(), String> {
+    let Some(folder) = mon.settings.sync_folder.as_deref() else { return Ok(()) };
+    let Some(status) = status(mon) else { return Ok(()) };
+    let path = Path::new(folder).join(file_name(&status.machine));
+    let json = serde_json::to_string_pretty(&status).map_err(|e| format!("Cannot serialize status: {}", e))?;
+    // Renamed over, so a sync client never uploads half a file
+    let temp = path.with_extension("tmp");
+    std::fs::write(&temp, json)
+        .and_then(|_| std::fs::rename(&temp, &path))
+        .map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+}
+
+// Unreadable files (mid-sync, or from a newer version) are skipped rather than failing the view
+pub fn load(folder: &str) -> Result<Vec<DeviceStatus>, String> {
+    let entries = std::fs::read_dir(folder).map_err(|e| format!("Cannot read {}: {}", folder, e))?;
+    let mut devices: Vec<DeviceStatus> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path: &PathBuf| path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.ends_with(SUFFIX)))
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .filter_map(|text| serde_json::from_str(&text).ok())
+        .collect();
+    devices.sort_by_key(|d| d.machine.to_lowercase());
+    Ok(devices)
+}
+
+pub fn summary(devices: &[DeviceStatus]) -> String {
+    if devices.is_empty() {
+        return "No devices have written to the sync folder yet.".to_string();
+    }
+    let this_machine = machine_name();
+    let now = Local::now();
+    devices
+        .iter()
+        .map(|d| {
+            let name = if d.machine == this_machine { format!("{} (this PC)", d.machine) } else { d.machine.clone() };
+            let state = match (d.charging, d.eta_minutes) {
+                (true, _) => "charging".to_string(),
+                (false, Some(minutes)) => format!("on battery · {} left", BatteryMonitor::format_time(minutes)),
+                (false, None) => "on battery".to_string(),
+            };
+            let mut line = format!("{}\n  {}% · {}", name, d.percentage, state);
+            if let Some(watts) = d.draw_watts {
+                line.push_str(&format!(" · {:.1} W", watts));
+            }
+            let mut extra = Vec::new();
+            if let Some(health) = d.health {
+                extra.push(format!("health {:.0}%", health));
+            }
+            if let Some(cycles) = d.cycle_count {
+                extra.push(format!("{} cycles", cycles));
+            }
+            if let Some(used) = d.used_today_percent {
+                extra.push(format!("{:.0}% used today", used));
+            }
+            if !extra.is_empty() {
+                line.push_str(&format!("\n  {}", extra.join(" · ")));
+            }
+            let age = (now - d.updated).num_minutes().max(0);
+            if age >= STALE_MINUTES {
+                line.push_str(&format!("\n  Last seen {} ago", BatteryMonitor::format_time(age as i32)));
+            } else {
+                line.push_str(&format!("\n  Updated {} ago · battesty {}", BatteryMonitor::format_time(age as i32), d.version));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
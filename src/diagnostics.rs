@@ -0,0 +1,206 @@
+use std::path::PathBuf;
+use chrono::{Datelike, Duration, Local, Timelike};
+use windows::Win32::UI::Shell::ShellExecuteW;
+use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+use windows::core::PCWSTR;
+use crate::battery::{BatteryMonitor, DEBUG_MODE};
+use crate::{power, vendor, versions};
+
+// How much of the recent data goes into the bundle
+const HISTORY_HOURS: i64 = 48;
+const MAX_EVENTS: usize = 200;
+const MAX_ALERTS: usize = 100;
+
+// Writes battesty_diagnostics_<time>.zip next to the exe for attaching to a GitHub issue
+pub fn collect(mon: &BatteryMonitor) -> Result<PathBuf, String> {
+    let mut zip = ZipWriter::default();
+    zip.add("system.txt", system_report(mon).as_bytes());
+    zip.add("capabilities.txt", capability_report(mon).as_bytes());
+
+    for name in ["battesty_config.json", "battesty_config.toml"] {
+        if let Ok(text) = std::fs::read_to_string(data_path(name)) {
+            zip.add(name, sanitize(&text).as_bytes());
+        }
+    }
+
+    let cutoff = Local::now() - Duration::hours(HISTORY_HOURS);
+    let history: Vec<_> = mon.measurements.iter().filter(|m| m.timestamp >= cutoff).collect();
+    let events = &mon.events[mon.events.len().saturating_sub(MAX_EVENTS)..];
+    let alerts = &mon.alerts[mon.alerts.len().saturating_sub(MAX_ALERTS)..];
+    let json_files = [
+        ("history_excerpt.json", serde_json::to_string_pretty(&history)),
+        ("events.json", serde_json::to_string_pretty(events)),
+        ("alerts.json", serde_json::to_string_pretty(alerts)),
+        ("daily.json", serde_json::to_string_pretty(&mon.daily)),
+    ];
+    for (name, json) in json_files {
+        let json = json.map_err(|e| format!("Cannot serialize {}: {}", name, e))?;
+        zip.add(name, sanitize(&json).as_bytes());
+    }
+
+    let path = data_path(&format!("battesty_diagnostics_{}.zip", Local::now().format("%Y%m%d-%H%M%S")));
+    std::fs::write(&path, zip.finish()).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+// Opens Explorer with the file selected
+pub fn reveal(path: &std::path::Path) {
+    let verb: Vec<u16> = "open\0".encode_utf16().collect();
+    let explorer: Vec<u16> = "explorer.exe\0".encode_utf16().collect();
+    let args: Vec<u16> = format!("/select,\"{}\"", path.display()).encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe {
+        ShellExecuteW(None, PCWSTR(verb.as_ptr()), PCWSTR(explorer.as_ptr()), PCWSTR(args.as_ptr()), PCWSTR::null(), SW_SHOWNORMAL);
+    }
+}
+
+fn system_report(mon: &BatteryMonitor) -> String {
+    let system = versions::read_versions();
+    let unknown = || "unknown".to_string();
+    format!(
+        "Battesty v{}{}\n\
+         Collected: {}\n\
+         Windows: {}\n\
+         Battery driver: {}\n\
+         ACPI driver: {}\n\
+         Vendor: {}\n\
+         {}\n\
+         Measurements in memory: {} · Events: {} · Daily rollups: {}\n",
+        versions::app_version(),
+        if DEBUG_MODE { " (debug build, simulated battery)" } else { "" },
+        Local::now().format("%Y-%m-%d %H:%M:%S %:z"),
+        system.os_build.unwrap_or_else(unknown),
+        system.battery_driver.unwrap_or_else(unknown),
+        system.acpi_driver.unwrap_or_else(unknown),
+        mon.vendor.label(),
+        mon.charge_limit.as_ref().map(|l| l.summary()).unwrap_or_else(|| "Charge limit: none detected".to_string()),
+        mon.measurements.len(),
+        mon.events.len(),
+        mon.daily.len(),
+    )
+}
+
+// Which of the data sources battesty relies on answer on this machine
+fn capability_report(mon: &BatteryMonitor) -> String {
+    let yes_no = |ok: bool| if ok { "available" } else { "not available" };
+    let power = power::read_power();
+    let capacity = power::read_capacity();
+    let packs = power::read_pack_levels();
+    let cycle_count = power::read_cycle_count();
+
+    let mut report = String::new();
+    report.push_str(&format!("Charge rate (BatteryStatus.ChargeRate): {}\n", yes_no(power.as_ref().is_some_and(|p| p.charge_rate_mw.is_some()))));
+    report.push_str(&format!("Discharge rate (BatteryStatus.DischargeRate): {}\n", yes_no(power.as_ref().is_some_and(|p| p.discharge_rate_mw.is_some()))));
+    report.push_str(&format!(
+        "Capacity (BatteryStaticData / BatteryFullChargedCapacity): {}\n",
+        capacity.map(|c| c.summary()).unwrap_or_else(|| yes_no(false).to_string()),
+    ));
+    report.push_str(&format!(
+        "Cycle count (BatteryCycleCount): {}\n",
+        cycle_count.map(|c| c.to_string()).unwrap_or_else(|| yes_no(false).to_string()),
+    ));
+    report.push_str(&format!("Battery packs: {}\n", packs.len().max(1)));
+    report.push_str(&format!(
+        "Charge limit control: {}\n",
+        match vendor::limit_control(mon.vendor) {
+            Some(vendor::LimitControl::LenovoConservation) => "Lenovo conservation mode",
+            Some(vendor::LimitControl::AsusLimit) => "ASUS charge limit",
+            None => "none",
+        },
+    ));
+    report
+}
+
+// Paths in the config and event messages contain the Windows user name
+fn sanitize(text: &str) -> String {
+    let mut text = text.to_string();
+    for (variable, placeholder) in [("USERPROFILE", "%USERPROFILE%"), ("COMPUTERNAME", "%COMPUTERNAME%")] {
+        let Ok(value) = std::env::var(variable) else { continue };
+        if value.len() < 3 {
+            continue;
+        }
+        // JSON escapes the backslashes
+        text = text.replace(&value.replace('\\', "\\\\"), placeholder).replace(&value, placeholder);
+    }
+    text
+}
+
+fn data_path(name: &str) -> PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push(name);
+    path
+}
+
+// Minimal ZIP writer: stored (uncompressed) entries, which every unzip tool reads
+#[derive(Default)]
+struct ZipWriter {
+    data: Vec<u8>,
+    directory: Vec<u8>,
+    entries: u16,
+}
+
+impl ZipWriter {
+    fn add(&mut self, name: &str, contents: &[u8]) {
+        let now = Local::now();
+        let time = ((now.hour() << 11) | (now.minute() << 5) | (now.second() / 2)) as u16;
+        let date = (((now.year() - 1980).max(0) as u32) << 9 | now.month() << 5 | now.day()) as u16;
+        let crc = crc32(contents);
+        let offset = self.data.len() as u32;
+        let size = contents.len() as u32;
+
+        // Local file header; bit 11 marks the name as UTF-8
+        self.data.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        for value in [20u16, 1 << 11, 0, time, date] {
+            self.data.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [crc, size, size] {
+            self.data.extend_from_slice(&value.to_le_bytes());
+        }
+        self.data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        self.data.extend_from_slice(&0u16.to_le_bytes());
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(contents);
+
+        // Central directory record
+        self.directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        for value in [20u16, 20, 1 << 11, 0, time, date] {
+            self.directory.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [crc, size, size] {
+            self.directory.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [name.len() as u16, 0, 0, 0, 0] {
+            self.directory.extend_from_slice(&value.to_le_bytes());
+        }
+        self.directory.extend_from_slice(&0u32.to_le_bytes());
+        self.directory.extend_from_slice(&offset.to_le_bytes());
+        self.directory.extend_from_slice(name.as_bytes());
+        self.entries += 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let directory_offset = self.data.len() as u32;
+        let directory_size = self.directory.len() as u32;
+        self.data.append(&mut self.directory);
+
+        self.data.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        for value in [0u16, 0, self.entries, self.entries] {
+            self.data.extend_from_slice(&value.to_le_bytes());
+        }
+        self.data.extend_from_slice(&directory_size.to_le_bytes());
+        self.data.extend_from_slice(&directory_offset.to_le_bytes());
+        self.data.extend_from_slice(&0u16.to_le_bytes());
+        self.data
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
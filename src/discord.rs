@@ -0,0 +1,120 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Mutex, OnceLock};
+use crate::settings::AppSettings;
+
+// Rich Presence over Discord's local IPC pipe: frames of (opcode, length, JSON), little-endian
+
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+
+#[derive(Clone, PartialEq)]
+pub struct Presence {
+    pub client_id: String,
+    pub details: String,
+    pub state: Option<String>,
+}
+
+static WORKER: OnceLock<Mutex<Sender<Option<Presence>>>> = OnceLock::new();
+static LAST_SENT: Mutex<Option<Presence>> = Mutex::new(None);
+
+// Builds the activity from only the fields the settings expose; None clears it
+pub fn presence(settings: &AppSettings, percentage: u8, is_charging: bool, eta: &str) -> Option<Presence> {
+    if !settings.discord_presence {
+        return None;
+    }
+    let client_id = settings.discord_client_id.clone().filter(|id| !id.trim().is_empty())?;
+    let mut parts = Vec::new();
+    if settings.discord_show_power_state {
+        parts.push(if is_charging { "Charging" } else { "On battery" }.to_string());
+    }
+    if settings.discord_show_percentage {
+        parts.push(format!("{}%", percentage));
+    }
+    let details = if parts.is_empty() { "Battery".to_string() } else { parts.join(" · ") };
+    let state = (settings.discord_show_eta && !eta.is_empty()).then(|| eta.to_string());
+    Some(Presence { client_id, details, state })
+}
+
+// Sends only changes; the pipe work happens on a worker thread so a missing Discord never blocks the UI
+pub fn update(presence: Option<Presence>) {
+    let Ok(mut last) = LAST_SENT.lock() else { return };
+    if *last == presence {
+        return;
+    }
+    *last = presence.clone();
+    let worker = WORKER.get_or_init(|| {
+        let (sender, receiver) = channel::<Option<Presence>>();
+        std::thread::spawn(move || {
+            let mut connection: Option<(File, String)> = None;
+            while let Ok(presence) = receiver.recv() {
+                let Some(presence) = presence else {
+                    // Closing the pipe removes the activity
+                    connection = None;
+                    continue;
+                };
+                if connection.as_ref().is_none_or(|(_, id)| *id != presence.client_id) {
+                    connection = connect(&presence.client_id).map(|pipe| (pipe, presence.client_id.clone()));
+                }
+                let sent = connection.as_mut().is_some_and(|(pipe, _)| set_activity(pipe, &presence).is_ok());
+                if !sent {
+                    // Discord restarted or isn't running; retry on the next change
+                    connection = None;
+                    if let Ok(mut last) = LAST_SENT.lock() {
+                        *last = None;
+                    }
+                }
+            }
+        });
+        Mutex::new(sender)
+    });
+    if let Ok(sender) = worker.lock() {
+        let _ = sender.send(presence);
+    }
+}
+
+// Discord listens on the first free of discord-ipc-0..9
+fn connect(client_id: &str) -> Option<File> {
+    (0..10).find_map(|i| {
+        let mut pipe = File::options().read(true).write(true).open(format!(r"\\.\pipe\discord-ipc-{}", i)).ok()?;
+        let handshake = serde_json::json!({ "v": 1, "client_id": client_id });
+        write_frame(&mut pipe, OP_HANDSHAKE, &handshake).ok()?;
+        // The READY reply; an error frame means the client ID was rejected
+        let (op, reply) = read_frame(&mut pipe).ok()?;
+        (op == OP_FRAME && reply["evt"] == "READY").then_some(pipe)
+    })
+}
+
+fn set_activity(pipe: &mut File, presence: &Presence) -> std::io::Result<()> {
+    let mut activity = serde_json::json!({ "details": presence.details });
+    if let Some(state) = &presence.state {
+        activity["state"] = serde_json::Value::from(state.as_str());
+    }
+    let command = serde_json::json!({
+        "cmd": "SET_ACTIVITY",
+        "args": { "pid": std::process::id(), "activity": activity },
+        "nonce": chrono::Local::now().timestamp_millis().to_string(),
+    });
+    write_frame(pipe, OP_FRAME, &command)?;
+    read_frame(pipe).map(|_| ())
+}
+
+fn write_frame(pipe: &mut File, op: u32, payload: &serde_json::Value) -> std::io::Result<()> {
+    let body = payload.to_string();
+    let mut frame = Vec::with_capacity(8 + body.len());
+    frame.extend_from_slice(&op.to_le_bytes());
+    frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    frame.extend_from_slice(body.as_bytes());
+    pipe.write_all(&frame)
+}
+
+fn read_frame(pipe: &mut File) -> std::io::Result<(u32, serde_json::Value)> {
+    let mut header = [0u8; 8];
+    pipe.read_exact(&mut header)?;
+    let op = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let mut body = vec![0u8; len];
+    pipe.read_exact(&mut body)?;
+    Ok((op, serde_json::from_slice(&body).unwrap_or_default()))
+}
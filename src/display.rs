@@ -0,0 +1,58 @@
+use crate::icon;
+
+// Smooths the percentage shown in the tray so noisy readings don't make the icon flicker
+#[derive(Default)]
+pub struct DisplayFilter {
+    shown: Option<u8>,
+    // First reading of a change, waiting for the next sample to confirm its direction
+    pending: Option<u8>,
+    // Level the icon's colour was last picked for
+    colour: Option<u8>,
+}
+
+impl DisplayFilter {
+    // `smooth` trades accuracy for a steady icon: at most 1% per update, never up on battery
+    pub fn update(&mut self, raw: u8, is_charging: bool, smooth: bool) -> u8 {
+        let Some(shown) = self.shown else {
+            self.shown = Some(raw);
+            return raw;
+        };
+        if raw == shown {
+            self.pending = None;
+            return shown;
+        }
+        if smooth {
+            self.pending = None;
+            let next = match raw.cmp(&shown) {
+                std::cmp::Ordering::Greater if is_charging => shown + 1,
+                std::cmp::Ordering::Less => shown - 1,
+                _ => shown,
+            };
+            self.shown = Some(next);
+            return next;
+        }
+
+        // A single sample that jumps away and back is a blip; two in the same direction are real
+        let confirmed = self.pending.is_some_and(|pending| (pending > shown) == (raw > shown));
+        if !confirmed {
+            self.pending = Some(raw);
+            return shown;
+        }
+
+        self.pending = None;
+        self.shown = Some(raw);
+        raw
+    }
+
+    // The number stays exact; only the colour waits until a rising reading is clear of a threshold
+    pub fn colour_level(&mut self, shown: u8) -> u8 {
+        let level = self.colour.map_or(shown, |previous| icon::colour_level(previous, shown));
+        self.colour = Some(level);
+        level
+    }
+
+    // After sleep the old value is stale, so the next reading is taken as is
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
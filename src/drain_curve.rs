@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Local};
+use crate::battery::{BatteryMeasurement, MAX_SAMPLE_GAP_MINUTES};
+use crate::charge_curve::Bucket;
+
+// Minutes per percent while discharging, learned for every level: the gauge's percent isn't
+// the same amount of energy everywhere, and packs often sag faster near the bottom. Only the
+// shape is used; how fast the machine drains right now comes from the estimator.
+
+// Newer ticks count for at least this share, so the curve follows an ageing pack
+const MIN_WEIGHT: f64 = 0.05;
+// Levels are read as the average of their neighbours within this many percent
+const SMOOTHING: u8 = 2;
+// Share of the levels still to go that must be learned before the curve is used
+const MIN_COVERAGE: f64 = 0.5;
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct DrainCurve {
+    // Index n is the percent used going from n + 1 down to n
+    pub levels: Vec<Bucket>,
+    // Time and level of the last percentage tick in the ongoing discharge
+    #[serde(skip)]
+    anchor: Option<(DateTime<Local>, u8)>,
+}
+
+impl DrainCurve {
+    // Returns true when the curve changed
+    pub fn record(&mut self, previous: &BatteryMeasurement, current: &BatteryMeasurement) -> bool {
+        if previous.is_charging || current.is_charging
+            || current.timestamp - previous.timestamp > Duration::minutes(MAX_SAMPLE_GAP_MINUTES)
+            || current.percentage > previous.percentage
+        {
+            self.anchor = None;
+            return false;
+        }
+        if current.percentage == previous.percentage {
+            return false;
+        }
+
+        let mut learned = false;
+        if let Some((since, from)) = self.anchor.filter(|(_, from)| current.percentage < *from) {
+            let minutes = (current.timestamp - since).num_seconds() as f64 / 60.0;
+            let per_percent = minutes / (from - current.percentage) as f64;
+            if self.levels.len() != 100 {
+                self.levels = vec![Bucket::default(); 100];
+            }
+            for bucket in &mut self.levels[current.percentage as usize..(from as usize).min(100)] {
+                bucket.ticks += 1;
+                let weight = (1.0 / bucket.ticks as f64).max(MIN_WEIGHT);
+                bucket.minutes_per_percent += weight * (per_percent - bucket.minutes_per_percent);
+            }
+            learned = true;
+        }
+        self.anchor = Some((current.timestamp, current.percentage));
+        learned
+    }
+
+    fn learned(&self, index: usize) -> Option<f64> {
+        self.levels.get(index).filter(|b| b.ticks > 0).map(|b| b.minutes_per_percent)
+    }
+
+    fn smoothed(&self, index: usize) -> Option<f64> {
+        let from = index.saturating_sub(SMOOTHING as usize);
+        let values: Vec<f64> = (from..=index + SMOOTHING as usize).filter_map(|i| self.learned(i)).collect();
+        (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+    }
+
+    // Minutes from `level` down to `target`, for a machine whose estimator puts it `eta` minutes
+    // from empty. The estimator's pace per percent is taken to hold at the current level and the
+    // curve bends it for the levels below; None until enough of them have been learned.
+    pub fn minutes_until(&self, level: u8, target: u8, eta: i32) -> Option<f64> {
+        if level == 0 || target >= level || eta <= 0 {
+            return None;
+        }
+        let below = target as usize..(level as usize).min(100);
+        let learned = below.clone().filter(|&i| self.learned(i).is_some()).count();
+        if (learned as f64) < MIN_COVERAGE * below.len() as f64 {
+            return None;
+        }
+        let here = self.smoothed(level as usize - 1)?;
+        let pace = eta as f64 / level as f64;
+        Some(below.map(|i| pace * self.smoothed(i).unwrap_or(here) / here).sum())
+    }
+
+    // Part of the time to empty spent getting from `level` down to `target`
+    pub fn share(&self, level: u8, target: u8) -> Option<f64> {
+        Some(self.minutes_until(level, target, 60)? / self.minutes_until(level, 0, 60)?)
+    }
+}
+
+pub fn load_curve() -> DrainCurve {
+    std::fs::read_to_string(curve_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_curve(curve: &DrainCurve) {
+    if let Ok(json) = serde_json::to_string(curve) {
+        let _ = std::fs::write(curve_path(), json);
+    }
+}
+
+fn curve_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_drain_curve.json");
+    path
+}
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Local};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Phase {
+    ScreenOn,
+    ScreenOff,
+    Asleep,
+}
+
+impl Phase {
+    fn index(self) -> usize {
+        match self {
+            Phase::ScreenOn => 0,
+            Phase::ScreenOff => 1,
+            Phase::Asleep => 2,
+        }
+    }
+}
+
+pub struct DrainTest {
+    pub armed_at: DateTime<Local>,
+    start_percentage: u8,
+    phase: Phase,
+    phase_started: DateTime<Local>,
+    phase_percentage: u8,
+    last_percentage: u8,
+    // (seconds, percentage lost) per phase, indexed by Phase::index
+    totals: [(i64, i32); 3],
+}
+
+impl DrainTest {
+    pub fn arm(percentage: u8, now: DateTime<Local>) -> Self {
+        Self {
+            armed_at: now,
+            start_percentage: percentage,
+            phase: Phase::ScreenOn,
+            phase_started: now,
+            phase_percentage: percentage,
+            last_percentage: percentage,
+            totals: [(0, 0); 3],
+        }
+    }
+
+    pub fn record(&mut self, percentage: u8) {
+        self.last_percentage = percentage;
+    }
+
+    pub fn set_phase(&mut self, phase: Phase, percentage: u8, now: DateTime<Local>) {
+        self.last_percentage = percentage;
+        if phase == self.phase {
+            return;
+        }
+        self.close_phase(percentage, now);
+        self.phase = phase;
+    }
+
+    fn close_phase(&mut self, percentage: u8, now: DateTime<Local>) {
+        let total = &mut self.totals[self.phase.index()];
+        total.0 += (now - self.phase_started).num_seconds().max(0);
+        total.1 += self.phase_percentage as i32 - percentage as i32;
+        self.phase_started = now;
+        self.phase_percentage = percentage;
+    }
+
+    pub fn summary(mut self, now: DateTime<Local>) -> String {
+        let percentage = self.last_percentage;
+        self.close_phase(percentage, now);
+
+        let elapsed = (now - self.armed_at).num_minutes();
+        let lost = self.start_percentage as i32 - percentage as i32;
+
+        let mut parts = Vec::new();
+        for (phase, label) in [(Phase::Asleep, "sleep"), (Phase::ScreenOff, "idle-screen-off"), (Phase::ScreenOn, "screen-on")] {
+            let (seconds, phase_lost) = self.totals[phase.index()];
+            if seconds > 0 {
+                parts.push(format!("{}% {} ({}h {}m)", phase_lost, label, seconds / 3600, (seconds % 3600) / 60));
+            }
+        }
+
+        format!(
+            "Lost {}% in {}h {}m ({}% → {}%)\n\n{}\n\nArmed at {}",
+            lost,
+            elapsed / 60,
+            elapsed % 60,
+            self.start_percentage,
+            percentage,
+            parts.join("\n"),
+            self.armed_at.format("%Y-%m-%d %H:%M"),
+        )
+    }
+}
@@ -0,0 +1,246 @@
+use windows::Win32::Foundation::{CloseHandle, FILETIME};
+use windows::Win32::System::Threading::{GetExitCodeProcess, GetSystemTimes, WaitForSingleObject};
+use windows::Win32::UI::Shell::{ShellExecuteExW, SEE_MASK_NOCLOSEPROCESS, SEE_MASK_NO_CONSOLE, SHELLEXECUTEINFOW};
+use windows::Win32::UI::WindowsAndMessaging::SW_HIDE;
+use windows::core::PCWSTR;
+use crate::battery::BatteryMonitor;
+use crate::{brightness, power_plan};
+
+// CPU load is sampled this many times, this far apart
+const CPU_SAMPLES: u32 = 4;
+const CPU_SAMPLE_MS: u64 = 250;
+
+// Current drain this much above the usual one is worth explaining
+const HIGH_DRAIN_RATIO: f64 = 1.3;
+const BUSY_CPU_PERCENT: f64 = 25.0;
+const BRIGHT_SCREEN_PERCENT: u8 = 70;
+const STANDBY_DRAIN_PER_HOUR: f64 = 1.0;
+const WORN_HEALTH_PERCENT: f64 = 70.0;
+
+// One likely reason; `weight` roughly tracks the extra drain in %/h, for ranking only
+pub struct Cause {
+    pub weight: f64,
+    pub title: String,
+    pub detail: String,
+    pub action: String,
+}
+
+pub struct Diagnosis {
+    pub causes: Vec<Cause>,
+    // Checks that couldn't run on this machine, listed so an empty result isn't misleading
+    pub skipped: Vec<String>,
+}
+
+impl Diagnosis {
+    pub fn summary(&self) -> String {
+        let mut text = if self.causes.is_empty() {
+            "Nothing stands out: drain, CPU load, brightness, power plan and standby all look normal.\n".to_string()
+        } else {
+            "Likely causes, most likely first:\n\n".to_string()
+        };
+        for (i, cause) in self.causes.iter().enumerate() {
+            text.push_str(&format!("{}. {}\n   {}\n   → {}\n\n", i + 1, cause.title, cause.detail, cause.action));
+        }
+        if !self.skipped.is_empty() {
+            text.push_str("Not checked:\n");
+            for skipped in &self.skipped {
+                text.push_str(&format!("• {}\n", skipped));
+            }
+        }
+        text
+    }
+}
+
+// Takes about a second for the CPU samples
+pub fn diagnose(mon: &BatteryMonitor) -> Diagnosis {
+    let mut causes = Vec::new();
+    let mut skipped = Vec::new();
+
+    let current = mon.measurements.back().filter(|m| !m.is_charging).map(|_| mon.estimate().rate as f64 / 100.0).filter(|r| *r > 0.0);
+    match (current, mon.average_drain_per_hour()) {
+        (Some(current), Some(usual)) if current > usual * HIGH_DRAIN_RATIO => causes.push(Cause {
+            weight: current - usual,
+            title: "Draining faster than usual".to_string(),
+            detail: format!("{:.1}%/h now against {:.1}%/h on average over the last week.", current, usual),
+            action: "Look at what changed recently: new apps, an external display, or a game or video running.".to_string(),
+        }),
+        (None, _) => skipped.push("Current drain: not on battery".to_string()),
+        (_, None) => skipped.push("Usual drain: needs an hour on battery in the last week".to_string()),
+        _ => {}
+    }
+
+    match cpu_busy_percent() {
+        Some(busy) if busy >= BUSY_CPU_PERCENT => causes.push(Cause {
+            weight: busy / 10.0,
+            title: "CPU is busy".to_string(),
+            detail: format!("{:.0}% average load over the last second.", busy),
+            action: "Open Task Manager, sort by CPU and close or pause what you don't need.".to_string(),
+        }),
+        Some(_) => {}
+        None => skipped.push("CPU load: GetSystemTimes failed".to_string()),
+    }
+
+    match brightness::read_built_in() {
+        Some(brightness) if brightness >= BRIGHT_SCREEN_PERCENT => causes.push(Cause {
+            weight: (brightness as f64 - 50.0) / 20.0,
+            title: "Screen brightness is high".to_string(),
+            detail: format!("The built-in display is at {}%.", brightness),
+            action: "Lower brightness or turn on Battery saver's dimming; the panel is often the largest consumer.".to_string(),
+        }),
+        Some(_) => {}
+        None => skipped.push("Brightness: no built-in display reported through WMI".to_string()),
+    }
+
+    match power_plan::active_plan() {
+        Some(plan) if plan.is_performance() => causes.push(Cause {
+            weight: 2.0,
+            title: "Performance power plan".to_string(),
+            detail: format!("\"{}\" keeps the CPU at high clocks even when idle.", plan.name),
+            action: "Switch to Balanced while on battery.".to_string(),
+        }),
+        Some(_) => {}
+        None => skipped.push("Power plan: cannot read the active scheme".to_string()),
+    }
+
+    match mon.standby_drain_per_hour() {
+        Some(rate) if rate >= STANDBY_DRAIN_PER_HOUR => causes.push(Cause {
+            weight: rate,
+            title: "Battery drains while asleep".to_string(),
+            detail: format!("{:.1}%/h lost across sleep periods in the last month.", rate),
+            action: "Run `powercfg /sleepstudy` to see what keeps waking the machine, or use hibernate for long breaks.".to_string(),
+        }),
+        Some(_) => {}
+        None => skipped.push("Standby drain: needs a few hours of sleep on battery in the last month".to_string()),
+    }
+
+    if let Some(capacity) = mon.capacity.filter(|c| c.health() < WORN_HEALTH_PERCENT) {
+        causes.push(Cause {
+            weight: (100.0 - capacity.health()) / 20.0,
+            title: "Battery is worn".to_string(),
+            detail: format!("It holds {:.0}% of its design capacity, so every hour of use costs more percent.", capacity.health()),
+            action: "Nothing software can fix; consider a replacement if runtime matters.".to_string(),
+        });
+    }
+
+    match srum_top_apps() {
+        Ok(apps) if !apps.is_empty() => causes.push(Cause {
+            weight: 1.5,
+            title: "Apps using the most energy (SRUM)".to_string(),
+            detail: apps.join(", "),
+            action: "Check these in Settings › System › Power & battery › Battery usage.".to_string(),
+        }),
+        Ok(_) => {}
+        Err(e) => skipped.push(format!("Per-app energy (SRUM): {}", e)),
+    }
+
+    causes.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+    Diagnosis { causes, skipped }
+}
+
+fn cpu_busy_percent() -> Option<f64> {
+    let ticks = |t: FILETIME| (t.dwHighDateTime as u64) << 32 | t.dwLowDateTime as u64;
+    let read = || unsafe {
+        let (mut idle, mut kernel, mut user) = (FILETIME::default(), FILETIME::default(), FILETIME::default());
+        GetSystemTimes(Some(&mut idle), Some(&mut kernel), Some(&mut user)).ok()?;
+        // Kernel time includes idle time
+        Some((ticks(idle), ticks(kernel) + ticks(user)))
+    };
+
+    let (start_idle, start_total) = read()?;
+    let mut busy = 0.0;
+    let (mut last_idle, mut last_total) = (start_idle, start_total);
+    for _ in 0..CPU_SAMPLES {
+        std::thread::sleep(std::time::Duration::from_millis(CPU_SAMPLE_MS));
+        let (idle, total) = read()?;
+        let elapsed = total.saturating_sub(last_total);
+        if elapsed > 0 {
+            busy += (1.0 - idle.saturating_sub(last_idle) as f64 / elapsed as f64) * 100.0;
+        }
+        (last_idle, last_total) = (idle, total);
+    }
+    Some(busy / CPU_SAMPLES as f64)
+}
+
+// SRUM's database is only readable through an elevated `powercfg /srumutil`; the export's
+// columns vary between builds, so every column with "Energy" in its header is summed per app
+fn srum_top_apps() -> Result<Vec<String>, String> {
+    let path = std::env::temp_dir().join("battesty_srum.csv");
+    let exit_code = run_hidden("powercfg.exe", &format!("/srumutil /csv /output \"{}\"", path.display()))
+        .map_err(|e| format!("cannot run powercfg: {}", e))?;
+    if exit_code != 0 {
+        return Err("powercfg /srumutil needs battesty to run as administrator".to_string());
+    }
+    let text = std::fs::read_to_string(&path).map_err(|e| e.to_string());
+    let _ = std::fs::remove_file(&path);
+    let text = text?;
+
+    let mut lines = text.lines();
+    let header: Vec<String> = split_csv(lines.next().unwrap_or_default());
+    let app = header.iter().position(|h| h.eq_ignore_ascii_case("AppId") || h.eq_ignore_ascii_case("App"))
+        .ok_or("unrecognised export format")?;
+    let energy: Vec<usize> = header.iter().enumerate().filter(|(_, h)| h.contains("Energy")).map(|(i, _)| i).collect();
+    if energy.is_empty() {
+        return Err("unrecognised export format".to_string());
+    }
+
+    let mut totals: std::collections::HashMap<String, f64> = std::collections::HashMap::new();
+    for line in lines {
+        let fields = split_csv(line);
+        let Some(name) = fields.get(app).filter(|n| !n.is_empty()) else { continue };
+        let sum: f64 = energy.iter().filter_map(|&i| fields.get(i)?.parse::<f64>().ok()).sum();
+        *totals.entry(name.clone()).or_default() += sum;
+    }
+
+    let mut totals: Vec<(String, f64)> = totals.into_iter().filter(|(_, e)| *e > 0.0).collect();
+    totals.sort_by(|a, b| b.1.total_cmp(&a.1));
+    Ok(totals
+        .into_iter()
+        .take(3)
+        .map(|(name, _)| {
+            // AppIds are often full paths; the file name is what users recognise
+            name.rsplit(['\\', '/']).next().unwrap_or(&name).to_string()
+        })
+        .collect())
+}
+
+// powercfg is a console program; started this way no console window flashes up
+fn run_hidden(program: &str, args: &str) -> windows::core::Result<u32> {
+    let program: Vec<u16> = program.encode_utf16().chain(std::iter::once(0)).collect();
+    let args: Vec<u16> = args.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe {
+        let mut info = SHELLEXECUTEINFOW {
+            cbSize: std::mem::size_of::<SHELLEXECUTEINFOW>() as u32,
+            fMask: SEE_MASK_NOCLOSEPROCESS | SEE_MASK_NO_CONSOLE,
+            lpFile: PCWSTR(program.as_ptr()),
+            lpParameters: PCWSTR(args.as_ptr()),
+            nShow: SW_HIDE.0,
+            ..std::mem::zeroed()
+        };
+        ShellExecuteExW(&mut info)?;
+        WaitForSingleObject(info.hProcess, 60_000);
+        let mut exit_code = 1;
+        let result = GetExitCodeProcess(info.hProcess, &mut exit_code);
+        let _ = CloseHandle(info.hProcess);
+        result.map(|_| exit_code)
+    }
+}
+
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
@@ -0,0 +1,312 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::sync::{mpsc, Mutex};
+use std::sync::atomic::{AtomicIsize, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use windows::Win32::Foundation::*;
+use windows::Win32::Storage::FileSystem::{FlushFileBuffers, ReadFile, WriteFile, PIPE_ACCESS_DUPLEX};
+use windows::Win32::System::Pipes::*;
+use windows::Win32::System::Registry::*;
+use windows::Win32::UI::WindowsAndMessaging::*;
+use windows::core::PCWSTR;
+use crate::MONITOR;
+use crate::battery::{BatteryMeasurement, ChargeState};
+use crate::companion::{self, Companion};
+use crate::notify;
+use crate::settings::AppSettings;
+use crate::ui::SNAPSHOT_COMMAND;
+
+// `battesty --engine` runs sampling, storage and alerts headless; with background_engine set the
+// tray only draws what the engine reports, so monitoring survives Explorer or tray restarts.
+// The setting also registers the engine in the user's Run key, so it starts at logon.
+// Any frontend can attach: one JSON line in ({"command": "status"}) and one JSON line back per
+// connection on \\.\pipe\battesty-engine-<user>. Commands: status, snapshot, reload, stop.
+
+const STANDALONE: u8 = 0;
+const ENGINE: u8 = 1;
+const CLIENT: u8 = 2;
+
+// A tray that can't reach the engine starts it again, but not more often than this
+const RESPAWN_INTERVAL: Duration = Duration::from_secs(60);
+// The tray asks from its UI thread, so a busy or hung engine counts as unreachable after this
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+const RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+const RUN_VALUE: &str = "BattestyEngine";
+
+static MODE: AtomicU8 = AtomicU8::new(STANDALONE);
+static ENGINE_HWND: AtomicIsize = AtomicIsize::new(0);
+static STATUS: Mutex<Option<Status>> = Mutex::new(None);
+// Alerts raised since the tray last asked; the engine has no icon to show them on
+static ALERTS: Mutex<Vec<Alert>> = Mutex::new(Vec::new());
+static SPAWNED_AT: Mutex<Option<Instant>> = Mutex::new(None);
+
+// Everything the tray needs to draw its icon and tooltip
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Status {
+    pub percentage: u8,
+    pub shown: u8,
+    // Level the icon colour is picked for; trails `shown` just past a threshold
+    #[serde(default)]
+    pub colour_level: Option<u8>,
+    pub charging: bool,
+    pub state: ChargeState,
+    #[serde(default)]
+    pub ups: bool,
+    pub eta: String,
+    pub eta_minutes: Option<i32>,
+    // Set in time-first mode, for the numeric icon
+    pub time_left: Option<String>,
+    pub tip: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Alert {
+    pub title: String,
+    pub text: String,
+    pub flags: u32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Response {
+    pub status: Option<Status>,
+    pub latest: Option<BatteryMeasurement>,
+    #[serde(default)]
+    pub alerts: Vec<Alert>,
+    #[serde(default)]
+    pub companions: Vec<Companion>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub fn is_engine() -> bool {
+    MODE.load(Ordering::Relaxed) == ENGINE
+}
+
+pub fn is_client() -> bool {
+    MODE.load(Ordering::Relaxed) == CLIENT
+}
+
+// The tray follows the setting; the engine process stays an engine
+pub fn set_client(client: bool) {
+    if !is_engine() {
+        MODE.store(if client { CLIENT } else { STANDALONE }, Ordering::Relaxed);
+    }
+}
+
+fn pipe_name() -> String {
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    let safe: String = user.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_').collect();
+    format!(r"\\.\pipe\battesty-engine-{}", safe)
+}
+
+// Called from the update on the engine side
+pub fn publish(status: Option<Status>) {
+    if is_engine() {
+        if let Ok(mut current) = STATUS.lock() {
+            *current = status;
+        }
+    }
+}
+
+// notify::show_balloon lands here in the engine
+pub fn queue_alert(title: &str, text: &str, flags: u32) {
+    if let Ok(mut alerts) = ALERTS.lock() {
+        alerts.push(Alert { title: title.to_string(), text: text.to_string(), flags });
+    }
+}
+
+pub fn set_engine() {
+    MODE.store(ENGINE, Ordering::Relaxed);
+}
+
+// Serves the pipe from its own thread for as long as the engine runs. The next instance is
+// created before a client is served, so a second client connects instead of finding the pipe busy.
+pub fn start(hwnd: HWND) {
+    ENGINE_HWND.store(hwnd.0, Ordering::Relaxed);
+    std::thread::spawn(|| {
+        let name: Vec<u16> = pipe_name().encode_utf16().chain(std::iter::once(0)).collect();
+        let mut next = None;
+        loop {
+            let Some(pipe) = next.take().or_else(|| create_instance(&name)) else {
+                std::thread::sleep(Duration::from_secs(5));
+                continue;
+            };
+            // ERROR_PIPE_CONNECTED means the client got in before the wait, which is fine
+            if let Err(e) = unsafe { ConnectNamedPipe(pipe, None) } {
+                if e.code() != ERROR_PIPE_CONNECTED.to_hresult() {
+                    let _ = unsafe { CloseHandle(pipe) };
+                    continue;
+                }
+            }
+            next = create_instance(&name);
+            let _ = unsafe { serve(pipe) };
+            unsafe {
+                let _ = FlushFileBuffers(pipe);
+                let _ = DisconnectNamedPipe(pipe);
+                let _ = CloseHandle(pipe);
+            }
+        }
+    });
+}
+
+fn create_instance(name: &[u16]) -> Option<HANDLE> {
+    let pipe = unsafe {
+        CreateNamedPipeW(
+            PCWSTR(name.as_ptr()),
+            PIPE_ACCESS_DUPLEX,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            65536,
+            4096,
+            0,
+            None,
+        )
+    };
+    (!pipe.is_invalid()).then_some(pipe)
+}
+
+unsafe fn serve(pipe: HANDLE) -> windows::core::Result<()> {
+    // The request is a single short line
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.contains(&b'\n') && request.len() < 64 * 1024 {
+        let mut read = 0u32;
+        ReadFile(pipe, Some(&mut buffer), Some(&mut read), None)?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read as usize]);
+    }
+    let command = serde_json::from_slice::<serde_json::Value>(request.split(|&b| b == b'\n').next().unwrap_or_default())
+        .ok()
+        .and_then(|v| v["command"].as_str().map(str::to_string))
+        .unwrap_or_default();
+
+    let response = handle(&command);
+    let mut json = serde_json::to_string(&response).unwrap_or_default();
+    json.push('\n');
+    let mut written = 0u32;
+    WriteFile(pipe, Some(json.as_bytes()), Some(&mut written), None)
+}
+
+fn handle(command: &str) -> Response {
+    let hwnd = HWND(ENGINE_HWND.load(Ordering::Relaxed));
+    match command {
+        "status" => {}
+        // Sent, not posted, so the status below already has the fresh sample
+        "snapshot" => unsafe {
+            SendMessageW(hwnd, WM_COMMAND, WPARAM(SNAPSHOT_COMMAND as usize), LPARAM(0));
+        },
+        // The tray changed settings or snoozes on disk
+        "reload" => {
+            if let Some(mut mon) = MONITOR.get().and_then(|m| m.lock().ok()) {
+                mon.settings = AppSettings::load();
+                mon.snoozes = notify::load_snoozes();
+                if mon.settings.companion_battery { companion::start() } else { companion::stop() }
+            }
+        }
+        "stop" => {
+            unsafe { let _ = PostMessageW(hwnd, WM_CLOSE, WPARAM(0), LPARAM(0)); }
+            return Response::default();
+        }
+        other => return Response { error: Some(format!("Unknown command '{}'", other)), ..Default::default() },
+    }
+    Response {
+        status: STATUS.lock().ok().and_then(|s| s.clone()),
+        latest: MONITOR.get().and_then(|m| m.lock().ok()).and_then(|mon| mon.measurements.back().cloned()),
+        alerts: ALERTS.lock().map(|mut a| std::mem::take(&mut *a)).unwrap_or_default(),
+        companions: companion::latest(),
+        error: None,
+    }
+}
+
+// Made on a helper thread so the caller waits at most REQUEST_TIMEOUT; a late reply is dropped
+pub fn request(command: &str) -> Result<Response, String> {
+    let (sender, receiver) = mpsc::channel();
+    let command = command.to_string();
+    std::thread::spawn(move || {
+        let _ = sender.send(exchange(&command));
+    });
+    receiver
+        .recv_timeout(REQUEST_TIMEOUT)
+        .unwrap_or_else(|_| Err("Background engine not responding".to_string()))
+}
+
+fn exchange(command: &str) -> Result<Response, String> {
+    let name = pipe_name();
+    // Busy while the engine sets up the next instance for another client
+    let mut attempts = 0;
+    let mut pipe = loop {
+        match File::options().read(true).write(true).open(&name) {
+            Ok(pipe) => break pipe,
+            Err(_) if attempts < 3 => {
+                attempts += 1;
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) => return Err(format!("Background engine not reachable: {}", e)),
+        }
+    };
+    let request = format!("{}\n", serde_json::json!({ "command": command }));
+    pipe.write_all(request.as_bytes()).map_err(|e| e.to_string())?;
+    // The reply is one line; reading to the end could fail with a broken pipe once the engine
+    // disconnects, instead of reporting end of file
+    let mut reply = String::new();
+    BufReader::new(pipe).read_line(&mut reply).map_err(|e| e.to_string())?;
+    let response: Response = serde_json::from_str(&reply).map_err(|e| format!("Bad reply from the background engine: {}", e))?;
+    match response.error {
+        Some(error) => Err(error),
+        None => Ok(response),
+    }
+}
+
+// Launches `battesty --engine` unless one already answers
+pub fn ensure_running() {
+    if request("status").is_ok() {
+        return;
+    }
+    let Ok(mut spawned_at) = SPAWNED_AT.lock() else { return };
+    if spawned_at.is_some_and(|t| t.elapsed() < RESPAWN_INTERVAL) {
+        return;
+    }
+    if let Ok(exe) = std::env::current_exe() {
+        if std::process::Command::new(exe).arg("--engine").spawn().is_ok() {
+            *spawned_at = Some(Instant::now());
+        }
+    }
+}
+
+// Off the UI thread, since the engine may be busy sampling
+pub fn settings_changed() {
+    if is_client() {
+        std::thread::spawn(|| {
+            let _ = request("reload");
+        });
+    }
+}
+
+pub fn stop() {
+    let _ = request("stop");
+}
+
+// Registers `battesty --engine` to start at logon, so monitoring begins before the tray is opened
+pub fn set_autostart(enabled: bool) {
+    let key: Vec<u16> = RUN_KEY.encode_utf16().chain(std::iter::once(0)).collect();
+    let value: Vec<u16> = RUN_VALUE.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe {
+        if !enabled {
+            let _ = RegDeleteKeyValueW(HKEY_CURRENT_USER, PCWSTR(key.as_ptr()), PCWSTR(value.as_ptr()));
+            return;
+        }
+        let Ok(exe) = std::env::current_exe() else { return };
+        let command: Vec<u16> = format!("\"{}\" --engine", exe.display()).encode_utf16().chain(std::iter::once(0)).collect();
+        let _ = RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            PCWSTR(key.as_ptr()),
+            PCWSTR(value.as_ptr()),
+            REG_SZ.0,
+            Some(command.as_ptr() as *const _),
+            (command.len() * 2) as u32,
+        );
+    }
+}
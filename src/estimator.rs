@@ -0,0 +1,533 @@
+use chrono::Duration;
+use crate::battery::{BatteryMeasurement, MAX_SAMPLE_GAP_MINUTES};
+use crate::kalman::SocFilter;
+use crate::settings::{AppSettings, EtaAlgorithm};
+
+// How much history the estimators are handed; none of them looks further back
+pub const INPUT_WINDOW_HOURS: i64 = 6;
+
+const EMA_WINDOW_HOURS: i64 = 2;
+// mW readings are averaged over this, so one busy moment doesn't swing the ETA
+const POWER_WINDOW_MINUTES: i64 = 10;
+// The filter is replayed over this much of the discharge; older readings have stopped mattering
+const KALMAN_WINDOW_HOURS: i64 = 3;
+// How far Windows' own estimate is trusted when it's blended in
+const OS_CONFIDENCE: f64 = 0.5;
+// How far from the median a pairwise rate may be, in scaled MADs, before it's a glitch
+const OUTLIER_MADS: f64 = 3.0;
+// The ETA range is drawn from the drain over this much of the discharge
+const RANGE_WINDOW_HOURS: i64 = 2;
+// Without mW readings, rates come from the drop over steps this long; shorter ones are mostly rounding
+const RANGE_STEP_MINUTES: i64 = 15;
+const RANGE_MIN_RATES: usize = 5;
+
+pub struct Estimate {
+    // Drain in hundredths of a percent per hour, positive while discharging
+    pub rate: i32,
+    pub eta_minutes: Option<i32>,
+    // 0.0 (no idea) to 1.0 (trustworthy)
+    pub confidence: f64,
+}
+
+impl Estimate {
+    fn from_rate(rate: i32, percentage: u8, confidence: f64) -> Self {
+        let eta_minutes = if rate > 0 {
+            let hours_remaining = (percentage as f64 / rate as f64) * 100.0;
+            Some((hours_remaining * 60.0) as i32)
+        } else {
+            None
+        };
+        Self { rate, eta_minutes, confidence: confidence.clamp(0.0, 1.0) }
+    }
+
+    fn from_eta(eta_minutes: i32, percentage: u8, confidence: f64) -> Self {
+        let rate = if eta_minutes > 0 {
+            (percentage as f64 / (eta_minutes as f64 / 60.0) * 100.0) as i32
+        } else {
+            0
+        };
+        Self { rate, eta_minutes: Some(eta_minutes), confidence: confidence.clamp(0.0, 1.0) }
+    }
+
+    // Same confidence, drain multiplied by `factor`
+    pub fn scaled(self, factor: f64) -> Self {
+        Self {
+            rate: (self.rate as f64 * factor) as i32,
+            eta_minutes: self.eta_minutes.map(|m| (m as f64 / factor) as i32),
+            confidence: self.confidence,
+        }
+    }
+
+    fn unknown() -> Self {
+        Self { rate: 0, eta_minutes: None, confidence: 0.0 }
+    }
+}
+
+// Samples are ordered oldest first; the last one is the current reading
+pub trait Estimator {
+    fn algorithm(&self) -> EtaAlgorithm;
+    fn estimate(&self, samples: &[BatteryMeasurement]) -> Estimate;
+}
+
+// Settings carry the tunables of the estimators that have any
+pub fn for_algorithm(algorithm: EtaAlgorithm, settings: &AppSettings) -> Box<dyn Estimator> {
+    match algorithm {
+        EtaAlgorithm::SimpleAverage => Box::new(SimpleAverage),
+        EtaAlgorithm::Ema => Box::new(Ema { half_life_secs: settings.ema_half_life_minutes.max(1.0) * 60.0 }),
+        EtaAlgorithm::Regression => Box::new(Regression::new(settings)),
+        EtaAlgorithm::Hybrid => Box::new(Hybrid { fallback: Regression::new(settings) }),
+        EtaAlgorithm::Kalman => Box::new(Kalman { process_noise: settings.kalman_process_noise.max(0.01) }),
+        EtaAlgorithm::Blended => Box::new(Blended { os_weight: settings.os_eta_weight.clamp(0.0, 1.0), regression: Regression::new(settings) }),
+        EtaAlgorithm::Energy => Box::new(Energy { fallback: Regression::new(settings) }),
+    }
+}
+
+pub fn all(settings: &AppSettings) -> Vec<Box<dyn Estimator>> {
+    [EtaAlgorithm::SimpleAverage, EtaAlgorithm::Ema, EtaAlgorithm::Regression, EtaAlgorithm::Hybrid, EtaAlgorithm::Kalman, EtaAlgorithm::Blended, EtaAlgorithm::Energy]
+        .into_iter()
+        .map(|algorithm| for_algorithm(algorithm, settings))
+        .collect()
+}
+
+// Most recent run of discharge samples covering `window` of time awake. A gap longer than
+// MAX_SAMPLE_GAP_MINUTES is sleep (standby drain is tracked on its own): the samples before it
+// are moved forward and down to meet the ones after, as if the machine had never slept.
+fn discharge_run(samples: &[BatteryMeasurement], window: Duration) -> Vec<BatteryMeasurement> {
+    let mut run: Vec<BatteryMeasurement> = Vec::new();
+    let mut shift = Duration::zero();
+    // Level drop over the latest gap, relative to the moved samples after it
+    let mut drop = 0i32;
+    for m in samples.iter().rev().take_while(|m| !m.is_charging) {
+        if let Some(newer) = run.last() {
+            let gap = newer.timestamp - (m.timestamp + shift);
+            if gap > Duration::minutes(MAX_SAMPLE_GAP_MINUTES) {
+                // Closed up to the interval just after the gap, or a minute when there's none
+                let step = run.iter().rev().nth(1).map(|n| n.timestamp - newer.timestamp).unwrap_or(Duration::minutes(1));
+                shift += gap - step.max(Duration::seconds(1));
+                drop = m.percentage as i32 - newer.percentage as i32;
+            }
+        }
+        let mut moved = m.clone();
+        moved.timestamp = m.timestamp + shift;
+        moved.percentage = (m.percentage as i32 - drop).clamp(0, u8::MAX as i32) as u8;
+        if run.first().is_some_and(|newest| newest.timestamp - moved.timestamp > window) {
+            break;
+        }
+        run.push(moved);
+    }
+    run.reverse();
+    run
+}
+
+// Drain in %/h from the driver's mW rate, when the sample has one
+fn power_drain(m: &BatteryMeasurement) -> Option<f64> {
+    Some(-(m.rate_mw? as f64) / m.full_charge_mwh? as f64 * 100.0)
+}
+
+// Best and worst case in minutes: the ETA if the 10th or the 90th percentile of recent drain
+// held from here on
+pub fn eta_range(samples: &[BatteryMeasurement]) -> Option<(i32, i32)> {
+    let run = discharge_run(samples, Duration::hours(RANGE_WINDOW_HOURS));
+    let mut rates: Vec<f64> = run.iter().filter_map(power_drain).collect();
+    if rates.len() < RANGE_MIN_RATES {
+        let step = Duration::minutes(RANGE_STEP_MINUTES);
+        rates = run
+            .iter()
+            .enumerate()
+            .filter_map(|(i, start)| {
+                let end = run[i..].iter().find(|m| m.timestamp - start.timestamp >= step)?;
+                let hours = (end.timestamp - start.timestamp).num_seconds() as f64 / 3600.0;
+                Some((start.percentage as f64 - end.percentage as f64) / hours)
+            })
+            .collect();
+    }
+    rates.retain(|rate| rate.is_finite() && *rate > 0.0);
+    if rates.len() < RANGE_MIN_RATES {
+        return None;
+    }
+    rates.sort_by(|a, b| a.total_cmp(b));
+    let percentage = current_percentage(&run) as f64;
+    Some(((percentage / percentile(&rates, 0.9) * 60.0) as i32, (percentage / percentile(&rates, 0.1) * 60.0) as i32))
+}
+
+// Nearest-rank percentile of sorted, non-empty values
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    sorted[((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1]
+}
+
+// Lower median, which is all the outlier cut needs
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    values[(values.len() - 1) / 2]
+}
+
+// Which (rate, level change) pairs to keep: a pair goes when its rate is more than OUTLIER_MADS
+// scaled median absolute deviations from the median, but only if it moved by more than a
+// percent, since single ticks between flat samples are how the gauge normally reports
+fn outlier_mask(pairs: &[(f64, f64)]) -> Vec<bool> {
+    if pairs.len() < 3 {
+        return vec![true; pairs.len()];
+    }
+    let center = median(&mut pairs.iter().map(|p| p.0).collect::<Vec<_>>());
+    let spread = 1.4826 * median(&mut pairs.iter().map(|p| (p.0 - center).abs()).collect::<Vec<_>>());
+    pairs
+        .iter()
+        .map(|(rate, change)| change.abs() <= 1.0 || (rate - center).abs() <= OUTLIER_MADS * spread)
+        .collect()
+}
+
+fn current_percentage(samples: &[BatteryMeasurement]) -> u8 {
+    samples.last().map(|m| m.percentage).unwrap_or(0)
+}
+
+pub struct SimpleAverage;
+
+impl Estimator for SimpleAverage {
+    fn algorithm(&self) -> EtaAlgorithm {
+        EtaAlgorithm::SimpleAverage
+    }
+
+    fn estimate(&self, samples: &[BatteryMeasurement]) -> Estimate {
+        let recent: Vec<_> = samples.iter().rev().take(10).collect();
+        if recent.len() < 2 {
+            return Estimate::unknown();
+        }
+
+        // Drain and level change of each pair
+        let mut pairs = Vec::new();
+        for i in 0..recent.len() - 1 {
+            let time_diff = (recent[i].timestamp - recent[i + 1].timestamp).num_seconds() as f64;
+            // Pairs across sleep would mix standby drain into the awake rate
+            let awake = time_diff <= (MAX_SAMPLE_GAP_MINUTES * 60) as f64;
+            if time_diff > 0.0 && awake && !recent[i].is_charging {
+                let percentage_diff = recent[i + 1].percentage as f64 - recent[i].percentage as f64;
+                pairs.push((percentage_diff / time_diff * 3600.0, percentage_diff));
+            }
+        }
+
+        let keep = outlier_mask(&pairs);
+        let rates: Vec<f64> = pairs.iter().zip(&keep).filter(|(_, keep)| **keep).map(|(pair, _)| pair.0).collect();
+        if rates.is_empty() {
+            return Estimate::unknown();
+        }
+        let rate = (rates.iter().sum::<f64>() / rates.len() as f64 * 100.0) as i32;
+        Estimate::from_rate(rate, current_percentage(samples), rates.len() as f64 / 9.0)
+    }
+}
+
+// Time-weighted exponential moving average of pairwise rates; a reading `half_life_secs` old
+// counts half as much as the newest
+pub struct Ema {
+    pub half_life_secs: f64,
+}
+
+impl Estimator for Ema {
+    fn algorithm(&self) -> EtaAlgorithm {
+        EtaAlgorithm::Ema
+    }
+
+    fn estimate(&self, samples: &[BatteryMeasurement]) -> Estimate {
+        // Older readings would weigh next to nothing, but long half-lives need a longer look back
+        let window = Duration::hours(EMA_WINDOW_HOURS).max(Duration::seconds((4.0 * self.half_life_secs) as i64));
+        let run = discharge_run(samples, window);
+        let mut ema: Option<f64> = None;
+        let mut covered = 0.0;
+
+        let pairs: Vec<(f64, f64)> = run
+            .windows(2)
+            .map(|pair| {
+                let time_diff = (pair[1].timestamp - pair[0].timestamp).num_seconds() as f64;
+                let change = pair[0].percentage as f64 - pair[1].percentage as f64;
+                (if time_diff > 0.0 { change / time_diff * 3600.0 } else { 0.0 }, change)
+            })
+            .collect();
+        let keep = outlier_mask(&pairs);
+
+        for ((pair, (rate, _)), keep) in run.windows(2).zip(pairs).zip(keep) {
+            let time_diff = (pair[1].timestamp - pair[0].timestamp).num_seconds() as f64;
+            if time_diff <= 0.0 || !keep {
+                continue;
+            }
+            let alpha = 1.0 - 0.5f64.powf(time_diff / self.half_life_secs);
+            ema = Some(match ema {
+                Some(prev) => prev + alpha * (rate - prev),
+                None => rate,
+            });
+            covered += time_diff;
+        }
+
+        match ema {
+            // Trust grows as the average covers a few half-lives
+            Some(rate) => Estimate::from_rate((rate * 100.0) as i32, current_percentage(samples), covered / (4.0 * self.half_life_secs)),
+            None => Estimate::unknown(),
+        }
+    }
+}
+
+// Least-squares slope of percentage over time, with R² as confidence
+pub struct Regression {
+    pub window_minutes: i64,
+    // Below this fit the slope is still reported, but no ETA is made from it
+    pub min_r_squared: f64,
+}
+
+impl Regression {
+    pub fn new(settings: &AppSettings) -> Self {
+        Self {
+            window_minutes: (settings.regression_window_minutes as i64).clamp(5, INPUT_WINDOW_HOURS * 60),
+            min_r_squared: settings.regression_min_r_squared.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Estimator for Regression {
+    fn algorithm(&self) -> EtaAlgorithm {
+        EtaAlgorithm::Regression
+    }
+
+    fn estimate(&self, samples: &[BatteryMeasurement]) -> Estimate {
+        let run = discharge_run(samples, Duration::minutes(self.window_minutes));
+        if run.len() < 3 {
+            return Estimate::unknown();
+        }
+
+        let origin = run[0].timestamp;
+        let points: Vec<(f64, f64)> = run
+            .iter()
+            .map(|m| ((m.timestamp - origin).num_seconds() as f64 / 3600.0, m.percentage as f64))
+            .collect();
+
+        let n = points.len() as f64;
+        let mean_t = points.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_p = points.iter().map(|p| p.1).sum::<f64>() / n;
+        let covariance: f64 = points.iter().map(|p| (p.0 - mean_t) * (p.1 - mean_p)).sum();
+        let variance_t: f64 = points.iter().map(|p| (p.0 - mean_t).powi(2)).sum();
+        let variance_p: f64 = points.iter().map(|p| (p.1 - mean_p).powi(2)).sum();
+        if variance_t <= 0.0 {
+            return Estimate::unknown();
+        }
+
+        let r_squared = if variance_p > 0.0 {
+            covariance * covariance / (variance_t * variance_p)
+        } else {
+            0.0
+        };
+
+        // Slope is negative while discharging; rates are stored as positive drain
+        let rate = (-covariance / variance_t * 100.0) as i32;
+        let estimate = Estimate::from_rate(rate, current_percentage(samples), r_squared);
+        if r_squared < self.min_r_squared {
+            return Estimate { eta_minutes: None, ..estimate };
+        }
+        estimate
+    }
+}
+
+// The battery driver's mW rate over the full-charge capacity, which moves as soon as the load
+// does; the percentage regression only stands in when there is no rate
+pub struct Hybrid {
+    pub fallback: Regression,
+}
+
+impl Estimator for Hybrid {
+    fn algorithm(&self) -> EtaAlgorithm {
+        EtaAlgorithm::Hybrid
+    }
+
+    fn estimate(&self, samples: &[BatteryMeasurement]) -> Estimate {
+        let run = discharge_run(samples, Duration::minutes(POWER_WINDOW_MINUTES));
+        // %/h per sample
+        let rates: Vec<f64> = run
+            .iter()
+            .filter_map(power_drain)
+            .filter(|rate| *rate > 0.0)
+            .collect();
+        if rates.is_empty() {
+            return self.fallback.estimate(samples);
+        }
+        let rate = rates.iter().sum::<f64>() / rates.len() as f64;
+        // A direct reading, so a few samples are already trustworthy
+        Estimate::from_rate((rate * 100.0) as i32, current_percentage(samples), rates.len() as f64 / 3.0)
+    }
+}
+
+// Percentage readings and the mW rate fused into a smoothed level and drain; the ETA runs from
+// the filtered level, so it moves smoothly between whole-percent ticks
+pub struct Kalman {
+    pub process_noise: f64,
+}
+
+impl Estimator for Kalman {
+    fn algorithm(&self) -> EtaAlgorithm {
+        EtaAlgorithm::Kalman
+    }
+
+    fn estimate(&self, samples: &[BatteryMeasurement]) -> Estimate {
+        let run = discharge_run(samples, Duration::hours(KALMAN_WINDOW_HOURS));
+        let Some(first) = run.first().filter(|_| run.len() >= 2) else {
+            return Estimate::unknown();
+        };
+
+        let mut filter = SocFilter::new(first.percentage as f64);
+        let mut previous = first.timestamp;
+        for m in &run {
+            let hours = (m.timestamp - previous).num_seconds() as f64 / 3600.0;
+            if hours > 0.0 {
+                filter.predict(hours, self.process_noise);
+            }
+            previous = m.timestamp;
+            filter.observe_percentage(m.percentage as f64);
+            if let Some(drain) = power_drain(m) {
+                filter.observe_drain(drain);
+            }
+        }
+
+        let (level, drain) = (filter.level().max(0.0), filter.drain());
+        let rate = (drain * 100.0) as i32;
+        if drain <= 0.0 {
+            return Estimate { rate, eta_minutes: None, confidence: 0.0 };
+        }
+        Estimate {
+            rate,
+            eta_minutes: Some((level / drain * 60.0) as i32),
+            confidence: (1.0 - filter.drain_deviation() / drain).clamp(0.0, 1.0),
+        }
+    }
+}
+
+// Weighted mean of the regression ETA and the BatteryLifeTime Windows reported with the newest
+// sample; whichever one exists is used alone when the other is missing
+pub struct Blended {
+    pub os_weight: f64,
+    pub regression: Regression,
+}
+
+impl Estimator for Blended {
+    fn algorithm(&self) -> EtaAlgorithm {
+        EtaAlgorithm::Blended
+    }
+
+    fn estimate(&self, samples: &[BatteryMeasurement]) -> Estimate {
+        let ours = self.regression.estimate(samples);
+        let os_eta = samples.last().filter(|m| !m.is_charging).and_then(|m| m.os_eta_minutes).filter(|eta| *eta > 0);
+        let percentage = current_percentage(samples);
+        match (ours.eta_minutes, os_eta) {
+            (Some(eta), Some(os_eta)) => {
+                let blended = self.os_weight * os_eta as f64 + (1.0 - self.os_weight) * eta as f64;
+                let confidence = self.os_weight * OS_CONFIDENCE + (1.0 - self.os_weight) * ours.confidence;
+                Estimate::from_eta(blended.round() as i32, percentage, confidence)
+            }
+            (None, Some(os_eta)) if self.os_weight > 0.0 => Estimate::from_eta(os_eta, percentage, OS_CONFIDENCE * self.os_weight),
+            _ => ours,
+        }
+    }
+}
+
+// Remaining energy over the average mW drain: a percent near full or empty doesn't hold the same
+// energy as one in the middle, so this stays right where percentage-based slopes bend. Without
+// mWh readings the regression stands in.
+pub struct Energy {
+    pub fallback: Regression,
+}
+
+impl Estimator for Energy {
+    fn algorithm(&self) -> EtaAlgorithm {
+        EtaAlgorithm::Energy
+    }
+
+    fn estimate(&self, samples: &[BatteryMeasurement]) -> Estimate {
+        let run = discharge_run(samples, Duration::minutes(POWER_WINDOW_MINUTES));
+        let remaining = run.last().and_then(|m| m.remaining_mwh);
+        let drains: Vec<f64> = run
+            .iter()
+            .filter_map(|m| m.rate_mw)
+            .map(|mw| -(mw as f64))
+            .filter(|mw| *mw > 0.0)
+            .collect();
+        let Some(remaining) = remaining.filter(|_| !drains.is_empty()) else {
+            return self.fallback.estimate(samples);
+        };
+        let drain_mw = drains.iter().sum::<f64>() / drains.len() as f64;
+        let eta_minutes = (remaining as f64 / drain_mw * 60.0) as i32;
+        Estimate::from_eta(eta_minutes, current_percentage(samples), drains.len() as f64 / 3.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace;
+
+    // 12%/h from 96% for three hours, read every five minutes as with power notifications on:
+    // 60% left, so 300 minutes to empty
+    fn steady() -> Vec<BatteryMeasurement> {
+        let mut samples = trace::discharge(trace::start(), 96.0, 12.0, 180, 5);
+        samples.last_mut().unwrap().os_eta_minutes = Some(300);
+        samples
+    }
+
+    fn percentage_only(mut samples: Vec<BatteryMeasurement>) -> Vec<BatteryMeasurement> {
+        for m in &mut samples {
+            m.rate_mw = None;
+            m.remaining_mwh = None;
+            m.os_eta_minutes = None;
+        }
+        samples
+    }
+
+    fn assert_near(algorithm: EtaAlgorithm, eta: Option<i32>, expected: i32) {
+        let eta = eta.unwrap_or_else(|| panic!("{} gave no ETA", algorithm.label()));
+        assert!((eta - expected).abs() <= expected / 10, "{} gave {} minutes, expected about {}", algorithm.label(), eta, expected);
+    }
+
+    #[test]
+    fn every_estimator_follows_a_steady_drain() {
+        let samples = steady();
+        assert_eq!(samples.last().unwrap().percentage, 60);
+        for estimator in all(&AppSettings::default()) {
+            let estimate = estimator.estimate(&samples);
+            assert_near(estimator.algorithm(), estimate.eta_minutes, 300);
+            assert!(estimate.confidence > 0.0, "{} has no confidence", estimator.algorithm().label());
+        }
+    }
+
+    // Without mW readings the rate-based estimators fall back to the percentage slope
+    #[test]
+    fn percentage_only_trace() {
+        let samples = percentage_only(steady());
+        for estimator in all(&AppSettings::default()) {
+            assert_near(estimator.algorithm(), estimator.estimate(&samples).eta_minutes, 300);
+        }
+    }
+
+    // Two hours asleep losing 2% in between mustn't count as awake drain
+    #[test]
+    fn sleep_gap_is_closed_up() {
+        let mut samples = trace::discharge(trace::start(), 96.0, 12.0, 90, 5);
+        let woke = samples.last().unwrap().timestamp + Duration::hours(2);
+        samples.extend(trace::discharge(woke, 76.0, 12.0, 90, 5));
+        let level = samples.last().unwrap().percentage as i32;
+        let expected = level * 5;
+        for algorithm in [EtaAlgorithm::SimpleAverage, EtaAlgorithm::Ema, EtaAlgorithm::Regression, EtaAlgorithm::Kalman] {
+            let estimator = for_algorithm(algorithm, &AppSettings::default());
+            assert_near(algorithm, estimator.estimate(&samples).eta_minutes, expected);
+        }
+    }
+
+    #[test]
+    fn no_eta_without_a_discharge() {
+        let charging = trace::charge(trace::start(), 40.0, 30.0, 60, 5);
+        // With an mW reading one sample is enough for the rate-based estimators
+        let single = &percentage_only(steady())[..1];
+        for estimator in all(&AppSettings::default()) {
+            assert_eq!(estimator.estimate(single).eta_minutes, None, "{} guessed from one sample", estimator.algorithm().label());
+            assert_eq!(estimator.estimate(&charging).eta_minutes, None, "{} gave an ETA while charging", estimator.algorithm().label());
+        }
+    }
+
+    #[test]
+    fn range_brackets_the_estimate() {
+        let (best, worst) = eta_range(&steady()).unwrap();
+        assert!(best <= worst && best <= 330 && worst >= 270, "range {}–{}", best, worst);
+    }
+}
@@ -0,0 +1,210 @@
+use std::sync::Once;
+use std::sync::atomic::{AtomicIsize, AtomicUsize, Ordering};
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Gdi::*;
+use windows::Win32::UI::Input::KeyboardAndMouse::SetFocus;
+use windows::Win32::UI::WindowsAndMessaging::*;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::core::PCWSTR;
+
+use crate::events::{EventKind, EVENT_KINDS};
+use crate::prompt;
+use crate::versions;
+use crate::MONITOR;
+use crate::icon::app_icon;
+
+static LOG_HWND: AtomicIsize = AtomicIsize::new(0);
+static FILTER_HWND: AtomicIsize = AtomicIsize::new(0);
+static LIST_HWND: AtomicIsize = AtomicIsize::new(0);
+// Event count at the last fill, so the timer only refills on change
+static SHOWN_EVENTS: AtomicUsize = AtomicUsize::new(usize::MAX);
+static REGISTER: Once = Once::new();
+
+const ID_FILTER: i32 = 100;
+const ID_LIST: i32 = 101;
+const ID_ADD_NOTE: i32 = 102;
+const ID_FILTER_LABEL: i32 = 103;
+const ID_LIST_LABEL: i32 = 104;
+
+pub fn show_event_log(owner: HWND) {
+    unsafe {
+        let existing = HWND(LOG_HWND.load(Ordering::Relaxed));
+        if existing.0 != 0 && IsWindow(existing).as_bool() {
+            ShowWindow(existing, SW_SHOWNORMAL);
+            SetForegroundWindow(existing);
+            return;
+        }
+
+        let instance: HINSTANCE = GetModuleHandleW(PCWSTR::null()).unwrap().into();
+        let class_name = "BattestyEventLog\0".encode_utf16().collect::<Vec<u16>>();
+
+        REGISTER.call_once(|| {
+            let wc = WNDCLASSW {
+                lpfnWndProc: Some(log_proc),
+                hInstance: instance,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                hbrBackground: HBRUSH((COLOR_BTNFACE.0 + 1) as isize),
+                hCursor: LoadCursorW(None, IDC_ARROW).unwrap_or_default(),
+                hIcon: app_icon(),
+                ..std::mem::zeroed()
+            };
+            RegisterClassW(&wc);
+        });
+
+        let title = format!("Battesty v{} - Event Log\0", versions::app_version()).encode_utf16().collect::<Vec<u16>>();
+        let hwnd = CreateWindowExW(
+            WS_EX_CONTROLPARENT,
+            PCWSTR(class_name.as_ptr()),
+            PCWSTR(title.as_ptr()),
+            WS_OVERLAPPEDWINDOW,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            640,
+            480,
+            owner,
+            None,
+            instance,
+            None,
+        );
+        LOG_HWND.store(hwnd.0, Ordering::Relaxed);
+
+        let font = GetStockObject(DEFAULT_GUI_FONT);
+        let child = |class: &str, text: &str, style: WINDOW_STYLE, id: i32| {
+            let class_wide: Vec<u16> = class.encode_utf16().chain(std::iter::once(0)).collect();
+            let text_wide: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+            let control = CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                PCWSTR(class_wide.as_ptr()),
+                PCWSTR(text_wide.as_ptr()),
+                WS_CHILD | WS_VISIBLE | style,
+                0, 0, 0, 0,
+                hwnd,
+                HMENU(id as isize),
+                instance,
+                None,
+            );
+            SendMessageW(control, WM_SETFONT, WPARAM(font.0 as usize), LPARAM(1));
+            control
+        };
+
+        // Screen readers name each control after the label created just before it
+        child("STATIC", "Show", WINDOW_STYLE(0), ID_FILTER_LABEL);
+        let filter = child("COMBOBOX", "", WS_TABSTOP | WS_VSCROLL | WINDOW_STYLE(CBS_DROPDOWNLIST as u32), ID_FILTER);
+        child("BUTTON", "Add note...", WS_TABSTOP | WINDOW_STYLE(BS_PUSHBUTTON as u32), ID_ADD_NOTE);
+        child("STATIC", "Events, newest first", WINDOW_STYLE(0), ID_LIST_LABEL);
+        let list = child("LISTBOX", "", WS_BORDER | WS_VSCROLL | WS_TABSTOP | WINDOW_STYLE((LBS_NOINTEGRALHEIGHT | LBS_NOTIFY) as u32), ID_LIST);
+
+        let options = std::iter::once("All events").chain(EVENT_KINDS.iter().map(|kind| kind.label()));
+        for option in options {
+            let label: Vec<u16> = option.encode_utf16().chain(std::iter::once(0)).collect();
+            SendMessageW(filter, CB_ADDSTRING, WPARAM(0), LPARAM(label.as_ptr() as isize));
+        }
+        SendMessageW(filter, CB_SETCURSEL, WPARAM(0), LPARAM(0));
+
+        FILTER_HWND.store(filter.0, Ordering::Relaxed);
+        LIST_HWND.store(list.0, Ordering::Relaxed);
+        layout(hwnd);
+        fill();
+
+        ShowWindow(hwnd, SW_SHOWNORMAL);
+        SetForegroundWindow(hwnd);
+        SetFocus(filter);
+    }
+}
+
+// Picks up events logged since the window was filled, if it is open
+pub fn refresh() {
+    if LOG_HWND.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    let count = MONITOR.get()
+        .and_then(|m| m.lock().ok())
+        .map(|mon| mon.events.len())
+        .unwrap_or(0);
+    if count != SHOWN_EVENTS.load(Ordering::Relaxed) {
+        fill();
+    }
+}
+
+unsafe extern "system" fn log_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_COMMAND => {
+            let id = (wparam.0 & 0xFFFF) as i32;
+            let code = ((wparam.0 >> 16) & 0xFFFF) as u32;
+            if id == ID_FILTER && code == CBN_SELCHANGE {
+                fill();
+            } else if id == ID_ADD_NOTE {
+                add_note(hwnd);
+            }
+            LRESULT(0)
+        }
+        WM_SIZE => {
+            layout(hwnd);
+            LRESULT(0)
+        }
+        WM_DESTROY => {
+            LOG_HWND.store(0, Ordering::Relaxed);
+            SHOWN_EVENTS.store(usize::MAX, Ordering::Relaxed);
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+unsafe fn layout(hwnd: HWND) {
+    let mut client = RECT::default();
+    let _ = GetClientRect(hwnd, &mut client);
+    let width = client.right - client.left;
+    let height = client.bottom - client.top;
+
+    let _ = MoveWindow(GetDlgItem(hwnd, ID_FILTER_LABEL), 8, 12, 32, 20, TRUE);
+    let _ = MoveWindow(HWND(FILTER_HWND.load(Ordering::Relaxed)), 42, 8, 180, 200, TRUE);
+    let _ = MoveWindow(GetDlgItem(hwnd, ID_ADD_NOTE), width - 108, 8, 100, 24, TRUE);
+    let _ = MoveWindow(GetDlgItem(hwnd, ID_LIST_LABEL), 8, 40, 200, 16, TRUE);
+    let _ = MoveWindow(HWND(LIST_HWND.load(Ordering::Relaxed)), 8, 58, width - 16, height - 66, TRUE);
+}
+
+fn selected_kind() -> Option<EventKind> {
+    let filter = HWND(FILTER_HWND.load(Ordering::Relaxed));
+    let index = unsafe { SendMessageW(filter, CB_GETCURSEL, WPARAM(0), LPARAM(0)).0 };
+    // Entry 0 is "All events"
+    if index <= 0 {
+        None
+    } else {
+        EVENT_KINDS.get(index as usize - 1).copied()
+    }
+}
+
+// Newest first, restricted to the selected kind
+fn fill() {
+    let Some(mon) = MONITOR.get().and_then(|m| m.lock().ok()) else { return };
+    let list = HWND(LIST_HWND.load(Ordering::Relaxed));
+    let kind = selected_kind();
+
+    unsafe {
+        SendMessageW(list, WM_SETREDRAW, WPARAM(0), LPARAM(0));
+        SendMessageW(list, LB_RESETCONTENT, WPARAM(0), LPARAM(0));
+        for event in mon.events.iter().rev().filter(|e| kind.is_none_or(|k| e.kind == k)) {
+            let line: Vec<u16> = event.summary().encode_utf16().chain(std::iter::once(0)).collect();
+            SendMessageW(list, LB_ADDSTRING, WPARAM(0), LPARAM(line.as_ptr() as isize));
+        }
+        SendMessageW(list, WM_SETREDRAW, WPARAM(1), LPARAM(0));
+        InvalidateRect(list, None, TRUE);
+    }
+    SHOWN_EVENTS.store(mon.events.len(), Ordering::Relaxed);
+}
+
+fn add_note(hwnd: HWND) {
+    let Some(text) = prompt::prompt_text(hwnd, "Add Note", "Note (e.g. \"new charger\", \"BIOS update\"):", "") else {
+        return;
+    };
+    if text.trim().is_empty() {
+        return;
+    }
+    if let Some(monitor) = MONITOR.get() {
+        if let Ok(mut mon) = monitor.lock() {
+            mon.log_event(EventKind::Note, text.trim());
+        }
+    }
+    fill();
+}
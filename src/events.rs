@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Local};
+
+const MAX_EVENTS: usize = 2000;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EventKind {
+    AcConnected,
+    AcDisconnected,
+    Suspend,
+    Resume,
+    Alert,
+    Anomaly,
+    Note,
+    SystemUpdate,
+    PowerPlan,
+    Boot,
+}
+
+pub const EVENT_KINDS: [EventKind; 10] = [
+    EventKind::AcConnected,
+    EventKind::AcDisconnected,
+    EventKind::Suspend,
+    EventKind::Resume,
+    EventKind::Alert,
+    EventKind::Anomaly,
+    EventKind::Note,
+    EventKind::SystemUpdate,
+    EventKind::PowerPlan,
+    EventKind::Boot,
+];
+
+impl EventKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            EventKind::AcConnected => "Plugged in",
+            EventKind::AcDisconnected => "Unplugged",
+            EventKind::Suspend => "Suspend",
+            EventKind::Resume => "Resume",
+            EventKind::Alert => "Alert",
+            EventKind::Anomaly => "Anomaly",
+            EventKind::Note => "Note",
+            EventKind::SystemUpdate => "System update",
+            EventKind::PowerPlan => "Power plan",
+            EventKind::Boot => "Boot",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub timestamp: DateTime<Local>,
+    pub kind: EventKind,
+    pub percentage: Option<u8>,
+    pub message: String,
+}
+
+impl Event {
+    pub fn summary(&self) -> String {
+        let percentage = self.percentage.map(|p| format!(" ({}%)", p)).unwrap_or_default();
+        if self.message.is_empty() {
+            format!("{}  {}{}", self.timestamp.format("%Y-%m-%d %H:%M"), self.kind.label(), percentage)
+        } else {
+            format!("{}  {}{}: {}", self.timestamp.format("%Y-%m-%d %H:%M"), self.kind.label(), percentage, self.message)
+        }
+    }
+}
+
+pub fn load_events() -> Vec<Event> {
+    std::fs::read_to_string(events_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_events(events: &[Event]) {
+    let start = events.len().saturating_sub(MAX_EVENTS);
+    if let Ok(json) = serde_json::to_string(&events[start..]) {
+        let _ = std::fs::write(events_path(), json);
+    }
+}
+
+fn events_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_events.json");
+    path
+}
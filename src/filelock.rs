@@ -0,0 +1,56 @@
+use std::path::Path;
+use windows::Win32::Foundation::{CloseHandle, HANDLE};
+use windows::Win32::Storage::FileSystem::*;
+use windows::Win32::System::IO::OVERLAPPED;
+use windows::core::PCWSTR;
+
+// Advisory lock on a separate `<file>.lock`, so the data file itself can still be replaced
+// by rename while it is held. Released on drop.
+pub struct FileLock {
+    handle: HANDLE,
+}
+
+impl FileLock {
+    // Exclusive for writers, shared for readers. A single attempt, since callers run on the UI
+    // thread: None while another instance holds it, and writers leave their data for the next
+    // save while readers go ahead, as the stores are only ever replaced by rename.
+    pub fn acquire(data_file: &Path, exclusive: bool) -> Option<FileLock> {
+        let lock_path = data_file.with_extension("lock");
+        let path_wide: Vec<u16> = lock_path.as_os_str().to_string_lossy().encode_utf16().chain(std::iter::once(0)).collect();
+        let flags = if exclusive {
+            LOCK_FILE_FLAGS(LOCKFILE_EXCLUSIVE_LOCK.0 | LOCKFILE_FAIL_IMMEDIATELY.0)
+        } else {
+            LOCKFILE_FAIL_IMMEDIATELY
+        };
+
+        unsafe {
+            let handle = CreateFileW(
+                PCWSTR(path_wide.as_ptr()),
+                (FILE_GENERIC_READ | FILE_GENERIC_WRITE).0,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                None,
+                OPEN_ALWAYS,
+                FILE_ATTRIBUTE_NORMAL,
+                None,
+            )
+            .ok()?;
+
+            let mut overlapped: OVERLAPPED = std::mem::zeroed();
+            if LockFileEx(handle, flags, 0, 1, 0, &mut overlapped).is_ok() {
+                return Some(FileLock { handle });
+            }
+            let _ = CloseHandle(handle);
+            None
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        unsafe {
+            let mut overlapped: OVERLAPPED = std::mem::zeroed();
+            let _ = UnlockFileEx(self.handle, 0, 1, 0, &mut overlapped);
+            let _ = CloseHandle(self.handle);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use windows::Win32::Foundation::FILETIME;
+use windows::Win32::System::ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+use windows::Win32::System::Threading::*;
+
+// Updates before the GDI baseline is taken, so startup allocations settle first
+const GDI_WARMUP_UPDATES: u32 = 10;
+// Open windows legitimately hold fonts, brushes and bitmaps on top of the baseline
+const GDI_SLACK: u32 = 100;
+
+static UPDATES: AtomicU32 = AtomicU32::new(0);
+static GDI_BASELINE: AtomicU32 = AtomicU32::new(0);
+
+// What battesty itself costs, shown so users can tell the monitor isn't the drain
+pub struct Footprint {
+    pub working_set_bytes: usize,
+    pub handles: u32,
+    pub gdi_objects: u32,
+    pub user_objects: u32,
+    pub cpu_seconds: f64,
+    pub uptime_seconds: f64,
+}
+
+impl Footprint {
+    pub fn summary(&self) -> String {
+        let cpu_share = if self.uptime_seconds > 0.0 { self.cpu_seconds / self.uptime_seconds * 100.0 } else { 0.0 };
+        format!(
+            "Battesty Itself\n\
+             Memory: {:.1} MB · Handles: {} · GDI/USER objects: {}/{}\n\
+             CPU time: {:.1}s ({:.3}% of the {:.1}h it has been running)\n",
+            self.working_set_bytes as f64 / 1_048_576.0,
+            self.handles,
+            self.gdi_objects,
+            self.user_objects,
+            self.cpu_seconds,
+            cpu_share,
+            self.uptime_seconds / 3600.0,
+        )
+    }
+}
+
+pub fn measure() -> Option<Footprint> {
+    unsafe {
+        let process = GetCurrentProcess();
+
+        let mut memory: PROCESS_MEMORY_COUNTERS = std::mem::zeroed();
+        GetProcessMemoryInfo(process, &mut memory, std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32).ok()?;
+
+        let mut handles = 0;
+        GetProcessHandleCount(process, &mut handles).ok()?;
+
+        let mut created = FILETIME::default();
+        let mut exited = FILETIME::default();
+        let mut kernel = FILETIME::default();
+        let mut user = FILETIME::default();
+        GetProcessTimes(process, &mut created, &mut exited, &mut kernel, &mut user).ok()?;
+
+        // FILETIME counts 100 ns ticks; the creation time is since 1601, like the system clock
+        let ticks = |t: FILETIME| ((t.dwHighDateTime as u64) << 32 | t.dwLowDateTime as u64) as f64 / 10_000_000.0;
+        let now = windows::Win32::System::SystemInformation::GetSystemTimeAsFileTime();
+
+        Some(Footprint {
+            working_set_bytes: memory.WorkingSetSize,
+            handles,
+            gdi_objects: GetGuiResources(process, GR_GDIOBJECTS),
+            user_objects: GetGuiResources(process, GR_USEROBJECTS),
+            cpu_seconds: ticks(kernel) + ticks(user),
+            uptime_seconds: (ticks(now) - ticks(created)).max(0.0),
+        })
+    }
+}
+
+// Called on every tray update; in debug builds a steadily growing GDI count (a leaked icon or
+// DC per update) trips the assertion long before it exhausts the 10,000-object quota
+pub fn check_gdi_growth() {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let count = unsafe { GetGuiResources(GetCurrentProcess(), GR_GDIOBJECTS) };
+    let updates = UPDATES.fetch_add(1, Ordering::Relaxed) + 1;
+    if updates == GDI_WARMUP_UPDATES {
+        GDI_BASELINE.store(count, Ordering::Relaxed);
+    } else if updates > GDI_WARMUP_UPDATES {
+        let baseline = GDI_BASELINE.load(Ordering::Relaxed);
+        debug_assert!(
+            count <= baseline + GDI_SLACK,
+            "GDI objects grew from {} to {} over {} updates",
+            baseline,
+            count,
+            updates - GDI_WARMUP_UPDATES,
+        );
+    }
+}
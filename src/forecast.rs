@@ -0,0 +1,62 @@
+use chrono::{DateTime, Duration, Local, NaiveTime};
+use crate::estimator::Estimate;
+
+pub struct Verdict {
+    pub target: DateTime<Local>,
+    pub lasts: bool,
+    // Minutes of battery left over at the target (negative when it runs out first)
+    pub margin_minutes: i64,
+    pub percentage_at_target: f64,
+}
+
+pub fn parse_target(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text.trim(), "%H:%M").ok()
+}
+
+// Next time the wall clock shows `time`, today or tomorrow
+pub fn next_occurrence(time: NaiveTime, now: DateTime<Local>) -> DateTime<Local> {
+    let today = now.date_naive().and_time(time).and_local_timezone(Local).earliest();
+    match today {
+        Some(target) if target > now => target,
+        _ => (now.date_naive() + Duration::days(1))
+            .and_time(time)
+            .and_local_timezone(Local)
+            .earliest()
+            .unwrap_or(now + Duration::days(1)),
+    }
+}
+
+pub fn will_it_last(percentage: u8, estimate: &Estimate, target: DateTime<Local>, now: DateTime<Local>) -> Option<Verdict> {
+    let eta_minutes = estimate.eta_minutes? as i64;
+    let minutes_until = (target - now).num_minutes();
+    let drain_per_minute = estimate.rate as f64 / 100.0 / 60.0;
+
+    Some(Verdict {
+        target,
+        lasts: eta_minutes >= minutes_until,
+        margin_minutes: eta_minutes - minutes_until,
+        percentage_at_target: (percentage as f64 - drain_per_minute * minutes_until as f64).max(0.0),
+    })
+}
+
+impl Verdict {
+    pub fn summary(&self) -> String {
+        let margin = self.margin_minutes.abs();
+        if self.lasts {
+            format!(
+                "Lasts until {} (+{}h {}m, ~{:.0}% left)",
+                self.target.format("%H:%M"),
+                margin / 60,
+                margin % 60,
+                self.percentage_at_target,
+            )
+        } else {
+            format!(
+                "Won't last until {} (short by {}h {}m)",
+                self.target.format("%H:%M"),
+                margin / 60,
+                margin % 60,
+            )
+        }
+    }
+}
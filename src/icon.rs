@@ -1,8 +1,21 @@
 use windows::Win32::Graphics::Gdi::*;
 use windows::Win32::UI::WindowsAndMessaging::*;
 use windows::Win32::Foundation::*;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::core::PCWSTR;
 
-const CANVAS_SIZE: i32 = 64; // 16x16 base (scales to 64x64 for taskbar)
+use crate::battery::ChargeState;
+use crate::settings::{AppSettings, IconStyle};
+
+const SMALL_ICON_SIZE: i32 = 16;
+// Tray icon sizes at 100/125/150/200% scaling; each is drawn natively instead of scaled down
+const ICON_SIZES: [i32; 4] = [16, 20, 24, 32];
+
+// Fill turns red below URGENT_LEVEL and orange below WARNING_LEVEL
+pub const URGENT_LEVEL: u8 = 5;
+pub const WARNING_LEVEL: u8 = 15;
+// How far past a colour threshold a rising reading must get before the icon changes colour
+const THRESHOLD_HYSTERESIS: u8 = 2;
 
 // Convert relative coordinates (0.0-1.0) to canvas pixels
 #[inline]
@@ -10,11 +23,64 @@ fn rel(val: f32, canvas: i32) -> i32 {
     (val * canvas as f32).round() as i32
 }
 
-pub fn create_battery_icon(hdc: HDC, percentage: u8, is_charging: bool) -> HICON {
+// Fills a glyph polygon and marks it opaque in the mask
+unsafe fn draw_glyph(hdc_mem: HDC, hdc_mask: HDC, color: COLORREF, points: &[POINT]) {
+    let brush = CreateSolidBrush(color);
+    SelectObject(hdc_mem, brush);
+    SelectObject(hdc_mem, GetStockObject(NULL_PEN));
+    Polygon(hdc_mem, points);
+    DeleteObject(brush);
+    
+    let brush_mask_black = CreateSolidBrush(COLORREF(0x00000000));
+    SelectObject(hdc_mask, brush_mask_black);
+    Polygon(hdc_mask, points);
+    DeleteObject(brush_mask_black);
+}
+
+// Rectangle in 16x16 grid coordinates as a polygon
+fn cell_rect(left: f32, top: f32, right: f32, bottom: f32, c: i32) -> [POINT; 4] {
+    [
+        POINT { x: rel(left/16.0, c), y: rel(top/16.0, c) },
+        POINT { x: rel(right/16.0, c), y: rel(top/16.0, c) },
+        POINT { x: rel(right/16.0, c), y: rel(bottom/16.0, c) },
+        POINT { x: rel(left/16.0, c), y: rel(bottom/16.0, c) },
+    ]
+}
+
+// Resource ID of the icon in assets/battesty.rc
+pub const APP_ICON_ID: usize = 1;
+
+// The embedded application icon; shared, so never destroy it
+pub fn app_icon() -> HICON {
+    unsafe {
+        let instance = GetModuleHandleW(PCWSTR::null()).unwrap_or_default();
+        LoadIconW(instance, PCWSTR(APP_ICON_ID as *const u16)).unwrap_or_default()
+    }
+}
+
+// Size the shell shows tray icons at, from the small-icon metric and the display DPI
+pub fn tray_icon_size(hdc: HDC) -> i32 {
+    let wanted = unsafe {
+        let dpi = GetDeviceCaps(hdc, LOGPIXELSX).max(96);
+        GetSystemMetrics(SM_CXSMICON).max(SMALL_ICON_SIZE * dpi / 96)
+    };
+    ICON_SIZES.iter().copied().find(|size| *size >= wanted).unwrap_or(ICON_SIZES[ICON_SIZES.len() - 1])
+}
+
+pub fn create_battery_icon(hdc: HDC, percentage: u8, colour: u8, state: ChargeState, ups: bool) -> HICON {
+    draw_battery_icon(hdc, percentage, colour, state, ups, tray_icon_size(hdc))
+}
+
+// At 16 px the warning/urgent marks are a couple of pixels of noise; the fill colour already says it
+// A UPS gets a tower without the terminal, with a front panel above the level
+fn draw_battery_icon(hdc: HDC, percentage: u8, colour: u8, state: ChargeState, ups: bool, size: i32) -> HICON {
+    let small = size <= SMALL_ICON_SIZE;
+    let is_charging = matches!(state, ChargeState::Charging | ChargeState::Full | ChargeState::NotCharging);
+    let percentage = if matches!(state, ChargeState::Unknown | ChargeState::NoBattery) { 0 } else { percentage };
     unsafe {
         let hdc_mem = CreateCompatibleDC(hdc);
-        let hbm = CreateCompatibleBitmap(hdc, CANVAS_SIZE, CANVAS_SIZE);
-        let hbm_mask = CreateCompatibleBitmap(hdc, CANVAS_SIZE, CANVAS_SIZE);
+        let hbm = CreateCompatibleBitmap(hdc, size, size);
+        let hbm_mask = CreateCompatibleBitmap(hdc, size, size);
         SelectObject(hdc_mem, hbm);
         
         // === Create mask bitmap for transparency ===
@@ -22,7 +88,7 @@ pub fn create_battery_icon(hdc: HDC, percentage: u8, is_charging: bool) -> HICON
         let hdc_mask = CreateCompatibleDC(hdc);
         SelectObject(hdc_mask, hbm_mask);
         let brush_mask_white = CreateSolidBrush(COLORREF(0x00FFFFFF)); // White = transparent
-        let rect = RECT { left: 0, top: 0, right: CANVAS_SIZE, bottom: CANVAS_SIZE };
+        let rect = RECT { left: 0, top: 0, right: size, bottom: size };
         FillRect(hdc_mask, &rect, brush_mask_white);
         DeleteObject(brush_mask_white);
         
@@ -31,42 +97,54 @@ pub fn create_battery_icon(hdc: HDC, percentage: u8, is_charging: bool) -> HICON
         FillRect(hdc_mem, &rect, brush_bg);
         DeleteObject(brush_bg);
         
-        let c = CANVAS_SIZE;
+        let c = size;
         
         // === Draw Battery Body (vector outline) ===
-        let pen_outline = CreatePen(PS_SOLID, 1, COLORREF(0x00FFFFFF)); // White outline
+        let pen_outline = CreatePen(PS_SOLID, (c / 16).max(1), COLORREF(0x00FFFFFF)); // White outline, one grid cell wide
         let old_pen = SelectObject(hdc_mem, pen_outline);
         let brush_null = GetStockObject(NULL_BRUSH);
         let old_brush = SelectObject(hdc_mem, brush_null);
         
         // Battery body polygon (from GIMP 16x16 coords, relative coords)
         // (2,2), (5,2), (5,1), (10,1), (10,2), (13,2), (13,14), (2,14)
-        let battery_points = [
-            POINT { x: rel(2.0/16.0, c), y: rel(2.0/16.0, c) },      // (2,2)
-            POINT { x: rel(5.0/16.0, c), y: rel(2.0/16.0, c) },      // (5,2)
-            POINT { x: rel(5.0/16.0, c), y: rel(1.0/16.0, c) },      // (5,1)
-            POINT { x: rel(10.0/16.0, c), y: rel(1.0/16.0, c) },     // (10,1)
-            POINT { x: rel(10.0/16.0, c), y: rel(2.0/16.0, c) },     // (10,2)
-            POINT { x: rel(13.0/16.0, c), y: rel(2.0/16.0, c) },     // (13,2)
-            POINT { x: rel(13.0/16.0, c), y: rel(14.0/16.0, c) },    // (13,14)
-            POINT { x: rel(2.0/16.0, c), y: rel(14.0/16.0, c) },     // (2,14)
-        ];
+        let battery_points = if ups {
+            vec![
+                POINT { x: rel(2.0/16.0, c), y: rel(1.0/16.0, c) },
+                POINT { x: rel(13.0/16.0, c), y: rel(1.0/16.0, c) },
+                POINT { x: rel(13.0/16.0, c), y: rel(14.0/16.0, c) },
+                POINT { x: rel(2.0/16.0, c), y: rel(14.0/16.0, c) },
+            ]
+        } else {
+            vec![
+                POINT { x: rel(2.0/16.0, c), y: rel(2.0/16.0, c) },      // (2,2)
+                POINT { x: rel(5.0/16.0, c), y: rel(2.0/16.0, c) },      // (5,2)
+                POINT { x: rel(5.0/16.0, c), y: rel(1.0/16.0, c) },      // (5,1)
+                POINT { x: rel(10.0/16.0, c), y: rel(1.0/16.0, c) },     // (10,1)
+                POINT { x: rel(10.0/16.0, c), y: rel(2.0/16.0, c) },     // (10,2)
+                POINT { x: rel(13.0/16.0, c), y: rel(2.0/16.0, c) },     // (13,2)
+                POINT { x: rel(13.0/16.0, c), y: rel(14.0/16.0, c) },    // (13,14)
+                POINT { x: rel(2.0/16.0, c), y: rel(14.0/16.0, c) },     // (2,14)
+            ]
+        };
+        let closing = [battery_points[battery_points.len() - 1], battery_points[0]];
         Polyline(hdc_mem, &battery_points);
         
         // Close the polygon
-        Polyline(hdc_mem, &[
-            battery_points[7],
-            battery_points[0],
-        ]);
+        Polyline(hdc_mem, &closing);
+        
+        // === Draw UPS Front Panel ===
+        if ups {
+            draw_glyph(hdc_mem, hdc_mask, COLORREF(0x00FFFFFF), &cell_rect(4.0, 3.0, 11.0, 4.0, c));
+        }
         
         // === Draw Fill Level ===
         if percentage > 0 {
             // Determine fill color based on percentage and charging state
             let fill_color = if is_charging {
                 COLORREF(0x0000C800) // Green for charging
-            } else if percentage < 5 {
+            } else if colour < URGENT_LEVEL {
                 COLORREF(0x000000FF) // Red for urgent (<5%)
-            } else if percentage < 15 {
+            } else if colour < WARNING_LEVEL {
                 COLORREF(0x000080FF) // Orange for warning (<15%)
             } else {
                 COLORREF(0x00FFFFFF) // White/normal for good
@@ -81,7 +159,7 @@ pub fn create_battery_icon(hdc: HDC, percentage: u8, is_charging: bool) -> HICON
             let fill_left = rel(3.0/16.0, c);
             let fill_right = rel(13.0/16.0, c);
             let fill_bottom = rel(14.0/16.0, c);
-            let fill_top_full = rel(2.0/16.0, c);
+            let fill_top_full = rel(if ups { 5.0 } else { 2.0 } / 16.0, c);
             let fill_height = fill_bottom - fill_top_full;
             
             let current_fill_height = (fill_height * percentage as i32 / 100).max(1);
@@ -100,13 +178,17 @@ pub fn create_battery_icon(hdc: HDC, percentage: u8, is_charging: bool) -> HICON
         
         // === Draw Battery Outline as Opaque in Mask ===
         let brush_mask_black = CreateSolidBrush(COLORREF(0x00000000));
+        let pen_mask = CreatePen(PS_SOLID, (c / 16).max(1), COLORREF(0x00000000));
         SelectObject(hdc_mask, brush_mask_black);
+        let old_mask_pen = SelectObject(hdc_mask, pen_mask);
         Polyline(hdc_mask, &battery_points);
-        Polyline(hdc_mask, &[battery_points[7], battery_points[0]]);
+        Polyline(hdc_mask, &closing);
+        SelectObject(hdc_mask, old_mask_pen);
+        DeleteObject(pen_mask);
         DeleteObject(brush_mask_black);
         
         // === Draw Charging Indicator (Lightning Bolt) ===
-        if is_charging && percentage < 100 {
+        if state == ChargeState::Charging {
             let brush_bolt = CreateSolidBrush(COLORREF(0x0000FFFF)); // Yellow for charging
             SelectObject(hdc_mem, brush_bolt);
             SelectObject(hdc_mem, GetStockObject(NULL_PEN));
@@ -132,8 +214,44 @@ pub fn create_battery_icon(hdc: HDC, percentage: u8, is_charging: bool) -> HICON
             DeleteObject(brush_bolt);
         }
         
+        // === Draw Plug Indicator (on AC, not charging: full, held by a threshold, or no battery) ===
+        if matches!(state, ChargeState::Full | ChargeState::NotCharging | ChargeState::NoBattery) {
+            let color = if state == ChargeState::NotCharging {
+                COLORREF(0x000090FF) // Amber: on AC but not charging
+            } else {
+                COLORREF(0x0000FFFF) // Yellow, same as the bolt
+            };
+            draw_glyph(hdc_mem, hdc_mask, color, &cell_rect(9.0, 5.0, 10.0, 7.0, c));   // Prongs
+            draw_glyph(hdc_mem, hdc_mask, color, &cell_rect(11.0, 5.0, 12.0, 7.0, c));
+            draw_glyph(hdc_mem, hdc_mask, color, &cell_rect(8.0, 7.0, 13.0, 10.0, c));  // Body
+            draw_glyph(hdc_mem, hdc_mask, color, &cell_rect(10.0, 10.0, 11.0, 13.0, c)); // Cable
+        }
+        
+        // === Draw Question Mark (status unknown) ===
+        if state == ChargeState::Unknown {
+            let font_name: Vec<u16> = "Segoe UI\0".encode_utf16().collect();
+            let font = CreateFontW(
+                rel(11.0/16.0, c), 0, 0, 0, FW_BOLD.0 as i32, 0, 0, 0,
+                DEFAULT_CHARSET.0 as u32, OUT_DEFAULT_PRECIS.0 as u32, CLIP_DEFAULT_PRECIS.0 as u32,
+                ANTIALIASED_QUALITY.0 as u32, (DEFAULT_PITCH.0 | FF_SWISS.0) as u32,
+                PCWSTR(font_name.as_ptr()),
+            );
+            let text: Vec<u16> = "?".encode_utf16().collect();
+            let mut text_rect = RECT { left: rel(2.0/16.0, c), top: rel(2.0/16.0, c), right: rel(13.0/16.0, c), bottom: rel(14.0/16.0, c) };
+            
+            // Same glyph on both bitmaps: white on the icon, black (opaque) on the mask
+            for (dc, color) in [(hdc_mem, COLORREF(0x00FFFFFF)), (hdc_mask, COLORREF(0x00000000))] {
+                let old_font = SelectObject(dc, font);
+                SetBkMode(dc, TRANSPARENT);
+                SetTextColor(dc, color);
+                DrawTextW(dc, &mut text.clone(), &mut text_rect, DT_CENTER | DT_VCENTER | DT_SINGLELINE);
+                SelectObject(dc, old_font);
+            }
+            DeleteObject(font);
+        }
+        
         // === Draw Warning Indicator (5% <= battery < 15%) ===
-        if !is_charging && percentage > 0 && percentage < 15 {
+        if !small && state == ChargeState::Discharging && percentage > 0 && colour < WARNING_LEVEL {
             // Step 1: Draw filled black rectangle with black border
             let brush_black = CreateSolidBrush(COLORREF(0x00000000)); // Black fill
             let pen_black = CreatePen(PS_SOLID, 1, COLORREF(0x00000000)); // Black border
@@ -183,7 +301,7 @@ pub fn create_battery_icon(hdc: HDC, percentage: u8, is_charging: bool) -> HICON
         }
         
         // === Draw Urgent Indicator (battery < 5%) ===
-        if !is_charging && percentage < 5 {
+        if !small && state == ChargeState::Discharging && colour < URGENT_LEVEL {
             // Step 1: Draw filled black rectangle with black border (9,6) to (13,14)
             let brush_black = CreateSolidBrush(COLORREF(0x00000000)); // Black fill
             let pen_black = CreatePen(PS_SOLID, 1, COLORREF(0x00000000)); // Black border
@@ -273,3 +391,117 @@ pub fn create_battery_icon(hdc: HDC, percentage: u8, is_charging: bool) -> HICON
     }
 }
 
+// Level the colour is picked for after `previous`: a reading that just rose past a threshold keeps
+// the colour below it until it's THRESHOLD_HYSTERESIS clear, so 14/15/14 doesn't flicker
+pub fn colour_level(previous: u8, percentage: u8) -> u8 {
+    for threshold in [URGENT_LEVEL, WARNING_LEVEL] {
+        if previous < threshold && percentage >= threshold && percentage < threshold + THRESHOLD_HYSTERESIS {
+            return threshold - 1;
+        }
+    }
+    percentage
+}
+
+// `time_left` replaces the digits of the numeric icon; the battery glyph has no room for it.
+// `colour` is the level the colour is picked for, see colour_level.
+pub fn create_icon(hdc: HDC, percentage: u8, colour: u8, state: ChargeState, time_left: Option<&str>, ups: bool, settings: &AppSettings) -> HICON {
+    match settings.icon_style {
+        IconStyle::Battery => create_battery_icon(hdc, percentage, colour, state, ups),
+        IconStyle::Numeric => create_numeric_icon(hdc, percentage, colour, state, time_left, &settings.icon_font, settings.icon_font_weight),
+    }
+}
+
+// Fits a tray tile: "45m" under an hour, whole hours above
+pub fn short_time(minutes: i32) -> String {
+    if minutes < 60 {
+        format!("{}m", minutes.max(0))
+    } else {
+        format!("{}h", minutes / 60)
+    }
+}
+
+// Digits on an opaque tile, drawn at the size the tray actually shows. ClearType needs an opaque
+// background and hinting at the final pixel size, so nothing is scaled down afterwards.
+pub fn create_numeric_icon(hdc: HDC, percentage: u8, colour: u8, state: ChargeState, time_left: Option<&str>, font_name: &str, weight: u32) -> HICON {
+    unsafe {
+        let size = tray_icon_size(hdc);
+        let hdc_mem = CreateCompatibleDC(hdc);
+        let hbm = CreateCompatibleBitmap(hdc, size, size);
+        let hbm_mask = CreateCompatibleBitmap(hdc, size, size);
+        let old_bitmap = SelectObject(hdc_mem, hbm);
+        
+        // Whole tile opaque
+        let hdc_mask = CreateCompatibleDC(hdc);
+        let old_mask = SelectObject(hdc_mask, hbm_mask);
+        let rect = RECT { left: 0, top: 0, right: size, bottom: size };
+        FillRect(hdc_mask, &rect, HBRUSH(GetStockObject(BLACK_BRUSH).0));
+        
+        let background = match state {
+            ChargeState::Charging | ChargeState::Full => 0x00007800, // Green
+            ChargeState::NotCharging => 0x00006C90, // Amber: on AC but not charging
+            ChargeState::Unknown | ChargeState::NoBattery => 0x00606060,
+            _ if colour < URGENT_LEVEL => 0x000000C0, // Red
+            _ if colour < WARNING_LEVEL => 0x000060D0, // Orange
+            _ => 0x00303030,
+        };
+        let brush_bg = CreateSolidBrush(COLORREF(background));
+        FillRect(hdc_mem, &rect, brush_bg);
+        DeleteObject(brush_bg);
+        
+        let text = match (state, time_left) {
+            (ChargeState::Unknown, _) => "?".to_string(),
+            (ChargeState::NoBattery, _) => "AC".to_string(),
+            (_, Some(time_left)) => time_left.to_string(),
+            _ => percentage.min(100).to_string(),
+        };
+        let mut text_wide: Vec<u16> = text.encode_utf16().collect();
+        let font_wide: Vec<u16> = font_name.encode_utf16().chain(std::iter::once(0)).collect();
+        
+        // Largest font whose text still fits the tile; "100" ends up smaller than two digits
+        let mut height = size + 2;
+        let font = loop {
+            let font = CreateFontW(
+                -height, 0, 0, 0, weight.clamp(100, 900) as i32, 0, 0, 0,
+                DEFAULT_CHARSET.0 as u32, OUT_TT_PRECIS.0 as u32, CLIP_DEFAULT_PRECIS.0 as u32,
+                CLEARTYPE_QUALITY.0 as u32, (DEFAULT_PITCH.0 | FF_SWISS.0) as u32,
+                PCWSTR(font_wide.as_ptr()),
+            );
+            let old_font = SelectObject(hdc_mem, font);
+            let mut extent = SIZE::default();
+            let _ = GetTextExtentPoint32W(hdc_mem, &text_wide, &mut extent);
+            SelectObject(hdc_mem, old_font);
+            if extent.cx <= size || height <= 6 {
+                break font;
+            }
+            DeleteObject(font);
+            height -= 1;
+        };
+        
+        let old_font = SelectObject(hdc_mem, font);
+        SetBkMode(hdc_mem, TRANSPARENT);
+        SetTextColor(hdc_mem, COLORREF(0x00FFFFFF));
+        let mut text_rect = rect;
+        DrawTextW(hdc_mem, &mut text_wide, &mut text_rect, DT_CENTER | DT_VCENTER | DT_SINGLELINE | DT_NOPREFIX);
+        SelectObject(hdc_mem, old_font);
+        DeleteObject(font);
+        
+        SelectObject(hdc_mask, old_mask);
+        SelectObject(hdc_mem, old_bitmap);
+        DeleteDC(hdc_mask);
+        
+        let icon_info = ICONINFO {
+            fIcon: TRUE,
+            xHotspot: 0,
+            yHotspot: 0,
+            hbmMask: hbm_mask,
+            hbmColor: hbm,
+        };
+        let icon = CreateIconIndirect(&icon_info).unwrap_or_default();
+        
+        DeleteObject(hbm);
+        DeleteObject(hbm_mask);
+        DeleteDC(hdc_mem);
+        
+        icon
+    }
+}
@@ -0,0 +1,297 @@
+use windows::Win32::Devices::DeviceAndDriverInstallation::*;
+use windows::Win32::Foundation::*;
+use windows::Win32::Storage::FileSystem::*;
+use windows::Win32::System::IO::DeviceIoControl;
+use windows::Win32::System::Power::*;
+use windows::Win32::UI::WindowsAndMessaging::{
+    RegisterDeviceNotificationW, DBT_DEVICEARRIVAL, DBT_DEVICEREMOVECOMPLETE, DBT_DEVTYP_DEVICEINTERFACE,
+    DEVICE_NOTIFY_WINDOW_HANDLE, DEV_BROADCAST_DEVICEINTERFACE_W, DEV_BROADCAST_HDR,
+};
+use windows::core::{GUID, PCWSTR};
+use crate::power::group_thousands;
+
+// Battery data straight from the battery class driver, the same source Windows' own battery
+// report uses. Needs no WMI and no admin rights, and gives the real charge/discharge rate in mW
+// instead of the percentage GetSystemPowerStatus rounds everything to.
+
+pub struct BatteryDevice {
+    pub designed_mwh: Option<u32>,
+    pub full_charge_mwh: Option<u32>,
+    pub remaining_mwh: Option<u32>,
+    // 0 from the driver means the firmware doesn't count cycles
+    pub cycle_count: Option<u32>,
+    pub voltage_mv: Option<u32>,
+    // Tenths of a kelvin, as the driver reports it; few batteries expose a sensor
+    pub temperature_dk: Option<u32>,
+    // Positive while charging, negative while discharging
+    pub rate_mw: Option<i32>,
+    // BATTERY_POWER_ON_LINE, BATTERY_CHARGING and friends
+    pub power_state: u32,
+    // A UPS: only meant to bridge a power cut, not to run on
+    pub short_term: bool,
+    // "LION", "LiP" and so on, as the firmware reports it
+    pub chemistry: String,
+    pub device_name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub serial: Option<String>,
+}
+
+impl BatteryDevice {
+    pub fn temperature_celsius(&self) -> Option<f64> {
+        self.temperature_dk.map(celsius)
+    }
+
+    // On AC with the driver holding the battery where it is
+    pub fn idle_on_ac(&self) -> bool {
+        self.power_state & BATTERY_POWER_ON_LINE != 0 && self.power_state & BATTERY_CHARGING == 0
+    }
+
+    pub fn percentage(&self) -> Option<u8> {
+        let (remaining, full) = (self.remaining_mwh?, self.full_charge_mwh?);
+        (full > 0).then(|| (remaining as f64 / full as f64 * 100.0).round().clamp(0.0, 100.0) as u8)
+    }
+}
+
+pub fn summary(batteries: &[BatteryDevice]) -> String {
+    if batteries.is_empty() {
+        return "Battery Hardware\nThe battery driver doesn't answer queries; using WMI instead.\n".to_string();
+    }
+    let mut text = "Battery Hardware\n".to_string();
+    for (i, battery) in batteries.iter().enumerate() {
+        let mut parts = Vec::new();
+        if !battery.chemistry.is_empty() {
+            parts.push(battery.chemistry.clone());
+        }
+        if let Some(design) = battery.designed_mwh {
+            parts.push(format!("design {} mWh", group_thousands(design)));
+        }
+        if let Some(full) = battery.full_charge_mwh {
+            parts.push(format!("full {} mWh", group_thousands(full)));
+        }
+        if let Some(cycles) = battery.cycle_count {
+            parts.push(format!("{} cycles", cycles));
+        }
+        if let Some(voltage) = battery.voltage_mv {
+            parts.push(format!("{:.2} V", voltage as f64 / 1000.0));
+        }
+        if let Some(temperature) = battery.temperature_celsius() {
+            parts.push(format!("{:.1} °C", temperature));
+        }
+        if let Some(rate) = battery.rate_mw {
+            parts.push(format!("{:+.1} W", rate as f64 / 1000.0));
+        }
+        let label = if batteries.len() > 1 { format!("Battery {}: ", i + 1) } else { String::new() };
+        text.push_str(&format!("{}{}\n", label, parts.join(" · ")));
+    }
+    text
+}
+
+pub fn celsius(temperature_dk: u32) -> f64 {
+    temperature_dk as f64 / 10.0 - 273.15
+}
+
+// WM_DEVICECHANGE with DBT_DEVICEARRIVAL/DBT_DEVICEREMOVECOMPLETE whenever a pack comes or goes
+pub fn watch_batteries(hwnd: HWND) {
+    let filter = DEV_BROADCAST_DEVICEINTERFACE_W {
+        dbcc_size: std::mem::size_of::<DEV_BROADCAST_DEVICEINTERFACE_W>() as u32,
+        dbcc_devicetype: DBT_DEVTYP_DEVICEINTERFACE.0,
+        dbcc_classguid: GUID_DEVICE_BATTERY,
+        ..Default::default()
+    };
+    unsafe {
+        let _ = RegisterDeviceNotificationW(hwnd, &filter as *const _ as *const _, DEVICE_NOTIFY_WINDOW_HANDLE);
+    }
+}
+
+// Volumes and ports are broadcast to every window too, so only battery interfaces count
+pub fn is_battery_change(wparam: WPARAM, lparam: LPARAM) -> bool {
+    if !matches!(wparam.0 as u32, DBT_DEVICEARRIVAL | DBT_DEVICEREMOVECOMPLETE) || lparam.0 == 0 {
+        return false;
+    }
+    unsafe {
+        let header = &*(lparam.0 as *const DEV_BROADCAST_HDR);
+        header.dbch_devicetype == DBT_DEVTYP_DEVICEINTERFACE
+            && (*(lparam.0 as *const DEV_BROADCAST_DEVICEINTERFACE_W)).dbcc_classguid == GUID_DEVICE_BATTERY
+    }
+}
+
+// Every battery present, in the order the driver enumerates them
+pub fn read_batteries() -> Vec<BatteryDevice> {
+    device_interfaces(&GUID_DEVICE_BATTERY)
+        .iter()
+        .filter_map(|(path, _)| unsafe { read_battery(path) })
+        .collect()
+}
+
+// Path (null-terminated, for CreateFileW) and device instance of every present interface of `class`
+pub fn device_interfaces(class: &GUID) -> Vec<(Vec<u16>, u32)> {
+    let mut found = Vec::new();
+    unsafe {
+        let Ok(set) = SetupDiGetClassDevsW(Some(class), PCWSTR::null(), HWND(0), DIGCF_PRESENT | DIGCF_DEVICEINTERFACE) else {
+            return found;
+        };
+        for index in 0.. {
+            let mut interface = SP_DEVICE_INTERFACE_DATA {
+                cbSize: std::mem::size_of::<SP_DEVICE_INTERFACE_DATA>() as u32,
+                ..Default::default()
+            };
+            if SetupDiEnumDeviceInterfaces(set, None, class, index, &mut interface).is_err() {
+                break;
+            }
+            let mut device = SP_DEVINFO_DATA {
+                cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+                ..Default::default()
+            };
+            if let Some(path) = interface_path(set, &interface, &mut device) {
+                found.push((path, device.DevInst));
+            }
+        }
+        let _ = SetupDiDestroyDeviceInfoList(set);
+    }
+    found
+}
+
+unsafe fn interface_path(set: HDEVINFO, interface: &SP_DEVICE_INTERFACE_DATA, device: &mut SP_DEVINFO_DATA) -> Option<Vec<u16>> {
+    let mut required = 0u32;
+    let _ = SetupDiGetDeviceInterfaceDetailW(set, interface, None, 0, Some(&mut required), None);
+    if required == 0 {
+        return None;
+    }
+    // u32s keep the buffer aligned for the cbSize field
+    let mut buffer = vec![0u32; required as usize / 4 + 1];
+    let detail = buffer.as_mut_ptr() as *mut SP_DEVICE_INTERFACE_DETAIL_DATA_W;
+    (*detail).cbSize = std::mem::size_of::<SP_DEVICE_INTERFACE_DETAIL_DATA_W>() as u32;
+    SetupDiGetDeviceInterfaceDetailW(set, interface, Some(detail), required, None, Some(device)).ok()?;
+
+    let start = std::ptr::addr_of!((*detail).DevicePath) as *const u16;
+    let mut path = Vec::new();
+    for i in 0.. {
+        let c = *start.add(i);
+        path.push(c);
+        if c == 0 {
+            break;
+        }
+    }
+    Some(path)
+}
+
+unsafe fn read_battery(path: &[u16]) -> Option<BatteryDevice> {
+    let handle = CreateFileW(
+        PCWSTR(path.as_ptr()),
+        (FILE_GENERIC_READ | FILE_GENERIC_WRITE).0,
+        FILE_SHARE_READ | FILE_SHARE_WRITE,
+        None,
+        OPEN_EXISTING,
+        FILE_ATTRIBUTE_NORMAL,
+        None,
+    ).ok()?;
+    let battery = query(handle);
+    let _ = CloseHandle(handle);
+    battery
+}
+
+unsafe fn query(handle: HANDLE) -> Option<BatteryDevice> {
+    // The tag identifies this battery until it's removed; 0 wait means don't block for one
+    let wait = 0u32;
+    let mut tag = 0u32;
+    control(handle, IOCTL_BATTERY_QUERY_TAG, &wait, &mut tag)?;
+    if tag == 0 {
+        return None;
+    }
+
+    let request = BATTERY_QUERY_INFORMATION { BatteryTag: tag, InformationLevel: BatteryInformation, AtRate: 0 };
+    let mut info = BATTERY_INFORMATION::default();
+    control(handle, IOCTL_BATTERY_QUERY_INFORMATION, &request, &mut info)?;
+
+    // Not supported by most drivers, which fail the request
+    let request = BATTERY_QUERY_INFORMATION { BatteryTag: tag, InformationLevel: BatteryTemperature, AtRate: 0 };
+    let mut temperature = 0u32;
+    let temperature = control(handle, IOCTL_BATTERY_QUERY_INFORMATION, &request, &mut temperature).map(|_| temperature);
+
+    let text = |level| query_string(handle, tag, level);
+    let (device_name, manufacturer, serial) = (text(BatteryDeviceName), text(BatteryManufactureName), text(BatterySerialNumber));
+
+    let wait = BATTERY_WAIT_STATUS { BatteryTag: tag, ..Default::default() };
+    let mut status = BATTERY_STATUS::default();
+    control(handle, IOCTL_BATTERY_QUERY_STATUS, &wait, &mut status)?;
+
+    // Relative batteries report capacities as a 0–100 scale instead of mWh
+    let absolute = info.Capabilities & BATTERY_CAPACITY_RELATIVE == 0;
+    let mwh = |value: u32| (absolute && value != 0 && value != BATTERY_UNKNOWN_CAPACITY).then_some(value);
+    Some(BatteryDevice {
+        designed_mwh: mwh(info.DesignedCapacity),
+        full_charge_mwh: mwh(info.FullChargedCapacity),
+        remaining_mwh: mwh(status.Capacity),
+        cycle_count: Some(info.CycleCount).filter(|c| *c > 0),
+        voltage_mv: Some(status.Voltage).filter(|v| *v != 0 && *v != BATTERY_UNKNOWN_VOLTAGE),
+        temperature_dk: temperature.filter(|t| *t > 0),
+        power_state: status.PowerState,
+        short_term: info.Capabilities & BATTERY_IS_SHORT_TERM != 0,
+        rate_mw: Some(status.Rate).filter(|r| absolute && *r as u32 != BATTERY_UNKNOWN_RATE),
+        chemistry: String::from_utf8_lossy(&info.Chemistry).trim_end_matches(['\0', ' ']).to_string(),
+        device_name,
+        manufacturer,
+        serial,
+    })
+}
+
+// The name and serial levels answer with a NUL-terminated UTF-16 string of any length up to this
+unsafe fn query_string(handle: HANDLE, tag: u32, level: BATTERY_QUERY_INFORMATION_LEVEL) -> Option<String> {
+    let request = BATTERY_QUERY_INFORMATION { BatteryTag: tag, InformationLevel: level, AtRate: 0 };
+    let mut buffer = [0u16; 128];
+    let mut returned = 0u32;
+    DeviceIoControl(
+        handle,
+        IOCTL_BATTERY_QUERY_INFORMATION,
+        Some(&request as *const _ as *const _),
+        std::mem::size_of::<BATTERY_QUERY_INFORMATION>() as u32,
+        Some(buffer.as_mut_ptr() as *mut _),
+        std::mem::size_of_val(&buffer) as u32,
+        Some(&mut returned),
+        None,
+    ).ok()?;
+    let chars = &buffer[..(returned as usize / 2).min(buffer.len())];
+    let text = String::from_utf16_lossy(chars).trim_end_matches(['\0', ' ']).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+// "Hardware" lines for the detail dialog, one per pack
+pub fn hardware(batteries: &[BatteryDevice]) -> Vec<String> {
+    batteries
+        .iter()
+        .enumerate()
+        .map(|(i, battery)| {
+            let unknown = || "unknown".to_string();
+            let label = if batteries.len() > 1 { format!("Battery {}: ", i + 1) } else { String::new() };
+            format!(
+                "{}{} by {} · {} · serial {}",
+                label,
+                battery.device_name.clone().unwrap_or_else(unknown),
+                battery.manufacturer.clone().unwrap_or_else(unknown),
+                if battery.chemistry.is_empty() { unknown() } else { battery.chemistry.clone() },
+                battery.serial.clone().unwrap_or_else(unknown),
+            )
+        })
+        .collect()
+}
+
+// Serials of every pack, in one string so a swap of any of them shows up as a change
+pub fn serials(batteries: &[BatteryDevice]) -> Option<String> {
+    let serials: Vec<&str> = batteries.iter().filter_map(|b| b.serial.as_deref()).collect();
+    (!serials.is_empty()).then(|| serials.join(","))
+}
+
+unsafe fn control<I, O>(handle: HANDLE, code: u32, input: &I, output: &mut O) -> Option<()> {
+    let mut returned = 0u32;
+    DeviceIoControl(
+        handle,
+        code,
+        Some(input as *const I as *const _),
+        std::mem::size_of::<I>() as u32,
+        Some(output as *mut O as *mut _),
+        std::mem::size_of::<O>() as u32,
+        Some(&mut returned),
+        None,
+    ).ok()?;
+    (returned as usize == std::mem::size_of::<O>()).then_some(())
+}
@@ -0,0 +1,47 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use crate::battery::BatteryMeasurement;
+
+// Measurements taken since the last full save, one JSON object per line. Appending a line is
+// cheap and leaves the earlier ones intact if the process dies mid-write.
+
+pub fn append(measurement: &BatteryMeasurement) {
+    let Ok(mut line) = serde_json::to_string(measurement) else { return };
+    line.push('\n');
+    if let Ok(mut file) = std::fs::OpenOptions::new().create(true).append(true).open(journal_path()) {
+        let _ = file.write_all(line.as_bytes());
+    }
+}
+
+// Adds journaled measurements newer than the loaded history; returns how many were recovered
+pub fn replay(measurements: &mut VecDeque<BatteryMeasurement>) -> usize {
+    let newest = measurements.back().map(|m| m.timestamp);
+    let mut recovered = 0;
+    for measurement in read() {
+        if newest.is_none_or(|newest| measurement.timestamp > newest) {
+            measurements.push_back(measurement);
+            recovered += 1;
+        }
+    }
+    recovered
+}
+
+// Everything journaled, from every instance sharing the data directory. A torn last line
+// from a crash is skipped.
+pub fn read() -> Vec<BatteryMeasurement> {
+    std::fs::read_to_string(journal_path())
+        .map(|text| text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+        .unwrap_or_default()
+}
+
+// Called once the main store holds everything the journal had
+pub fn clear() {
+    let _ = std::fs::remove_file(journal_path());
+}
+
+fn journal_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_history.journal");
+    path
+}
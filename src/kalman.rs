@@ -0,0 +1,79 @@
+// Two-state Kalman filter over charge level (%) and drain (%/h, positive while discharging).
+// The level falls by drain × time between readings; the drain itself is modelled as a random
+// walk, so load changes show up as growing uncertainty instead of being ignored.
+
+// A whole-percent reading is off by up to half a percent, plus firmware rounding
+pub const PERCENT_VARIANCE: f64 = 0.25;
+// The driver's mW rate over full-charge capacity, in (%/h)²
+pub const RATE_VARIANCE: f64 = 4.0;
+// Where the drain starts before any reading pulls it into place, and how unsure that is
+const INITIAL_DRAIN: f64 = 10.0;
+const INITIAL_DRAIN_VARIANCE: f64 = 100.0;
+
+pub struct SocFilter {
+    // Level and drain
+    state: [f64; 2],
+    covariance: [[f64; 2]; 2],
+}
+
+impl SocFilter {
+    pub fn new(percentage: f64) -> Self {
+        Self {
+            state: [percentage, INITIAL_DRAIN],
+            covariance: [[PERCENT_VARIANCE, 0.0], [0.0, INITIAL_DRAIN_VARIANCE]],
+        }
+    }
+
+    // Moves `hours` ahead; `process_noise` is how far the drain may wander, in (%/h)² per hour
+    pub fn predict(&mut self, hours: f64, process_noise: f64) {
+        let [level, drain] = self.state;
+        self.state = [level - drain * hours, drain];
+
+        // P = F P Fᵀ + Q with F = [[1, -h], [0, 1]]
+        let p = self.covariance;
+        let p00 = p[0][0] - hours * (p[0][1] + p[1][0]) + hours * hours * p[1][1];
+        let p01 = p[0][1] - hours * p[1][1];
+        let q = process_noise;
+        let cross = p01 - q * hours * hours / 2.0;
+        self.covariance = [
+            [p00 + q * hours.powi(3) / 3.0, cross],
+            [cross, p[1][1] + q * hours],
+        ];
+    }
+
+    pub fn observe_percentage(&mut self, percentage: f64) {
+        self.update(0, percentage, PERCENT_VARIANCE);
+    }
+
+    pub fn observe_drain(&mut self, drain: f64) {
+        self.update(1, drain, RATE_VARIANCE);
+    }
+
+    // Scalar update of one state component
+    fn update(&mut self, index: usize, measured: f64, variance: f64) {
+        let p = self.covariance;
+        let innovation_variance = p[index][index] + variance;
+        let gain = [p[0][index] / innovation_variance, p[1][index] / innovation_variance];
+        let innovation = measured - self.state[index];
+        self.state[0] += gain[0] * innovation;
+        self.state[1] += gain[1] * innovation;
+        let row = p[index];
+        self.covariance = [
+            [p[0][0] - gain[0] * row[0], p[0][1] - gain[0] * row[1]],
+            [p[1][0] - gain[1] * row[0], p[1][1] - gain[1] * row[1]],
+        ];
+    }
+
+    pub fn level(&self) -> f64 {
+        self.state[0]
+    }
+
+    pub fn drain(&self) -> f64 {
+        self.state[1]
+    }
+
+    // Standard deviation of the drain, in %/h
+    pub fn drain_deviation(&self) -> f64 {
+        self.covariance[1][1].max(0.0).sqrt()
+    }
+}
@@ -1,20 +1,83 @@
 #![windows_subsystem = "windows"]
 
+mod about;
+mod accuracy;
+mod activity;
+mod alert_history;
+mod age;
+mod archive;
 mod battery;
+mod benchmark;
+mod boot;
+mod brightness;
+mod charge_curve;
+mod chart;
+mod clock;
+mod compaction;
+mod companion;
+mod countdown;
+mod cycles;
+mod devices;
+mod diagnostics;
+mod discord;
+mod display;
+mod drain_curve;
+mod drain_test;
+mod drain_wizard;
+mod engine;
+mod estimator;
+mod event_log;
+mod events;
+mod filelock;
+mod footprint;
+mod forecast;
 mod icon;
+mod ioctl;
+mod journal;
+mod kalman;
+mod notify;
+mod parquet;
+mod patterns;
+mod power;
+mod plan_profiles;
+mod power_plan;
+mod prompt;
+mod report;
+mod rollup;
+mod score;
+mod server;
+mod session_list;
+mod sessions;
 mod settings;
+mod suspend;
+mod taskbar_text;
+mod toml_config;
+#[cfg(test)]
+mod trace;
 mod ui;
+mod update;
+mod usage_model;
+mod vendor;
+mod versions;
+mod wear;
+mod widget;
+mod wmi;
 
 use std::sync::{Arc, Mutex, OnceLock};
 use windows::Win32::Foundation::*;
 use windows::Win32::UI::WindowsAndMessaging::*;
+use windows::Win32::System::Com::{CoInitializeEx, COINIT_APARTMENTTHREADED};
 use windows::Win32::System::LibraryLoader::*;
+use windows::Win32::System::Power::RegisterPowerSettingNotification;
+use windows::Win32::System::SystemServices::{GUID_ACDC_POWER_SOURCE, GUID_BATTERY_PERCENTAGE_REMAINING, GUID_CONSOLE_DISPLAY_STATE};
 use windows::core::PCWSTR;
 
-use battery::{BatteryMonitor, DEBUG_MODE};
-use ui::{add_tray_icon, update_tray_icon, handle_power_event, handle_timer_event, handle_tray_event, handle_menu_command, cleanup_and_exit};
+use battery::BatteryMonitor;
+use ui::{add_tray_icon, update_tray_icon, handle_device_change, handle_power_event, handle_timer_event, handle_tray_event, handle_menu_command, handle_update_event, cleanup_and_exit};
 
 pub const WM_TRAYICON: u32 = WM_USER + 1;
+// Posted by the update worker thread when a step finished
+pub const WM_UPDATE: u32 = WM_USER + 2;
 pub const ID_TRAY_ICON: u32 = 1;
 pub const TIMER_UPDATE: usize = 1;
 pub const TIMER_SAVE: usize = 2;
@@ -37,18 +100,66 @@ unsafe extern "system" fn window_proc(
             let msg_id = RegisterWindowMessageW(PCWSTR(taskbar_created.as_ptr()));
             let _ = WM_TASKBARCREATED_MSG.set(msg_id);
             
-            add_tray_icon(hwnd, &monitor);
+            if engine::is_engine() {
+                engine::start(hwnd);
+            } else {
+                engine::set_client(monitor.lock().unwrap().settings.background_engine);
+            }
+            
+            // In client mode the engine logs the boot and keeps the tray's sampling
+            if !engine::is_client() {
+                monitor.lock().unwrap().annotate_boot();
+            }
+            if !engine::is_engine() {
+                add_tray_icon(hwnd, &monitor);
+            }
+            if engine::is_client() {
+                // Kept pointing at this exe, which may have moved since the engine was enabled
+                engine::set_autostart(true);
+                engine::ensure_running();
+            }
             update_tray_icon(hwnd, &monitor);
+            if !engine::is_engine() && update::just_updated() {
+                let text = format!("Now running v{}", versions::app_version());
+                notify::show_balloon(hwnd, "Battesty Updated", &text, windows::Win32::UI::Shell::NIIF_INFO);
+            }
+            
+            if !engine::is_engine() {
+                if let Some(port) = monitor.lock().unwrap().settings.overlay_server_port {
+                    let _ = server::start(port);
+                }
+                widget::register_hotkey(hwnd, &monitor.lock().unwrap().settings);
+            }
+            // The engine polls for the tray and passes the readings on
+            if !engine::is_client() && monitor.lock().unwrap().settings.companion_battery {
+                companion::start();
+            }
             
-            let interval = monitor.lock().unwrap().settings.update_interval_ms;
-            let update_interval = if DEBUG_MODE { 2000 } else { interval };
+            let update_interval = monitor.lock().unwrap().update_interval();
             SetTimer(hwnd, TIMER_UPDATE, update_interval, None);
             SetTimer(hwnd, TIMER_SAVE, 300000, None);
             
+            let _ = RegisterPowerSettingNotification(hwnd, &GUID_CONSOLE_DISPLAY_STATE, DEVICE_NOTIFY_WINDOW_HANDLE.0);
+            ioctl::watch_batteries(hwnd);
+            // Plugging in and percentage ticks arrive as events; TIMER_UPDATE slows down to a fallback
+            // only if both registrations worked
+            if monitor.lock().unwrap().settings.power_notifications {
+                let registered = [GUID_BATTERY_PERCENTAGE_REMAINING, GUID_ACDC_POWER_SOURCE]
+                    .iter()
+                    .all(|guid| RegisterPowerSettingNotification(hwnd, guid, DEVICE_NOTIFY_WINDOW_HANDLE.0).is_ok());
+                let mut mon = monitor.lock().unwrap();
+                mon.power_notifications = registered;
+                SetTimer(hwnd, TIMER_UPDATE, mon.update_interval(), None);
+            }
+            
             LRESULT(0)
         }
+        WM_DEVICECHANGE => {
+            handle_device_change(wparam, lparam, hwnd);
+            LRESULT(1)
+        }
         WM_POWERBROADCAST => {
-            handle_power_event(wparam, hwnd);
+            handle_power_event(wparam, lparam, hwnd);
             LRESULT(1)
         }
         WM_TIMER => {
@@ -63,13 +174,21 @@ unsafe extern "system" fn window_proc(
             handle_menu_command(wparam, hwnd);
             LRESULT(0)
         }
+        WM_HOTKEY if wparam.0 as i32 == widget::HOTKEY_WIDGET => {
+            widget::toggle(hwnd);
+            LRESULT(0)
+        }
+        WM_UPDATE => {
+            handle_update_event(hwnd);
+            LRESULT(0)
+        }
         WM_DESTROY => {
             cleanup_and_exit(hwnd);
             LRESULT(0)
         }
         _ => {
             if let Some(&taskbar_msg) = WM_TASKBARCREATED_MSG.get() {
-                if msg == taskbar_msg && msg != 0 {
+                if msg == taskbar_msg && msg != 0 && !engine::is_engine() {
                     if let Some(monitor) = MONITOR.get() {
                         add_tray_icon(hwnd, monitor);
                         update_tray_icon(hwnd, monitor);
@@ -83,13 +202,57 @@ unsafe extern "system" fn window_proc(
 }
 
 fn main() {
+    // `battesty --evaluate [history.json]` replays a recorded history through all estimators
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("--evaluate") {
+        let path = match args.get(2) {
+            Some(path) => std::path::PathBuf::from(path),
+            None => {
+                let mut path = std::env::current_exe().unwrap();
+                path.pop();
+                path.push("battesty_history.json");
+                path
+            }
+        };
+        ui::run_evaluation(HWND(0), Some(&path));
+        return;
+    }
+    
+    // `battesty --snapshot` asks the running instance to measure and save right now
+    if args.get(1).map(String::as_str) == Some("--snapshot") {
+        unsafe {
+            let class_name = "BattestyWindow\0".encode_utf16().collect::<Vec<u16>>();
+            let hwnd = FindWindowW(PCWSTR(class_name.as_ptr()), PCWSTR::null());
+            if hwnd.0 != 0 {
+                SendMessageW(hwnd, WM_COMMAND, WPARAM(ui::SNAPSHOT_COMMAND as usize), LPARAM(0));
+            }
+        }
+        return;
+    }
+    
+    // `battesty --engine` runs headless behind the tray; see engine.rs
+    let engine_mode = args.get(1).map(String::as_str) == Some("--engine");
+    if engine_mode {
+        if engine::request("status").is_ok() {
+            return;
+        }
+        engine::set_engine();
+    } else {
+        update::remove_old_exe();
+    }
+    
     unsafe {
-        let class_name = "BattestyWindow\0".encode_utf16().collect::<Vec<u16>>();
+        // WMI queries for vendor charge settings need COM on this thread
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+        
+        let class_name = if engine_mode { "BattestyEngineWindow\0" } else { "BattestyWindow\0" };
+        let class_name = class_name.encode_utf16().collect::<Vec<u16>>();
         
         let wc = WNDCLASSW {
             lpfnWndProc: Some(window_proc),
             hInstance: GetModuleHandleW(PCWSTR::null()).unwrap().into(),
             lpszClassName: PCWSTR(class_name.as_ptr()),
+            hIcon: icon::app_icon(),
             ..std::mem::zeroed()
         };
         
@@ -114,8 +277,16 @@ fn main() {
         
         let mut msg: MSG = std::mem::zeroed();
         while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+            // Tab, arrow keys and mnemonics in the windows with controls
+            let root = GetAncestor(msg.hwnd, GA_ROOT);
+            let has_controls = GetWindowLongW(root, GWL_EXSTYLE) as u32 & WS_EX_CONTROLPARENT.0 != 0;
+            if has_controls && IsDialogMessageW(root, &msg).as_bool() {
+                continue;
+            }
             TranslateMessage(&msg);
             DispatchMessageW(&msg);
         }
     }
+    
+    update::restart_if_pending();
 }
\ No newline at end of file
@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use windows::Win32::Foundation::HWND;
+use windows::Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress};
+use windows::Win32::UI::Accessibility::*;
+use windows::Win32::UI::Shell::*;
+use windows::core::{s, w, BSTR};
+
+use crate::ID_TRAY_ICON;
+use crate::battery::BatteryMonitor;
+use crate::engine;
+use crate::events::EventKind;
+use crate::ui::copy_wide;
+
+const MAX_ALERTS: usize = 500;
+
+// Focus Assist's active profile (0 off, 1 priority only, 2 alarms only). Only published as this
+// WNF state, which ntdll exports no documented reader for; the shell's own settings page reads it.
+const WNF_SHEL_QUIETHOURS_ACTIVE_PROFILE_CHANGED: u64 = 0x0D83_063E_A3BF_1C75;
+
+type NtQueryWnfStateData = unsafe extern "system" fn(
+    state_name: *const u64,
+    type_id: *const std::ffi::c_void,
+    explicit_scope: *const std::ffi::c_void,
+    change_stamp: *mut u32,
+    buffer: *mut std::ffi::c_void,
+    buffer_size: *mut u32,
+) -> i32;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AlertKind {
+    CriticalBattery,
+    LowBattery,
+    ChargeReminder,
+    CapacityChange,
+    DrainTest,
+    CompanionBattery,
+}
+
+pub const ALERT_KINDS: [AlertKind; 6] = [
+    AlertKind::CriticalBattery,
+    AlertKind::LowBattery,
+    AlertKind::ChargeReminder,
+    AlertKind::CapacityChange,
+    AlertKind::DrainTest,
+    AlertKind::CompanionBattery,
+];
+
+// Snooze lengths offered in the menu, in minutes
+pub const SNOOZE_OPTIONS: [u32; 4] = [15, 60, 240, 1440];
+
+impl AlertKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AlertKind::CriticalBattery => "Critical battery",
+            AlertKind::LowBattery => "Low battery",
+            AlertKind::ChargeReminder => "Charge reminder",
+            AlertKind::CapacityChange => "Capacity change",
+            AlertKind::DrainTest => "Drain test",
+            AlertKind::CompanionBattery => "Companion device battery",
+        }
+    }
+
+    // Used when a balloon is clicked, unless the settings override it
+    pub fn default_snooze_minutes(&self) -> u32 {
+        match self {
+            AlertKind::CriticalBattery | AlertKind::LowBattery => 15,
+            AlertKind::CompanionBattery => 60,
+            AlertKind::ChargeReminder => 240,
+            AlertKind::CapacityChange | AlertKind::DrainTest => 1440,
+        }
+    }
+
+    // Critical alerts ignore Focus Assist
+    pub fn is_critical(&self) -> bool {
+        *self == AlertKind::CriticalBattery
+    }
+}
+
+// An alert held back while Focus Assist is on, shown once it ends
+pub struct QueuedAlert {
+    pub kind: AlertKind,
+    pub title: String,
+    pub text: String,
+    pub flags: NOTIFY_ICON_INFOTIP_FLAGS,
+}
+
+pub fn format_snooze(minutes: u32) -> String {
+    match minutes {
+        0..=59 => format!("{} min", minutes),
+        60..=1439 => format!("{} h", minutes / 60),
+        _ => format!("{} day(s)", minutes / 1440),
+    }
+}
+
+// Every alert battesty fired or held back, for the notification history
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AlertRecord {
+    pub timestamp: DateTime<Local>,
+    pub kind: AlertKind,
+    pub percentage: Option<u8>,
+    pub message: String,
+    // Held back instead of shown
+    pub snoozed: bool,
+    // Held back by Focus Assist (queued or dropped)
+    #[serde(default)]
+    pub deferred: bool,
+}
+
+impl AlertRecord {
+    pub fn summary(&self) -> String {
+        format!(
+            "{}  {}{}{}: {}",
+            self.timestamp.format("%Y-%m-%d %H:%M"),
+            self.kind.label(),
+            self.percentage.map(|p| format!(" ({}%)", p)).unwrap_or_default(),
+            if self.snoozed { " [snoozed]" } else if self.deferred { " [focus assist]" } else { "" },
+            self.message,
+        )
+    }
+}
+
+pub fn show_balloon(hwnd: HWND, title: &str, text: &str, flags: NOTIFY_ICON_INFOTIP_FLAGS) {
+    // The engine has no icon; the tray shows its alerts on the next update
+    if engine::is_engine() {
+        engine::queue_alert(title, text, flags.0);
+        return;
+    }
+    unsafe {
+        let mut nid: NOTIFYICONDATAW = std::mem::zeroed();
+        nid.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
+        nid.hWnd = hwnd;
+        nid.uID = ID_TRAY_ICON;
+        nid.uFlags = NIF_INFO;
+        nid.dwInfoFlags = flags;
+        copy_wide(&mut nid.szInfoTitle, title);
+        copy_wide(&mut nid.szInfo, text);
+        
+        Shell_NotifyIconW(NIM_MODIFY, &nid);
+    }
+}
+
+// All alerts go through here so they end up in the history and the event log;
+// snoozed ones are recorded but not shown
+pub fn raise(hwnd: HWND, mon: &mut BatteryMonitor, kind: AlertKind, title: &str, text: &str, flags: NOTIFY_ICON_INFOTIP_FLAGS) {
+    let snoozed = mon.snoozes.get(&kind).is_some_and(|until| *until > mon.clock.now());
+    let deferred = !snoozed && !kind.is_critical() && focus_assist_active();
+    record(mon, kind, text, snoozed);
+    if deferred {
+        if let Some(last) = mon.alerts.last_mut() {
+            last.deferred = true;
+        }
+        save_alerts(&mon.alerts);
+        if mon.settings.queue_alerts_during_focus {
+            // Only the newest alert of each type is worth showing later
+            mon.queued_alerts.retain(|q| q.kind != kind);
+            mon.queued_alerts.push(QueuedAlert { kind, title: title.to_string(), text: text.to_string(), flags });
+        }
+    } else if !snoozed {
+        show(hwnd, mon, kind, title, text, flags);
+    }
+    if kind.is_critical() && mon.settings.announce_critical_alerts {
+        announce(hwnd, &format!("{}. {}", title, text));
+    }
+}
+
+// Balloons aren't reliably read out, so critical alerts are also raised as a UIA notification
+fn announce(hwnd: HWND, text: &str) {
+    unsafe {
+        if let Ok(provider) = UiaHostProviderFromHwnd(hwnd) {
+            let _ = UiaRaiseNotificationEvent(
+                &provider,
+                NotificationKind_Other,
+                NotificationProcessing_ImportantAll,
+                &BSTR::from(text),
+                &BSTR::from("battesty.alert"),
+            );
+        }
+    }
+}
+
+fn show(hwnd: HWND, mon: &BatteryMonitor, kind: AlertKind, title: &str, text: &str, flags: NOTIFY_ICON_INFOTIP_FLAGS) {
+    let hint = format_snooze(snooze_minutes(mon, kind));
+    show_balloon(hwnd, title, &format!("{}\nClick to snooze for {}.", text, hint), flags);
+}
+
+// Focus Assist (or Do Not Disturb) in either profile, or the shell not taking notifications
+// because of presentation mode, a fullscreen app or a D3D game
+pub fn focus_assist_active() -> bool {
+    if quiet_hours_profile().is_some_and(|profile| profile != 0) {
+        return true;
+    }
+    unsafe {
+        match SHQueryUserNotificationState() {
+            Ok(state) => state != QUNS_ACCEPTS_NOTIFICATIONS && state != QUNS_NOT_PRESENT,
+            Err(_) => false,
+        }
+    }
+}
+
+// Looked up at run time, so a Windows build without it just falls back to the shell's state
+fn quiet_hours_profile() -> Option<u32> {
+    unsafe {
+        let ntdll = GetModuleHandleW(w!("ntdll.dll")).ok()?;
+        let query: NtQueryWnfStateData = std::mem::transmute(GetProcAddress(ntdll, s!("NtQueryWnfStateData"))?);
+        let mut stamp = 0u32;
+        let mut profile = 0u32;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        let status = query(
+            &WNF_SHEL_QUIETHOURS_ACTIVE_PROFILE_CHANGED,
+            std::ptr::null(),
+            std::ptr::null(),
+            &mut stamp,
+            &mut profile as *mut u32 as *mut _,
+            &mut size,
+        );
+        // Nothing published yet means Focus Assist was never turned on
+        (status >= 0).then_some(if size >= 4 { profile } else { 0 })
+    }
+}
+
+// Shows what Focus Assist held back, once it is off; called on every update
+pub fn flush_queued(hwnd: HWND, mon: &mut BatteryMonitor) {
+    if mon.queued_alerts.is_empty() || focus_assist_active() {
+        return;
+    }
+    // The tray only shows one balloon at a time, so several are merged into one
+    let queued = std::mem::take(&mut mon.queued_alerts);
+    if let [alert] = queued.as_slice() {
+        show(hwnd, mon, alert.kind, &alert.title, &alert.text, alert.flags);
+    } else {
+        let text = queued.iter().map(|q| format!("{}: {}", q.title, q.text)).collect::<Vec<_>>().join("\n");
+        show_balloon(hwnd, "While Focus Assist was on", &text, NIIF_INFO);
+    }
+}
+
+pub fn snooze_minutes(mon: &BatteryMonitor, kind: AlertKind) -> u32 {
+    mon.settings.alert_snooze_minutes.get(&kind).copied().unwrap_or_else(|| kind.default_snooze_minutes())
+}
+
+// None clears the snooze
+pub fn snooze(mon: &mut BatteryMonitor, kind: AlertKind, minutes: Option<u32>) {
+    match minutes {
+        Some(minutes) => {
+            let until = mon.clock.now() + Duration::minutes(minutes as i64);
+            mon.snoozes.insert(kind, until);
+        }
+        None => {
+            mon.snoozes.remove(&kind);
+        }
+    }
+    save_snoozes(&mon.snoozes);
+    engine::settings_changed();
+}
+
+// For alerts shown some other way (e.g. a message box)
+pub fn record(mon: &mut BatteryMonitor, kind: AlertKind, message: &str, snoozed: bool) {
+    mon.alerts.push(AlertRecord {
+        timestamp: mon.clock.now(),
+        kind,
+        percentage: mon.measurements.back().map(|m| m.percentage),
+        message: message.to_string(),
+        snoozed,
+        deferred: false,
+    });
+    save_alerts(&mon.alerts);
+    mon.log_event(EventKind::Alert, &format!("{}: {}", kind.label(), message));
+}
+
+pub fn load_alerts() -> Vec<AlertRecord> {
+    std::fs::read_to_string(alerts_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_alerts(alerts: &[AlertRecord]) {
+    let start = alerts.len().saturating_sub(MAX_ALERTS);
+    if let Ok(json) = serde_json::to_string(&alerts[start..]) {
+        let _ = std::fs::write(alerts_path(), json);
+    }
+}
+
+// Snoozes are kept across restarts; expired ones are dropped on load
+pub fn load_snoozes() -> BTreeMap<AlertKind, DateTime<Local>> {
+    let now = Local::now();
+    std::fs::read_to_string(data_path("battesty_snooze.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<BTreeMap<AlertKind, DateTime<Local>>>(&s).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, until)| *until > now)
+        .collect()
+}
+
+fn save_snoozes(snoozes: &BTreeMap<AlertKind, DateTime<Local>>) {
+    if let Ok(json) = serde_json::to_string_pretty(snoozes) {
+        let _ = std::fs::write(data_path("battesty_snooze.json"), json);
+    }
+}
+
+fn data_path(name: &str) -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push(name);
+    path
+}
+
+fn alerts_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_alerts.json");
+    path
+}
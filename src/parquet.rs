@@ -0,0 +1,438 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use chrono::{DateTime, Local};
+use crate::archive;
+use crate::battery::{BatteryMeasurement, BatteryMonitor};
+use crate::sessions::Session;
+
+// Minimal Parquet writer: one row group, one uncompressed PLAIN page per column, every column
+// nullable. That's enough for pandas, Polars and DuckDB, and keeps battesty free of Arrow.
+
+const MAGIC: &[u8] = b"PAR1";
+
+// parquet.thrift enums
+const TYPE_BOOLEAN: i32 = 0;
+const TYPE_INT32: i32 = 1;
+const TYPE_INT64: i32 = 2;
+const TYPE_DOUBLE: i32 = 5;
+const TYPE_BYTE_ARRAY: i32 = 6;
+const CONVERTED_UTF8: i32 = 0;
+const CONVERTED_TIMESTAMP_MILLIS: i32 = 9;
+const REPETITION_OPTIONAL: i32 = 1;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const CODEC_UNCOMPRESSED: i32 = 0;
+const PAGE_DATA: i32 = 0;
+
+enum Values {
+    Bool(Vec<Option<bool>>),
+    Int32(Vec<Option<i32>>),
+    Timestamp(Vec<Option<i64>>),
+    Double(Vec<Option<f64>>),
+    Text(Vec<Option<String>>),
+}
+
+pub struct Column {
+    name: &'static str,
+    values: Values,
+}
+
+impl Column {
+    pub fn bool(name: &'static str, values: impl Iterator<Item = Option<bool>>) -> Self {
+        Self { name, values: Values::Bool(values.collect()) }
+    }
+
+    pub fn int32(name: &'static str, values: impl Iterator<Item = Option<i32>>) -> Self {
+        Self { name, values: Values::Int32(values.collect()) }
+    }
+
+    pub fn timestamp(name: &'static str, values: impl Iterator<Item = Option<DateTime<Local>>>) -> Self {
+        Self { name, values: Values::Timestamp(values.map(|t| t.map(|t| t.timestamp_millis())).collect()) }
+    }
+
+    pub fn double(name: &'static str, values: impl Iterator<Item = Option<f64>>) -> Self {
+        Self { name, values: Values::Double(values.collect()) }
+    }
+
+    pub fn text(name: &'static str, values: impl Iterator<Item = Option<String>>) -> Self {
+        Self { name, values: Values::Text(values.collect()) }
+    }
+
+    fn len(&self) -> usize {
+        match &self.values {
+            Values::Bool(v) => v.len(),
+            Values::Int32(v) => v.len(),
+            Values::Timestamp(v) => v.len(),
+            Values::Double(v) => v.len(),
+            Values::Text(v) => v.len(),
+        }
+    }
+
+    fn physical_type(&self) -> i32 {
+        match self.values {
+            Values::Bool(_) => TYPE_BOOLEAN,
+            Values::Int32(_) => TYPE_INT32,
+            Values::Timestamp(_) => TYPE_INT64,
+            Values::Double(_) => TYPE_DOUBLE,
+            Values::Text(_) => TYPE_BYTE_ARRAY,
+        }
+    }
+
+    fn converted_type(&self) -> Option<i32> {
+        match self.values {
+            Values::Timestamp(_) => Some(CONVERTED_TIMESTAMP_MILLIS),
+            Values::Text(_) => Some(CONVERTED_UTF8),
+            _ => None,
+        }
+    }
+
+    // Definition levels (1 = present) followed by the present values
+    fn page_data(&self) -> Vec<u8> {
+        fn split<T: Clone>(values: &[Option<T>]) -> (Vec<bool>, Vec<T>) {
+            (values.iter().map(Option::is_some).collect(), values.iter().flatten().cloned().collect())
+        }
+        let mut plain = Vec::new();
+        let present = match &self.values {
+            Values::Bool(v) => {
+                let (present, values) = split(v);
+                // Bit-packed, least significant bit first
+                plain.resize(values.len().div_ceil(8), 0);
+                for (i, value) in values.iter().enumerate() {
+                    if *value {
+                        plain[i / 8] |= 1 << (i % 8);
+                    }
+                }
+                present
+            }
+            Values::Int32(v) => {
+                let (present, values) = split(v);
+                values.iter().for_each(|value| plain.extend_from_slice(&value.to_le_bytes()));
+                present
+            }
+            Values::Timestamp(v) => {
+                let (present, values) = split(v);
+                values.iter().for_each(|value| plain.extend_from_slice(&value.to_le_bytes()));
+                present
+            }
+            Values::Double(v) => {
+                let (present, values) = split(v);
+                values.iter().for_each(|value| plain.extend_from_slice(&value.to_le_bytes()));
+                present
+            }
+            Values::Text(v) => {
+                let (present, values) = split(v);
+                for value in values {
+                    plain.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                    plain.extend_from_slice(value.as_bytes());
+                }
+                present
+            }
+        };
+
+        // RLE runs of the 1-bit definition levels, prefixed with their byte length
+        let mut levels = Vec::new();
+        let mut i = 0;
+        while i < present.len() {
+            let run = present[i..].iter().take_while(|p| **p == present[i]).count();
+            write_varint(&mut levels, (run as u64) << 1);
+            levels.push(present[i] as u8);
+            i += run;
+        }
+        let mut data = (levels.len() as u32).to_le_bytes().to_vec();
+        data.extend_from_slice(&levels);
+        data.extend_from_slice(&plain);
+        data
+    }
+}
+
+pub fn write(path: &std::path::Path, columns: &[Column]) -> Result<(), String> {
+    let rows = columns.first().map_or(0, Column::len);
+    if columns.iter().any(|c| c.len() != rows) {
+        return Err("Columns differ in length".to_string());
+    }
+
+    let mut file = MAGIC.to_vec();
+    // (offset, size) of each column chunk
+    let mut chunks = Vec::new();
+    for column in columns {
+        let data = column.page_data();
+        let mut header = Thrift::default();
+        header.i32(1, PAGE_DATA);
+        header.i32(2, data.len() as i32);
+        header.i32(3, data.len() as i32);
+        header.struct_begin(5);
+        header.i32(1, rows as i32);
+        header.i32(2, ENCODING_PLAIN);
+        header.i32(3, ENCODING_RLE);
+        header.i32(4, ENCODING_RLE);
+        header.struct_end();
+        header.stop();
+
+        let offset = file.len();
+        file.extend_from_slice(&header.out);
+        file.extend_from_slice(&data);
+        chunks.push((offset, file.len() - offset));
+    }
+
+    let mut meta = Thrift::default();
+    meta.i32(1, 1);
+    meta.list_begin(2, Thrift::STRUCT, columns.len() + 1);
+    // The root of the schema is a group holding every column
+    meta.element_begin();
+    meta.binary(4, b"schema");
+    meta.i32(5, columns.len() as i32);
+    meta.element_end();
+    for column in columns {
+        meta.element_begin();
+        meta.i32(1, column.physical_type());
+        meta.i32(3, REPETITION_OPTIONAL);
+        meta.binary(4, column.name.as_bytes());
+        if let Some(converted) = column.converted_type() {
+            meta.i32(6, converted);
+        }
+        meta.element_end();
+    }
+    meta.i64(3, rows as i64);
+    meta.list_begin(4, Thrift::STRUCT, 1);
+    meta.element_begin();
+    meta.list_begin(1, Thrift::STRUCT, columns.len());
+    for (column, (offset, size)) in columns.iter().zip(&chunks) {
+        meta.element_begin();
+        meta.i64(2, *offset as i64);
+        meta.struct_begin(3);
+        meta.i32(1, column.physical_type());
+        meta.list_begin(2, Thrift::I32, 2);
+        meta.list_i32(ENCODING_PLAIN);
+        meta.list_i32(ENCODING_RLE);
+        meta.list_begin(3, Thrift::BINARY, 1);
+        meta.list_binary(column.name.as_bytes());
+        meta.i32(4, CODEC_UNCOMPRESSED);
+        meta.i64(5, rows as i64);
+        meta.i64(6, *size as i64);
+        meta.i64(7, *size as i64);
+        meta.i64(9, *offset as i64);
+        meta.struct_end();
+        meta.element_end();
+    }
+    meta.i64(2, chunks.iter().map(|(_, size)| *size as i64).sum());
+    meta.i64(3, rows as i64);
+    meta.element_end();
+    meta.binary(6, format!("battesty {}", env!("CARGO_PKG_VERSION")).as_bytes());
+    meta.stop();
+
+    file.extend_from_slice(&meta.out);
+    file.extend_from_slice(&(meta.out.len() as u32).to_le_bytes());
+    file.extend_from_slice(MAGIC);
+    std::fs::write(path, file).map_err(|e| format!("Cannot write {}: {}", path.display(), e))
+}
+
+// Thrift compact protocol, only as much of it as the Parquet footer needs
+#[derive(Default)]
+struct Thrift {
+    out: Vec<u8>,
+    // Last field ID of each open struct; IDs are written as deltas from it
+    last: Vec<i16>,
+}
+
+impl Thrift {
+    const I32: u8 = 5;
+    const I64: u8 = 6;
+    const BINARY: u8 = 8;
+    const LIST: u8 = 9;
+    const STRUCT: u8 = 12;
+
+    fn field(&mut self, id: i16, kind: u8) {
+        if self.last.is_empty() {
+            self.last.push(0);
+        }
+        let last = self.last.last_mut().unwrap();
+        let delta = id - *last;
+        if (1..=15).contains(&delta) {
+            self.out.push((delta as u8) << 4 | kind);
+        } else {
+            self.out.push(kind);
+            write_varint(&mut self.out, zigzag(id as i64));
+        }
+        *last = id;
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, Self::I32);
+        write_varint(&mut self.out, zigzag(value as i64));
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, Self::I64);
+        write_varint(&mut self.out, zigzag(value));
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.field(id, Self::BINARY);
+        self.list_binary(value);
+    }
+
+    fn struct_begin(&mut self, id: i16) {
+        self.field(id, Self::STRUCT);
+        self.last.push(0);
+    }
+
+    fn struct_end(&mut self) {
+        self.out.push(0);
+        self.last.pop();
+    }
+
+    fn list_begin(&mut self, id: i16, element: u8, len: usize) {
+        self.field(id, Self::LIST);
+        if len < 15 {
+            self.out.push((len as u8) << 4 | element);
+        } else {
+            self.out.push(0xF0 | element);
+            write_varint(&mut self.out, len as u64);
+        }
+    }
+
+    // Structs inside a list have no field header of their own
+    fn element_begin(&mut self) {
+        if self.last.is_empty() {
+            self.last.push(0);
+        }
+        self.last.push(0);
+    }
+
+    fn element_end(&mut self) {
+        self.struct_end();
+    }
+
+    fn list_i32(&mut self, value: i32) {
+        write_varint(&mut self.out, zigzag(value as i64));
+    }
+
+    fn list_binary(&mut self, value: &[u8]) {
+        write_varint(&mut self.out, value.len() as u64);
+        self.out.extend_from_slice(value);
+    }
+
+    fn stop(&mut self) {
+        self.out.push(0);
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+// What the export needs from the monitor, copied so the files are written without holding its lock
+pub struct Snapshot {
+    measurements: VecDeque<BatteryMeasurement>,
+    sessions: Vec<Session>,
+    archive_weekly: bool,
+}
+
+pub fn snapshot(mon: &BatteryMonitor) -> Snapshot {
+    Snapshot {
+        measurements: mon.measurements.clone(),
+        sessions: mon.sessions.clone(),
+        archive_weekly: mon.settings.archive_weekly,
+    }
+}
+
+// Measurements and sessions as two files next to the exe
+pub fn export(snapshot: Snapshot) -> Result<Vec<PathBuf>, String> {
+    let stamp = Local::now().format("%Y%m%d-%H%M%S");
+    let folder = {
+        let mut path = std::env::current_exe().unwrap();
+        path.pop();
+        path
+    };
+
+    let m = match archive::oldest().filter(|_| snapshot.archive_weekly) {
+        Some(oldest) => archive::measurements_since(&snapshot.measurements, oldest),
+        None => snapshot.measurements.into_iter().collect(),
+    };
+    let measurements = folder.join(format!("battesty_measurements_{}.parquet", stamp));
+    write(&measurements, &[
+        Column::timestamp("timestamp", m.iter().map(|m| Some(m.timestamp))),
+        Column::int32("percentage", m.iter().map(|m| Some(m.percentage as i32))),
+        Column::bool("charging", m.iter().map(|m| Some(m.is_charging))),
+        Column::double("rate_percent_per_hour", m.iter().map(|m| Some(m.discharge_rate as f64 / 100.0))),
+        Column::int32("eta_minutes", m.iter().map(|m| m.eta_minutes)),
+        Column::int32("os_eta_minutes", m.iter().map(|m| m.os_eta_minutes)),
+        Column::bool("screen_on", m.iter().map(|m| m.screen_on)),
+        Column::bool("user_idle", m.iter().map(|m| m.user_idle)),
+        Column::int32("rate_mw", m.iter().map(|m| m.rate_mw)),
+        Column::int32("full_charge_mwh", m.iter().map(|m| m.full_charge_mwh.map(|v| v as i32))),
+        Column::int32("design_mwh", m.iter().map(|m| m.design_mwh.map(|v| v as i32))),
+        Column::int32("remaining_mwh", m.iter().map(|m| m.remaining_mwh.map(|v| v as i32))),
+        Column::int32("voltage_mv", m.iter().map(|m| m.voltage_mv.map(|v| v as i32))),
+        Column::int32("temperature_dk", m.iter().map(|m| m.temperature_dk.map(|v| v as i32))),
+        Column::bool("not_charging", m.iter().map(|m| Some(m.not_charging))),
+        Column::text("power_plan", m.iter().map(|m| m.power_plan.clone())),
+    ])?;
+
+    let s = &snapshot.sessions;
+    let sessions = folder.join(format!("battesty_sessions_{}.parquet", stamp));
+    write(&sessions, &[
+        Column::text("kind", s.iter().map(|s| Some(s.kind.label().to_string()))),
+        Column::timestamp("started", s.iter().map(|s| Some(s.started))),
+        Column::timestamp("ended", s.iter().map(|s| s.ended)),
+        Column::int32("start_percentage", s.iter().map(|s| Some(s.start_percentage as i32))),
+        Column::int32("end_percentage", s.iter().map(|s| Some(s.end_percentage as i32))),
+        Column::double("average_watts", s.iter().map(|s| s.average_watts)),
+        Column::double("peak_watts", s.iter().map(|s| s.peak_watts)),
+        Column::int32("limited_at", s.iter().map(|s| s.limited_at.map(i32::from))),
+    ])?;
+    Ok(vec![measurements, sessions])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_and_footer() {
+        let path = std::env::temp_dir().join(format!("battesty_parquet_{}.parquet", std::process::id()));
+        write(&path, &[
+            Column::int32("a", [Some(1), None, Some(3)].into_iter()),
+            Column::bool("b", [Some(true), Some(false), Some(true)].into_iter()),
+        ])
+        .unwrap();
+        let file = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(&file[..4], MAGIC);
+        // Data page of 18 bytes for 3 values, PLAIN values with RLE levels
+        assert_eq!(&file[4..21], &[0x15, 0x00, 0x15, 0x24, 0x15, 0x24, 0x2C, 0x15, 0x06, 0x15, 0x00, 0x15, 0x06, 0x15, 0x06, 0x00, 0x00]);
+        // Levels 1, 0, 1 as three runs, then only the two present values
+        assert_eq!(&file[21..39], &[6, 0, 0, 0, 2, 1, 2, 0, 2, 1, 1, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(&file[39..56], &[0x15, 0x00, 0x15, 0x0E, 0x15, 0x0E, 0x2C, 0x15, 0x06, 0x15, 0x00, 0x15, 0x06, 0x15, 0x06, 0x00, 0x00]);
+        // One run of three present values, then true, false, true bit-packed
+        assert_eq!(&file[56..63], &[2, 0, 0, 0, 6, 1, 0b101]);
+
+        let footer_len = u32::from_le_bytes(file[file.len() - 8..file.len() - 4].try_into().unwrap()) as usize;
+        assert_eq!(&file[file.len() - 4..], MAGIC);
+        assert_eq!(file.len() - 8 - footer_len, 63);
+        // Version 1, then a schema of three elements starting with the root group
+        let footer = &file[63..file.len() - 8];
+        assert_eq!(&footer[..12], &[0x15, 0x02, 0x19, 0x3C, 0x48, 6, b's', b'c', b'h', b'e', b'm', b'a']);
+        assert_eq!(footer.last(), Some(&0));
+    }
+
+    #[test]
+    fn columns_must_match() {
+        let path = std::env::temp_dir().join("battesty_parquet_mismatch.parquet");
+        let result = write(&path, &[
+            Column::int32("a", [Some(1)].into_iter()),
+            Column::bool("b", [Some(true), None].into_iter()),
+        ]);
+        assert!(result.is_err());
+        assert!(!path.exists());
+    }
+}
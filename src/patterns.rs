@@ -0,0 +1,155 @@
+use std::collections::{BTreeMap, VecDeque};
+use chrono::{NaiveDate, NaiveTime, Timelike};
+use crate::battery::BatteryMeasurement;
+
+// Longest gap still counted as continuous time between two samples
+const MAX_INTERVAL_SECS: i64 = 15 * 60;
+const MIN_DAYS: usize = 3;
+
+pub struct ChargingPatterns {
+    pub days: usize,
+    // Fraction of observed time on AC for each hour of the day
+    pub ac_share_by_hour: [Option<f64>; 24],
+    pub full_on_ac_hours_per_day: f64,
+    pub typical_unplug: Option<NaiveTime>,
+    pub typical_plug_in: Option<NaiveTime>,
+    pub deep_discharges: usize,
+}
+
+pub fn analyze(measurements: &VecDeque<BatteryMeasurement>) -> Option<ChargingPatterns> {
+    let mut ac_secs = [0i64; 24];
+    let mut total_secs = [0i64; 24];
+    let mut full_on_ac_secs = 0i64;
+    let mut days: BTreeMap<NaiveDate, (Option<NaiveTime>, Option<NaiveTime>)> = BTreeMap::new();
+    let mut deep_discharges = 0;
+    let mut was_low = false;
+
+    for (current, next) in measurements.iter().zip(measurements.iter().skip(1)) {
+        let date = current.timestamp.date_naive();
+        let day = days.entry(date).or_default();
+
+        let seconds = (next.timestamp - current.timestamp).num_seconds();
+        if seconds > 0 && seconds <= MAX_INTERVAL_SECS {
+            let hour = current.timestamp.hour() as usize;
+            total_secs[hour] += seconds;
+            if current.is_charging {
+                ac_secs[hour] += seconds;
+                if current.percentage >= 98 {
+                    full_on_ac_secs += seconds;
+                }
+            }
+        }
+
+        // First unplug of the morning and last plug-in of the evening per day
+        let time = next.timestamp.time();
+        if current.is_charging && !next.is_charging && day.0.is_none() && (4..13).contains(&time.hour()) {
+            day.0 = Some(time);
+        }
+        if !current.is_charging && next.is_charging && time.hour() >= 15 {
+            day.1 = Some(time);
+        }
+
+        let is_low = !next.is_charging && next.percentage < 10;
+        if is_low && !was_low {
+            deep_discharges += 1;
+        }
+        was_low = is_low;
+    }
+
+    if days.len() < MIN_DAYS {
+        return None;
+    }
+
+    let mut ac_share_by_hour = [None; 24];
+    for hour in 0..24 {
+        if total_secs[hour] > 0 {
+            ac_share_by_hour[hour] = Some(ac_secs[hour] as f64 / total_secs[hour] as f64);
+        }
+    }
+
+    Some(ChargingPatterns {
+        days: days.len(),
+        ac_share_by_hour,
+        full_on_ac_hours_per_day: full_on_ac_secs as f64 / 3600.0 / days.len() as f64,
+        typical_unplug: median_time(days.values().filter_map(|d| d.0)),
+        typical_plug_in: median_time(days.values().filter_map(|d| d.1)),
+        deep_discharges,
+    })
+}
+
+fn median_time(times: impl Iterator<Item = NaiveTime>) -> Option<NaiveTime> {
+    let mut minutes: Vec<u32> = times.map(|t| t.hour() * 60 + t.minute()).collect();
+    if minutes.len() < MIN_DAYS {
+        return None;
+    }
+    minutes.sort_unstable();
+    let median = minutes[minutes.len() / 2];
+    NaiveTime::from_hms_opt(median / 60, median % 60, 0)
+}
+
+impl ChargingPatterns {
+    fn ac_share(&self, hours: std::ops::Range<usize>) -> Option<f64> {
+        let shares: Vec<f64> = hours.filter_map(|h| self.ac_share_by_hour[h]).collect();
+        if shares.is_empty() {
+            None
+        } else {
+            Some(shares.iter().sum::<f64>() / shares.len() as f64)
+        }
+    }
+
+    pub fn suggestions(&self) -> Vec<String> {
+        let mut suggestions = Vec::new();
+
+        if self.full_on_ac_hours_per_day >= 4.0 {
+            suggestions.push(format!(
+                "You spend ~{:.0}h/day at 100% on AC — consider a charge limit (e.g. 80%).",
+                self.full_on_ac_hours_per_day,
+            ));
+        }
+
+        let night = self.ac_share(0..6).unwrap_or(0.0);
+        if night >= 0.8 {
+            suggestions.push("Plugged in overnight most days — a charge limit or scheduled charging would reduce time at high charge.".to_string());
+        }
+
+        let work = self.ac_share(9..17).unwrap_or(0.0);
+        if work >= 0.8 {
+            suggestions.push("Mostly on AC during 9–17 — the battery is rarely needed then, so a charge limit costs you nothing.".to_string());
+        } else if work <= 0.2 && self.ac_share_by_hour[9..17].iter().any(Option::is_some) {
+            suggestions.push("Usually on battery during 9–17 — make sure you leave home fully charged.".to_string());
+        }
+
+        if self.deep_discharges as f64 / self.days as f64 >= 0.5 {
+            suggestions.push(format!(
+                "The battery dropped below 10% {} times in {} days — plugging in earlier reduces wear.",
+                self.deep_discharges,
+                self.days,
+            ));
+        }
+
+        suggestions
+    }
+
+    pub fn summary(&self) -> String {
+        let format_time = |t: Option<NaiveTime>| t.map(|t| t.format("%H:%M").to_string()).unwrap_or_else(|| "N/A".to_string());
+        let mut text = format!(
+            "Charging Patterns ({} days)\n\
+             Typical unplug: {} · Typical plug-in: {}\n\
+             Time at 100% on AC: ~{:.1}h per day\n",
+            self.days,
+            format_time(self.typical_unplug),
+            format_time(self.typical_plug_in),
+            self.full_on_ac_hours_per_day,
+        );
+
+        let suggestions = self.suggestions();
+        if suggestions.is_empty() {
+            text.push_str("No charging habits worth changing were found.\n");
+        } else {
+            for suggestion in suggestions {
+                text.push_str(&format!("• {}\n", suggestion));
+            }
+        }
+        text
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use chrono::Duration;
+use crate::battery::{BatteryMeasurement, MAX_SAMPLE_GAP_MINUTES};
+
+// Older time on a plan fades out past this, so the profile follows changing habits
+const MAX_HOURS: f64 = 20.0;
+// A plan needs this much discharge before its drain is trusted
+const MIN_HOURS: f64 = 1.0;
+// How long after a switch the profiles steer the ETA; by then the estimator has caught up
+const ADAPT_MINUTES: f64 = 30.0;
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct PlanDrain {
+    pub name: String,
+    pub hours: f64,
+    pub percent: f64,
+}
+
+impl PlanDrain {
+    pub fn percent_per_hour(&self) -> Option<f64> {
+        Some(self.percent / self.hours).filter(|_| self.hours >= MIN_HOURS)
+    }
+}
+
+// Discharge time and drop per power scheme, keyed by the scheme GUID
+#[derive(Default, Serialize, Deserialize)]
+pub struct PlanProfiles {
+    pub plans: BTreeMap<String, PlanDrain>,
+}
+
+impl PlanProfiles {
+    // Returns true when the level dropped, which is when the profile is worth saving
+    pub fn record(&mut self, previous: &BatteryMeasurement, current: &BatteryMeasurement) -> bool {
+        let Some(plan) = current.power_plan.as_ref().filter(|p| previous.power_plan.as_ref() == Some(*p)) else {
+            return false;
+        };
+        let elapsed = current.timestamp - previous.timestamp;
+        if previous.is_charging || current.is_charging
+            || elapsed <= Duration::zero() || elapsed > Duration::minutes(MAX_SAMPLE_GAP_MINUTES)
+        {
+            return false;
+        }
+
+        let drain = self.plans.entry(plan.clone()).or_default();
+        drain.hours += elapsed.num_seconds() as f64 / 3600.0;
+        drain.percent += previous.percentage.saturating_sub(current.percentage) as f64;
+        if drain.hours > MAX_HOURS {
+            let scale = MAX_HOURS / drain.hours;
+            drain.hours *= scale;
+            drain.percent *= scale;
+        }
+        current.percentage < previous.percentage
+    }
+
+    // Right after a plan switch the samples still describe the old plan; this is how much faster
+    // (above 1) or slower the new plan drains, fading to 1 as samples on the new plan come in
+    pub fn switch_factor(&self, samples: &[BatteryMeasurement]) -> Option<f64> {
+        let newest = samples.last()?;
+        let plan = newest.power_plan.as_ref()?;
+        let switch = samples.iter().rposition(|m| m.power_plan.as_ref().is_some_and(|p| p != plan))?;
+        let previous = samples[switch].power_plan.as_ref()?;
+        let minutes = (newest.timestamp - samples[switch + 1].timestamp).num_seconds() as f64 / 60.0;
+        if minutes >= ADAPT_MINUTES {
+            return None;
+        }
+        let ratio = self.plans.get(plan)?.percent_per_hour()? / self.plans.get(previous)?.percent_per_hour()?;
+        if !ratio.is_finite() || ratio <= 0.0 {
+            return None;
+        }
+        Some(1.0 + (ratio - 1.0) * (1.0 - minutes / ADAPT_MINUTES))
+    }
+
+    // "Balanced: 9.8%/h" for every plan with enough discharge behind it
+    pub fn summary(&self) -> Vec<String> {
+        self.plans
+            .iter()
+            .filter_map(|(guid, drain)| {
+                let name = if drain.name.is_empty() { guid } else { &drain.name };
+                drain.percent_per_hour().map(|rate| format!("{}: {:.1}%/h", name, rate))
+            })
+            .collect()
+    }
+}
+
+pub fn load_profiles() -> PlanProfiles {
+    std::fs::read_to_string(profiles_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_profiles(profiles: &PlanProfiles) {
+    if let Ok(json) = serde_json::to_string_pretty(profiles) {
+        let _ = std::fs::write(profiles_path(), json);
+    }
+}
+
+fn profiles_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_plan_profiles.json");
+    path
+}
@@ -0,0 +1,174 @@
+use std::cell::RefCell;
+use serde::{Deserialize, Serialize};
+use crate::ioctl;
+use crate::wmi::Wmi;
+
+// Instantaneous battery power, from the battery driver's IOCTLs or else root\wmi BatteryStatus
+pub struct PowerReading {
+    pub charge_rate_mw: Option<i32>,
+    pub discharge_rate_mw: Option<i32>,
+}
+
+pub fn read_power() -> Option<PowerReading> {
+    let rates: Vec<i32> = ioctl::read_batteries().iter().filter_map(|b| b.rate_mw).collect();
+    if !rates.is_empty() {
+        // Summed, so a pack charging the other still shows as the net flow
+        let net: i32 = rates.iter().sum();
+        return Some(PowerReading {
+            charge_rate_mw: Some(net).filter(|r| *r > 0),
+            discharge_rate_mw: Some(-net).filter(|r| *r > 0),
+        });
+    }
+    read_power_wmi()
+}
+
+thread_local! {
+    // Kept between samples, as connecting costs far more than the query; COM objects stay on
+    // the thread that made them, hence per thread
+    static POWER_WMI: RefCell<Option<Wmi>> = const { RefCell::new(None) };
+}
+
+fn read_power_wmi() -> Option<PowerReading> {
+    let rows = POWER_WMI.with(|cached| {
+        let mut cached = cached.borrow_mut();
+        if cached.is_none() {
+            *cached = Wmi::connect(r"root\wmi").ok();
+        }
+        let rows = cached.as_ref()?.query("SELECT ChargeRate, DischargeRate FROM BatteryStatus", &["ChargeRate", "DischargeRate"]);
+        // Reconnect next time, in case the service was restarted
+        if rows.is_err() {
+            *cached = None;
+        }
+        rows.ok()
+    })?;
+    let row = rows.into_iter().next()?;
+
+    // Drivers report 0 for the direction that isn't active
+    let rate = |value: &Option<String>| value.as_deref().and_then(|v| v.parse::<i32>().ok()).filter(|r| *r > 0);
+    Some(PowerReading {
+        charge_rate_mw: rate(&row[0]),
+        discharge_rate_mw: rate(&row[1]),
+    })
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Capacity {
+    pub design_mwh: u32,
+    pub full_charge_mwh: u32,
+}
+
+impl Capacity {
+    // Full-charge capacity as a share of design capacity
+    pub fn health(&self) -> f64 {
+        self.full_charge_mwh as f64 / self.design_mwh as f64 * 100.0
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "Design: {} mWh · Full charge: {} mWh ({:.1}%)",
+            group_thousands(self.design_mwh),
+            group_thousands(self.full_charge_mwh),
+            self.health(),
+        )
+    }
+}
+
+pub fn group_thousands(value: u32) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+// All packs together on multi-battery machines
+pub fn read_capacity() -> Option<Capacity> {
+    let batteries = ioctl::read_batteries();
+    let design: Option<u32> = batteries.iter().map(|b| b.designed_mwh).sum();
+    let full: Option<u32> = batteries.iter().map(|b| b.full_charge_mwh).sum();
+    if let (Some(design_mwh), Some(full_charge_mwh)) = (design, full) {
+        return Some(Capacity { design_mwh, full_charge_mwh });
+    }
+    read_capacity_wmi()
+}
+
+fn read_capacity_wmi() -> Option<Capacity> {
+    let wmi = Wmi::connect(r"root\wmi").ok()?;
+    let value = |class: &str, property: &str| -> Option<u32> {
+        wmi.query(&format!("SELECT {} FROM {}", property, class), &[property])
+            .ok()?
+            .into_iter()
+            .next()?
+            .into_iter()
+            .next()
+            .flatten()?
+            .parse()
+            .ok()
+            .filter(|v| *v > 0)
+    };
+
+    Some(Capacity {
+        design_mwh: value("BatteryStaticData", "DesignedCapacity")?,
+        full_charge_mwh: value("BatteryFullChargedCapacity", "FullChargedCapacity")?,
+    })
+}
+
+// Not every battery reports one; 0 means the firmware doesn't count cycles.
+// The most worn pack counts on multi-battery machines.
+pub fn read_cycle_count() -> Option<u32> {
+    ioctl::read_batteries().iter().filter_map(|b| b.cycle_count).max().or_else(read_cycle_count_wmi)
+}
+
+fn read_cycle_count_wmi() -> Option<u32> {
+    let wmi = Wmi::connect(r"root\wmi").ok()?;
+    wmi.query("SELECT CycleCount FROM BatteryCycleCount", &["CycleCount"])
+        .ok()?
+        .into_iter()
+        .next()?
+        .into_iter()
+        .next()
+        .flatten()?
+        .parse()
+        .ok()
+        .filter(|c| *c > 0)
+}
+
+// Charge level of each pack on multi-battery machines, ordered by driver instance
+pub fn read_pack_levels() -> Vec<u8> {
+    let batteries = ioctl::read_batteries();
+    let levels: Vec<u8> = batteries.iter().filter_map(|b| b.percentage()).collect();
+    if !levels.is_empty() && levels.len() == batteries.len() {
+        return levels;
+    }
+    read_pack_levels_wmi()
+}
+
+fn read_pack_levels_wmi() -> Vec<u8> {
+    let Ok(wmi) = Wmi::connect(r"root\wmi") else {
+        return Vec::new();
+    };
+    let by_instance = |class: &str, property: &str| -> Vec<(String, f64)> {
+        let mut rows: Vec<(String, f64)> = wmi
+            .query(&format!("SELECT InstanceName, {} FROM {}", property, class), &["InstanceName", property])
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|row| Some((row[0].clone()?, row[1].as_deref()?.parse().ok()?)))
+            .collect();
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+        rows
+    };
+
+    let remaining = by_instance("BatteryStatus", "RemainingCapacity");
+    let full = by_instance("BatteryFullChargedCapacity", "FullChargedCapacity");
+    remaining
+        .iter()
+        .filter_map(|(instance, remaining)| {
+            let (_, full) = full.iter().find(|(i, _)| i == instance)?;
+            (*full > 0.0).then(|| (remaining / full * 100.0).round().clamp(0.0, 100.0) as u8)
+        })
+        .collect()
+}
@@ -0,0 +1,72 @@
+use windows::Win32::Foundation::{LocalFree, HLOCAL};
+use windows::Win32::System::Power::{PowerGetActiveScheme, PowerReadFriendlyName, PowerSetActiveScheme};
+use windows::Win32::System::Registry::HKEY;
+use windows::core::GUID;
+
+// Built-in schemes from powrprof.h
+pub const SCHEME_BALANCED: GUID = GUID::from_u128(0x381b4222_f694_41f0_9685_ff5bb260df2e);
+pub const SCHEME_HIGH_PERFORMANCE: GUID = GUID::from_u128(0x8c5e7fda_e8bf_4a96_9a85_a6e23a8c635c);
+pub const SCHEME_POWER_SAVER: GUID = GUID::from_u128(0xa1841308_3541_4fab_bc81_f71556f20b4a);
+pub const SCHEME_ULTIMATE_PERFORMANCE: GUID = GUID::from_u128(0xe9a42b02_d5df_448d_aa00_03f14749eb61);
+
+pub struct PowerPlan {
+    pub guid: GUID,
+    pub name: String,
+}
+
+impl PowerPlan {
+    pub fn is_performance(&self) -> bool {
+        self.guid == SCHEME_HIGH_PERFORMANCE || self.guid == SCHEME_ULTIMATE_PERFORMANCE
+    }
+}
+
+pub fn active_plan() -> Option<PowerPlan> {
+    let guid = active_guid()?;
+    Some(PowerPlan { guid, name: plan_name(&guid).unwrap_or_else(|| format!("{:?}", guid)) })
+}
+
+pub fn active_guid() -> Option<GUID> {
+    unsafe {
+        let mut scheme: *mut GUID = std::ptr::null_mut();
+        PowerGetActiveScheme(HKEY::default(), &mut scheme).ok()?;
+        if scheme.is_null() {
+            return None;
+        }
+        let guid = *scheme;
+        let _ = LocalFree(HLOCAL(scheme as *mut _));
+        Some(guid)
+    }
+}
+
+pub fn plan_name(guid: &GUID) -> Option<String> {
+    unsafe {
+        let mut size = 0u32;
+        PowerReadFriendlyName(HKEY::default(), Some(guid), None, None, None, &mut size).ok()?;
+        let mut buffer = vec![0u16; (size as usize).div_ceil(2)];
+        PowerReadFriendlyName(HKEY::default(), Some(guid), None, None, Some(buffer.as_mut_ptr() as *mut u8), &mut size).ok()?;
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        Some(String::from_utf16_lossy(&buffer[..len]))
+    }
+}
+
+pub fn set_active(guid: &GUID) -> Result<(), String> {
+    unsafe { PowerSetActiveScheme(HKEY::default(), Some(guid)) }
+        .map_err(|e| format!("Cannot switch to {}: {}", plan_name(guid).unwrap_or_else(|| format!("{:?}", guid)), e))
+}
+
+// Settings name plans by their built-in name or, for custom ones, by the GUID `powercfg /list` shows
+pub fn resolve(plan: &str) -> Option<GUID> {
+    let key: String = plan.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase();
+    match key.as_str() {
+        "balanced" => return Some(SCHEME_BALANCED),
+        "powersaver" => return Some(SCHEME_POWER_SAVER),
+        "highperformance" => return Some(SCHEME_HIGH_PERFORMANCE),
+        "ultimateperformance" => return Some(SCHEME_ULTIMATE_PERFORMANCE),
+        _ => {}
+    }
+    let hex = plan.trim().trim_start_matches('{').trim_end_matches('}').replace('-', "");
+    if hex.len() != 32 {
+        return None;
+    }
+    u128::from_str_radix(&hex, 16).ok().map(GUID::from_u128)
+}
@@ -0,0 +1,141 @@
+use std::cell::RefCell;
+use std::sync::Once;
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Gdi::*;
+use windows::Win32::UI::Input::KeyboardAndMouse::SetFocus;
+use windows::Win32::UI::WindowsAndMessaging::*;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::core::PCWSTR;
+use crate::icon::app_icon;
+
+const ID_EDIT: i32 = 100;
+const EM_SETSEL: u32 = 0x00B1;
+
+struct PromptState {
+    edit: HWND,
+    result: Option<String>,
+    done: bool,
+}
+
+thread_local! {
+    static STATE: RefCell<Option<PromptState>> = const { RefCell::new(None) };
+}
+
+static REGISTER: Once = Once::new();
+
+unsafe extern "system" fn prompt_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_COMMAND => {
+            let id = (wparam.0 & 0xFFFF) as i32;
+            if id == IDOK.0 || id == IDCANCEL.0 {
+                STATE.with(|state| {
+                    if let Some(state) = state.borrow_mut().as_mut() {
+                        if id == IDOK.0 {
+                            let mut buffer = [0u16; 256];
+                            let len = GetWindowTextW(state.edit, &mut buffer) as usize;
+                            state.result = Some(String::from_utf16_lossy(&buffer[..len]));
+                        }
+                        state.done = true;
+                    }
+                });
+            }
+            LRESULT(0)
+        }
+        WM_CLOSE => {
+            STATE.with(|state| {
+                if let Some(state) = state.borrow_mut().as_mut() {
+                    state.done = true;
+                }
+            });
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+// Small modal text-input window; returns None when cancelled
+pub fn prompt_text(owner: HWND, title: &str, label: &str, initial: &str) -> Option<String> {
+    unsafe {
+        let instance: HINSTANCE = GetModuleHandleW(PCWSTR::null()).unwrap().into();
+        let class_name = "BattestyPrompt\0".encode_utf16().collect::<Vec<u16>>();
+
+        REGISTER.call_once(|| {
+            let wc = WNDCLASSW {
+                lpfnWndProc: Some(prompt_proc),
+                hInstance: instance,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                hbrBackground: HBRUSH((COLOR_BTNFACE.0 + 1) as isize),
+                hCursor: LoadCursorW(None, IDC_ARROW).unwrap_or_default(),
+                hIcon: app_icon(),
+                ..std::mem::zeroed()
+            };
+            RegisterClassW(&wc);
+        });
+
+        let mut pt = POINT { x: 0, y: 0 };
+        let _ = GetCursorPos(&mut pt);
+
+        let title_wide: Vec<u16> = title.encode_utf16().chain(std::iter::once(0)).collect();
+        let hwnd = CreateWindowExW(
+            WS_EX_DLGMODALFRAME | WS_EX_TOPMOST,
+            PCWSTR(class_name.as_ptr()),
+            PCWSTR(title_wide.as_ptr()),
+            WS_POPUP | WS_CAPTION | WS_SYSMENU,
+            (pt.x - 340).max(0),
+            (pt.y - 160).max(0),
+            340,
+            150,
+            owner,
+            None,
+            instance,
+            None,
+        );
+
+        let font = GetStockObject(DEFAULT_GUI_FONT);
+        let child = |class: &str, text: &str, style: WINDOW_STYLE, x: i32, y: i32, w: i32, h: i32, id: i32| {
+            let class_wide: Vec<u16> = class.encode_utf16().chain(std::iter::once(0)).collect();
+            let text_wide: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+            let control = CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                PCWSTR(class_wide.as_ptr()),
+                PCWSTR(text_wide.as_ptr()),
+                WS_CHILD | WS_VISIBLE | style,
+                x, y, w, h,
+                hwnd,
+                HMENU(id as isize),
+                instance,
+                None,
+            );
+            SendMessageW(control, WM_SETFONT, WPARAM(font.0 as usize), LPARAM(1));
+            control
+        };
+
+        child("STATIC", label, WINDOW_STYLE(0), 12, 12, 300, 20, 0);
+        let edit = child("EDIT", initial, WS_BORDER | WS_TABSTOP | WINDOW_STYLE(ES_AUTOHSCROLL as u32), 12, 36, 300, 22, ID_EDIT);
+        child("BUTTON", "OK", WS_TABSTOP | WINDOW_STYLE(BS_DEFPUSHBUTTON as u32), 156, 72, 75, 24, IDOK.0);
+        child("BUTTON", "Cancel", WS_TABSTOP | WINDOW_STYLE(BS_PUSHBUTTON as u32), 237, 72, 75, 24, IDCANCEL.0);
+
+        STATE.with(|state| *state.borrow_mut() = Some(PromptState { edit, result: None, done: false }));
+
+        ShowWindow(hwnd, SW_SHOW);
+        SetForegroundWindow(hwnd);
+        SetFocus(edit);
+        SendMessageW(edit, EM_SETSEL, WPARAM(0), LPARAM(-1));
+
+        let mut msg: MSG = std::mem::zeroed();
+        while !STATE.with(|state| state.borrow().as_ref().map(|s| s.done).unwrap_or(true)) {
+            if !GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                // Let the main loop see the quit request too
+                PostQuitMessage(msg.wParam.0 as i32);
+                break;
+            }
+            if !IsDialogMessageW(hwnd, &msg).as_bool() {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+
+        let _ = DestroyWindow(hwnd);
+        STATE.with(|state| state.borrow_mut().take()).and_then(|s| s.result)
+    }
+}
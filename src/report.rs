@@ -0,0 +1,291 @@
+use std::path::PathBuf;
+use chrono::{DateTime, Duration, Local};
+use crate::battery::{BatteryMonitor, BatteryMeasurement};
+use crate::power::group_thousands;
+use crate::sessions::SessionKind;
+use crate::{ioctl, versions};
+
+// A standalone page in the spirit of `powercfg /batteryreport`: no scripts, no external files,
+// charts drawn as inline SVG so it opens the same in any browser and can be mailed around
+
+const RECENT_DAYS: i64 = 3;
+const MAX_SESSIONS: usize = 60;
+const CHART_WIDTH: f64 = 900.0;
+const CHART_HEIGHT: f64 = 220.0;
+
+// Writes battesty_report_<time>.html next to the history file
+pub fn generate(mon: &BatteryMonitor) -> Result<PathBuf, String> {
+    let now = Local::now();
+    let mut html = String::new();
+    html.push_str(HEAD);
+    html.push_str(&format!(
+        "<h1>Battery report</h1>\n<p class=\"muted\">Generated by Battesty v{} on {}</p>\n",
+        versions::app_version(),
+        now.format("%Y-%m-%d %H:%M"),
+    ));
+
+    html.push_str(&system_section(mon));
+    html.push_str(&recent_section(mon, now));
+    html.push_str(&cycles_section(mon));
+    html.push_str(&sessions_section(mon));
+    html.push_str(&charge_curve_section(mon));
+    html.push_str(&capacity_section(mon));
+    html.push_str(&usage_section(mon));
+    html.push_str("</body>\n</html>\n");
+
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push(format!("battesty_report_{}.html", now.format("%Y%m%d-%H%M%S")));
+    std::fs::write(&path, html).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+fn system_section(mon: &BatteryMonitor) -> String {
+    let system = versions::read_versions();
+    let unknown = || "unknown".to_string();
+    let mut rows = vec![
+        ("Windows", system.os_build.unwrap_or_else(unknown)),
+        ("Battery driver", system.battery_driver.unwrap_or_else(unknown)),
+        ("Vendor", mon.vendor.label().to_string()),
+    ];
+    if let Some(capacity) = mon.capacity {
+        rows.push(("Design capacity", format!("{} mWh", group_thousands(capacity.design_mwh))));
+        rows.push(("Full charge capacity", format!("{} mWh", group_thousands(capacity.full_charge_mwh))));
+        rows.push(("Health", format!("{:.1}%", capacity.health())));
+    }
+    if let Some(cycles) = mon.cycle_count {
+        rows.push(("Cycle count", cycles.to_string()));
+    }
+
+    let mut html = "<h2>Installed batteries</h2>\n<table>\n".to_string();
+    for (label, value) in rows {
+        html.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", label, escape(&value)));
+    }
+    html.push_str("</table>\n");
+    for line in ioctl::summary(&ioctl::read_batteries()).lines().skip(1) {
+        html.push_str(&format!("<p class=\"muted\">{}</p>\n", escape(line)));
+    }
+    html
+}
+
+fn recent_section(mon: &BatteryMonitor, now: DateTime<Local>) -> String {
+    let start = now - Duration::days(RECENT_DAYS);
+    let recent: Vec<&BatteryMeasurement> = mon.measurements.iter().filter(|m| m.timestamp >= start).collect();
+    let mut html = format!("<h2>Recent usage</h2>\n<p class=\"muted\">Charge level over the last {} days; charging in green.</p>\n", RECENT_DAYS);
+    if recent.len() < 2 {
+        html.push_str("<p>Not enough measurements yet.</p>\n");
+        return html;
+    }
+
+    let span = (now - start).num_seconds() as f64;
+    let x = |t: DateTime<Local>| (t - start).num_seconds() as f64 / span;
+    // One run per charging state so the colour follows the charger
+    let mut runs: Vec<(bool, Vec<(f64, f64)>)> = Vec::new();
+    for m in &recent {
+        let point = (x(m.timestamp), m.percentage as f64 / 100.0);
+        match runs.last_mut() {
+            Some((charging, points)) if *charging == m.is_charging => points.push(point),
+            Some((_, points)) => {
+                let joint = *points.last().unwrap();
+                runs.push((m.is_charging, vec![joint, point]));
+            }
+            None => runs.push((m.is_charging, vec![point])),
+        }
+    }
+    let lines: Vec<(&str, Vec<(f64, f64)>)> = runs
+        .into_iter()
+        .map(|(charging, points)| (if charging { "#2e9e44" } else { "#2f6fbf" }, points))
+        .collect();
+    let labels: Vec<String> = (0..=RECENT_DAYS).map(|d| (start + Duration::days(d)).format("%a %H:%M").to_string()).collect();
+    html.push_str(&chart(&lines, "100%", "0%", &labels));
+    html
+}
+
+fn cycles_section(mon: &BatteryMonitor) -> String {
+    let mut html = format!("<h2>Charge cycles</h2>\n<p>{}</p>\n", escape(&mon.cycles.summary()));
+    if mon.cycles.cycles.is_empty() {
+        return html;
+    }
+    html.push_str("<table>\n<tr><th>Cycle</th><th>Started</th><th>Completed</th><th>Took</th></tr>\n");
+    let first = (mon.cycles.total as usize).saturating_sub(mon.cycles.cycles.len()) + 1;
+    for (i, cycle) in mon.cycles.cycles.iter().enumerate().rev().take(MAX_SESSIONS) {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            first + i,
+            cycle.started.format("%Y-%m-%d %H:%M"),
+            cycle.completed.format("%Y-%m-%d %H:%M"),
+            BatteryMonitor::format_duration(cycle.completed - cycle.started),
+        ));
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn sessions_section(mon: &BatteryMonitor) -> String {
+    let mut html = "<h2>Charge and discharge sessions</h2>\n".to_string();
+    if mon.sessions.is_empty() {
+        html.push_str("<p>No sessions recorded yet.</p>\n");
+        return html;
+    }
+    html.push_str("<table>\n<tr><th>Started</th><th>Type</th><th>Duration</th><th>Level</th><th>Average</th><th>Peak</th></tr>\n");
+    let watts = |w: Option<f64>| w.map(|w| format!("{:.1} W", w)).unwrap_or_else(|| "-".to_string());
+    for session in mon.sessions.iter().rev().take(MAX_SESSIONS) {
+        let minutes = session.duration(mon.clock.now()).num_minutes();
+        html.push_str(&format!(
+            "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}h {:02}m{}</td><td>{}% → {}%</td><td>{}</td><td>{}</td></tr>\n",
+            if session.kind == SessionKind::Charge { "charge" } else { "discharge" },
+            session.started.format("%Y-%m-%d %H:%M"),
+            session.kind.label(),
+            minutes / 60,
+            minutes % 60,
+            if session.is_open() { " (ongoing)" } else { "" },
+            session.start_percentage,
+            session.end_percentage,
+            watts(session.average_watts),
+            watts(session.peak_watts),
+        ));
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn charge_curve_section(mon: &BatteryMonitor) -> String {
+    let mut html = "<h2>Charging speed</h2>\n".to_string();
+    let bands = mon.charge_curve.summary();
+    if bands.is_empty() {
+        html.push_str("<p>Nothing learned yet; time until full assumes 1.5% per minute until it has.</p>\n");
+        return html;
+    }
+    html.push_str("<p class=\"muted\">Minutes per percent, learned from past charges.</p>\n<ul>\n");
+    for band in bands {
+        html.push_str(&format!("<li>{}</li>\n", escape(&band)));
+    }
+    html.push_str("</ul>\n");
+    html
+}
+
+fn capacity_section(mon: &BatteryMonitor) -> String {
+    let mut html = "<h2>Battery capacity history</h2>\n".to_string();
+    let days: Vec<_> = mon.daily.iter().filter_map(|d| Some((d.date, d.capacity?))).collect();
+    let (Some(first), Some(last)) = (days.first(), days.last()) else {
+        html.push_str("<p>No capacity snapshots recorded yet.</p>\n");
+        return html;
+    };
+
+    // Scaled to the design capacity, which is the top of the chart
+    let top = days.iter().map(|(_, c)| c.design_mwh.max(c.full_charge_mwh)).max().unwrap_or(1).max(1) as f64;
+    let span = (last.0 - first.0).num_days().max(1) as f64;
+    let x = |date: chrono::NaiveDate| (date - first.0).num_days() as f64 / span;
+    let full: Vec<(f64, f64)> = days.iter().map(|(d, c)| (x(*d), c.full_charge_mwh as f64 / top)).collect();
+    let design: Vec<(f64, f64)> = days.iter().map(|(d, c)| (x(*d), c.design_mwh as f64 / top)).collect();
+    html.push_str("<p class=\"muted\">Full charge capacity in blue, design capacity in grey.</p>\n");
+    html.push_str(&chart(
+        &[("#999999", design), ("#2f6fbf", full)],
+        &format!("{} mWh", group_thousands(top as u32)),
+        "0",
+        &[first.0.format("%Y-%m-%d").to_string(), last.0.format("%Y-%m-%d").to_string()],
+    ));
+
+    // One row per week keeps a year of history readable
+    html.push_str("<table>\n<tr><th>Week of</th><th>Full charge capacity</th><th>Design capacity</th><th>Health</th></tr>\n");
+    let mut shown_week = None;
+    for (date, capacity) in days.iter().rev() {
+        let week = date.format("%G-%V").to_string();
+        if shown_week.as_ref() == Some(&week) {
+            continue;
+        }
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{} mWh</td><td>{} mWh</td><td>{:.1}%</td></tr>\n",
+            date.format("%Y-%m-%d"),
+            group_thousands(capacity.full_charge_mwh),
+            group_thousands(capacity.design_mwh),
+            capacity.health(),
+        ));
+        shown_week = Some(week);
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn usage_section(mon: &BatteryMonitor) -> String {
+    let mut html = "<h2>Usage history</h2>\n".to_string();
+    if mon.daily.is_empty() {
+        html.push_str("<p>No finished days recorded yet.</p>\n");
+        return html;
+    }
+    html.push_str("<table>\n<tr><th>Day</th><th>Range</th><th>Discharged</th><th>Screen on</th><th>Charges</th></tr>\n");
+    for day in mon.daily.iter().rev() {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}% – {}%</td><td>{:.0}%{}</td><td>{:.1} h</td><td>{}</td></tr>\n",
+            day.date.format("%Y-%m-%d"),
+            day.min_percentage,
+            day.max_percentage,
+            day.discharge_percent,
+            day.discharge_wh.map(|wh| format!(" ({:.1} Wh)", wh)).unwrap_or_default(),
+            day.screen_on_hours,
+            day.charge_count,
+        ));
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+// Points are 0..1 on both axes, y upwards; labels are spread evenly along the x axis
+fn chart(lines: &[(&str, Vec<(f64, f64)>)], top_label: &str, bottom_label: &str, x_labels: &[String]) -> String {
+    let (left, bottom) = (60.0, 24.0);
+    let (width, height) = (CHART_WIDTH - left, CHART_HEIGHT - bottom);
+    let mut svg = format!(
+        "<svg viewBox=\"0 0 {} {}\" width=\"{}\" height=\"{}\">\n\
+         <rect x=\"{}\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"#fafafa\" stroke=\"#ccc\"/>\n\
+         <text x=\"{}\" y=\"12\" text-anchor=\"end\">{}</text>\n\
+         <text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>\n",
+        CHART_WIDTH, CHART_HEIGHT, CHART_WIDTH, CHART_HEIGHT,
+        left, width, height,
+        left - 6.0, escape(top_label),
+        left - 6.0, height, escape(bottom_label),
+    );
+    let steps = x_labels.len().saturating_sub(1).max(1) as f64;
+    for (i, label) in x_labels.iter().enumerate() {
+        let x = left + width * i as f64 / steps;
+        let anchor = if i == 0 { "start" } else if i + 1 == x_labels.len() { "end" } else { "middle" };
+        svg.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"{}\">{}</text>\n",
+            x, CHART_HEIGHT - 6.0, anchor, escape(label),
+        ));
+    }
+    for (colour, points) in lines {
+        let coordinates: Vec<String> = points
+            .iter()
+            .map(|(x, y)| format!("{:.1},{:.1}", left + x.clamp(0.0, 1.0) * width, height - y.clamp(0.0, 1.0) * height))
+            .collect();
+        svg.push_str(&format!(
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>\n",
+            colour, coordinates.join(" "),
+        ));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+const HEAD: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Battesty battery report</title>
+<style>
+body { font-family: Segoe UI, sans-serif; margin: 2em; color: #222; }
+h2 { margin-top: 2em; border-bottom: 1px solid #ddd; }
+table { border-collapse: collapse; margin: 0.5em 0; }
+th, td { padding: 3px 12px; text-align: left; border-bottom: 1px solid #eee; }
+tr.charge td:nth-child(2) { color: #2e9e44; }
+tr.discharge td:nth-child(2) { color: #2f6fbf; }
+.muted { color: #777; }
+svg text { font-size: 11px; fill: #555; }
+</style>
+</head>
+<body>
+";
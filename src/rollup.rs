@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use chrono::{Duration, NaiveDate};
+use crate::battery::BatteryMeasurement;
+use crate::estimator::percentile;
+use crate::power::Capacity;
+
+// Longest gap between two samples still counted as continuous time
+const MAX_INTERVAL_SECS: i64 = 15 * 60;
+// A charge that adds less than this is a top-up rather than a real cycle
+const SHALLOW_CHARGE_PERCENT: u8 = 10;
+// Plugging in more often than this per day is worth pointing out
+const FREQUENT_PLUGS_PER_DAY: f64 = 5.0;
+
+// One row per calendar day, kept long after the raw samples are gone
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DailyRollup {
+    pub date: NaiveDate,
+    pub min_percentage: u8,
+    pub max_percentage: u8,
+    pub avg_percentage: f64,
+    // Sum of all drops while on battery
+    pub discharge_percent: f64,
+    pub discharge_wh: Option<f64>,
+    pub screen_on_hours: f64,
+    pub charge_count: u32,
+    // Missing in rollups written before these were counted
+    #[serde(default)]
+    pub unplug_count: Option<u32>,
+    #[serde(default)]
+    pub shallow_charge_count: Option<u32>,
+    pub samples: u32,
+    // Capacity snapshot taken when the day was rolled up
+    #[serde(default)]
+    pub capacity: Option<Capacity>,
+}
+
+// Adds rollups for finished days that aren't in the table yet
+pub fn update(rollups: &mut Vec<DailyRollup>, measurements: &VecDeque<BatteryMeasurement>, capacity: Option<Capacity>, today: NaiveDate) -> bool {
+    let last_rolled = rollups.last().map(|r| r.date);
+    let mut dates: Vec<NaiveDate> = measurements
+        .iter()
+        .map(|m| m.timestamp.date_naive())
+        .filter(|d| *d < today && last_rolled.is_none_or(|last| *d > last))
+        .collect();
+    dates.dedup();
+
+    for date in &dates {
+        if let Some(rollup) = compute(*date, measurements, capacity) {
+            rollups.push(rollup);
+        }
+    }
+    !dates.is_empty()
+}
+
+// Today so far, in the same shape as the finished days
+pub fn today(measurements: &VecDeque<BatteryMeasurement>, capacity: Option<Capacity>, today: NaiveDate) -> Option<DailyRollup> {
+    compute(today, measurements, capacity)
+}
+
+fn compute(date: NaiveDate, measurements: &VecDeque<BatteryMeasurement>, capacity: Option<Capacity>) -> Option<DailyRollup> {
+    let day: Vec<&BatteryMeasurement> = measurements.iter().filter(|m| m.timestamp.date_naive() == date).collect();
+    let first = day.first()?;
+    // What the day itself recorded beats the current reading when rolling up older days
+    let capacity = day.iter().rev().find_map(|m| m.capacity()).or(capacity);
+
+    let mut discharge_percent = 0.0;
+    let mut screen_on_secs = 0;
+    let mut charge_count = 0;
+    let mut unplug_count = 0;
+    let mut shallow_charge_count = 0;
+    let mut charge_start = first.is_charging.then_some(first.percentage);
+    let mut was_charging = first.is_charging;
+    for pair in day.windows(2) {
+        let seconds = (pair[1].timestamp - pair[0].timestamp).num_seconds();
+        if !pair[0].is_charging && !pair[1].is_charging && pair[1].percentage < pair[0].percentage {
+            discharge_percent += (pair[0].percentage - pair[1].percentage) as f64;
+        }
+        if seconds > 0 && seconds <= MAX_INTERVAL_SECS && pair[0].screen_on != Some(false) {
+            screen_on_secs += seconds;
+        }
+        if pair[1].is_charging && !was_charging {
+            charge_count += 1;
+            charge_start = Some(pair[0].percentage);
+        }
+        if !pair[1].is_charging && was_charging {
+            unplug_count += 1;
+            if charge_start.is_some_and(|start| pair[0].percentage.saturating_sub(start) < SHALLOW_CHARGE_PERCENT) {
+                shallow_charge_count += 1;
+            }
+            charge_start = None;
+        }
+        was_charging = pair[1].is_charging;
+    }
+
+    Some(DailyRollup {
+        date,
+        min_percentage: day.iter().map(|m| m.percentage).min()?,
+        max_percentage: day.iter().map(|m| m.percentage).max()?,
+        avg_percentage: day.iter().map(|m| m.percentage as f64).sum::<f64>() / day.len() as f64,
+        discharge_percent,
+        discharge_wh: capacity.map(|c| discharge_percent / 100.0 * c.full_charge_mwh as f64 / 1000.0),
+        screen_on_hours: Duration::seconds(screen_on_secs).num_minutes() as f64 / 60.0,
+        charge_count,
+        unplug_count: Some(unplug_count),
+        shallow_charge_count: Some(shallow_charge_count),
+        samples: day.len() as u32,
+        capacity,
+    })
+}
+
+pub struct DrainPercentiles {
+    pub days: usize,
+    pub p10: f64,
+    pub p50: f64,
+    pub p90: f64,
+}
+
+impl DrainPercentiles {
+    pub fn summary(&self) -> String {
+        format!(
+            "Daily Drain (last 30 days, {} days on battery)\np10: {:.0}% · p50: {:.0}% · p90: {:.0}%\n",
+            self.days, self.p10, self.p50, self.p90,
+        )
+    }
+}
+
+// Spread of daily consumption; days spent entirely on AC are left out
+pub fn drain_percentiles(rollups: &[DailyRollup], today: NaiveDate) -> Option<DrainPercentiles> {
+    let cutoff = today - Duration::days(30);
+    let mut drains: Vec<f64> = rollups
+        .iter()
+        .filter(|r| r.date >= cutoff && r.discharge_percent > 0.0)
+        .map(|r| r.discharge_percent)
+        .collect();
+    if drains.len() < 5 {
+        return None;
+    }
+    drains.sort_by(f64::total_cmp);
+    Some(DrainPercentiles {
+        days: drains.len(),
+        p10: percentile(&drains, 0.1),
+        p50: percentile(&drains, 0.5),
+        p90: percentile(&drains, 0.9),
+    })
+}
+
+pub struct CyclingStats {
+    pub days: usize,
+    pub plugs_per_day: f64,
+    pub unplugs_per_day: f64,
+    pub shallow_per_day: f64,
+    pub this_week: u32,
+    pub last_week: Option<u32>,
+}
+
+impl CyclingStats {
+    pub fn summary(&self) -> String {
+        let mut text = format!(
+            "Plug/Unplug Cycles (last {} days)\nYou plug in ~{:.0}× and unplug ~{:.0}× per day; {:.1} of those charges a day add under {}%.\n",
+            self.days, self.plugs_per_day, self.unplugs_per_day, self.shallow_per_day, SHALLOW_CHARGE_PERCENT,
+        );
+        text.push_str(&match self.last_week {
+            Some(last_week) => format!("Plug-ins: {} in the last 7 days, {} in the 7 before.\n", self.this_week, last_week),
+            None => format!("Plug-ins: {} in the last 7 days.\n", self.this_week),
+        });
+        if self.plugs_per_day >= FREQUENT_PLUGS_PER_DAY {
+            text.push_str("Frequent short top-ups keep the battery near full, which ages it faster than the cycles themselves; a charge limit or staying plugged in for longer stretches helps.\n");
+        }
+        text
+    }
+}
+
+// Connect/disconnect counts per day and week from the rollups that have them
+pub fn cycling_stats(rollups: &[DailyRollup], today: NaiveDate) -> Option<CyclingStats> {
+    let counted = |from: i64, to: i64| rollups
+        .iter()
+        .filter(move |r| r.date >= today - Duration::days(to) && r.date < today - Duration::days(from) && r.unplug_count.is_some());
+    let week: Vec<&DailyRollup> = counted(0, 7).collect();
+    if week.len() < 3 {
+        return None;
+    }
+    let days = week.len() as f64;
+    let previous: Vec<&DailyRollup> = counted(7, 14).collect();
+
+    Some(CyclingStats {
+        days: week.len(),
+        plugs_per_day: week.iter().map(|r| r.charge_count as f64).sum::<f64>() / days,
+        unplugs_per_day: week.iter().filter_map(|r| r.unplug_count).sum::<u32>() as f64 / days,
+        shallow_per_day: week.iter().filter_map(|r| r.shallow_charge_count).sum::<u32>() as f64 / days,
+        this_week: week.iter().map(|r| r.charge_count).sum(),
+        last_week: (previous.len() >= 3).then(|| previous.iter().map(|r| r.charge_count).sum()),
+    })
+}
+
+pub fn load_rollups() -> Vec<DailyRollup> {
+    std::fs::read_to_string(rollups_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_rollups(rollups: &[DailyRollup]) {
+    if let Ok(json) = serde_json::to_string(rollups) {
+        let _ = std::fs::write(rollups_path(), json);
+    }
+}
+
+fn rollups_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_daily.json");
+    path
+}
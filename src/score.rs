@@ -0,0 +1,120 @@
+// Points each factor can take off the 100-point score
+const WEAR_WEIGHT: f64 = 40.0;
+const CYCLES_WEIGHT: f64 = 20.0;
+const RUNTIME_WEIGHT: f64 = 25.0;
+const STANDBY_WEIGHT: f64 = 15.0;
+
+// Rated cycle life of a typical laptop cell
+const RATED_CYCLES: f64 = 1000.0;
+// Average draw a runtime is judged against, for light mixed use
+const TYPICAL_DRAW_WATTS: f64 = 7.0;
+// Modern standby should lose well under 1% per hour
+const HEALTHY_STANDBY_DRAIN: f64 = 0.5;
+
+pub struct ScoreInputs {
+    pub health: Option<f64>,
+    pub cycle_count: Option<u32>,
+    // Runtime a full charge currently gives and the battery's full-charge energy
+    pub runtime_hours: Option<f64>,
+    pub full_charge_wh: Option<f64>,
+    pub standby_drain_per_hour: Option<f64>,
+}
+
+pub struct ScoreFactor {
+    pub name: &'static str,
+    pub penalty: f64,
+    pub detail: String,
+}
+
+pub struct BatteryScore {
+    pub score: u8,
+    pub factors: Vec<ScoreFactor>,
+    // Factors left out for lack of data
+    pub missing: Vec<&'static str>,
+}
+
+pub fn compute(inputs: &ScoreInputs) -> Option<BatteryScore> {
+    let mut factors = Vec::new();
+    let mut missing = Vec::new();
+
+    match inputs.health {
+        Some(health) => factors.push(ScoreFactor {
+            name: "Wear",
+            // 25% capacity loss takes the full weight
+            penalty: ((100.0 - health).max(0.0) / 25.0 * WEAR_WEIGHT).min(WEAR_WEIGHT),
+            detail: format!("{:.1}% of design capacity left", health),
+        }),
+        None => missing.push("wear"),
+    }
+
+    match inputs.cycle_count {
+        Some(cycles) => factors.push(ScoreFactor {
+            name: "Cycles",
+            penalty: (cycles as f64 / RATED_CYCLES * CYCLES_WEIGHT).min(CYCLES_WEIGHT),
+            detail: format!("{} of ~{:.0} rated cycles", cycles, RATED_CYCLES),
+        }),
+        None => missing.push("cycle count"),
+    }
+
+    match (inputs.runtime_hours, inputs.full_charge_wh) {
+        (Some(runtime), Some(wh)) => {
+            let typical = wh / TYPICAL_DRAW_WATTS;
+            let ratio = runtime / typical;
+            factors.push(ScoreFactor {
+                name: "Runtime",
+                // Half the typical runtime takes the full weight
+                penalty: ((1.0 - ratio).max(0.0) * 2.0 * RUNTIME_WEIGHT).min(RUNTIME_WEIGHT),
+                detail: format!("{:.1}h per charge vs ~{:.1}h typical for {:.0} Wh", runtime, typical, wh),
+            });
+        }
+        _ => missing.push("runtime"),
+    }
+
+    match inputs.standby_drain_per_hour {
+        Some(drain) => factors.push(ScoreFactor {
+            name: "Standby drain",
+            penalty: ((drain - HEALTHY_STANDBY_DRAIN).max(0.0) * 10.0).min(STANDBY_WEIGHT),
+            detail: format!("{:.1}% per hour asleep", drain),
+        }),
+        None => missing.push("standby drain"),
+    }
+
+    if factors.is_empty() {
+        return None;
+    }
+
+    // Scale to the factors that could be measured so missing data doesn't count as perfect
+    let available: f64 = factors.iter().map(|f| weight(f.name)).sum();
+    let lost: f64 = factors.iter().map(|f| f.penalty).sum();
+    let score = (100.0 * (1.0 - lost / available)).round().clamp(0.0, 100.0) as u8;
+
+    factors.sort_by(|a, b| b.penalty.total_cmp(&a.penalty));
+    Some(BatteryScore { score, factors, missing })
+}
+
+fn weight(name: &str) -> f64 {
+    match name {
+        "Wear" => WEAR_WEIGHT,
+        "Cycles" => CYCLES_WEIGHT,
+        "Runtime" => RUNTIME_WEIGHT,
+        _ => STANDBY_WEIGHT,
+    }
+}
+
+impl BatteryScore {
+    pub fn summary(&self) -> String {
+        let mut text = format!("Battery Score: {}/100\n", self.score);
+        for factor in &self.factors {
+            let points = factor.penalty.round() as i32;
+            if points > 0 {
+                text.push_str(&format!("• {}: -{} ({})\n", factor.name, points, factor.detail));
+            } else {
+                text.push_str(&format!("• {}: fine ({})\n", factor.name, factor.detail));
+            }
+        }
+        if !self.missing.is_empty() {
+            text.push_str(&format!("Not scored yet: {}\n", self.missing.join(", ")));
+        }
+        text
+    }
+}
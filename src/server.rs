@@ -0,0 +1,144 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use crate::MONITOR;
+
+// Local-only HTTP server for the streaming overlay (/overlay) and the data behind it (/status.json)
+
+pub const DEFAULT_PORT: u16 = 8765;
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static PORT: AtomicU16 = AtomicU16::new(0);
+
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::Relaxed)
+}
+
+pub fn overlay_url() -> String {
+    format!("http://127.0.0.1:{}/overlay", PORT.load(Ordering::Relaxed))
+}
+
+// Only binds to loopback, so nothing is exposed to the network
+pub fn start(port: u16) -> Result<(), String> {
+    if RUNNING.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("Cannot listen on port {}: {}", port, e))?;
+    RUNNING.store(true, Ordering::Relaxed);
+    PORT.store(port, Ordering::Relaxed);
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            if !RUNNING.load(Ordering::Relaxed) {
+                break;
+            }
+            if let Ok(stream) = stream {
+                let _ = stream.set_read_timeout(Some(std::time::Duration::from_secs(5)));
+                let _ = handle(stream);
+            }
+        }
+    });
+    Ok(())
+}
+
+pub fn stop() {
+    if RUNNING.swap(false, Ordering::Relaxed) {
+        // Wakes the blocking accept so the thread sees the flag and drops the listener
+        let _ = TcpStream::connect(("127.0.0.1", PORT.load(Ordering::Relaxed)));
+    }
+}
+
+fn handle(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(&stream);
+    reader.read_line(&mut request_line)?;
+    // The headers aren't needed, but are read so the client doesn't see a reset
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or("/");
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/overlay") => ("200 OK", "text/html; charset=utf-8", overlay_page(query)),
+        ("GET", "/status.json") => ("200 OK", "application/json", status_json()),
+        ("GET", "/") => ("200 OK", "text/html; charset=utf-8", "<a href=\"/overlay\">/overlay</a> · <a href=\"/status.json\">/status.json</a>".to_string()),
+        ("GET", _) => ("404 Not Found", "text/plain", "Not found".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "Only GET is supported".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body,
+    )
+}
+
+fn status_json() -> String {
+    let latest = MONITOR.get()
+        .and_then(|m| m.lock().ok())
+        .and_then(|mon| mon.measurements.back().cloned());
+    let value = match latest {
+        Some(m) => serde_json::json!({
+            "percentage": m.percentage,
+            "charging": m.is_charging,
+            "eta_minutes": m.eta_minutes,
+            "rate_per_hour": m.discharge_rate as f64 / 100.0,
+            "timestamp": m.timestamp.to_rfc3339(),
+        }),
+        None => serde_json::json!({ "percentage": null }),
+    };
+    value.to_string()
+}
+
+// ?theme=dark|light|transparent, ?size=<px>; the page polls /status.json itself
+fn overlay_page(query: &str) -> String {
+    let param = |name: &str| query.split('&').find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='));
+    let (background, text, accent) = match param("theme").unwrap_or("dark") {
+        "light" => ("rgba(255,255,255,0.85)", "#202020", "#2e7d32"),
+        "transparent" => ("transparent", "#ffffff", "#7CFC8A"),
+        _ => ("rgba(20,20,20,0.8)", "#ffffff", "#7CFC8A"),
+    };
+    let size: u32 = param("size").and_then(|s| s.parse().ok()).filter(|s| (8..=200).contains(s)).unwrap_or(28);
+
+    OVERLAY_TEMPLATE
+        .replace("{background}", background)
+        .replace("{text}", text)
+        .replace("{accent}", accent)
+        .replace("{size}", &size.to_string())
+}
+
+const OVERLAY_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><title>Battesty</title>
+<style>
+html, body { margin: 0; background: transparent; overflow: hidden; }
+#box { display: inline-flex; align-items: center; gap: 0.5em; padding: 0.3em 0.7em; border-radius: 0.4em;
+  background: {background}; color: {text}; font: 600 {size}px "Segoe UI", sans-serif; text-shadow: 0 1px 2px rgba(0,0,0,0.4); }
+#bar { width: 2.2em; height: 1em; border: 2px solid {text}; border-radius: 0.15em; position: relative; }
+#fill { position: absolute; left: 1px; top: 1px; bottom: 1px; background: {accent}; }
+#eta { opacity: 0.8; font-weight: 400; }
+.low #fill { background: #ff5050; }
+</style></head>
+<body><div id="box"><div id="bar"><div id="fill"></div></div><span id="pct">–</span><span id="eta"></span></div>
+<script>
+function duration(m) { return m >= 60 ? Math.floor(m / 60) + "h " + (m % 60) + "m" : m + "m"; }
+async function refresh() {
+  try {
+    const s = await (await fetch("/status.json", { cache: "no-store" })).json();
+    if (s.percentage === null) return;
+    document.getElementById("pct").textContent = s.percentage + "%";
+    document.getElementById("fill").style.width = "calc(" + s.percentage + "% - 2px)";
+    document.getElementById("eta").textContent = s.charging ? "⚡ charging" : (s.eta_minutes != null ? duration(s.eta_minutes) + " left" : "");
+    document.getElementById("box").className = !s.charging && s.percentage <= 15 ? "low" : "";
+  } catch (e) {}
+}
+refresh();
+setInterval(refresh, 5000);
+</script></body></html>
+"#;
@@ -0,0 +1,204 @@
+use std::sync::Once;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use chrono::{Duration, NaiveDate};
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Gdi::*;
+use windows::Win32::UI::Input::KeyboardAndMouse::SetFocus;
+use windows::Win32::UI::WindowsAndMessaging::*;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::core::PCWSTR;
+
+use crate::sessions::SessionKind;
+use crate::MONITOR;
+use crate::icon::app_icon;
+
+static LIST_WINDOW: AtomicIsize = AtomicIsize::new(0);
+static REGISTER: Once = Once::new();
+
+const ID_KIND: i32 = 100;
+const ID_SHORT: i32 = 101;
+const ID_ANOMALIES: i32 = 102;
+const ID_FROM: i32 = 103;
+const ID_TO: i32 = 104;
+const ID_LIST: i32 = 105;
+
+const SHORT_SESSION_HOURS: i64 = 2;
+
+struct Filter {
+    kind: Option<SessionKind>,
+    short_only: bool,
+    anomalies_only: bool,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+pub fn show_session_list(owner: HWND) {
+    unsafe {
+        let existing = HWND(LIST_WINDOW.load(Ordering::Relaxed));
+        if existing.0 != 0 && IsWindow(existing).as_bool() {
+            ShowWindow(existing, SW_SHOWNORMAL);
+            SetForegroundWindow(existing);
+            return;
+        }
+
+        let instance: HINSTANCE = GetModuleHandleW(PCWSTR::null()).unwrap().into();
+        let class_name = "BattestySessions\0".encode_utf16().collect::<Vec<u16>>();
+
+        REGISTER.call_once(|| {
+            let wc = WNDCLASSW {
+                lpfnWndProc: Some(list_proc),
+                hInstance: instance,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                hbrBackground: HBRUSH((COLOR_BTNFACE.0 + 1) as isize),
+                hCursor: LoadCursorW(None, IDC_ARROW).unwrap_or_default(),
+                hIcon: app_icon(),
+                ..std::mem::zeroed()
+            };
+            RegisterClassW(&wc);
+        });
+
+        let title = "Battesty - Sessions\0".encode_utf16().collect::<Vec<u16>>();
+        let hwnd = CreateWindowExW(
+            WS_EX_CONTROLPARENT,
+            PCWSTR(class_name.as_ptr()),
+            PCWSTR(title.as_ptr()),
+            WS_OVERLAPPEDWINDOW,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            760,
+            480,
+            owner,
+            None,
+            instance,
+            None,
+        );
+        LIST_WINDOW.store(hwnd.0, Ordering::Relaxed);
+
+        let font = GetStockObject(DEFAULT_GUI_FONT);
+        let child = |class: &str, text: &str, style: WINDOW_STYLE, x: i32, y: i32, w: i32, h: i32, id: i32| {
+            let class_wide: Vec<u16> = class.encode_utf16().chain(std::iter::once(0)).collect();
+            let text_wide: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+            let control = CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                PCWSTR(class_wide.as_ptr()),
+                PCWSTR(text_wide.as_ptr()),
+                WS_CHILD | WS_VISIBLE | style,
+                x, y, w, h,
+                hwnd,
+                HMENU(id as isize),
+                instance,
+                None,
+            );
+            SendMessageW(control, WM_SETFONT, WPARAM(font.0 as usize), LPARAM(1));
+            control
+        };
+
+        // Screen readers name each control after the label created just before it
+        child("STATIC", "Kind", WINDOW_STYLE(0), 8, 12, 26, 20, 0);
+        let kind = child("COMBOBOX", "", WS_TABSTOP | WS_VSCROLL | WINDOW_STYLE(CBS_DROPDOWNLIST as u32), 36, 8, 130, 200, ID_KIND);
+        for option in ["All sessions", "Charge only", "Discharge only"] {
+            let label: Vec<u16> = option.encode_utf16().chain(std::iter::once(0)).collect();
+            SendMessageW(kind, CB_ADDSTRING, WPARAM(0), LPARAM(label.as_ptr() as isize));
+        }
+        SendMessageW(kind, CB_SETCURSEL, WPARAM(0), LPARAM(0));
+
+        let checkbox = WS_TABSTOP | WINDOW_STYLE(BS_AUTOCHECKBOX as u32);
+        let edit = WS_BORDER | WS_TABSTOP | WINDOW_STYLE(ES_AUTOHSCROLL as u32);
+        child("BUTTON", &format!("Shorter than {}h", SHORT_SESSION_HOURS), checkbox, 178, 8, 120, 24, ID_SHORT);
+        child("BUTTON", "With anomalies", checkbox, 304, 8, 120, 24, ID_ANOMALIES);
+        child("STATIC", "From (YYYY-MM-DD)", WINDOW_STYLE(0), 436, 12, 100, 20, 0);
+        child("EDIT", "", edit, 536, 8, 80, 22, ID_FROM);
+        child("STATIC", "To", WINDOW_STYLE(0), 624, 12, 16, 20, 0);
+        child("EDIT", "", edit, 642, 8, 80, 22, ID_TO);
+        child("STATIC", "Sessions, newest first", WINDOW_STYLE(0), 8, 40, 200, 16, 0);
+        child("LISTBOX", "", WS_BORDER | WS_VSCROLL | WS_TABSTOP | WINDOW_STYLE(LBS_NOINTEGRALHEIGHT as u32), 8, 58, 0, 0, ID_LIST);
+
+        layout(hwnd);
+        fill(hwnd);
+
+        ShowWindow(hwnd, SW_SHOWNORMAL);
+        SetForegroundWindow(hwnd);
+        SetFocus(kind);
+    }
+}
+
+unsafe extern "system" fn list_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_COMMAND => {
+            let id = (wparam.0 & 0xFFFF) as i32;
+            let code = ((wparam.0 >> 16) & 0xFFFF) as u32;
+            let changed = match id {
+                ID_KIND => code == CBN_SELCHANGE,
+                ID_SHORT | ID_ANOMALIES => code == BN_CLICKED,
+                ID_FROM | ID_TO => code == EN_CHANGE,
+                _ => false,
+            };
+            if changed {
+                fill(hwnd);
+            }
+            LRESULT(0)
+        }
+        WM_SIZE => {
+            layout(hwnd);
+            LRESULT(0)
+        }
+        WM_DESTROY => {
+            LIST_WINDOW.store(0, Ordering::Relaxed);
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+unsafe fn layout(hwnd: HWND) {
+    let mut client = RECT::default();
+    let _ = GetClientRect(hwnd, &mut client);
+    let _ = MoveWindow(GetDlgItem(hwnd, ID_LIST), 8, 58, client.right - 16, client.bottom - 66, TRUE);
+}
+
+unsafe fn read_filter(hwnd: HWND) -> Filter {
+    let checked = |id: i32| SendMessageW(GetDlgItem(hwnd, id), BM_GETCHECK, WPARAM(0), LPARAM(0)).0 == 1;
+    // Incomplete dates are ignored while they're being typed
+    let date = |id: i32| {
+        let mut buffer = [0u16; 32];
+        let len = GetWindowTextW(GetDlgItem(hwnd, id), &mut buffer) as usize;
+        NaiveDate::parse_from_str(String::from_utf16_lossy(&buffer[..len]).trim(), "%Y-%m-%d").ok()
+    };
+
+    Filter {
+        kind: match SendMessageW(GetDlgItem(hwnd, ID_KIND), CB_GETCURSEL, WPARAM(0), LPARAM(0)).0 {
+            1 => Some(SessionKind::Charge),
+            2 => Some(SessionKind::Discharge),
+            _ => None,
+        },
+        short_only: checked(ID_SHORT),
+        anomalies_only: checked(ID_ANOMALIES),
+        from: date(ID_FROM),
+        to: date(ID_TO),
+    }
+}
+
+// Newest first
+unsafe fn fill(hwnd: HWND) {
+    let filter = read_filter(hwnd);
+    let Some(mon) = MONITOR.get().and_then(|m| m.lock().ok()) else { return };
+    let list = GetDlgItem(hwnd, ID_LIST);
+
+    SendMessageW(list, WM_SETREDRAW, WPARAM(0), LPARAM(0));
+    SendMessageW(list, LB_RESETCONTENT, WPARAM(0), LPARAM(0));
+    let now = mon.clock.now();
+    for session in mon.sessions.iter().rev() {
+        let date = session.started.date_naive();
+        let matches = filter.kind.is_none_or(|k| session.kind == k)
+            && (!filter.short_only || session.duration(now) < Duration::hours(SHORT_SESSION_HOURS))
+            && (!filter.anomalies_only || session.has_anomaly(&mon.events, now))
+            && filter.from.is_none_or(|from| date >= from)
+            && filter.to.is_none_or(|to| date <= to);
+        if matches {
+            let line: Vec<u16> = session.summary(now).encode_utf16().chain(std::iter::once(0)).collect();
+            SendMessageW(list, LB_ADDSTRING, WPARAM(0), LPARAM(line.as_ptr() as isize));
+        }
+    }
+    SendMessageW(list, WM_SETREDRAW, WPARAM(1), LPARAM(0));
+    InvalidateRect(list, None, TRUE);
+}
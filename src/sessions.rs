@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Local};
+use crate::events::{Event, EventKind};
+
+const MAX_SESSIONS: usize = 400;
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum SessionKind {
+    // Files written before discharge sessions existed only hold charge sessions
+    #[default]
+    Charge,
+    Discharge,
+}
+
+impl SessionKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SessionKind::Charge => "Charge",
+            SessionKind::Discharge => "Discharge",
+        }
+    }
+}
+
+// One plug-in to unplug period, or the time on battery between them
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default)]
+    pub kind: SessionKind,
+    pub started: DateTime<Local>,
+    pub ended: Option<DateTime<Local>>,
+    pub start_percentage: u8,
+    pub end_percentage: u8,
+    // Charger input power while charging, system draw while discharging
+    pub average_watts: Option<f64>,
+    pub peak_watts: Option<f64>,
+    // Level a charge limit held the battery at during this session
+    #[serde(default)]
+    pub limited_at: Option<u8>,
+    #[serde(default)]
+    watt_samples: u32,
+}
+
+impl Session {
+    pub fn start(kind: SessionKind, percentage: u8, now: DateTime<Local>) -> Self {
+        Self {
+            kind,
+            started: now,
+            ended: None,
+            start_percentage: percentage,
+            end_percentage: percentage,
+            average_watts: None,
+            peak_watts: None,
+            limited_at: None,
+            watt_samples: 0,
+        }
+    }
+
+    pub fn record(&mut self, percentage: u8, watts: Option<f64>) {
+        self.end_percentage = percentage;
+        if let Some(watts) = watts {
+            let average = self.average_watts.unwrap_or(0.0);
+            self.watt_samples += 1;
+            self.average_watts = Some(average + (watts - average) / self.watt_samples as f64);
+            self.peak_watts = Some(self.peak_watts.map_or(watts, |peak| peak.max(watts)));
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.ended.is_none()
+    }
+
+    // `now` ends a session that is still open
+    pub fn duration(&self, now: DateTime<Local>) -> chrono::Duration {
+        self.ended.unwrap_or(now) - self.started
+    }
+
+    pub fn has_anomaly(&self, events: &[Event], now: DateTime<Local>) -> bool {
+        let end = self.ended.unwrap_or(now);
+        events.iter().any(|e| e.kind == EventKind::Anomaly && e.timestamp >= self.started && e.timestamp <= end)
+    }
+
+    pub fn summary(&self, now: DateTime<Local>) -> String {
+        let minutes = self.duration(now).num_minutes();
+        let watts = |w: Option<f64>| match w {
+            Some(w) => format!("~{:.0} W", w),
+            None => "n/a".to_string(),
+        };
+        format!(
+            "{}  {}  {}% → {}% in {}h {}m{} · Average {} · Peak {}{}",
+            self.started.format("%Y-%m-%d %H:%M"),
+            self.kind.label(),
+            self.start_percentage,
+            self.end_percentage,
+            minutes / 60,
+            minutes % 60,
+            if self.is_open() { " (ongoing)" } else { "" },
+            watts(self.average_watts),
+            watts(self.peak_watts),
+            match self.limited_at {
+                Some(limit) => format!(" · Charge limited ({}%)", limit),
+                None => String::new(),
+            },
+        )
+    }
+}
+
+pub fn load_sessions() -> Vec<Session> {
+    std::fs::read_to_string(sessions_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_sessions(sessions: &[Session]) {
+    let start = sessions.len().saturating_sub(MAX_SESSIONS);
+    if let Ok(json) = serde_json::to_string_pretty(&sessions[start..]) {
+        let _ = std::fs::write(sessions_path(), json);
+    }
+}
+
+fn sessions_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_sessions.json");
+    path
+}
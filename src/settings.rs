@@ -1,24 +1,279 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use serde::{Deserialize, Serialize};
+use windows::Win32::UI::WindowsAndMessaging::{MessageBoxW, MB_ICONWARNING, MB_OK};
+use windows::core::PCWSTR;
+use crate::engine;
+use crate::notify::AlertKind;
+use crate::toml_config;
+
+// Set when battesty_config.toml failed to parse, so save() leaves it alone
+static TOML_BROKEN: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EtaAlgorithm {
+    SimpleAverage,
+    Ema,
+    Regression,
+    Hybrid,
+    Kalman,
+    // Battesty's regression mixed with Windows' own BatteryLifeTime
+    Blended,
+    // Remaining mWh over the mW drain, no percentages involved
+    Energy,
+}
+
+impl EtaAlgorithm {
+    pub fn label(&self) -> &'static str {
+        match self {
+            EtaAlgorithm::SimpleAverage => "Simple average",
+            EtaAlgorithm::Ema => "Time-weighted EMA",
+            EtaAlgorithm::Regression => "Linear regression",
+            EtaAlgorithm::Hybrid => "Hybrid (mW)",
+            EtaAlgorithm::Kalman => "Kalman filter",
+            EtaAlgorithm::Blended => "Blend with Windows",
+            EtaAlgorithm::Energy => "Energy (mWh)",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum IconStyle {
+    Battery,
+    // The charge level as digits
+    Numeric,
+}
+
+impl IconStyle {
+    pub fn label(&self) -> &'static str {
+        match self {
+            IconStyle::Battery => "Battery",
+            IconStyle::Numeric => "Percentage",
+        }
+    }
+}
+
+// Switch to `plan` once the battery drops to `below_percentage` or lower
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct PowerPlanRule {
+    pub below_percentage: u8,
+    // "Power Saver", "Balanced", "High Performance" or a scheme GUID
+    pub plan: String,
+}
+
+// Dim displays to `brightness` percent once the battery drops to `below_percentage` or lower
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrightnessRule {
+    pub below_percentage: u8,
+    pub brightness: u8,
+}
 
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppSettings {
     pub update_interval_ms: u32,
+    // Update on Windows' battery percentage and power source notifications, polling only every
+    // fallback_update_interval_ms in between
+    pub power_notifications: bool,
+    pub fallback_update_interval_ms: u32,
     pub history_retention_hours: u32,
+    // How often the full history file is rewritten; new samples are journaled in between
+    pub save_interval_minutes: u32,
+    pub save_interval_on_battery_minutes: u32,
+    // Older history is downsampled once the file would grow past this
+    pub history_max_mb: u32,
+    // Sampling, storage and alerts run in a separate `battesty --engine` process the tray talks to
+    pub background_engine: bool,
+    // Samples past the retention window go to battesty_history_<year>-W<week>.json instead of being dropped
+    pub archive_weekly: bool,
     pub show_percentage_on_icon: bool,
+    pub benchmark_cpu_percent: u8,
+    pub benchmark_video_path: Option<String>,
+    pub benchmark_stop_percentage: u8,
+    pub drain_test_interval_ms: u32,
+    pub track_eta_accuracy: bool,
+    pub eta_algorithm: EtaAlgorithm,
+    // Longer is steadier, shorter follows load changes sooner
+    pub ema_half_life_minutes: f64,
+    // How far back the regression fits a line, and the R² below which it shows no ETA
+    pub regression_window_minutes: u32,
+    pub regression_min_r_squared: f64,
+    // How quickly the Kalman filter lets the drain wander, in (%/h)² per hour; higher reacts faster
+    pub kalman_process_noise: f64,
+    // Share of Windows' estimate in the Blended ETA, 0.0 (ignore it) to 1.0 (only it)
+    pub os_eta_weight: f64,
+    // Bend the ETA by the learned drain per level instead of assuming today's pace down to 0%
+    pub nonlinear_drain: bool,
+    pub target_time: Option<String>,
+    pub target_time_presets: Vec<String>,
+    pub chart_hours: u32,
+    pub charge_reminder_enabled: bool,
+    pub charge_reminder_lead_minutes: u32,
+    pub charge_reminder_target_percentage: u8,
+    // Limit battesty applied through the vendor driver; ASUS firmware forgets it on reboot
+    pub charge_limit: Option<u8>,
+    pub tooltip_power_draw: bool,
+    pub tooltip_time_on_battery: bool,
+    pub tooltip_health: bool,
+    pub tooltip_voltage_temperature: bool,
+    // Best and worst case after the ETA, from the spread of recent drain
+    pub tooltip_eta_range: bool,
+    pub tooltip_threshold_time: bool,
+    pub tooltip_active_idle: bool,
+    pub tooltip_typical_eta: bool,
+    // Level the "hits 20% at 16:42" estimate runs to; 0 turns it off
+    pub eta_threshold_percentage: u8,
+    // Without keyboard or mouse input for this long, samples count towards the idle drain
+    pub idle_after_minutes: u32,
+    pub low_battery_percentage: u8,
+    pub critical_battery_percentage: u8,
+    // None follows the driver's short-term flag; a UPS alerts much earlier, since it only has to
+    // last until the PC is shut down
+    pub ups_mode: Option<bool>,
+    pub ups_low_battery_percentage: u8,
+    pub ups_critical_battery_percentage: u8,
+    // Show alerts held back by Focus Assist once it ends, instead of dropping them
+    pub queue_alerts_during_focus: bool,
+    // Have screen readers read critical alerts aloud through UI Automation
+    pub announce_critical_alerts: bool,
+    // Show the countdown overlay at or below this level while on battery
+    pub countdown_overlay_percentage: Option<u8>,
+    // Per alert type, overriding its built-in default
+    pub alert_snooze_minutes: BTreeMap<AlertKind, u32>,
+    pub power_plan_rules: Vec<PowerPlanRule>,
+    // Plan to switch to when plugged in after a rule fired; None restores the plan from before
+    pub power_plan_on_ac: Option<String>,
+    pub brightness_rules: Vec<BrightnessRule>,
+    pub restore_brightness_on_ac: bool,
+    // Port of the local streaming overlay server; None keeps it off
+    pub overlay_server_port: Option<u16>,
+    // Discord Rich Presence; needs the ID of an application from the Discord developer portal
+    pub discord_presence: bool,
+    pub discord_client_id: Option<String>,
+    pub discord_show_power_state: bool,
+    pub discord_show_percentage: bool,
+    pub discord_show_eta: bool,
+    // Global shortcut for the summary popup, e.g. "Ctrl+Alt+B"; None disables it
+    pub widget_hotkey: Option<String>,
+    pub icon_style: IconStyle,
+    pub icon_font: String,
+    // GDI weight, 100–900
+    pub icon_font_weight: u32,
+    // An extra tray icon for each pack on machines with more than one battery; the main icon
+    // keeps the combined level either way
+    pub icon_per_battery: bool,
+    // "64% · 2h 10m" as text on the taskbar, next to the notification area
+    pub taskbar_text: bool,
+    // Steps the shown percentage by at most 1% per update and never up while discharging
+    pub smooth_display: bool,
+    // Time left leads the tooltip and replaces the digits of the numeric icon on battery
+    pub time_first_display: bool,
+    // Folder shared between PCs (OneDrive etc.) for the other devices view; None keeps this PC to itself
+    pub sync_folder: Option<String>,
+    // Battery Service readings from paired Bluetooth LE devices, with their own low alert
+    pub companion_battery: bool,
+    pub companion_low_percentage: u8,
 }
 
 impl Default for AppSettings {
     fn default() -> Self {
         Self {
             update_interval_ms: 30000,
+            power_notifications: true,
+            fallback_update_interval_ms: 300000,
             history_retention_hours: 168,
+            save_interval_minutes: 5,
+            save_interval_on_battery_minutes: 30,
+            history_max_mb: 20,
+            background_engine: false,
+            archive_weekly: false,
             show_percentage_on_icon: true,
+            benchmark_cpu_percent: 25,
+            benchmark_video_path: None,
+            benchmark_stop_percentage: 10,
+            drain_test_interval_ms: 60000,
+            track_eta_accuracy: true,
+            eta_algorithm: EtaAlgorithm::Hybrid,
+            ema_half_life_minutes: 10.0,
+            regression_window_minutes: 60,
+            regression_min_r_squared: 0.3,
+            kalman_process_noise: 4.0,
+            os_eta_weight: 0.3,
+            nonlinear_drain: true,
+            target_time: None,
+            target_time_presets: vec!["12:00".to_string(), "17:00".to_string(), "18:00".to_string(), "22:00".to_string()],
+            chart_hours: 24,
+            charge_reminder_enabled: true,
+            charge_reminder_lead_minutes: 60,
+            charge_reminder_target_percentage: 80,
+            charge_limit: None,
+            tooltip_power_draw: true,
+            tooltip_time_on_battery: true,
+            tooltip_health: false,
+            tooltip_voltage_temperature: false,
+            tooltip_eta_range: true,
+            tooltip_threshold_time: true,
+            tooltip_active_idle: false,
+            tooltip_typical_eta: true,
+            eta_threshold_percentage: 20,
+            idle_after_minutes: 5,
+            low_battery_percentage: 15,
+            critical_battery_percentage: 5,
+            ups_mode: None,
+            ups_low_battery_percentage: 50,
+            ups_critical_battery_percentage: 25,
+            queue_alerts_during_focus: true,
+            announce_critical_alerts: false,
+            countdown_overlay_percentage: None,
+            alert_snooze_minutes: BTreeMap::new(),
+            power_plan_rules: Vec::new(),
+            power_plan_on_ac: None,
+            brightness_rules: Vec::new(),
+            restore_brightness_on_ac: true,
+            overlay_server_port: None,
+            discord_presence: false,
+            discord_client_id: None,
+            discord_show_power_state: true,
+            discord_show_percentage: true,
+            discord_show_eta: true,
+            widget_hotkey: Some("Ctrl+Alt+B".to_string()),
+            icon_style: IconStyle::Battery,
+            icon_font: "Segoe UI".to_string(),
+            icon_font_weight: 600,
+            icon_per_battery: false,
+            taskbar_text: false,
+            smooth_display: false,
+            time_first_display: false,
+            sync_folder: None,
+            companion_battery: false,
+            companion_low_percentage: 20,
         }
     }
 }
 
 impl AppSettings {
+    // battesty_config.toml wins when present; otherwise the JSON file, created on first run
     pub fn load() -> Self {
+        let toml_path = Self::get_toml_path();
+        if toml_path.exists() {
+            let parsed = std::fs::read_to_string(&toml_path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| toml_config::from_toml(&text))
+                .and_then(|value| serde_json::from_value(value).map_err(|e| e.to_string()));
+            return match parsed {
+                Ok(settings) => settings,
+                Err(e) => {
+                    // Saving now would overwrite the hand-edited values with defaults
+                    TOML_BROKEN.store(true, Ordering::Relaxed);
+                    show_load_error(&format!(
+                        "battesty_config.toml could not be read, so defaults are used and the file won't be changed until it is fixed.\n\n{}",
+                        e,
+                    ));
+                    Self::default()
+                }
+            };
+        }
+
         let config_path = Self::get_config_path();
         let settings = std::fs::read_to_string(&config_path)
             .ok()
@@ -34,6 +289,25 @@ impl AppSettings {
     }
 
     pub fn save(&self) {
+        self.write();
+        // A background engine keeps its own copy of the settings
+        engine::settings_changed();
+    }
+
+    fn write(&self) {
+        let toml_path = Self::get_toml_path();
+        if toml_path.exists() {
+            if TOML_BROKEN.load(Ordering::Relaxed) {
+                return;
+            }
+            // Patch the existing file so the user's comments survive
+            let existing = std::fs::read_to_string(&toml_path).ok();
+            if let Ok(value) = serde_json::to_value(self) {
+                let _ = std::fs::write(&toml_path, toml_config::to_toml(&value, existing.as_deref()));
+            }
+            return;
+        }
+
         let config_path = Self::get_config_path();
         if let Ok(json) = serde_json::to_string_pretty(&self) {
             let _ = std::fs::write(&config_path, json);
@@ -46,4 +320,19 @@ impl AppSettings {
         path.push("battesty_config.json");
         path
     }
-}
\ No newline at end of file
+
+    fn get_toml_path() -> std::path::PathBuf {
+        let mut path = std::env::current_exe().unwrap();
+        path.pop();
+        path.push("battesty_config.toml");
+        path
+    }
+}
+
+fn show_load_error(msg: &str) {
+    let msg_wide: Vec<u16> = msg.encode_utf16().chain(std::iter::once(0)).collect();
+    let title_wide: Vec<u16> = "Battesty settings".encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe {
+        MessageBoxW(None, PCWSTR(msg_wide.as_ptr()), PCWSTR(title_wide.as_ptr()), MB_OK | MB_ICONWARNING);
+    }
+}
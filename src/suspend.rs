@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Local};
+use crate::battery::BatteryMonitor;
+
+const MAX_PERIODS: usize = 1000;
+
+// One sleep, from the suspend notification to the first sample after resume
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SuspendPeriod {
+    pub started: DateTime<Local>,
+    pub ended: DateTime<Local>,
+    pub start_percentage: u8,
+    pub end_percentage: u8,
+    // Plugged in at either end, so the change says nothing about standby drain
+    pub on_ac: bool,
+}
+
+impl SuspendPeriod {
+    pub fn duration(&self) -> Duration {
+        self.ended - self.started
+    }
+
+    pub fn lost(&self) -> i32 {
+        self.start_percentage as i32 - self.end_percentage as i32
+    }
+
+    pub fn drain_per_hour(&self) -> Option<f64> {
+        let hours = self.duration().num_seconds() as f64 / 3600.0;
+        (!self.on_ac && hours > 0.0).then(|| self.lost().max(0) as f64 / hours)
+    }
+
+    pub fn summary(&self) -> String {
+        let asleep = BatteryMonitor::format_duration(self.duration());
+        match self.drain_per_hour() {
+            Some(rate) => format!("Asleep {}, {}% → {}% ({:.1}%/h)", asleep, self.start_percentage, self.end_percentage, rate),
+            None => format!("Asleep {} on AC", asleep),
+        }
+    }
+}
+
+pub fn load_periods() -> Vec<SuspendPeriod> {
+    std::fs::read_to_string(periods_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_periods(periods: &[SuspendPeriod]) {
+    let start = periods.len().saturating_sub(MAX_PERIODS);
+    if let Ok(json) = serde_json::to_string(&periods[start..]) {
+        let _ = std::fs::write(periods_path(), json);
+    }
+}
+
+fn periods_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_suspends.json");
+    path
+}
@@ -0,0 +1,199 @@
+use std::cell::RefCell;
+use std::sync::Once;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Gdi::*;
+use windows::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+use windows::Win32::UI::Shell::*;
+use windows::Win32::UI::WindowsAndMessaging::*;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::core::{w, PCWSTR};
+
+// "64% · 2h 10m" drawn on the taskbar, left of the notification area. Real deskbands (IDeskBand
+// COM servers) are no longer loaded by the Windows 11 taskbar, and a child window of Explorer's
+// taskbar would share its input queue, so a hang here would freeze the taskbar. This is a topmost
+// tool window of our own placed over it instead. The taskbar doesn't know about it and reserves
+// no space, so a crowded taskbar has buttons underneath it. It is hidden while an auto-hidden
+// taskbar is out of sight or a fullscreen app, game or presentation is up, and isn't shown at all
+// on a taskbar docked to the left or right (Windows 10), where there's no row to sit in.
+
+static TEXT_HWND: AtomicIsize = AtomicIsize::new(0);
+static REGISTER: Once = Once::new();
+
+const TIMER_REPOSITION: usize = 1;
+const PADDING: i32 = 8;
+
+thread_local! {
+    static TEXT: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+pub fn update(text: &str, enabled: bool) {
+    let existing = HWND(TEXT_HWND.load(Ordering::Relaxed));
+    unsafe {
+        let alive = existing.0 != 0 && IsWindow(existing).as_bool();
+        if !enabled {
+            if alive {
+                let _ = DestroyWindow(existing);
+            }
+            TEXT_HWND.store(0, Ordering::Relaxed);
+            return;
+        }
+
+        TEXT.with(|t| *t.borrow_mut() = text.to_string());
+        if alive {
+            reposition(existing);
+            InvalidateRect(existing, None, TRUE);
+        } else {
+            create();
+        }
+    }
+}
+
+unsafe fn create() {
+    let instance: HINSTANCE = GetModuleHandleW(PCWSTR::null()).unwrap().into();
+    let class_name = "BattestyTaskbarText\0".encode_utf16().collect::<Vec<u16>>();
+
+    REGISTER.call_once(|| {
+        let wc = WNDCLASSW {
+            lpfnWndProc: Some(text_proc),
+            hInstance: instance,
+            lpszClassName: PCWSTR(class_name.as_ptr()),
+            hCursor: LoadCursorW(None, IDC_ARROW).unwrap_or_default(),
+            ..std::mem::zeroed()
+        };
+        RegisterClassW(&wc);
+    });
+
+    let hwnd = CreateWindowExW(
+        WS_EX_TOPMOST | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+        PCWSTR(class_name.as_ptr()),
+        PCWSTR::null(),
+        WS_POPUP,
+        0,
+        0,
+        0,
+        0,
+        None,
+        None,
+        instance,
+        None,
+    );
+    if hwnd.0 == 0 {
+        return;
+    }
+    TEXT_HWND.store(hwnd.0, Ordering::Relaxed);
+    reposition(hwnd);
+    // The tray grows and shrinks as icons come and go, clicking the taskbar raises it above us,
+    // and fullscreen apps come and go
+    SetTimer(hwnd, TIMER_REPOSITION, 2000, None);
+}
+
+// Just left of the notification area, as wide as the text needs, in screen coordinates.
+// Looked up each time, since Explorer restarting replaces the taskbar window.
+unsafe fn reposition(hwnd: HWND) {
+    let taskbar = FindWindowW(w!("Shell_TrayWnd"), PCWSTR::null());
+    let mut bar = RECT::default();
+    if taskbar.0 == 0 || !IsWindowVisible(taskbar).as_bool() || GetWindowRect(taskbar, &mut bar).is_err() {
+        ShowWindow(hwnd, SW_HIDE);
+        return;
+    }
+    let mut position = APPBARDATA { cbSize: std::mem::size_of::<APPBARDATA>() as u32, ..Default::default() };
+    let side_docked = SHAppBarMessage(ABM_GETTASKBARPOS, &mut position) != 0
+        && (position.uEdge == ABE_LEFT || position.uEdge == ABE_RIGHT);
+    // Topmost would otherwise put it over the video or game
+    let fullscreen = SHQueryUserNotificationState()
+        .is_ok_and(|state| state == QUNS_BUSY || state == QUNS_RUNNING_D3D_FULL_SCREEN || state == QUNS_PRESENTATION_MODE);
+    if side_docked || fullscreen {
+        ShowWindow(hwnd, SW_HIDE);
+        return;
+    }
+    // An auto-hidden taskbar slides mostly off its monitor
+    let mut monitor = MONITORINFO { cbSize: std::mem::size_of::<MONITORINFO>() as u32, ..Default::default() };
+    let on_screen = GetMonitorInfoW(MonitorFromWindow(taskbar, MONITOR_DEFAULTTONEAREST), &mut monitor).as_bool()
+        && bar.top >= monitor.rcMonitor.top
+        && bar.bottom <= monitor.rcMonitor.bottom;
+    if !on_screen {
+        ShowWindow(hwnd, SW_HIDE);
+        return;
+    }
+
+    let notify = FindWindowExW(taskbar, None, w!("TrayNotifyWnd"), PCWSTR::null());
+    let mut area = RECT::default();
+    let right = if notify.0 != 0 && GetWindowRect(notify, &mut area).is_ok() {
+        area.left
+    } else {
+        bar.right - (bar.right - bar.left) / 5
+    };
+
+    let hdc = GetDC(hwnd);
+    let font = GetStockObject(DEFAULT_GUI_FONT);
+    let old_font = SelectObject(hdc, font);
+    let text: Vec<u16> = TEXT.with(|t| t.borrow().encode_utf16().collect());
+    let mut extent = SIZE::default();
+    let _ = GetTextExtentPoint32W(hdc, &text, &mut extent);
+    SelectObject(hdc, old_font);
+    ReleaseDC(hwnd, hdc);
+
+    let width = extent.cx + 2 * PADDING;
+    let _ = SetWindowPos(hwnd, HWND_TOPMOST, right - width, bar.top, width, bar.bottom - bar.top, SWP_NOACTIVATE | SWP_SHOWWINDOW);
+}
+
+unsafe extern "system" fn text_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_PAINT => {
+            let mut ps: PAINTSTRUCT = std::mem::zeroed();
+            let hdc = BeginPaint(hwnd, &mut ps);
+            paint(hwnd, hdc);
+            EndPaint(hwnd, &ps);
+            LRESULT(0)
+        }
+        WM_ERASEBKGND => LRESULT(1),
+        WM_TIMER if wparam.0 == TIMER_REPOSITION => {
+            reposition(hwnd);
+            LRESULT(0)
+        }
+        WM_DESTROY => {
+            let _ = KillTimer(hwnd, TIMER_REPOSITION);
+            if TEXT_HWND.load(Ordering::Relaxed) == hwnd.0 {
+                TEXT_HWND.store(0, Ordering::Relaxed);
+            }
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+unsafe fn paint(hwnd: HWND, hdc: HDC) {
+    let mut rect = RECT::default();
+    let _ = GetClientRect(hwnd, &mut rect);
+    // Close to the taskbar's own colours; it has no API for its exact background
+    let (background, foreground) = if light_taskbar() { (0x00EEEEEE, 0x00000000) } else { (0x001C1C1C, 0x00FFFFFF) };
+    let brush = CreateSolidBrush(COLORREF(background));
+    FillRect(hdc, &rect, brush);
+    DeleteObject(brush);
+
+    let old_font = SelectObject(hdc, GetStockObject(DEFAULT_GUI_FONT));
+    SetBkMode(hdc, TRANSPARENT);
+    SetTextColor(hdc, COLORREF(foreground));
+    let mut text: Vec<u16> = TEXT.with(|t| t.borrow().encode_utf16().collect());
+    DrawTextW(hdc, &mut text, &mut rect, DT_CENTER | DT_VCENTER | DT_SINGLELINE);
+    SelectObject(hdc, old_font);
+}
+
+fn light_taskbar() -> bool {
+    let mut value: u32 = 0;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            w!(r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize"),
+            w!("SystemUsesLightTheme"),
+            RRF_RT_REG_DWORD,
+            None,
+            Some(&mut value as *mut u32 as *mut _),
+            Some(&mut size),
+        )
+        .is_ok()
+            && value == 1
+    }
+}
@@ -0,0 +1,445 @@
+use serde_json::{Map, Value};
+
+// The subset of TOML the settings need: root keys, one level of [tables], strings, numbers,
+// booleans, single-line arrays and inline tables. Converting through serde_json::Value keeps
+// AppSettings' serde attributes the single source of truth for both formats.
+
+pub fn from_toml(text: &str) -> Result<Value, String> {
+    let mut root = Map::new();
+    let mut table: Option<String> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fail = |e: String| format!("line {}: {}", index + 1, e);
+
+        if let Some(name) = line.strip_prefix('[') {
+            let name = name.strip_suffix(']').ok_or_else(|| fail("unclosed table header".to_string()))?;
+            let name = parse_key(name.trim()).map_err(fail)?;
+            root.entry(name.clone()).or_insert_with(|| Value::Object(Map::new()));
+            table = Some(name);
+            continue;
+        }
+
+        let (key, value) = split_assignment(line).ok_or_else(|| fail("expected key = value".to_string()))?;
+        let key = parse_key(key).map_err(fail)?;
+        let mut parser = Parser { text: value, pos: 0 };
+        let value = parser.value().map_err(fail)?;
+        parser.skip_spaces();
+        if parser.pos < parser.text.len() {
+            return Err(fail(format!("unexpected text after the value: {}", &parser.text[parser.pos..])));
+        }
+
+        let target = match &table {
+            Some(name) => match root.get_mut(name) {
+                Some(Value::Object(map)) => map,
+                _ => return Err(fail(format!("[{}] is also a plain key", name))),
+            },
+            None => &mut root,
+        };
+        if target.insert(key.clone(), value).is_some() {
+            return Err(fail(format!("{} is set twice", key)));
+        }
+    }
+    Ok(Value::Object(root))
+}
+
+// Writes `value` (an object) as TOML. With `existing`, that file is patched in place so
+// comments, blank lines and key order survive; new keys go at the end of their section and
+// keys that are now unset (null) are dropped.
+pub fn to_toml(value: &Value, existing: Option<&str>) -> String {
+    let empty = Map::new();
+    let root = value.as_object().unwrap_or(&empty);
+    let mut out: Vec<String> = Vec::new();
+    let mut written: Vec<(Option<String>, String)> = Vec::new();
+    let mut table: Option<String> = None;
+
+    for line in existing.unwrap_or_default().lines() {
+        let trimmed = strip_comment(line).trim();
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|n| n.strip_suffix(']')) {
+            append_missing(&mut out, root, &table, &written);
+            table = parse_key(name.trim()).ok();
+            // A section the settings no longer have is dropped together with its keys
+            if table.as_ref().is_some_and(|t| !root.get(t).is_some_and(Value::is_object)) {
+                table = Some(String::new());
+                continue;
+            }
+            out.push(line.to_string());
+            continue;
+        }
+        if table.as_deref() == Some("") {
+            continue;
+        }
+
+        let Some((key, _)) = split_assignment(trimmed) else {
+            // Comments, blank lines and anything unparseable are kept as written
+            out.push(line.to_string());
+            continue;
+        };
+        let Ok(key) = parse_key(key) else {
+            out.push(line.to_string());
+            continue;
+        };
+        let section = match &table {
+            Some(name) => root.get(name).and_then(Value::as_object),
+            None => Some(root),
+        };
+        match section.and_then(|s| s.get(&key)) {
+            Some(new) if !new.is_null() && (table.is_some() || !new.is_object()) => {
+                let indent = &line[..line.len() - line.trim_start().len()];
+                let code = strip_comment(line);
+                let comment = if code.len() < line.len() { &line[code.trim_end().len()..] } else { "" };
+                out.push(format!("{}{} = {}{}", indent, format_key(&key), format_value(new), comment));
+                written.push((table.clone(), key));
+            }
+            _ => {}
+        }
+    }
+    append_missing(&mut out, root, &table, &written);
+
+    // Tables that weren't in the file yet
+    for (name, value) in root {
+        let Value::Object(map) = value else { continue };
+        let present = existing.is_some_and(|text| text.lines().any(|l| {
+            strip_comment(l).trim().strip_prefix('[').and_then(|n| n.strip_suffix(']')).and_then(|n| parse_key(n.trim()).ok()).as_ref() == Some(name)
+        }));
+        if present {
+            continue;
+        }
+        if out.last().is_some_and(|l| !l.trim().is_empty()) {
+            out.push(String::new());
+        }
+        out.push(format!("[{}]", format_key(name)));
+        for (key, value) in map.iter().filter(|(_, v)| !v.is_null()) {
+            out.push(format!("{} = {}", format_key(key), format_value(value)));
+        }
+    }
+
+    while out.last().is_some_and(|l| l.trim().is_empty()) {
+        out.pop();
+    }
+    let mut text = out.join("\n");
+    text.push('\n');
+    text
+}
+
+// Keys of the current section that the existing file didn't have, placed before the
+// section's trailing blank lines
+fn append_missing(out: &mut Vec<String>, root: &Map<String, Value>, table: &Option<String>, written: &[(Option<String>, String)]) {
+    let section = match table {
+        Some(name) if name.is_empty() => return,
+        Some(name) => match root.get(name).and_then(Value::as_object) {
+            Some(map) => map,
+            None => return,
+        },
+        None => root,
+    };
+
+    let mut position = out.len();
+    while position > 0 && out[position - 1].trim().is_empty() {
+        position -= 1;
+    }
+    let missing: Vec<String> = section
+        .iter()
+        .filter(|(_, value)| !value.is_null() && (table.is_some() || !value.is_object()))
+        .filter(|(key, _)| !written.iter().any(|(t, k)| t == table && k == *key))
+        .map(|(key, value)| format!("{} = {}", format_key(key), format_value(value)))
+        .collect();
+    out.splice(position..position, missing);
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string: Option<char> = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match in_string {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(quote) if c == quote => in_string = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => in_string = Some(c),
+            None if c == '#' => return &line[..i],
+            None => {}
+        }
+    }
+    line
+}
+
+fn split_assignment(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once('=')?;
+    Some((key.trim(), value.trim()))
+}
+
+fn parse_key(key: &str) -> Result<String, String> {
+    if key.starts_with('"') || key.starts_with('\'') {
+        let mut parser = Parser { text: key, pos: 0 };
+        return match parser.value()? {
+            Value::String(s) if parser.pos == key.len() => Ok(s),
+            _ => Err(format!("bad key {}", key)),
+        };
+    }
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        Ok(key.to_string())
+    } else {
+        Err(format!("bad key {}", key))
+    }
+}
+
+fn format_key(key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        key.to_string()
+    } else {
+        format_value(&Value::String(key.to_string()))
+    }
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Null => "\"\"".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) if n.is_f64() => {
+            let text = n.to_string();
+            if text.contains(['.', 'e', 'E']) { text } else { format!("{}.0", text) }
+        }
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => {
+            let mut text = String::from("\"");
+            for c in s.chars() {
+                match c {
+                    '"' => text.push_str("\\\""),
+                    '\\' => text.push_str("\\\\"),
+                    '\n' => text.push_str("\\n"),
+                    '\t' => text.push_str("\\t"),
+                    '\r' => text.push_str("\\r"),
+                    c if c.is_control() => text.push_str(&format!("\\u{:04X}", c as u32)),
+                    c => text.push(c),
+                }
+            }
+            text.push('"');
+            text
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().filter(|v| !v.is_null()).map(format_value).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) => {
+            let items: Vec<String> = map
+                .iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| format!("{} = {}", format_key(k), format_value(v)))
+                .collect();
+            if items.is_empty() { "{}".to_string() } else { format!("{{ {} }}", items.join(", ")) }
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_spaces();
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_spaces();
+        let text = self.text;
+        let rest = &text[self.pos..];
+        match rest.chars().next() {
+            Some('"') => self.basic_string().map(Value::String),
+            Some('\'') => {
+                let end = rest[1..].find('\'').ok_or("unclosed string")?;
+                self.pos += end + 2;
+                Ok(Value::String(rest[1..end + 1].to_string()))
+            }
+            Some('[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                while !self.eat(']') {
+                    items.push(self.value()?);
+                    if !self.eat(',') {
+                        if !self.eat(']') {
+                            return Err("expected , or ] in array".to_string());
+                        }
+                        break;
+                    }
+                }
+                Ok(Value::Array(items))
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut map = Map::new();
+                while !self.eat('}') {
+                    self.skip_spaces();
+                    let end = self.rest().find('=').ok_or("expected key = value in inline table")?;
+                    let key = parse_key(self.rest()[..end].trim())?;
+                    self.pos += end + 1;
+                    let value = self.value()?;
+                    map.insert(key, value);
+                    if !self.eat(',') {
+                        if !self.eat('}') {
+                            return Err("expected , or } in inline table".to_string());
+                        }
+                        break;
+                    }
+                }
+                Ok(Value::Object(map))
+            }
+            Some(_) => {
+                let end = rest.find([',', ']', '}']).unwrap_or(rest.len());
+                let token = rest[..end].trim_end();
+                self.pos += token.len();
+                match token {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    _ => {
+                        let number = token.replace('_', "");
+                        if let Ok(n) = number.parse::<i64>() {
+                            Ok(Value::from(n))
+                        } else if let Some(n) = number.parse::<f64>().ok().filter(|n| n.is_finite()) {
+                            Ok(Value::from(n))
+                        } else {
+                            Err(format!("unknown value {}", token))
+                        }
+                    }
+                }
+            }
+            None => Err("missing value".to_string()),
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        let source = self.text;
+        let mut text = String::new();
+        let mut chars = source[self.pos..].char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(text);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('"') => text.push('"'),
+                    Some('\\') => text.push('\\'),
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some('r') => text.push('\r'),
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let c = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32).ok_or("bad \\u escape")?;
+                        text.push(c);
+                    }
+                    _ => return Err("unknown escape in string".to_string()),
+                },
+                c => text.push(c),
+            }
+        }
+        Err("unclosed string".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::AlertKind;
+    use crate::settings::{AppSettings, PowerPlanRule};
+
+    #[test]
+    fn settings_round_trip() {
+        let mut settings = AppSettings {
+            icon_font: "Segoe UI # Semibold".to_string(),
+            chart_hours: 36,
+            ..AppSettings::default()
+        };
+        settings.power_plan_rules = vec![
+            PowerPlanRule { below_percentage: 20, plan: "Power Saver".to_string() },
+            PowerPlanRule { below_percentage: 50, plan: "Balanced".to_string() },
+        ];
+        settings.alert_snooze_minutes.insert(AlertKind::LowBattery, 30);
+
+        let value = serde_json::to_value(&settings).unwrap();
+        let text = to_toml(&value, None);
+        let parsed: AppSettings = serde_json::from_value(from_toml(&text).unwrap()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), value);
+        assert!(text.contains("power_plan_rules = [{ below_percentage = 20, plan = \"Power Saver\" }, { below_percentage = 50, plan = \"Balanced\" }]"));
+        assert!(text.contains("[alert_snooze_minutes]\nLowBattery = 30"));
+    }
+
+    #[test]
+    fn patch_keeps_comments_and_order() {
+        let existing = "# battesty settings\n\nchart_hours = 24 # one day\n\n# font for the numeric icon\nicon_font = \"Segoe UI\"\n";
+        let value = serde_json::json!({ "icon_font": "Consolas", "chart_hours": 48, "smooth_display": true });
+        assert_eq!(
+            to_toml(&value, Some(existing)),
+            "# battesty settings\n\nchart_hours = 48 # one day\n\n# font for the numeric icon\nicon_font = \"Consolas\"\nsmooth_display = true\n",
+        );
+    }
+
+    #[test]
+    fn patch_drops_and_adds_tables() {
+        let existing = "a = 1\n\n[gone]\nx = 1 # stale\n\n[kept]\ny = 2\n";
+        let value = serde_json::json!({ "a": 1, "kept": { "y": 3, "z": "new" }, "added": { "w": false }, "unset": null });
+        assert_eq!(
+            to_toml(&value, Some(existing)),
+            "a = 1\n\n[kept]\ny = 3\nz = \"new\"\n\n[added]\nw = false\n",
+        );
+    }
+
+    #[test]
+    fn hash_inside_strings() {
+        assert_eq!(strip_comment("a = \"#1\" # real"), "a = \"#1\" ");
+        assert_eq!(strip_comment("a = 'C:\\#dir' # real"), "a = 'C:\\#dir' ");
+        assert_eq!(strip_comment("a = \"quote \\\" # still inside\""), "a = \"quote \\\" # still inside\"");
+
+        let value = from_toml("a = \"#1\" # comment\nb = 'x#y'").unwrap();
+        assert_eq!(value, serde_json::json!({ "a": "#1", "b": "x#y" }));
+        let patched = to_toml(&serde_json::json!({ "a": "#2", "b": "x#y" }), Some("a = \"#1\" # keep me\nb = 'x#y'\n"));
+        assert_eq!(patched, "a = \"#2\" # keep me\nb = \"x#y\"\n");
+    }
+
+    #[test]
+    fn inline_table_arrays() {
+        let value = from_toml("power_plan_rules = [{ below_percentage = 20, plan = \"Power Saver\" }, {plan='Balanced',below_percentage=50}]").unwrap();
+        assert_eq!(value, serde_json::json!({ "power_plan_rules": [
+            { "below_percentage": 20, "plan": "Power Saver" },
+            { "below_percentage": 50, "plan": "Balanced" },
+        ]}));
+        assert_eq!(from_toml("rules = []").unwrap(), serde_json::json!({ "rules": [] }));
+    }
+
+    #[test]
+    fn missing_keys_go_before_trailing_blank_lines() {
+        let mut out = vec!["a = 1".to_string(), String::new(), String::new()];
+        let root = serde_json::json!({ "a": 1, "b": 2 });
+        append_missing(&mut out, root.as_object().unwrap(), &None, &[(None, "a".to_string())]);
+        assert_eq!(out, ["a = 1", "b = 2", "", ""]);
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(from_toml("a = 1\n\n# note\nb = \"x\" 3").unwrap_err(), "line 4: unexpected text after the value: 3");
+        assert_eq!(from_toml("a = 1\na = 2").unwrap_err(), "line 2: a is set twice");
+        assert_eq!(from_toml("[table\n").unwrap_err(), "line 1: unclosed table header");
+        assert_eq!(from_toml("a = 1\nb = \"open").unwrap_err(), "line 2: unclosed string");
+        assert_eq!(from_toml("a = 1\n\njust words").unwrap_err(), "line 3: expected key = value");
+    }
+}
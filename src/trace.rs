@@ -0,0 +1,90 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use chrono::{DateTime, Duration, Local, TimeZone};
+use crate::battery::BatteryMeasurement;
+use crate::clock::Clock;
+
+// Synthetic traces and a hand-driven clock for the tests: readings look like what the battery
+// driver reports (whole percents, mW rate, mWh left) without a battery behind them
+
+pub const FULL_MWH: u32 = 50_000;
+
+// A Monday morning, clear of daylight saving changes
+pub fn start() -> DateTime<Local> {
+    Local.with_ymd_and_hms(2026, 3, 2, 9, 0, 0).unwrap()
+}
+
+// One reading with the gauge's whole percent and the driver fields that follow from `level`
+pub fn sample(time: DateTime<Local>, level: f64, rate_mw: Option<i32>, is_charging: bool) -> BatteryMeasurement {
+    BatteryMeasurement {
+        timestamp: time,
+        percentage: level.clamp(0.0, 100.0) as u8,
+        is_charging,
+        discharge_rate: 0,
+        eta_minutes: None,
+        os_eta_minutes: None,
+        eta_algorithm: None,
+        packs: Vec::new(),
+        screen_on: None,
+        user_idle: None,
+        rate_mw,
+        full_charge_mwh: Some(FULL_MWH),
+        design_mwh: Some(FULL_MWH),
+        remaining_mwh: Some((level.max(0.0) / 100.0 * FULL_MWH as f64) as u32),
+        voltage_mv: None,
+        temperature_dk: None,
+        not_charging: false,
+        serial: None,
+        power_plan: None,
+    }
+}
+
+// Steady drain from `from` percent, one reading every `step` for `minutes`
+pub fn discharge(since: DateTime<Local>, from: f64, percent_per_hour: f64, minutes: i64, step: i64) -> Vec<BatteryMeasurement> {
+    let rate_mw = -(percent_per_hour / 100.0 * FULL_MWH as f64) as i32;
+    (0..=minutes / step)
+        .map(|i| {
+            let hours = (i * step) as f64 / 60.0;
+            sample(since + Duration::minutes(i * step), from - percent_per_hour * hours, Some(rate_mw), false)
+        })
+        .collect()
+}
+
+// Same, filling up
+pub fn charge(since: DateTime<Local>, from: f64, percent_per_hour: f64, minutes: i64, step: i64) -> Vec<BatteryMeasurement> {
+    let rate_mw = (percent_per_hour / 100.0 * FULL_MWH as f64) as i32;
+    (0..=minutes / step)
+        .map(|i| {
+            let hours = (i * step) as f64 / 60.0;
+            sample(since + Duration::minutes(i * step), from + percent_per_hour * hours, Some(rate_mw), true)
+        })
+        .collect()
+}
+
+// Shared with the monitor it's handed to, so the test can move time along
+#[derive(Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<DateTime<Local>>>,
+    origin: (DateTime<Local>, Instant),
+}
+
+impl MockClock {
+    pub fn at(time: DateTime<Local>) -> Self {
+        Self { now: Arc::new(Mutex::new(time)), origin: (time, Instant::now()) }
+    }
+
+    pub fn set(&self, time: DateTime<Local>) {
+        *self.now.lock().unwrap() = time;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.now.lock().unwrap()
+    }
+
+    fn monotonic(&self) -> Instant {
+        let elapsed = (self.now() - self.origin.0).to_std().unwrap_or_default();
+        self.origin.1 + elapsed
+    }
+}
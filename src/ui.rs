@@ -1,18 +1,55 @@
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use windows::Win32::Foundation::*;
 use windows::Win32::UI::WindowsAndMessaging::*;
 use windows::Win32::UI::Shell::*;
 use windows::Win32::Graphics::Gdi::*;
 use windows::core::PCWSTR;
 
-use crate::battery::{BatteryMonitor, DEBUG_MODE};
-use crate::icon::create_battery_icon;
+use chrono::Duration;
+
+use crate::about;
+use crate::accuracy;
+use crate::chart;
+use crate::companion;
+use crate::countdown;
+use crate::devices;
+use crate::taskbar_text;
+use crate::diagnostics;
+use crate::discord;
+use crate::engine;
+use crate::ioctl;
+use crate::parquet;
+use crate::report;
+use crate::battery::{BatteryMonitor, ChargeState, DEBUG_MODE};
+use crate::benchmark::{self, ChargeTest, Workload};
+use crate::drain_test::Phase;
+use crate::drain_wizard;
+use crate::alert_history;
+use crate::event_log;
+use crate::events::EventKind;
+use crate::footprint;
+use crate::forecast;
+use crate::notify::{self, AlertKind, ALERT_KINDS, SNOOZE_OPTIONS};
+use crate::prompt;
+use crate::server;
+use crate::session_list;
+use crate::settings::{AppSettings, BrightnessRule, EtaAlgorithm, IconStyle, PowerPlanRule};
+use crate::update::{self, UpdateResult};
+use crate::vendor::{self, LimitControl};
+use crate::versions;
+use crate::wear;
+use crate::widget;
+use crate::icon::{app_icon, create_battery_icon, create_icon, short_time};
 use crate::{MONITOR, WM_TRAYICON, ID_TRAY_ICON, TIMER_UPDATE, TIMER_SAVE};
 
+// Extra tray icons currently shown, one per battery pack
+static PACK_ICONS: AtomicUsize = AtomicUsize::new(0);
+
 pub fn add_tray_icon(hwnd: HWND, monitor: &Arc<Mutex<BatteryMonitor>>) {
     unsafe {
         let hdc = GetDC(hwnd);
-        let icon = create_battery_icon(hdc, 50, false);
+        let icon = create_battery_icon(hdc, 0, 0, ChargeState::Unknown, false);
         ReleaseDC(hwnd, hdc);
         
         let mut nid: NOTIFYICONDATAW = std::mem::zeroed();
@@ -28,10 +65,11 @@ pub fn add_tray_icon(hwnd: HWND, monitor: &Arc<Mutex<BatteryMonitor>>) {
         } else {
             "Battesty - Battery Monitor"
         };
-        let tip_wide: Vec<u16> = tip.encode_utf16().chain(std::iter::once(0)).collect();
-        nid.szTip[..tip_wide.len()].copy_from_slice(&tip_wide);
+        copy_wide(&mut nid.szTip, tip);
         
         Shell_NotifyIconW(NIM_ADD, &nid);
+        // After an Explorer restart the pack icons are gone too and get added again
+        PACK_ICONS.store(0, Ordering::Relaxed);
         
         if let Ok(mut mon) = monitor.lock() {
             mon.destroy_icon();
@@ -40,67 +78,456 @@ pub fn add_tray_icon(hwnd: HWND, monitor: &Arc<Mutex<BatteryMonitor>>) {
     }
 }
 
+// Copies into a fixed NOTIFYICONDATAW buffer, truncating but keeping the terminating null
+pub fn copy_wide(dest: &mut [u16], text: &str) {
+    let text_wide: Vec<u16> = text.encode_utf16().take(dest.len() - 1).collect();
+    dest[..text_wide.len()].copy_from_slice(&text_wide);
+    dest[text_wide.len()] = 0;
+}
+
 pub fn update_tray_icon(hwnd: HWND, monitor: &Arc<Mutex<BatteryMonitor>>) {
+    if engine::is_client() {
+        if update_from_engine(hwnd, monitor) {
+            return;
+        }
+        // Measured here until the engine is back, so nothing is missed
+        engine::ensure_running();
+    }
     if let Ok(mut mon) = monitor.lock() {
-        if let Some((percentage, eta, is_charging)) = mon.get_battery_status() {
-            unsafe {
-                let hdc = GetDC(hwnd);
-                let icon = create_battery_icon(hdc, percentage, is_charging);
-                ReleaseDC(hwnd, hdc);
-                
-                let mut nid: NOTIFYICONDATAW = std::mem::zeroed();
-                nid.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
-                nid.hWnd = hwnd;
-                nid.uID = ID_TRAY_ICON;
-                nid.uFlags = NIF_ICON | NIF_TIP;
-                nid.hIcon = icon;
-                
-                let tip = if DEBUG_MODE {
-                    format!("[DEBUG] {}% · {}", percentage, eta)
-                } else {
-                    format!("{}% · {}", percentage, eta)
-                };
-                let tip_wide: Vec<u16> = tip.encode_utf16().chain(std::iter::once(0)).collect();
-                nid.szTip[..tip_wide.len().min(128)].copy_from_slice(&tip_wide[..tip_wide.len().min(128)]);
-                
-                Shell_NotifyIconW(NIM_MODIFY, &nid);
-                
-                mon.destroy_icon();
-                mon.last_icon = Some(icon);
+        let Some((percentage, eta, is_charging)) = mon.get_battery_status() else {
+            if mon.battery_present == Some(false) {
+                let status = no_battery_status();
+                engine::publish(Some(status.clone()));
+                if !engine::is_engine() {
+                    unsafe { render(hwnd, &mut mon, &status) };
+                }
+                return;
+            }
+            engine::publish(None);
+            if !engine::is_engine() {
+                unsafe { show_unavailable(hwnd, &mut mon) };
+            }
+            if mon.events.last().is_none_or(|e| e.kind != EventKind::Anomaly) {
+                mon.log_event(EventKind::Anomaly, "Battery status unavailable");
+            }
+            return;
+        };
+        
+        mon.check_benchmark(percentage, is_charging);
+        mon.track_drain_test(percentage, is_charging, None);
+        mon.track_charge_test(percentage, is_charging);
+        mon.track_session(percentage, is_charging);
+        
+        let status = tray_status(&mut mon, percentage, eta, is_charging);
+        engine::publish(Some(status.clone()));
+        
+        unsafe {
+            if !engine::is_engine() {
+                render(hwnd, &mut mon, &status);
+            }
+            
+            notify::flush_queued(hwnd, &mut mon);
+            match mon.check_low_battery(percentage, is_charging) {
+                Some((AlertKind::CriticalBattery, alert)) => {
+                    notify::raise(hwnd, &mut mon, AlertKind::CriticalBattery, "Critical Battery", &alert, NIIF_ERROR);
+                }
+                Some((kind, alert)) => notify::raise(hwnd, &mut mon, kind, "Low Battery", &alert, NIIF_WARNING),
+                None => {}
+            }
+            mon.check_power_plan(percentage, is_charging);
+            mon.check_brightness(percentage, is_charging);
+            if let Some(reminder) = mon.check_charge_reminder(percentage, is_charging) {
+                notify::raise(hwnd, &mut mon, AlertKind::ChargeReminder, "Charge Reminder", &reminder, NIIF_WARNING);
+            }
+            if let Some(alert) = mon.capacity_alert.take() {
+                notify::raise(hwnd, &mut mon, AlertKind::CapacityChange, "Battery Capacity Changed", &alert, NIIF_WARNING);
+            }
+            if mon.settings.companion_battery {
+                for device in companion::check_low(mon.settings.companion_low_percentage) {
+                    let text = format!("{} is at {}%", device.name, device.percentage);
+                    notify::raise(hwnd, &mut mon, AlertKind::CompanionBattery, "Companion Battery Low", &text, NIIF_WARNING);
+                }
             }
         }
     }
 }
 
-pub fn handle_power_event(wparam: WPARAM, hwnd: HWND) {
+// Draws what the background engine last measured; false when it can't be reached
+fn update_from_engine(hwnd: HWND, monitor: &Arc<Mutex<BatteryMonitor>>) -> bool {
+    let Ok(response) = engine::request("status") else { return false };
+    let Ok(mut mon) = monitor.lock() else { return true };
+    if let Some(latest) = response.latest {
+        // Tests and benchmarks started from this tray follow the engine's readings
+        mon.track_tests(&latest);
+        mon.mirror_measurement(latest);
+    }
+    companion::set_latest(response.companions);
+    unsafe {
+        match &response.status {
+            Some(status) => render(hwnd, &mut mon, status),
+            None => show_unavailable(hwnd, &mut mon),
+        }
+    }
+    for alert in response.alerts {
+        notify::show_balloon(hwnd, &alert.title, &alert.text, NOTIFY_ICON_INFOTIP_FLAGS(alert.flags));
+    }
+    true
+}
+
+// The displayed values, icon key and tooltip for one reading
+fn tray_status(mon: &mut BatteryMonitor, percentage: u8, eta: String, is_charging: bool) -> engine::Status {
+    // The icon and tooltip show the filtered value; everything else keeps the raw reading
+    let smooth = mon.settings.smooth_display;
+    let shown = mon.display.update(percentage, is_charging, smooth);
+    let colour_level = mon.display.colour_level(shown);
+    let eta_minutes = mon.estimate().eta_minutes;
+    let time_first = mon.settings.time_first_display;
+    // In time-first mode the numeric icon shows "2h" instead of the percentage while on battery
+    let time_left = eta_minutes.filter(|_| time_first && !is_charging).map(short_time);
+    
+    let tip_eta = if mon.settings.tooltip_eta_range && !is_charging { format!("{}{}", eta, mon.eta_range_text()) } else { eta.clone() };
+    let lead = if time_first { format!("{} · {}%", tip_eta, shown) } else { format!("{}% · {}", shown, tip_eta) };
+    let tip = match mon.draw_watts {
+        Some(watts) if mon.settings.tooltip_power_draw => format!("{} · {:.1} W", lead, watts),
+        _ => lead,
+    };
+    let tip = if DEBUG_MODE { format!("[DEBUG] {}", tip) } else { tip };
+    let tip = if mon.is_ups() { format!("UPS {}", tip) } else { tip };
+    let tip = match mon.time_on_battery() {
+        Some(duration) if mon.settings.tooltip_time_on_battery => {
+            format!("{}\nOn battery for {}", tip, BatteryMonitor::format_duration(duration))
+        }
+        _ => tip,
+    };
+    let tip = match mon.typical_eta() {
+        Some(minutes) if mon.settings.tooltip_typical_eta && !is_charging => {
+            format!("{}\nTypical usage: {}", tip, BatteryMonitor::format_time(minutes))
+        }
+        _ => tip,
+    };
+    let tip = match mon.threshold_text() {
+        Some(text) if mon.settings.tooltip_threshold_time => format!("{}\n{}", tip, text),
+        _ => tip,
+    };
+    let tip = match mon.activity_text() {
+        Some(text) if mon.settings.tooltip_active_idle && !is_charging => format!("{}\n{}", tip, text),
+        _ => tip,
+    };
+    let tip = match mon.capacity {
+        Some(capacity) if mon.settings.tooltip_health => format!("{} · health {:.0}%", tip, capacity.health()),
+        _ => tip,
+    };
+    let tip = match mon.measurements.back().filter(|_| mon.settings.tooltip_voltage_temperature) {
+        Some(m) => {
+            let mut parts = vec![tip];
+            if let Some(voltage) = m.voltage_mv {
+                parts.push(format!("{:.2} V", voltage as f64 / 1000.0));
+            }
+            if let Some(temperature) = m.temperature_dk {
+                parts.push(format!("{:.0} °C", ioctl::celsius(temperature)));
+            }
+            parts.join(" · ")
+        }
+        None => tip,
+    };
+    let tip = match &mon.benchmark {
+        Some(bench) => format!("[{}] {}", bench.workload.label(), tip),
+        None => tip,
+    };
+    let tip = match &mon.charge_test {
+        Some(test) if test.is_waiting() => format!("[Charge test: plug in] {}", tip),
+        Some(_) => format!("[Charge test] {}", tip),
+        None => tip,
+    };
+    let tip = match mon.input_watts {
+        Some(watts) if is_charging => format!("{}\nCharging at ~{:.0} W", tip, watts),
+        _ => tip,
+    };
+    let tip = match mon.target_verdict(percentage, is_charging) {
+        Some(verdict) => format!("{}\n{}", tip, verdict.summary()),
+        None => tip,
+    };
+    
+    engine::Status {
+        percentage,
+        shown,
+        colour_level: Some(colour_level),
+        charging: is_charging,
+        state: mon.charge_state(percentage, is_charging),
+        ups: mon.is_ups(),
+        eta,
+        eta_minutes,
+        time_left,
+        tip,
+    }
+}
+
+fn no_battery_status() -> engine::Status {
+    engine::Status {
+        percentage: 0,
+        shown: 0,
+        colour_level: None,
+        charging: true,
+        state: ChargeState::NoBattery,
+        ups: false,
+        eta: "AC only".to_string(),
+        eta_minutes: None,
+        time_left: None,
+        tip: "No battery · running on AC".to_string(),
+    }
+}
+
+// Icon, tooltip and everything else on screen that follows the reading
+unsafe fn render(hwnd: HWND, mon: &mut BatteryMonitor, status: &engine::Status) {
+    let colour = status.colour_level.unwrap_or(status.shown);
+    let key = (status.shown, colour, status.state, status.time_left.clone(), status.ups);
+    let icon = (mon.icon_key.as_ref() != Some(&key)).then(|| {
+        let hdc = GetDC(hwnd);
+        let icon = create_icon(hdc, key.0, key.1, key.2, key.3.as_deref(), key.4, &mon.settings);
+        ReleaseDC(hwnd, hdc);
+        // A copy, since the tray icon is destroyed when replaced
+        if icon.is_invalid() { CopyIcon(app_icon()).unwrap_or_default() } else { icon }
+    });
+    mon.icon_key = Some(key);
+    set_tray_icon(hwnd, mon, icon, &status.tip);
+    if status.state == ChargeState::NoBattery {
+        countdown::update(hwnd, 0, None, false);
+        taskbar_text::update("AC only", mon.settings.taskbar_text);
+        remove_pack_icons(hwnd, 0);
+        return;
+    }
+    
+    let (shown, is_charging) = (status.shown, status.charging);
+    let overlay = mon.settings.countdown_overlay_percentage.is_some_and(|level| !is_charging && shown <= level);
+    countdown::update(hwnd, shown, status.eta_minutes, overlay);
+    let taskbar = match status.eta_minutes {
+        _ if status.state == ChargeState::NotCharging => format!("{}% · not charging", shown),
+        _ if is_charging => format!("{}% · charging", shown),
+        Some(minutes) if mon.settings.time_first_display => format!("{} · {}%", BatteryMonitor::format_time(minutes), shown),
+        Some(minutes) => format!("{}% · {}", shown, BatteryMonitor::format_time(minutes)),
+        None => format!("{}%", shown),
+    };
+    taskbar_text::update(&taskbar, mon.settings.taskbar_text);
+    discord::update(discord::presence(&mon.settings, shown, is_charging, &status.eta));
+    update_pack_icons(hwnd, mon, status.state);
+}
+
+// One extra icon per pack, with IDs following the main icon's
+unsafe fn update_pack_icons(hwnd: HWND, mon: &BatteryMonitor, state: ChargeState) {
+    let packs = match mon.measurements.back() {
+        Some(m) if mon.settings.icon_per_battery && m.packs.len() > 1 => m.packs.clone(),
+        _ => Vec::new(),
+    };
+    let shown = PACK_ICONS.load(Ordering::Relaxed);
+    for (i, level) in packs.iter().enumerate() {
+        let hdc = GetDC(hwnd);
+        let icon = create_icon(hdc, *level, *level, state, None, mon.is_ups(), &mon.settings);
+        ReleaseDC(hwnd, hdc);
+        
+        let mut nid: NOTIFYICONDATAW = std::mem::zeroed();
+        nid.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
+        nid.hWnd = hwnd;
+        nid.uID = ID_TRAY_ICON + 1 + i as u32;
+        nid.uFlags = NIF_ICON | NIF_MESSAGE | NIF_TIP;
+        nid.uCallbackMessage = WM_TRAYICON;
+        nid.hIcon = icon;
+        copy_wide(&mut nid.szTip, &format!("Battery {}: {}%", i + 1, level));
+        Shell_NotifyIconW(if i < shown { NIM_MODIFY } else { NIM_ADD }, &nid);
+        // The shell keeps its own copy
+        let _ = DestroyIcon(icon);
+    }
+    remove_pack_icons(hwnd, packs.len());
+}
+
+unsafe fn remove_pack_icons(hwnd: HWND, keep: usize) {
+    for i in keep..PACK_ICONS.load(Ordering::Relaxed) {
+        let mut nid: NOTIFYICONDATAW = std::mem::zeroed();
+        nid.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
+        nid.hWnd = hwnd;
+        nid.uID = ID_TRAY_ICON + 1 + i as u32;
+        Shell_NotifyIconW(NIM_DELETE, &nid);
+    }
+    PACK_ICONS.store(keep, Ordering::Relaxed);
+}
+
+unsafe fn show_unavailable(hwnd: HWND, mon: &mut BatteryMonitor) {
+    let hdc = GetDC(hwnd);
+    let icon = create_battery_icon(hdc, 0, 0, ChargeState::Unknown, false);
+    ReleaseDC(hwnd, hdc);
+    mon.icon_key = None;
+    set_tray_icon(hwnd, mon, Some(icon), "Battery status unavailable");
+}
+
+// Without a new icon only the tooltip is updated
+unsafe fn set_tray_icon(hwnd: HWND, mon: &mut BatteryMonitor, icon: Option<HICON>, tip: &str) {
+    let mut nid: NOTIFYICONDATAW = std::mem::zeroed();
+    nid.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
+    nid.hWnd = hwnd;
+    nid.uID = ID_TRAY_ICON;
+    nid.uFlags = NIF_TIP;
+    if let Some(icon) = icon {
+        nid.uFlags |= NIF_ICON;
+        nid.hIcon = icon;
+    }
+    copy_wide(&mut nid.szTip, tip);
+    
+    Shell_NotifyIconW(NIM_MODIFY, &nid);
+    
+    if let Some(icon) = icon {
+        mon.destroy_icon();
+        mon.last_icon = Some(icon);
+    }
+}
+
+// A pack was inserted or removed; sampled right away instead of at the next timer
+pub fn handle_device_change(wparam: WPARAM, lparam: LPARAM, hwnd: HWND) {
+    if !ioctl::is_battery_change(wparam, lparam) || engine::is_client() {
+        return;
+    }
+    if let Some(monitor) = MONITOR.get() {
+        if let Ok(mut mon) = monitor.lock() {
+            mon.battery_changed();
+        }
+        update_tray_icon(hwnd, monitor);
+    }
+}
+
+pub fn handle_power_event(wparam: WPARAM, lparam: LPARAM, hwnd: HWND) {
     use windows::Win32::System::Power::*;
+    use windows::Win32::System::SystemServices::{GUID_ACDC_POWER_SOURCE, GUID_BATTERY_PERCENTAGE_REMAINING, GUID_CONSOLE_DISPLAY_STATE};
     
     match wparam.0 as u32 {
         PBT_APMSUSPEND => {
             if let Some(monitor) = MONITOR.get() {
-                if let Ok(mon) = monitor.lock() {
+                if let Ok(mut mon) = monitor.lock() {
+                    // The background engine gets the same notification and records the sleep
+                    if !engine::is_client() {
+                        mon.log_event(EventKind::Suspend, "");
+                        mon.begin_suspend();
+                    }
+                    mon.screen_on = false;
+                    if mon.drain_test.is_some() {
+                        set_drain_phase(&mut mon, Phase::Asleep);
+                    }
                     mon.save_history();
                 }
             }
         }
         PBT_APMRESUMESUSPEND | PBT_APMRESUMEAUTOMATIC => {
             if let Some(monitor) = MONITOR.get() {
+                if let Ok(mut mon) = monitor.lock() {
+                    mon.screen_on = true;
+                    mon.display.reset();
+                    if mon.drain_test.is_some() {
+                        set_drain_phase(&mut mon, Phase::ScreenOn);
+                    }
+                }
+                // Sampled right away, so the drain across the sleep is measured from a fresh reading
                 update_tray_icon(hwnd, monitor);
+                if let Ok(mut mon) = monitor.lock() {
+                    mon.end_suspend();
+                }
+                show_pending_drain_summary(hwnd);
+            }
+        }
+        PBT_POWERSETTINGCHANGE => {
+            let setting = unsafe { &*(lparam.0 as *const POWERBROADCAST_SETTING) };
+            if setting.PowerSetting == GUID_CONSOLE_DISPLAY_STATE {
+                // 0 = off, 1 = on, 2 = dimmed
+                let phase = if setting.Data[0] == 0 { Phase::ScreenOff } else { Phase::ScreenOn };
+                if let Some(monitor) = MONITOR.get() {
+                    if let Ok(mut mon) = monitor.lock() {
+                        mon.screen_on = phase == Phase::ScreenOn;
+                        if mon.drain_test.is_some() {
+                            set_drain_phase(&mut mon, phase);
+                        }
+                    }
+                }
+            } else if setting.PowerSetting == GUID_BATTERY_PERCENTAGE_REMAINING || setting.PowerSetting == GUID_ACDC_POWER_SOURCE {
+                let Some(monitor) = MONITOR.get() else { return };
+                // Both also arrive once right after registering, with the state just sampled
+                let value = setting.Data[0] as u32;
+                let changed = match monitor.lock() {
+                    Ok(mon) if mon.settings.power_notifications && !engine::is_client() => match mon.measurements.back() {
+                        // 0 is AC, 1 battery, 2 short-term source such as a UPS
+                        Some(last) if setting.PowerSetting == GUID_ACDC_POWER_SOURCE => (value == 0) != last.is_charging,
+                        Some(last) => value != last.percentage as u32,
+                        None => true,
+                    },
+                    _ => false,
+                };
+                if changed {
+                    update_tray_icon(hwnd, monitor);
+                    chart::refresh();
+                }
             }
         }
         _ => {}
     }
 }
 
+fn set_drain_phase(mon: &mut BatteryMonitor, phase: Phase) {
+    if let Some((percentage, _, is_charging)) = mon.get_battery_status() {
+        mon.track_drain_test(percentage, is_charging, Some(phase));
+    }
+}
+
+fn show_pending_drain_summary(hwnd: HWND) {
+    let Some(monitor) = MONITOR.get() else { return };
+    let summary = match monitor.lock() {
+        Ok(mut mon) => {
+            let summary = mon.drain_test_summary.take();
+            if summary.is_some() {
+                notify::record(&mut mon, AlertKind::DrainTest, "Drain test finished", false);
+                unsafe { SetTimer(hwnd, TIMER_UPDATE, mon.update_interval(), None) };
+            }
+            summary
+        }
+        Err(_) => return,
+    };
+    
+    if let Some(summary) = summary {
+        show_message(hwnd, "Drain Test Summary", &summary);
+    }
+}
+
+fn show_pending_benchmark_summary(hwnd: HWND) {
+    let Some(monitor) = MONITOR.get() else { return };
+    let summary = match monitor.lock() {
+        Ok(mut mon) => mon.benchmark_summary.take(),
+        Err(_) => return,
+    };
+
+    if let Some(summary) = summary {
+        notify::show_balloon(hwnd, "Benchmark finished", &summary, NIIF_INFO);
+    }
+}
+
 pub fn handle_timer_event(wparam: WPARAM, hwnd: HWND) {
     if wparam.0 == TIMER_UPDATE {
         if let Some(monitor) = MONITOR.get() {
             update_tray_icon(hwnd, monitor);
+            footprint::check_gdi_growth();
+            chart::refresh();
+            event_log::refresh();
+            alert_history::refresh();
+            show_pending_drain_summary(hwnd);
+            show_pending_benchmark_summary(hwnd);
         }
     } else if wparam.0 == TIMER_SAVE {
         if let Some(monitor) = MONITOR.get() {
-            if let Ok(mon) = monitor.lock() {
-                mon.save_history();
+            if let Ok(mut mon) = monitor.lock() {
+                if engine::is_client() {
+                    // The engine does the bookkeeping; the tray only catches up with what it saved
+                    mon.reload_history();
+                    mon.save_history_if_due();
+                    return;
+                }
+                mon.update_rollups();
+                mon.check_versions();
+                mon.save_history_if_due();
+                // Retried on the next save if the folder is briefly unavailable
+                let _ = devices::export(&mon);
             }
         }
     }
@@ -114,8 +541,11 @@ pub fn handle_tray_event(lparam: LPARAM, hwnd: HWND) {
                     if let Some(last) = mon.measurements.back() {
                         let percentage = last.percentage;
                         let is_charging = last.is_charging;
-                        let info = mon.get_detailed_info(percentage, is_charging);
+                        let mut info = mon.get_detailed_info(percentage, is_charging);
                         drop(mon);
+                        for device in companion::latest() {
+                            info.push_str(&format!("\n{}: {}%", device.name, device.percentage));
+                        }
                         
                         let msg_wide: Vec<u16> = info.encode_utf16().chain(std::iter::once(0)).collect();
                         let title_wide: Vec<u16> = "Battery Details".encode_utf16().chain(std::iter::once(0)).collect();
@@ -125,6 +555,8 @@ pub fn handle_tray_event(lparam: LPARAM, hwnd: HWND) {
             }
         } else if lparam.0 as u32 == WM_RBUTTONUP {
             show_context_menu(hwnd);
+        } else if lparam.0 as u32 == NIN_BALLOONUSERCLICK {
+            snooze_last_alert();
         }
     }
 }
@@ -133,14 +565,109 @@ fn show_context_menu(hwnd: HWND) {
     unsafe {
         let hmenu = CreatePopupMenu().unwrap();
         let battery_info = "Battery Info\0".encode_utf16().collect::<Vec<u16>>();
+        let snapshot = "Snapshot Now\0".encode_utf16().collect::<Vec<u16>>();
+        let summary_hotkey = MONITOR.get()
+            .and_then(|m| m.lock().ok())
+            .and_then(|mon| mon.settings.widget_hotkey.clone());
+        let summary = match summary_hotkey {
+            Some(hotkey) => format!("Summary\t{}\0", hotkey),
+            None => "Summary\0".to_string(),
+        }.encode_utf16().collect::<Vec<u16>>();
+        let drain_wizard = "Why Is My Battery Draining?\0".encode_utf16().collect::<Vec<u16>>();
+        let graph = "Battery Graph\0".encode_utf16().collect::<Vec<u16>>();
+        let sessions = "Sessions\0".encode_utf16().collect::<Vec<u16>>();
+        let event_log = "Event Log\0".encode_utf16().collect::<Vec<u16>>();
+        let alert_history = "Notification History\0".encode_utf16().collect::<Vec<u16>>();
+        let wear = "Monthly Wear\0".encode_utf16().collect::<Vec<u16>>();
         let settings = "Settings\0".encode_utf16().collect::<Vec<u16>>();
+        let benchmark = "Benchmark\0".encode_utf16().collect::<Vec<u16>>();
+        let eta_algorithm = "ETA Algorithm\0".encode_utf16().collect::<Vec<u16>>();
+        let target = "Will It Last Until...\0".encode_utf16().collect::<Vec<u16>>();
+        let charge_limit = "Charge Limit\0".encode_utf16().collect::<Vec<u16>>();
+        let power_plan = "Power Plan Switching\0".encode_utf16().collect::<Vec<u16>>();
+        let brightness = "Brightness Step-Down\0".encode_utf16().collect::<Vec<u16>>();
+        let tooltip = "Tooltip\0".encode_utf16().collect::<Vec<u16>>();
+        let icon = "Icon\0".encode_utf16().collect::<Vec<u16>>();
+        let snooze = "Alerts\0".encode_utf16().collect::<Vec<u16>>();
         let about = "About\0".encode_utf16().collect::<Vec<u16>>();
+        let discord = "Discord Status\0".encode_utf16().collect::<Vec<u16>>();
+        let overlay = "Streaming Overlay (OBS)\0".encode_utf16().collect::<Vec<u16>>();
+        let devices = "Other Devices\0".encode_utf16().collect::<Vec<u16>>();
+        let companion = "Bluetooth Device Batteries\0".encode_utf16().collect::<Vec<u16>>();
+        let diagnostics = "Collect Diagnostics...\0".encode_utf16().collect::<Vec<u16>>();
+        let export_parquet = "Export to Parquet...\0".encode_utf16().collect::<Vec<u16>>();
+        let report = "Generate Battery Report...\0".encode_utf16().collect::<Vec<u16>>();
+        let background_engine = "Run in Background Engine\0".encode_utf16().collect::<Vec<u16>>();
+        let (update_flags, check_update) = if update::busy() {
+            (MF_STRING | MF_GRAYED, "Checking for updates...\0".encode_utf16().collect::<Vec<u16>>())
+        } else {
+            (MF_STRING, "Check for Updates...\0".encode_utf16().collect::<Vec<u16>>())
+        };
         let exit = "Exit\0".encode_utf16().collect::<Vec<u16>>();
         
+        let bench_menu = create_benchmark_menu();
+        let algorithm_menu = create_algorithm_menu();
+        let target_menu = create_target_menu();
+        let limit_menu = create_charge_limit_menu();
+        let power_plan_menu = create_power_plan_menu();
+        let brightness_menu = create_brightness_menu();
+        let tooltip_menu = create_tooltip_menu();
+        let icon_menu = create_icon_menu();
+        let snooze_menu = create_snooze_menu();
+        let drain_test_armed = MONITOR.get()
+            .and_then(|m| m.lock().ok())
+            .map(|mon| mon.drain_test.is_some())
+            .unwrap_or(false);
+        let (drain_test_id, drain_test) = if drain_test_armed {
+            (1021, "End drain test\0".encode_utf16().collect::<Vec<u16>>())
+        } else {
+            (1020, "Arm overnight drain test\0".encode_utf16().collect::<Vec<u16>>())
+        };
+        
+        let _ = AppendMenuW(hmenu, MF_STRING, 1164, PCWSTR(summary.as_ptr()));
+        // Read-only lines for paired Bluetooth devices, under the laptop's own summary
+        for device in companion::latest() {
+            let label = format!("{}: {}%\0", device.name, device.percentage).encode_utf16().collect::<Vec<u16>>();
+            let _ = AppendMenuW(hmenu, MF_STRING | MF_GRAYED, 0, PCWSTR(label.as_ptr()));
+        }
         let _ = AppendMenuW(hmenu, MF_STRING, 1001, PCWSTR(battery_info.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_STRING, SNAPSHOT_COMMAND as usize, PCWSTR(snapshot.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_STRING, 1162, PCWSTR(drain_wizard.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_STRING, 1005, PCWSTR(graph.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_STRING, 1006, PCWSTR(sessions.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_STRING, 1007, PCWSTR(event_log.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_STRING, 1009, PCWSTR(alert_history.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_STRING, 1008, PCWSTR(wear.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_STRING, 1180, PCWSTR(report.as_ptr()));
         let _ = AppendMenuW(hmenu, MF_STRING, 1002, PCWSTR(settings.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_POPUP, icon_menu.0 as usize, PCWSTR(icon.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_POPUP, tooltip_menu.0 as usize, PCWSTR(tooltip.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_POPUP, snooze_menu.0 as usize, PCWSTR(snooze.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_POPUP, algorithm_menu.0 as usize, PCWSTR(eta_algorithm.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_POPUP, target_menu.0 as usize, PCWSTR(target.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_POPUP, limit_menu.0 as usize, PCWSTR(charge_limit.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_POPUP, power_plan_menu.0 as usize, PCWSTR(power_plan.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_POPUP, brightness_menu.0 as usize, PCWSTR(brightness.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_POPUP, bench_menu.0 as usize, PCWSTR(benchmark.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_STRING, drain_test_id, PCWSTR(drain_test.as_ptr()));
+        let discord_menu = create_discord_menu();
+        let _ = AppendMenuW(hmenu, MF_POPUP, discord_menu.0 as usize, PCWSTR(discord.as_ptr()));
+        let overlay_flags = if server::is_running() { MF_STRING | MF_CHECKED } else { MF_STRING };
+        let _ = AppendMenuW(hmenu, overlay_flags, 1163, PCWSTR(overlay.as_ptr()));
+        let companion_enabled = MONITOR.get()
+            .and_then(|m| m.lock().ok())
+            .is_some_and(|mon| mon.settings.companion_battery);
+        let companion_flags = if companion_enabled { MF_STRING | MF_CHECKED } else { MF_STRING };
+        let _ = AppendMenuW(hmenu, companion_flags, 1175, PCWSTR(companion.as_ptr()));
+        let engine_flags = if engine::is_client() { MF_STRING | MF_CHECKED } else { MF_STRING };
+        let _ = AppendMenuW(hmenu, engine_flags, 1178, PCWSTR(background_engine.as_ptr()));
+        let devices_menu = create_devices_menu();
+        let _ = AppendMenuW(hmenu, MF_POPUP, devices_menu.0 as usize, PCWSTR(devices.as_ptr()));
         let _ = AppendMenuW(hmenu, MF_SEPARATOR, 0, PCWSTR::null());
         let _ = AppendMenuW(hmenu, MF_STRING, 1003, PCWSTR(about.as_ptr()));
+        let _ = AppendMenuW(hmenu, update_flags, 1160, PCWSTR(check_update.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_STRING, 1161, PCWSTR(diagnostics.as_ptr()));
+        let _ = AppendMenuW(hmenu, MF_STRING, 1177, PCWSTR(export_parquet.as_ptr()));
         let _ = AppendMenuW(hmenu, MF_SEPARATOR, 0, PCWSTR::null());
         let _ = AppendMenuW(hmenu, MF_STRING, 1004, PCWSTR(exit.as_ptr()));
         
@@ -152,30 +679,1002 @@ fn show_context_menu(hwnd: HWND) {
     }
 }
 
+unsafe fn create_benchmark_menu() -> HMENU {
+    let (running, charge_test, cpu_percent) = match MONITOR.get().and_then(|m| m.lock().ok()) {
+        Some(mon) => (mon.benchmark.is_some(), mon.charge_test.is_some(), mon.settings.benchmark_cpu_percent),
+        None => (false, false, 25),
+    };
+    let start_flags = if running { MF_STRING | MF_GRAYED } else { MF_STRING };
+    let stop_flags = if running { MF_STRING } else { MF_STRING | MF_GRAYED };
+    
+    let idle = "Start: Idle\0".encode_utf16().collect::<Vec<u16>>();
+    let cpu = format!("Start: CPU spin {}%\0", cpu_percent).encode_utf16().collect::<Vec<u16>>();
+    let video = "Start: Video loop\0".encode_utf16().collect::<Vec<u16>>();
+    let stop = "Stop benchmark\0".encode_utf16().collect::<Vec<u16>>();
+    let charge = if charge_test {
+        "Cancel charge test\0".encode_utf16().collect::<Vec<u16>>()
+    } else {
+        "Start charge test\0".encode_utf16().collect::<Vec<u16>>()
+    };
+    let results = "Results...\0".encode_utf16().collect::<Vec<u16>>();
+    
+    let menu = CreatePopupMenu().unwrap();
+    let _ = AppendMenuW(menu, start_flags, 1010, PCWSTR(idle.as_ptr()));
+    let _ = AppendMenuW(menu, start_flags, 1011, PCWSTR(cpu.as_ptr()));
+    let _ = AppendMenuW(menu, start_flags, 1012, PCWSTR(video.as_ptr()));
+    let _ = AppendMenuW(menu, stop_flags, 1013, PCWSTR(stop.as_ptr()));
+    let _ = AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::null());
+    let _ = AppendMenuW(menu, MF_STRING, 1015, PCWSTR(charge.as_ptr()));
+    let _ = AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::null());
+    let _ = AppendMenuW(menu, MF_STRING, 1014, PCWSTR(results.as_ptr()));
+    menu
+}
+
+const ETA_ALGORITHMS: [EtaAlgorithm; 7] = [
+    EtaAlgorithm::SimpleAverage,
+    EtaAlgorithm::Ema,
+    EtaAlgorithm::Regression,
+    EtaAlgorithm::Hybrid,
+    EtaAlgorithm::Kalman,
+    EtaAlgorithm::Blended,
+    EtaAlgorithm::Energy,
+];
+
+unsafe fn create_algorithm_menu() -> HMENU {
+    let (current, nonlinear) = match MONITOR.get().and_then(|m| m.lock().ok()) {
+        Some(mon) => (Some(mon.settings.eta_algorithm), mon.settings.nonlinear_drain),
+        None => (None, false),
+    };
+    
+    let menu = CreatePopupMenu().unwrap();
+    for (i, algorithm) in ETA_ALGORITHMS.iter().enumerate() {
+        let label = format!("{}\0", algorithm.label()).encode_utf16().collect::<Vec<u16>>();
+        let flags = if current == Some(*algorithm) { MF_STRING | MF_CHECKED } else { MF_STRING };
+        let _ = AppendMenuW(menu, flags, 1030 + i, PCWSTR(label.as_ptr()));
+    }
+    let curve = "Follow the learned drain curve\0".encode_utf16().collect::<Vec<u16>>();
+    let _ = AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::null());
+    let _ = AppendMenuW(menu, if nonlinear { MF_STRING | MF_CHECKED } else { MF_STRING }, 1038, PCWSTR(curve.as_ptr()));
+    let evaluate = "Evaluate on history...\0".encode_utf16().collect::<Vec<u16>>();
+    let _ = AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::null());
+    let _ = AppendMenuW(menu, MF_STRING, 1039, PCWSTR(evaluate.as_ptr()));
+    menu
+}
+
+// Replays a history file (the live one by default) through every estimator
+pub fn run_evaluation(hwnd: HWND, history_path: Option<&std::path::Path>) {
+    // `--evaluate` runs without a monitor, so the settings come from disk there
+    let (measurements, settings): (Vec<_>, _) = match history_path {
+        Some(path) => (BatteryMonitor::load_history_from(path).into_iter().collect(), AppSettings::load()),
+        None => match MONITOR.get().and_then(|m| m.lock().ok()) {
+            Some(mon) => (mon.measurements.iter().cloned().collect(), mon.settings.clone()),
+            None => return,
+        },
+    };
+    
+    let results = accuracy::replay(&measurements, Duration::minutes(30), &settings);
+    show_message(hwnd, "ETA Algorithm Evaluation", &accuracy::format_replay(&results, measurements.len()));
+}
+
+fn set_eta_algorithm(hwnd: HWND, algorithm: EtaAlgorithm) {
+    if let Some(monitor) = MONITOR.get() {
+        if let Ok(mut mon) = monitor.lock() {
+            mon.settings.eta_algorithm = algorithm;
+            mon.settings.save();
+        }
+        update_tray_icon(hwnd, monitor);
+    }
+}
+
+fn toggle_nonlinear_drain(hwnd: HWND) {
+    if let Some(monitor) = MONITOR.get() {
+        if let Ok(mut mon) = monitor.lock() {
+            mon.settings.nonlinear_drain = !mon.settings.nonlinear_drain;
+            mon.settings.save();
+        }
+        update_tray_icon(hwnd, monitor);
+    }
+}
+
+unsafe fn create_target_menu() -> HMENU {
+    let (current, presets) = match MONITOR.get().and_then(|m| m.lock().ok()) {
+        Some(mon) => (mon.settings.target_time.clone(), mon.settings.target_time_presets.clone()),
+        None => (None, Vec::new()),
+    };
+    
+    let menu = CreatePopupMenu().unwrap();
+    for (i, preset) in presets.iter().take(8).enumerate() {
+        let label = format!("{}\0", preset).encode_utf16().collect::<Vec<u16>>();
+        let flags = if current.as_ref() == Some(preset) { MF_STRING | MF_CHECKED } else { MF_STRING };
+        let _ = AppendMenuW(menu, flags, 1040 + i, PCWSTR(label.as_ptr()));
+    }
+    
+    let custom = match &current {
+        Some(time) if !presets.contains(time) => format!("Custom ({})...\0", time),
+        _ => "Custom time...\0".to_string(),
+    }.encode_utf16().collect::<Vec<u16>>();
+    let clear = "Clear\0".encode_utf16().collect::<Vec<u16>>();
+    let clear_flags = if current.is_some() { MF_STRING } else { MF_STRING | MF_GRAYED };
+    
+    let _ = AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::null());
+    let _ = AppendMenuW(menu, MF_STRING, 1048, PCWSTR(custom.as_ptr()));
+    let _ = AppendMenuW(menu, clear_flags, 1049, PCWSTR(clear.as_ptr()));
+    menu
+}
+
+fn set_target_time(hwnd: HWND, target: Option<String>) {
+    if let Some(monitor) = MONITOR.get() {
+        if let Ok(mut mon) = monitor.lock() {
+            mon.settings.target_time = target;
+            mon.settings.save();
+        }
+        update_tray_icon(hwnd, monitor);
+    }
+}
+
+fn prompt_target_time(hwnd: HWND) {
+    let current = MONITOR.get()
+        .and_then(|m| m.lock().ok())
+        .and_then(|mon| mon.settings.target_time.clone())
+        .unwrap_or_default();
+    
+    let Some(text) = prompt::prompt_text(hwnd, "Will It Last Until...", "Target time (HH:MM, 24-hour):", &current) else {
+        return;
+    };
+    match forecast::parse_target(&text) {
+        Some(time) => set_target_time(hwnd, Some(time.format("%H:%M").to_string())),
+        None => show_message(hwnd, "Will It Last Until...", &format!("\"{}\" is not a valid time. Use HH:MM, e.g. 18:00.", text.trim())),
+    }
+}
+
+fn preset_target(index: usize) -> Option<String> {
+    MONITOR.get()
+        .and_then(|m| m.lock().ok())
+        .and_then(|mon| mon.settings.target_time_presets.get(index).cloned())
+}
+
+// Optional tooltip lines, in menu order
+const TOOLTIP_OPTIONS: [&str; 8] = ["Show power draw", "Show time on battery", "Show battery health", "Show voltage and temperature", "Show ETA range", "Show when the threshold is reached", "Show active and idle estimates", "Show typical-usage ETA"];
+
+const ICON_STYLES: [IconStyle; 2] = [IconStyle::Battery, IconStyle::Numeric];
+const ICON_FONT_WEIGHTS: [(u32, &str); 3] = [(400, "Regular"), (600, "Semibold"), (700, "Bold")];
+
+unsafe fn create_icon_menu() -> HMENU {
+    let settings = match MONITOR.get().and_then(|m| m.lock().ok()) {
+        Some(mon) => mon.settings.clone(),
+        None => AppSettings::default(),
+    };
+    
+    let menu = CreatePopupMenu().unwrap();
+    for (i, style) in ICON_STYLES.iter().enumerate() {
+        let label = format!("{}\0", style.label()).encode_utf16().collect::<Vec<u16>>();
+        let flags = if settings.icon_style == *style { MF_STRING | MF_CHECKED } else { MF_STRING };
+        let _ = AppendMenuW(menu, flags, 1070 + i, PCWSTR(label.as_ptr()));
+    }
+    
+    // Font options only apply to the percentage icon
+    let numeric = if settings.icon_style == IconStyle::Numeric { MF_STRING } else { MF_STRING | MF_GRAYED };
+    let _ = AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::null());
+    let label = format!("Font: {}...\0", settings.icon_font).encode_utf16().collect::<Vec<u16>>();
+    let _ = AppendMenuW(menu, numeric, 1072, PCWSTR(label.as_ptr()));
+    for (i, (weight, name)) in ICON_FONT_WEIGHTS.iter().enumerate() {
+        let label = format!("{}\0", name).encode_utf16().collect::<Vec<u16>>();
+        let flags = if settings.icon_font_weight == *weight { numeric | MF_CHECKED } else { numeric };
+        let _ = AppendMenuW(menu, flags, 1073 + i, PCWSTR(label.as_ptr()));
+    }
+    
+    let _ = AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::null());
+    let label = "Time Left First\0".encode_utf16().collect::<Vec<u16>>();
+    let flags = if settings.time_first_display { MF_STRING | MF_CHECKED } else { MF_STRING };
+    let _ = AppendMenuW(menu, flags, 1169, PCWSTR(label.as_ptr()));
+    let label = "Smooth Displayed Value\0".encode_utf16().collect::<Vec<u16>>();
+    let flags = if settings.smooth_display { MF_STRING | MF_CHECKED } else { MF_STRING };
+    let _ = AppendMenuW(menu, flags, 1168, PCWSTR(label.as_ptr()));
+    // For when the tray icon is too small to read
+    let label = "Show on Taskbar\0".encode_utf16().collect::<Vec<u16>>();
+    let flags = if settings.taskbar_text { MF_STRING | MF_CHECKED } else { MF_STRING };
+    let _ = AppendMenuW(menu, flags, 1165, PCWSTR(label.as_ptr()));
+    let label = "One Icon per Battery\0".encode_utf16().collect::<Vec<u16>>();
+    let flags = if settings.icon_per_battery { MF_STRING | MF_CHECKED } else { MF_STRING };
+    let _ = AppendMenuW(menu, flags, 1179, PCWSTR(label.as_ptr()));
+    menu
+}
+
+fn change_icon_settings(hwnd: HWND, change: impl FnOnce(&mut AppSettings)) {
+    if let Some(monitor) = MONITOR.get() {
+        if let Ok(mut mon) = monitor.lock() {
+            change(&mut mon.settings);
+            mon.settings.save();
+            mon.icon_key = None;
+        }
+        update_tray_icon(hwnd, monitor);
+    }
+}
+
+fn prompt_icon_font(hwnd: HWND) {
+    let current = MONITOR.get()
+        .and_then(|m| m.lock().ok())
+        .map(|mon| mon.settings.icon_font.clone())
+        .unwrap_or_default();
+    
+    let Some(text) = prompt::prompt_text(hwnd, "Icon Font", "Font name (e.g. Segoe UI, Bahnschrift, Consolas):", &current) else {
+        return;
+    };
+    let font = text.trim().to_string();
+    if !font.is_empty() {
+        change_icon_settings(hwnd, |settings| settings.icon_font = font);
+    }
+}
+
+fn tooltip_option(settings: &mut AppSettings, index: usize) -> &mut bool {
+    match index {
+        0 => &mut settings.tooltip_power_draw,
+        1 => &mut settings.tooltip_time_on_battery,
+        2 => &mut settings.tooltip_health,
+        3 => &mut settings.tooltip_voltage_temperature,
+        4 => &mut settings.tooltip_eta_range,
+        5 => &mut settings.tooltip_threshold_time,
+        6 => &mut settings.tooltip_active_idle,
+        _ => &mut settings.tooltip_typical_eta,
+    }
+}
+
+unsafe fn create_tooltip_menu() -> HMENU {
+    let mut settings = match MONITOR.get().and_then(|m| m.lock().ok()) {
+        Some(mon) => mon.settings.clone(),
+        None => AppSettings::default(),
+    };
+    
+    let menu = CreatePopupMenu().unwrap();
+    for (i, label) in TOOLTIP_OPTIONS.iter().enumerate() {
+        let label = format!("{}\0", label).encode_utf16().collect::<Vec<u16>>();
+        let flags = if *tooltip_option(&mut settings, i) { MF_STRING | MF_CHECKED } else { MF_STRING };
+        let _ = AppendMenuW(menu, flags, 1060 + i, PCWSTR(label.as_ptr()));
+    }
+    menu
+}
+
+fn toggle_tooltip_option(hwnd: HWND, index: usize) {
+    if let Some(monitor) = MONITOR.get() {
+        if let Ok(mut mon) = monitor.lock() {
+            let option = tooltip_option(&mut mon.settings, index);
+            *option = !*option;
+            mon.settings.save();
+        }
+        update_tray_icon(hwnd, monitor);
+    }
+}
+
+const COUNTDOWN_LEVELS: [u8; 3] = [20, 15, 10];
+
+// One submenu per alert type: IDs 1200 + 10 * type, then +0..3 snooze now,
+// +5..8 set the click default, +9 resume; 1150 toggles screen reader announcements,
+// 1151..1154 pick the countdown overlay level
+unsafe fn create_snooze_menu() -> HMENU {
+    let mon = MONITOR.get().and_then(|m| m.lock().ok());
+    let menu = CreatePopupMenu().unwrap();
+    for (i, kind) in ALERT_KINDS.iter().enumerate() {
+        let base = 1200 + 10 * i;
+        let until = mon.as_ref().and_then(|mon| mon.snoozes.get(kind).copied()).filter(|t| mon.as_ref().is_some_and(|mon| *t > mon.clock.now()));
+        let default = mon.as_ref().map(|mon| notify::snooze_minutes(mon, *kind)).unwrap_or_else(|| kind.default_snooze_minutes());
+        
+        let submenu = CreatePopupMenu().unwrap();
+        for (j, minutes) in SNOOZE_OPTIONS.iter().enumerate() {
+            let label = format!("Snooze for {}\0", notify::format_snooze(*minutes)).encode_utf16().collect::<Vec<u16>>();
+            let _ = AppendMenuW(submenu, MF_STRING, base + j, PCWSTR(label.as_ptr()));
+        }
+        let resume = "Resume now\0".encode_utf16().collect::<Vec<u16>>();
+        let flags = if until.is_some() { MF_STRING } else { MF_STRING | MF_GRAYED };
+        let _ = AppendMenuW(submenu, flags, base + 9, PCWSTR(resume.as_ptr()));
+        let _ = AppendMenuW(submenu, MF_SEPARATOR, 0, PCWSTR::null());
+        for (j, minutes) in SNOOZE_OPTIONS.iter().enumerate() {
+            let label = format!("Default on click: {}\0", notify::format_snooze(*minutes)).encode_utf16().collect::<Vec<u16>>();
+            let flags = if default == *minutes { MF_STRING | MF_CHECKED } else { MF_STRING };
+            let _ = AppendMenuW(submenu, flags, base + 5 + j, PCWSTR(label.as_ptr()));
+        }
+        
+        let label = match until {
+            Some(until) => format!("{} (snoozed until {})\0", kind.label(), until.format("%H:%M")),
+            None => format!("{}\0", kind.label()),
+        };
+        let label = label.encode_utf16().collect::<Vec<u16>>();
+        let _ = AppendMenuW(menu, MF_POPUP, submenu.0 as usize, PCWSTR(label.as_ptr()));
+    }
+    
+    let announce = mon.as_ref().is_some_and(|mon| mon.settings.announce_critical_alerts);
+    let label = "Announce critical alerts to screen readers\0".encode_utf16().collect::<Vec<u16>>();
+    let _ = AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::null());
+    let _ = AppendMenuW(menu, if announce { MF_STRING | MF_CHECKED } else { MF_STRING }, 1150, PCWSTR(label.as_ptr()));
+
+    let overlay = mon.as_ref().and_then(|mon| mon.settings.countdown_overlay_percentage);
+    let overlay_menu = CreatePopupMenu().unwrap();
+    let off = "Off\0".encode_utf16().collect::<Vec<u16>>();
+    let _ = AppendMenuW(overlay_menu, if overlay.is_none() { MF_STRING | MF_CHECKED } else { MF_STRING }, 1151, PCWSTR(off.as_ptr()));
+    for (i, level) in COUNTDOWN_LEVELS.iter().enumerate() {
+        let label = format!("Below {}%\0", level).encode_utf16().collect::<Vec<u16>>();
+        let flags = if overlay == Some(*level) { MF_STRING | MF_CHECKED } else { MF_STRING };
+        let _ = AppendMenuW(overlay_menu, flags, 1152 + i, PCWSTR(label.as_ptr()));
+    }
+    let label = "Countdown overlay\0".encode_utf16().collect::<Vec<u16>>();
+    let _ = AppendMenuW(menu, MF_POPUP, overlay_menu.0 as usize, PCWSTR(label.as_ptr()));
+    menu
+}
+
+fn handle_snooze_command(id: usize) {
+    let Some(kind) = ALERT_KINDS.get((id - 1200) / 10).copied() else { return };
+    let Some(mut mon) = MONITOR.get().and_then(|m| m.lock().ok()) else { return };
+    match (id - 1200) % 10 {
+        option @ 0..=3 => notify::snooze(&mut mon, kind, Some(SNOOZE_OPTIONS[option])),
+        option @ 5..=8 => {
+            mon.settings.alert_snooze_minutes.insert(kind, SNOOZE_OPTIONS[option - 5]);
+            mon.settings.save();
+        }
+        9 => notify::snooze(&mut mon, kind, None),
+        _ => {}
+    }
+}
+
+// Clicking a balloon snoozes that alert type for its default
+fn snooze_last_alert() {
+    let Some(mut mon) = MONITOR.get().and_then(|m| m.lock().ok()) else { return };
+    if let Some(kind) = mon.alerts.iter().rev().find(|a| !a.snoozed).map(|a| a.kind) {
+        let minutes = notify::snooze_minutes(&mon, kind);
+        notify::snooze(&mut mon, kind, Some(minutes));
+    }
+}
+
+const DISCORD_FIELDS: [&str; 3] = ["Show charging / on battery", "Show percentage", "Show time left"];
+
+fn discord_field(settings: &mut AppSettings, index: usize) -> &mut bool {
+    match index {
+        0 => &mut settings.discord_show_power_state,
+        1 => &mut settings.discord_show_percentage,
+        _ => &mut settings.discord_show_eta,
+    }
+}
+
+unsafe fn create_discord_menu() -> HMENU {
+    let mut settings = match MONITOR.get().and_then(|m| m.lock().ok()) {
+        Some(mon) => mon.settings.clone(),
+        None => AppSettings::default(),
+    };
+
+    let menu = CreatePopupMenu().unwrap();
+    let enabled = "Share battery status\0".encode_utf16().collect::<Vec<u16>>();
+    let flags = if settings.discord_presence { MF_STRING | MF_CHECKED } else { MF_STRING };
+    let _ = AppendMenuW(menu, flags, 1170, PCWSTR(enabled.as_ptr()));
+    let _ = AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::null());
+    for (i, label) in DISCORD_FIELDS.iter().enumerate() {
+        let label = format!("{}\0", label).encode_utf16().collect::<Vec<u16>>();
+        let flags = if *discord_field(&mut settings, i) { MF_STRING | MF_CHECKED } else { MF_STRING };
+        let _ = AppendMenuW(menu, flags, 1171 + i, PCWSTR(label.as_ptr()));
+    }
+    let client_id = "Application ID...\0".encode_utf16().collect::<Vec<u16>>();
+    let _ = AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::null());
+    let _ = AppendMenuW(menu, MF_STRING, 1174, PCWSTR(client_id.as_ptr()));
+    menu
+}
+
+fn change_discord_settings(hwnd: HWND, change: impl FnOnce(&mut AppSettings)) {
+    let Some(monitor) = MONITOR.get() else { return };
+    if let Ok(mut mon) = monitor.lock() {
+        change(&mut mon.settings);
+        mon.settings.save();
+    }
+    update_tray_icon(hwnd, monitor);
+}
+
+fn prompt_discord_client_id(hwnd: HWND) -> bool {
+    let current = MONITOR.get()
+        .and_then(|m| m.lock().ok())
+        .and_then(|mon| mon.settings.discord_client_id.clone())
+        .unwrap_or_default();
+    let Some(text) = prompt::prompt_text(hwnd, "Discord Status", "Application ID from discord.com/developers:", &current) else {
+        return false;
+    };
+    let id = text.trim().to_string();
+    if !id.is_empty() && !id.chars().all(|c| c.is_ascii_digit()) {
+        show_message(hwnd, "Discord Status", "An application ID is a number, e.g. 1234567890123456789.");
+        return false;
+    }
+    change_discord_settings(hwnd, |settings| settings.discord_client_id = (!id.is_empty()).then_some(id));
+    true
+}
+
+// Off by default; turning it on asks for the application ID first if there is none
+fn toggle_discord_presence(hwnd: HWND) {
+    let (enabled, has_id) = match MONITOR.get().and_then(|m| m.lock().ok()) {
+        Some(mon) => (mon.settings.discord_presence, mon.settings.discord_client_id.is_some()),
+        None => return,
+    };
+    if !enabled && !has_id && !prompt_discord_client_id(hwnd) {
+        return;
+    }
+    change_discord_settings(hwnd, |settings| settings.discord_presence = !enabled);
+}
+
+fn toggle_overlay_server(hwnd: HWND) {
+    let Some(monitor) = MONITOR.get() else { return };
+    if server::is_running() {
+        server::stop();
+        if let Ok(mut mon) = monitor.lock() {
+            mon.settings.overlay_server_port = None;
+            mon.settings.save();
+        }
+        return;
+    }
+
+    let port = server::DEFAULT_PORT;
+    if let Err(e) = server::start(port) {
+        show_message(hwnd, "Streaming Overlay", &e);
+        return;
+    }
+    if let Ok(mut mon) = monitor.lock() {
+        mon.settings.overlay_server_port = Some(port);
+        mon.settings.save();
+    }
+    let url = server::overlay_url();
+    let copied = if about::copy_to_clipboard(hwnd, &url) { " (copied to the clipboard)" } else { "" };
+    show_message(hwnd, "Streaming Overlay", &format!(
+        "Add a Browser source in OBS with this URL{}:\n\n{}\n\nAppend ?theme=light or ?theme=transparent and &size=40 to restyle it. Only this computer can reach it.",
+        copied, url,
+    ));
+}
+
+// Also sent by `battesty --snapshot` to the running instance
+pub const SNAPSHOT_COMMAND: u32 = 1176;
+
+// A fresh sample, ETA and icon, with history written out right away instead of at the next save
+fn snapshot_now(hwnd: HWND) {
+    let Some(monitor) = MONITOR.get() else { return };
+    // The engine measures and saves; its confirmation arrives with the update below
+    if engine::is_client() && engine::request("snapshot").is_ok() {
+        update_tray_icon(hwnd, monitor);
+        return;
+    }
+    update_tray_icon(hwnd, monitor);
+    let Ok(mut mon) = monitor.lock() else { return };
+    mon.update_rollups();
+    mon.save_history();
+    let text = match mon.measurements.back() {
+        Some(m) if m.is_charging => format!("{}% · charging · history saved", m.percentage),
+        Some(m) => match m.eta_minutes {
+            Some(eta) => format!("{}% · {} left · history saved", m.percentage, BatteryMonitor::format_time(eta)),
+            None => format!("{}% · history saved", m.percentage),
+        },
+        None => "Battery status unavailable".to_string(),
+    };
+    drop(mon);
+    notify::show_balloon(hwnd, "Snapshot", &text, NIIF_INFO);
+}
+
+fn toggle_companion_battery() {
+    let Some(mut mon) = MONITOR.get().and_then(|m| m.lock().ok()) else { return };
+    mon.settings.companion_battery = !mon.settings.companion_battery;
+    mon.settings.save();
+    // In client mode the engine polls and picks the change up from the saved settings
+    if mon.settings.companion_battery && !engine::is_client() {
+        companion::start();
+    } else {
+        companion::stop();
+    }
+}
+
+fn toggle_background_engine(hwnd: HWND) {
+    let Some(monitor) = MONITOR.get() else { return };
+    let enabled = {
+        let Ok(mut mon) = monitor.lock() else { return };
+        mon.settings.background_engine = !mon.settings.background_engine;
+        mon.settings.save();
+        // Whatever was measured here so far goes to disk before the engine takes over the file
+        mon.save_history();
+        mon.settings.background_engine
+    };
+    engine::set_client(enabled);
+    engine::set_autostart(enabled);
+    if enabled {
+        companion::stop();
+        engine::ensure_running();
+    } else {
+        engine::stop();
+        if monitor.lock().is_ok_and(|mon| mon.settings.companion_battery) {
+            companion::start();
+        }
+    }
+    update_tray_icon(hwnd, monitor);
+}
+
+unsafe fn create_devices_menu() -> HMENU {
+    let folder = MONITOR.get()
+        .and_then(|m| m.lock().ok())
+        .and_then(|mon| mon.settings.sync_folder.clone());
+    let menu = CreatePopupMenu().unwrap();
+    let show = "Show All Devices...\0".encode_utf16().collect::<Vec<u16>>();
+    let _ = AppendMenuW(menu, MF_STRING, 1166, PCWSTR(show.as_ptr()));
+    let label = match folder {
+        Some(folder) => format!("Sync Folder: {}...\0", folder),
+        None => "Sync Folder...\0".to_string(),
+    };
+    let label = label.encode_utf16().collect::<Vec<u16>>();
+    let _ = AppendMenuW(menu, MF_STRING, 1167, PCWSTR(label.as_ptr()));
+    menu
+}
+
+// A folder every machine syncs, e.g. one inside OneDrive; empty turns sharing off
+fn prompt_sync_folder(hwnd: HWND) -> bool {
+    let Some(monitor) = MONITOR.get() else { return false };
+    let current = monitor.lock().ok().and_then(|mon| mon.settings.sync_folder.clone()).unwrap_or_default();
+    let Some(text) = prompt::prompt_text(hwnd, "Other Devices", "Folder shared by all your PCs (e.g. in OneDrive):", &current) else {
+        return false;
+    };
+    let folder = text.trim().trim_matches('"').to_string();
+    if !folder.is_empty() && !std::path::Path::new(&folder).is_dir() {
+        show_message(hwnd, "Other Devices", &format!("{} is not a folder.", folder));
+        return false;
+    }
+    if let Ok(mut mon) = monitor.lock() {
+        mon.settings.sync_folder = (!folder.is_empty()).then_some(folder);
+        mon.settings.save();
+        if let Err(e) = devices::export(&mon) {
+            drop(mon);
+            show_message(hwnd, "Other Devices", &e);
+            return false;
+        }
+    }
+    true
+}
+
+fn show_devices(hwnd: HWND) {
+    let Some(monitor) = MONITOR.get() else { return };
+    let has_folder = monitor.lock().is_ok_and(|mon| mon.settings.sync_folder.is_some());
+    if !has_folder && !prompt_sync_folder(hwnd) {
+        return;
+    }
+    // This PC's entry is refreshed first so it's never staler than the others
+    let (folder, exported) = match monitor.lock() {
+        Ok(mon) => (mon.settings.sync_folder.clone(), devices::export(&mon)),
+        Err(_) => return,
+    };
+    let Some(folder) = folder else { return };
+    let text = match exported.and_then(|_| devices::load(&folder)) {
+        Ok(list) => format!("{}\n\nShared through {}", devices::summary(&list), folder),
+        Err(e) => e,
+    };
+    about::show_details(hwnd, "Other Devices", &text);
+}
+
+const ASUS_LIMIT_PRESETS: [u8; 3] = [60, 80, 100];
+
+// "Power Saver below N%" presets; other rules can be set in the config file
+const POWER_SAVER_PRESETS: [u8; 3] = [50, 30, 20];
+
+unsafe fn create_power_plan_menu() -> HMENU {
+    let rules = MONITOR.get()
+        .and_then(|m| m.lock().ok())
+        .map(|mon| mon.settings.power_plan_rules.clone())
+        .unwrap_or_default();
+    let preset = |below: u8| vec![PowerPlanRule { below_percentage: below, plan: "Power Saver".to_string() }];
+
+    let menu = CreatePopupMenu().unwrap();
+    let off = "Off\0".encode_utf16().collect::<Vec<u16>>();
+    let _ = AppendMenuW(menu, if rules.is_empty() { MF_STRING | MF_CHECKED } else { MF_STRING }, 1080, PCWSTR(off.as_ptr()));
+    for (i, below) in POWER_SAVER_PRESETS.iter().enumerate() {
+        let label = format!("Power Saver below {}%\0", below).encode_utf16().collect::<Vec<u16>>();
+        let flags = if rules == preset(*below) { MF_STRING | MF_CHECKED } else { MF_STRING };
+        let _ = AppendMenuW(menu, flags, 1081 + i, PCWSTR(label.as_ptr()));
+    }
+    if !rules.is_empty() && !POWER_SAVER_PRESETS.iter().any(|below| rules == preset(*below)) {
+        let label = format!("{} rule(s) from the config file\0", rules.len()).encode_utf16().collect::<Vec<u16>>();
+        let _ = AppendMenuW(menu, MF_STRING | MF_CHECKED | MF_GRAYED, 1089, PCWSTR(label.as_ptr()));
+    }
+    menu
+}
+
+fn set_power_plan_rules(rules: Vec<PowerPlanRule>) {
+    if let Some(mut mon) = MONITOR.get().and_then(|m| m.lock().ok()) {
+        mon.settings.power_plan_rules = rules;
+        mon.settings.save();
+    }
+}
+
+// (battery %, brightness %) presets; other rules can be set in the config file
+const BRIGHTNESS_PRESETS: [(u8, u8); 3] = [(30, 50), (20, 40), (10, 25)];
+
+unsafe fn create_brightness_menu() -> HMENU {
+    let settings = match MONITOR.get().and_then(|m| m.lock().ok()) {
+        Some(mon) => mon.settings.clone(),
+        None => AppSettings::default(),
+    };
+    let rules = &settings.brightness_rules;
+    let preset = |(below, level): (u8, u8)| vec![BrightnessRule { below_percentage: below, brightness: level }];
+
+    let menu = CreatePopupMenu().unwrap();
+    let off = "Off\0".encode_utf16().collect::<Vec<u16>>();
+    let _ = AppendMenuW(menu, if rules.is_empty() { MF_STRING | MF_CHECKED } else { MF_STRING }, 1090, PCWSTR(off.as_ptr()));
+    for (i, (below, level)) in BRIGHTNESS_PRESETS.iter().enumerate() {
+        let label = format!("Dim to {}% below {}%\0", level, below).encode_utf16().collect::<Vec<u16>>();
+        let flags = if *rules == preset((*below, *level)) { MF_STRING | MF_CHECKED } else { MF_STRING };
+        let _ = AppendMenuW(menu, flags, 1091 + i, PCWSTR(label.as_ptr()));
+    }
+    if !rules.is_empty() && !BRIGHTNESS_PRESETS.iter().any(|p| *rules == preset(*p)) {
+        let label = format!("{} rule(s) from the config file\0", rules.len()).encode_utf16().collect::<Vec<u16>>();
+        let _ = AppendMenuW(menu, MF_STRING | MF_CHECKED | MF_GRAYED, 1098, PCWSTR(label.as_ptr()));
+    }
+    let restore = "Restore when plugged in\0".encode_utf16().collect::<Vec<u16>>();
+    let flags = if settings.restore_brightness_on_ac { MF_STRING | MF_CHECKED } else { MF_STRING };
+    let _ = AppendMenuW(menu, MF_SEPARATOR, 0, PCWSTR::null());
+    let _ = AppendMenuW(menu, flags, 1099, PCWSTR(restore.as_ptr()));
+    menu
+}
+
+fn change_brightness_settings(change: impl FnOnce(&mut AppSettings)) {
+    if let Some(mut mon) = MONITOR.get().and_then(|m| m.lock().ok()) {
+        change(&mut mon.settings);
+        mon.settings.save();
+    }
+}
+
+unsafe fn create_charge_limit_menu() -> HMENU {
+    let (control, current) = match MONITOR.get().and_then(|m| m.lock().ok()) {
+        Some(mon) => (vendor::limit_control(mon.vendor), mon.charge_limit.clone()),
+        None => (None, None),
+    };
+    
+    let menu = CreatePopupMenu().unwrap();
+    match control {
+        Some(LimitControl::LenovoConservation) => {
+            let label = "Conservation mode\0".encode_utf16().collect::<Vec<u16>>();
+            let flags = if current.is_some() { MF_STRING | MF_CHECKED } else { MF_STRING };
+            let _ = AppendMenuW(menu, flags, 1050, PCWSTR(label.as_ptr()));
+        }
+        Some(LimitControl::AsusLimit) => {
+            let active = current.and_then(|c| c.limit).unwrap_or(100);
+            for (i, limit) in ASUS_LIMIT_PRESETS.iter().enumerate() {
+                let label = format!("{}%\0", limit).encode_utf16().collect::<Vec<u16>>();
+                let flags = if active == *limit { MF_STRING | MF_CHECKED } else { MF_STRING };
+                let _ = AppendMenuW(menu, flags, 1051 + i, PCWSTR(label.as_ptr()));
+            }
+        }
+        None => {
+            let label = match &current {
+                Some(limit) => format!("{} (set in vendor app)\0", limit.summary()),
+                None => "Not supported on this device\0".to_string(),
+            }.encode_utf16().collect::<Vec<u16>>();
+            let _ = AppendMenuW(menu, MF_STRING | MF_GRAYED, 1059, PCWSTR(label.as_ptr()));
+        }
+    }
+    menu
+}
+
+fn set_charge_limit(hwnd: HWND, control: LimitControl, limit: Option<u8>) {
+    let Some(monitor) = MONITOR.get() else { return };
+    let result = match monitor.lock() {
+        Ok(mut mon) => mon.apply_charge_limit(control, limit),
+        Err(_) => return,
+    };
+    
+    match result {
+        Ok(()) => update_tray_icon(hwnd, monitor),
+        Err(e) => show_message(hwnd, "Charge Limit", &format!("Could not change the charge limit:\n\n{}", e)),
+    }
+}
+
+fn toggle_conservation_mode(hwnd: HWND) {
+    let enabled = MONITOR.get()
+        .and_then(|m| m.lock().ok())
+        .map(|mon| mon.charge_limit.is_some())
+        .unwrap_or(false);
+    // The firmware picks the conservation level; 60% is the common one
+    set_charge_limit(hwnd, LimitControl::LenovoConservation, if enabled { None } else { Some(60) });
+}
+
+fn show_message(hwnd: HWND, title: &str, msg: &str) {
+    let msg_wide: Vec<u16> = msg.encode_utf16().chain(std::iter::once(0)).collect();
+    let title_wide: Vec<u16> = title.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe {
+        MessageBoxW(hwnd, PCWSTR(msg_wide.as_ptr()), PCWSTR(title_wide.as_ptr()), MB_OK | MB_ICONINFORMATION);
+    }
+}
+
+fn ask_yes_no(hwnd: HWND, title: &str, msg: &str) -> bool {
+    let msg_wide: Vec<u16> = msg.encode_utf16().chain(std::iter::once(0)).collect();
+    let title_wide: Vec<u16> = title.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe { MessageBoxW(hwnd, PCWSTR(msg_wide.as_ptr()), PCWSTR(title_wide.as_ptr()), MB_YESNO | MB_ICONQUESTION) == IDYES }
+}
+
+// A worker thread finished checking for or downloading an update
+pub fn handle_update_event(hwnd: HWND) {
+    match update::take_result() {
+        Some(UpdateResult::Checked(Ok(None))) => {
+            show_message(hwnd, "Check for Updates", &format!("Battesty v{} is the latest version.", versions::app_version()));
+        }
+        Some(UpdateResult::Checked(Ok(Some(release)))) if release.sha256.is_none() => {
+            show_message(hwnd, "Check for Updates", &format!(
+                "Battesty v{} is available, but the release has no checksum to verify the download against.\n\nGet it from {}",
+                release.version,
+                release.page_url,
+            ));
+        }
+        Some(UpdateResult::Checked(Ok(Some(release)))) => {
+            let msg = format!(
+                "Battesty v{} is available (this is v{}).\n\nDownload and install it now? Battesty restarts afterwards.",
+                release.version,
+                versions::app_version(),
+            );
+            if ask_yes_no(hwnd, "Check for Updates", &msg) {
+                update::spawn_download(hwnd, release);
+            }
+        }
+        Some(UpdateResult::Downloaded(Ok((release, path)))) => match update::install(&path) {
+            // WM_DESTROY saves and removes the tray icon; main then starts the new exe
+            Ok(()) => unsafe {
+                let _ = DestroyWindow(hwnd);
+            },
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                show_message(hwnd, "Update Failed", &format!("Battesty v{} could not be installed.\n\n{}", release.version, e));
+            }
+        },
+        Some(UpdateResult::Checked(Err(e))) => show_message(hwnd, "Update Check Failed", &e),
+        Some(UpdateResult::Downloaded(Err(e))) => show_message(hwnd, "Update Failed", &e),
+        None => {}
+    }
+}
+
+fn start_benchmark(hwnd: HWND, workload: Option<Workload>) {
+    let Some(monitor) = MONITOR.get() else { return };
+    let result = match monitor.lock() {
+        Ok(mut mon) => {
+            let workload = workload.unwrap_or(Workload::CpuSpin(mon.settings.benchmark_cpu_percent));
+            mon.start_benchmark(workload).map(|_| workload)
+        }
+        Err(_) => return,
+    };
+    
+    match result {
+        Ok(workload) => {
+            update_tray_icon(hwnd, monitor);
+            show_message(hwnd, "Benchmark", &format!("{} benchmark started.\n\nIt stops automatically when the charger is connected or the battery reaches the configured stop level.", workload.label()));
+        }
+        Err(e) => show_message(hwnd, "Benchmark", &format!("Could not start benchmark:\n\n{}", e)),
+    }
+}
+
+fn stop_benchmark(hwnd: HWND) {
+    let Some(monitor) = MONITOR.get() else { return };
+    let result = match monitor.lock() {
+        Ok(mut mon) => match mon.measurements.back().map(|m| m.percentage) {
+            Some(percentage) => mon.stop_benchmark(percentage),
+            None => None,
+        },
+        Err(_) => return,
+    };
+    
+    if let Some(result) = result {
+        update_tray_icon(hwnd, monitor);
+        show_message(hwnd, "Benchmark", &benchmark::format_results(&[result]));
+    }
+}
+
+fn toggle_charge_test(hwnd: HWND) {
+    let Some(monitor) = MONITOR.get() else { return };
+    let started = match monitor.lock() {
+        Ok(mut mon) => {
+            if mon.charge_test.take().is_some() {
+                false
+            } else {
+                mon.charge_test = Some(ChargeTest::new());
+                true
+            }
+        }
+        Err(_) => return,
+    };
+    
+    if started {
+        show_message(hwnd, "Charge Test", "Charge test armed.\n\nFor comparable results, start from a low charge (below 20%). Connect the charger now; recording starts as soon as charging is detected and ends at 100%.");
+    }
+}
+
+fn arm_drain_test(hwnd: HWND) {
+    let Some(monitor) = MONITOR.get() else { return };
+    let result = match monitor.lock() {
+        Ok(mut mon) => {
+            let result = mon.arm_drain_test();
+            unsafe { SetTimer(hwnd, TIMER_UPDATE, mon.update_interval(), None) };
+            result
+        }
+        Err(_) => return,
+    };
+    
+    match result {
+        Ok(()) => show_message(hwnd, "Drain Test", "Overnight drain test armed.\n\nLeave the machine idle or asleep; choose \"End drain test\" in the morning for a summary. Connecting the charger also ends the test."),
+        Err(e) => show_message(hwnd, "Drain Test", &format!("Could not arm drain test:\n\n{}", e)),
+    }
+}
+
+fn end_drain_test(hwnd: HWND) {
+    let Some(monitor) = MONITOR.get() else { return };
+    if let Ok(mut mon) = monitor.lock() {
+        if let Some((percentage, _, is_charging)) = mon.get_battery_status() {
+            mon.track_drain_test(percentage, is_charging, None);
+        }
+        if let Some(summary) = mon.end_drain_test() {
+            mon.drain_test_summary = Some(summary);
+        }
+    }
+    show_pending_drain_summary(hwnd);
+}
+
 pub fn handle_menu_command(wparam: WPARAM, hwnd: HWND) {
     unsafe {
         match wparam.0 as u32 {
             1001 => {
-                let msg = "Battery measurements and statistics\n\nView detailed battery history and estimated degradation.\n\nComing soon!";
-                let msg_wide: Vec<u16> = msg.encode_utf16().chain(std::iter::once(0)).collect();
-                let title_wide: Vec<u16> = "Battery Info".encode_utf16().chain(std::iter::once(0)).collect();
-                MessageBoxW(hwnd, PCWSTR(msg_wide.as_ptr()), PCWSTR(title_wide.as_ptr()), MB_OK | MB_ICONINFORMATION);
+                if let Some(monitor) = MONITOR.get() {
+                    let text = match monitor.lock() {
+                        Ok(mon) => mon.get_statistics(),
+                        Err(_) => return,
+                    };
+                    about::show_details(hwnd, "Battery Info", &text);
+                }
             }
             1002 => {
                 let msg = "Settings will allow you to:\n\n• Adjust update interval\n• Configure history retention\n• Customize display options\n\nComing soon!";
-                let msg_wide: Vec<u16> = msg.encode_utf16().chain(std::iter::once(0)).collect();
-                let title_wide: Vec<u16> = "Settings".encode_utf16().chain(std::iter::once(0)).collect();
-                MessageBoxW(hwnd, PCWSTR(msg_wide.as_ptr()), PCWSTR(title_wide.as_ptr()), MB_OK | MB_ICONINFORMATION);
+                show_message(hwnd, "Settings", msg);
+            }
+            1003 => about::show_about(hwnd),
+            1160 => update::spawn_check(hwnd),
+            1161 => {
+                let Some(monitor) = MONITOR.get() else { return };
+                let result = match monitor.lock() {
+                    Ok(mon) => diagnostics::collect(&mon),
+                    Err(_) => return,
+                };
+                match result {
+                    Ok(path) => {
+                        diagnostics::reveal(&path);
+                        show_message(hwnd, "Collect Diagnostics", &format!(
+                            "Saved {}\n\nAttach it to your GitHub issue. User and computer names are replaced with placeholders; the history excerpt covers the last 48 hours.",
+                            path.display(),
+                        ));
+                    }
+                    Err(e) => show_message(hwnd, "Collect Diagnostics", &e),
+                }
+            }
+            1177 => {
+                let Some(monitor) = MONITOR.get() else { return };
+                let snapshot = match monitor.lock() {
+                    Ok(mon) => parquet::snapshot(&mon),
+                    Err(_) => return,
+                };
+                let result = parquet::export(snapshot);
+                match result {
+                    Ok(paths) => {
+                        if let Some(first) = paths.first() {
+                            diagnostics::reveal(first);
+                        }
+                        let names: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                        show_message(hwnd, "Export to Parquet", &format!(
+                            "Saved\n{}\n\nLoad them with pandas.read_parquet, polars.read_parquet or DuckDB's read_parquet.",
+                            names.join("\n"),
+                        ));
+                    }
+                    Err(e) => show_message(hwnd, "Export to Parquet", &e),
+                }
+            }
+            1180 => {
+                let Some(monitor) = MONITOR.get() else { return };
+                let result = match monitor.lock() {
+                    Ok(mon) => report::generate(&mon),
+                    Err(_) => return,
+                };
+                match result {
+                    Ok(path) => about::open(&path.display().to_string()),
+                    Err(e) => show_message(hwnd, "Battery Report", &e),
+                }
+            }
+            1162 => {
+                let Some(monitor) = MONITOR.get() else { return };
+                let diagnosis = match monitor.lock() {
+                    Ok(mon) => drain_wizard::diagnose(&mon),
+                    Err(_) => return,
+                };
+                about::show_details(hwnd, "Why Is My Battery Draining?", &diagnosis.summary());
+            }
+            1163 => toggle_overlay_server(hwnd),
+            1164 => widget::toggle(hwnd),
+            1165 => change_icon_settings(hwnd, |settings| settings.taskbar_text = !settings.taskbar_text),
+            1166 => show_devices(hwnd),
+            1168 => change_icon_settings(hwnd, |settings| settings.smooth_display = !settings.smooth_display),
+            1169 => change_icon_settings(hwnd, |settings| settings.time_first_display = !settings.time_first_display),
+            1179 => change_icon_settings(hwnd, |settings| settings.icon_per_battery = !settings.icon_per_battery),
+            1167 => {
+                prompt_sync_folder(hwnd);
             }
-            1003 => {
-                let msg = "Battesty v1.0\n\nA Windows 11 battery monitor with accurate ETA estimation.\n\nGitHub: https://github.com/ArsenijN/battesty\nLicense: MIT, see LICENSE.md";
-                let msg_wide: Vec<u16> = msg.encode_utf16().chain(std::iter::once(0)).collect();
-                let title_wide: Vec<u16> = "About Battesty".encode_utf16().chain(std::iter::once(0)).collect();
-                MessageBoxW(hwnd, PCWSTR(msg_wide.as_ptr()), PCWSTR(title_wide.as_ptr()), MB_OK | MB_ICONINFORMATION);
+            1170 => toggle_discord_presence(hwnd),
+            1175 => toggle_companion_battery(),
+            1178 => toggle_background_engine(hwnd),
+            SNAPSHOT_COMMAND => snapshot_now(hwnd),
+            id @ 1171..=1173 => change_discord_settings(hwnd, |settings| {
+                let field = discord_field(settings, (id - 1171) as usize);
+                *field = !*field;
+            }),
+            1174 => {
+                prompt_discord_client_id(hwnd);
             }
             1004 => {
                 PostQuitMessage(0);
             }
+            1005 => chart::show_chart(hwnd),
+            1006 => session_list::show_session_list(hwnd),
+            1007 => event_log::show_event_log(hwnd),
+            1009 => alert_history::show_alert_history(hwnd),
+            1008 => wear::show_wear_chart(hwnd),
+            1010 => start_benchmark(hwnd, Some(Workload::Idle)),
+            1011 => start_benchmark(hwnd, None),
+            1012 => start_benchmark(hwnd, Some(Workload::VideoLoop)),
+            1013 => stop_benchmark(hwnd),
+            1014 => {
+                if let Some(monitor) = MONITOR.get() {
+                    let text = match monitor.lock() {
+                        Ok(mon) => format!(
+                            "Rundown\n{}\nCharge speed\n{}",
+                            benchmark::format_results(&mon.benchmark_results),
+                            benchmark::format_charge_results(&mon.charge_test_results),
+                        ),
+                        Err(_) => return,
+                    };
+                    show_message(hwnd, "Benchmark Results", &text);
+                }
+            }
+            1015 => toggle_charge_test(hwnd),
+            1020 => arm_drain_test(hwnd),
+            1021 => end_drain_test(hwnd),
+            id @ 1030..=1036 => set_eta_algorithm(hwnd, ETA_ALGORITHMS[(id - 1030) as usize]),
+            1038 => toggle_nonlinear_drain(hwnd),
+            1039 => run_evaluation(hwnd, None),
+            id @ 1040..=1047 => {
+                if let Some(preset) = preset_target((id - 1040) as usize) {
+                    set_target_time(hwnd, Some(preset));
+                }
+            }
+            1048 => prompt_target_time(hwnd),
+            1049 => set_target_time(hwnd, None),
+            1050 => toggle_conservation_mode(hwnd),
+            id @ 1051..=1053 => {
+                let limit = ASUS_LIMIT_PRESETS[(id - 1051) as usize];
+                set_charge_limit(hwnd, LimitControl::AsusLimit, (limit < 100).then_some(limit));
+            }
+            id @ 1060..=1067 => toggle_tooltip_option(hwnd, (id - 1060) as usize),
+            id @ 1070..=1071 => change_icon_settings(hwnd, |settings| settings.icon_style = ICON_STYLES[(id - 1070) as usize]),
+            1072 => prompt_icon_font(hwnd),
+            id @ 1073..=1075 => change_icon_settings(hwnd, |settings| settings.icon_font_weight = ICON_FONT_WEIGHTS[(id - 1073) as usize].0),
+            1080 => set_power_plan_rules(Vec::new()),
+            id @ 1081..=1083 => set_power_plan_rules(vec![PowerPlanRule {
+                below_percentage: POWER_SAVER_PRESETS[(id - 1081) as usize],
+                plan: "Power Saver".to_string(),
+            }]),
+            1090 => change_brightness_settings(|settings| settings.brightness_rules.clear()),
+            id @ 1091..=1093 => {
+                let (below, level) = BRIGHTNESS_PRESETS[(id - 1091) as usize];
+                change_brightness_settings(|settings| settings.brightness_rules = vec![BrightnessRule { below_percentage: below, brightness: level }]);
+            }
+            1099 => change_brightness_settings(|settings| settings.restore_brightness_on_ac = !settings.restore_brightness_on_ac),
+            id @ 1200..=1299 => handle_snooze_command(id as usize),
+            1150 => {
+                if let Some(mut mon) = MONITOR.get().and_then(|m| m.lock().ok()) {
+                    mon.settings.announce_critical_alerts = !mon.settings.announce_critical_alerts;
+                    mon.settings.save();
+                }
+            }
+            id @ 1151..=1154 => {
+                if let Some(monitor) = MONITOR.get() {
+                    if let Ok(mut mon) = monitor.lock() {
+                        mon.settings.countdown_overlay_percentage = (id > 1151).then(|| COUNTDOWN_LEVELS[(id - 1152) as usize]);
+                        mon.settings.save();
+                    }
+                    update_tray_icon(hwnd, monitor);
+                }
+            }
             _ => {}
         }
     }
@@ -188,6 +1687,7 @@ pub fn cleanup_and_exit(hwnd: HWND) {
         
         if let Some(monitor) = MONITOR.get() {
             if let Ok(mut mon) = monitor.lock() {
+                mon.benchmark.take();
                 mon.save_history();
                 mon.destroy_icon();
             }
@@ -198,6 +1698,7 @@ pub fn cleanup_and_exit(hwnd: HWND) {
         nid.hWnd = hwnd;
         nid.uID = ID_TRAY_ICON;
         Shell_NotifyIconW(NIM_DELETE, &nid);
+        remove_pack_icons(hwnd, 0);
         
         PostQuitMessage(0);
     }
@@ -0,0 +1,288 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use windows::Win32::Foundation::{HWND, LPARAM, WPARAM};
+use windows::Win32::Networking::WinHttp::*;
+use windows::Win32::Security::Cryptography::{BCryptHash, BCRYPT_SHA256_ALG_HANDLE};
+use windows::Win32::UI::WindowsAndMessaging::PostMessageW;
+use windows::core::PCWSTR;
+use crate::engine;
+use crate::versions;
+use crate::WM_UPDATE;
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/ArsenijN/battesty/releases/latest";
+
+// wparam of WM_UPDATE, telling the window which step finished
+pub const UPDATE_CHECKED: usize = 0;
+pub const UPDATE_DOWNLOADED: usize = 1;
+
+#[derive(Clone)]
+pub struct Release {
+    pub version: String,
+    pub page_url: String,
+    pub asset_name: String,
+    pub asset_url: String,
+    // Lowercase hex; releases without one can't be installed
+    pub sha256: Option<String>,
+}
+
+pub enum UpdateResult {
+    Checked(Result<Option<Release>, String>),
+    Downloaded(Result<(Release, PathBuf), String>),
+}
+
+static BUSY: AtomicBool = AtomicBool::new(false);
+static RESULT: Mutex<Option<UpdateResult>> = Mutex::new(None);
+// Set once the new exe is in place; main starts it after the message loop has saved and exited
+static RESTART_PENDING: AtomicBool = AtomicBool::new(false);
+
+pub fn busy() -> bool {
+    BUSY.load(Ordering::Relaxed)
+}
+
+pub fn take_result() -> Option<UpdateResult> {
+    RESULT.lock().ok().and_then(|mut r| r.take())
+}
+
+// Both steps run on a worker thread and report back through WM_UPDATE
+pub fn spawn_check(hwnd: HWND) {
+    spawn(hwnd, UPDATE_CHECKED, || UpdateResult::Checked(check_latest()));
+}
+
+pub fn spawn_download(hwnd: HWND, release: Release) {
+    spawn(hwnd, UPDATE_DOWNLOADED, move || UpdateResult::Downloaded(download(&release).map(|path| (release, path))));
+}
+
+fn spawn(hwnd: HWND, step: usize, work: impl FnOnce() -> UpdateResult + Send + 'static) {
+    if BUSY.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    std::thread::spawn(move || {
+        let result = work();
+        if let Ok(mut slot) = RESULT.lock() {
+            *slot = Some(result);
+        }
+        BUSY.store(false, Ordering::Relaxed);
+        unsafe {
+            let _ = PostMessageW(hwnd, WM_UPDATE, WPARAM(step), LPARAM(0));
+        }
+    });
+}
+
+// None when the running version is the latest
+pub fn check_latest() -> Result<Option<Release>, String> {
+    let body = http_get(LATEST_RELEASE_URL)?;
+    let json: serde_json::Value = serde_json::from_slice(&body).map_err(|e| format!("Unexpected reply from GitHub: {}", e))?;
+
+    let tag = json["tag_name"].as_str().ok_or("The latest release has no version tag")?;
+    let version = tag.trim_start_matches(['v', 'V']).to_string();
+    if parse_version(&version) <= parse_version(&versions::app_version()) {
+        return Ok(None);
+    }
+
+    let assets = json["assets"].as_array().cloned().unwrap_or_default();
+    let asset = assets
+        .iter()
+        .find(|a| a["name"].as_str().is_some_and(|n| n.to_lowercase().ends_with(".exe")))
+        .ok_or_else(|| format!("Release {} has no .exe to download", tag))?;
+    let asset_name = asset["name"].as_str().unwrap_or_default().to_string();
+    let asset_url = asset["browser_download_url"].as_str().ok_or("The release asset has no download link")?.to_string();
+
+    // GitHub publishes a digest per asset; older releases may ship a checksum file instead
+    let sha256 = asset["digest"]
+        .as_str()
+        .and_then(|d| d.strip_prefix("sha256:"))
+        .map(str::to_lowercase)
+        .or_else(|| checksum_from_files(&assets, &asset_name));
+
+    Ok(Some(Release {
+        version,
+        page_url: json["html_url"].as_str().unwrap_or_default().to_string(),
+        asset_name,
+        asset_url,
+        sha256,
+    }))
+}
+
+fn checksum_from_files(assets: &[serde_json::Value], asset_name: &str) -> Option<String> {
+    let wanted = [format!("{}.sha256", asset_name.to_lowercase()), "sha256sums.txt".to_string(), "sha256sums".to_string()];
+    let file = assets.iter().find(|a| a["name"].as_str().is_some_and(|n| wanted.contains(&n.to_lowercase())))?;
+    let text = String::from_utf8(http_get(file["browser_download_url"].as_str()?).ok()?).ok()?;
+
+    // "<hash>" alone, or "<hash>  <file>" lines as written by sha256sum
+    text.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let hash = parts.next()?;
+        let name = parts.next().map(|n| n.trim_start_matches('*'));
+        let matches = name.is_none_or(|n| n.eq_ignore_ascii_case(asset_name));
+        (matches && hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit())).then(|| hash.to_lowercase())
+    })
+}
+
+fn parse_version(version: &str) -> Vec<u32> {
+    version.split(['.', '-', '+']).map_while(|part| part.parse().ok()).collect()
+}
+
+// Downloads next to the exe and returns the path once the hash matched
+pub fn download(release: &Release) -> Result<PathBuf, String> {
+    let expected = release.sha256.as_deref().ok_or("This release doesn't publish a SHA-256 checksum, so it can't be verified")?;
+    let body = http_get(&release.asset_url)?;
+    let actual = sha256_hex(&body)?;
+    if actual != expected {
+        return Err(format!("Checksum mismatch for {}: expected {}, got {}", release.asset_name, expected, actual));
+    }
+
+    let path = new_exe_path();
+    std::fs::write(&path, &body).map_err(|e| format!("Cannot save {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+// The running exe can be renamed but not overwritten, so it moves aside and the new one takes
+// its name. The old copy is deleted by the next start.
+pub fn install(new_exe: &Path) -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let old = old_exe_path();
+    let _ = std::fs::remove_file(&old);
+
+    std::fs::rename(&exe, &old).map_err(|e| format!("Cannot move the running exe aside: {}", e))?;
+    if let Err(e) = std::fs::rename(new_exe, &exe) {
+        let _ = std::fs::rename(&old, &exe);
+        return Err(format!("Cannot put the new exe in place: {}", e));
+    }
+    RESTART_PENDING.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+// Called by main after the message loop ended, so history is saved and the tray icon removed
+pub fn restart_if_pending() {
+    if !RESTART_PENDING.load(Ordering::Relaxed) {
+        return;
+    }
+    // A background engine still runs the old exe; the new tray starts a fresh one
+    engine::stop();
+    if let Ok(exe) = std::env::current_exe() {
+        // current_exe still names the original path, which now holds the new version
+        let _ = std::process::Command::new(exe).arg("--updated").spawn();
+    }
+}
+
+pub fn just_updated() -> bool {
+    std::env::args().any(|arg| arg == "--updated")
+}
+
+// The previous instance may still be exiting, so give it a few seconds
+pub fn remove_old_exe() {
+    let old = old_exe_path();
+    if !old.exists() {
+        return;
+    }
+    std::thread::spawn(move || {
+        for _ in 0..20 {
+            if std::fs::remove_file(&old).is_ok() || !old.exists() {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(500));
+        }
+    });
+}
+
+fn old_exe_path() -> PathBuf {
+    std::env::current_exe().unwrap().with_extension("old.exe")
+}
+
+fn new_exe_path() -> PathBuf {
+    std::env::current_exe().unwrap().with_extension("new.exe")
+}
+
+fn sha256_hex(data: &[u8]) -> Result<String, String> {
+    let mut hash = [0u8; 32];
+    let status = unsafe { BCryptHash(BCRYPT_SHA256_ALG_HANDLE, None, data, &mut hash) };
+    if status.is_err() {
+        return Err(format!("Cannot hash the download (NTSTATUS {:#x})", status.0));
+    }
+    Ok(hash.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+struct Internet(*mut std::ffi::c_void);
+
+impl Drop for Internet {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe {
+                let _ = WinHttpCloseHandle(self.0);
+            }
+        }
+    }
+}
+
+// HTTPS GET through WinHTTP, which follows GitHub's redirects to its download hosts
+fn http_get(url: &str) -> Result<Vec<u8>, String> {
+    let rest = url.strip_prefix("https://").ok_or_else(|| format!("Not an https URL: {}", url))?;
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let path = if path.is_empty() { "/" } else { path };
+
+    let wide = |text: &str| text.encode_utf16().chain(std::iter::once(0)).collect::<Vec<u16>>();
+    let agent = wide(&format!("Battesty/{}", versions::app_version()));
+    let host_wide = wide(host);
+    let path_wide = wide(path);
+    let verb = wide("GET");
+    let failed = |what: &str| format!("{} failed: {}", what, windows::core::Error::from_win32());
+
+    unsafe {
+        let session = Internet(WinHttpOpen(PCWSTR(agent.as_ptr()), WINHTTP_ACCESS_TYPE_AUTOMATIC_PROXY, PCWSTR::null(), PCWSTR::null(), 0));
+        if session.0.is_null() {
+            return Err(failed("Opening WinHTTP"));
+        }
+        let connection = Internet(WinHttpConnect(session.0, PCWSTR(host_wide.as_ptr()), INTERNET_DEFAULT_HTTPS_PORT, 0));
+        if connection.0.is_null() {
+            return Err(failed(&format!("Connecting to {}", host)));
+        }
+        let request = Internet(WinHttpOpenRequest(
+            connection.0,
+            PCWSTR(verb.as_ptr()),
+            PCWSTR(path_wide.as_ptr()),
+            PCWSTR::null(),
+            PCWSTR::null(),
+            std::ptr::null(),
+            WINHTTP_FLAG_SECURE,
+        ));
+        if request.0.is_null() {
+            return Err(failed("Creating the request"));
+        }
+
+        WinHttpSendRequest(request.0, None, None, 0, 0, 0).map_err(|e| format!("Request to {} failed: {}", host, e))?;
+        WinHttpReceiveResponse(request.0, std::ptr::null_mut()).map_err(|e| format!("No response from {}: {}", host, e))?;
+
+        let mut status: u32 = 0;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        let mut index = 0;
+        WinHttpQueryHeaders(
+            request.0,
+            WINHTTP_QUERY_STATUS_CODE | WINHTTP_QUERY_FLAG_NUMBER,
+            PCWSTR::null(),
+            Some(&mut status as *mut u32 as *mut _),
+            &mut size,
+            &mut index,
+        )
+        .map_err(|e| format!("Cannot read the response status: {}", e))?;
+        if status != 200 {
+            return Err(format!("{} answered HTTP {}", host, status));
+        }
+
+        let mut body = Vec::new();
+        loop {
+            let mut available = 0;
+            WinHttpQueryDataAvailable(request.0, &mut available).map_err(|e| format!("Download interrupted: {}", e))?;
+            if available == 0 {
+                break;
+            }
+            let start = body.len();
+            body.resize(start + available as usize, 0);
+            let mut read = 0;
+            WinHttpReadData(request.0, body[start..].as_mut_ptr() as *mut _, available, &mut read)
+                .map_err(|e| format!("Download interrupted: {}", e))?;
+            body.truncate(start + read as usize);
+        }
+        Ok(body)
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+use chrono::{DateTime, Datelike, Duration, DurationRound, Local, Timelike, Weekday};
+use crate::battery::{BatteryMeasurement, MAX_SAMPLE_GAP_MINUTES};
+
+// How much history the model is built from
+const MODEL_DAYS: i64 = 28;
+// An hour of the day needs this much time on battery before its own drain is used
+const MIN_HOUR_SECONDS: f64 = 30.0 * 60.0;
+// Below this much battery time in total there's no model at all
+const MIN_TOTAL_SECONDS: f64 = 3.0 * 3600.0;
+// Walking the clock forward stops here; a machine that lasts longer than this isn't draining
+const MAX_DAYS_AHEAD: i64 = 7;
+
+// Typical drain in %/h for each hour of the day, weekdays and weekends apart: what the machine
+// usually goes through from now on, so a compile or a video call doesn't rewrite the forecast
+pub struct UsageModel {
+    pub weekday: [Option<f64>; 24],
+    pub weekend: [Option<f64>; 24],
+    pub overall: f64,
+}
+
+fn is_weekend(time: DateTime<Local>) -> bool {
+    matches!(time.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+pub fn build(measurements: &VecDeque<BatteryMeasurement>, now: DateTime<Local>) -> Option<UsageModel> {
+    let cutoff = now - Duration::days(MODEL_DAYS);
+    // Drained percent and seconds, [weekend][hour]
+    let mut drained = [[0.0; 24]; 2];
+    let mut seconds = [[0.0; 24]; 2];
+    for (current, next) in measurements.iter().zip(measurements.iter().skip(1)) {
+        let gap = next.timestamp - current.timestamp;
+        if current.timestamp < cutoff || current.is_charging || next.is_charging
+            || gap <= Duration::zero() || gap > Duration::minutes(MAX_SAMPLE_GAP_MINUTES)
+        {
+            continue;
+        }
+        let (day, hour) = (is_weekend(current.timestamp) as usize, current.timestamp.hour() as usize);
+        drained[day][hour] += current.percentage as f64 - next.percentage as f64;
+        seconds[day][hour] += gap.num_seconds() as f64;
+    }
+
+    let total_drained: f64 = drained.iter().flatten().sum();
+    let total_seconds: f64 = seconds.iter().flatten().sum();
+    if total_seconds < MIN_TOTAL_SECONDS || total_drained <= 0.0 {
+        return None;
+    }
+    let table = |day: usize| {
+        let mut rates = [None; 24];
+        for hour in 0..24 {
+            if seconds[day][hour] >= MIN_HOUR_SECONDS {
+                rates[hour] = Some((drained[day][hour] / seconds[day][hour] * 3600.0).max(0.0));
+            }
+        }
+        rates
+    };
+    Some(UsageModel { weekday: table(0), weekend: table(1), overall: total_drained / total_seconds * 3600.0 })
+}
+
+impl UsageModel {
+    // The same hour on the other kind of day stands in before the overall average does
+    fn drain_at(&self, time: DateTime<Local>) -> f64 {
+        let hour = time.hour() as usize;
+        let (own, other) = if is_weekend(time) { (&self.weekend, &self.weekday) } else { (&self.weekday, &self.weekend) };
+        own[hour].or(other[hour]).unwrap_or(self.overall)
+    }
+
+    // Minutes until empty, walking the clock forward an hour at a time at each hour's typical drain
+    pub fn eta_minutes(&self, percentage: u8, now: DateTime<Local>) -> Option<i32> {
+        let mut level = percentage as f64;
+        let mut time = now;
+        while time - now < Duration::days(MAX_DAYS_AHEAD) {
+            let hour_end = time.duration_trunc(Duration::hours(1)).ok()? + Duration::hours(1);
+            let hours = (hour_end - time).num_seconds() as f64 / 3600.0;
+            let rate = self.drain_at(time);
+            if rate * hours >= level {
+                let minutes = (time - now).num_seconds() as f64 / 60.0 + level / rate * 60.0;
+                return Some(minutes as i32);
+            }
+            level -= rate * hours;
+            time = hour_end;
+        }
+        None
+    }
+}
@@ -0,0 +1,293 @@
+use windows::Win32::Foundation::{CloseHandle, HANDLE};
+use windows::Win32::Storage::FileSystem::*;
+use windows::Win32::System::IO::DeviceIoControl;
+use windows::Win32::System::Registry::*;
+use windows::core::PCWSTR;
+
+use crate::wmi::Wmi;
+
+// Lenovo Energy Management driver, present on IdeaPad/Legion/Yoga machines
+const LENOVO_ENERGY_DEVICE: &str = r"\\.\EnergyDrv";
+const IOCTL_ENERGY_BATTERY_CHARGE_MODE: u32 = 0x831020F8;
+const LENOVO_CONSERVATION_ON: u32 = 0x03;
+const LENOVO_CONSERVATION_OFF: u32 = 0x05;
+
+// ASUS ATK ACPI driver, the same interface the Fn-key service uses
+const ASUS_ATK_DEVICE: &str = r"\\.\ATKACPI";
+const IOCTL_ATK_ACPI_CALL: u32 = 0x0022240C;
+const ATK_METHOD_DEVS: u32 = 0x53564544;
+const ATK_DEVICE_BATTERY_LIMIT: u32 = 0x00120057;
+
+const ASUS_OPTIMIZATION_KEY: &str = r"SOFTWARE\ASUS\ASUS System Control Interface\AsusOptimization\ASUS Keyboard Hotkeys";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Vendor {
+    Lenovo,
+    Asus,
+    Dell,
+    Hp,
+    Other,
+}
+
+impl Vendor {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Vendor::Lenovo => "Lenovo",
+            Vendor::Asus => "ASUS",
+            Vendor::Dell => "Dell",
+            Vendor::Hp => "HP",
+            Vendor::Other => "Unknown vendor",
+        }
+    }
+}
+
+// Which kind of limit battesty can change itself on this machine
+#[derive(Clone, Copy, PartialEq)]
+pub enum LimitControl {
+    LenovoConservation,
+    AsusLimit,
+}
+
+#[derive(Clone)]
+pub struct ChargeLimit {
+    pub vendor: Vendor,
+    // Level the firmware stops charging at, when the interface reports one
+    pub limit: Option<u8>,
+    pub mode: String,
+}
+
+impl ChargeLimit {
+    pub fn summary(&self) -> String {
+        match self.limit {
+            Some(limit) => format!("Charge limit: {}% ({} {})", limit, self.vendor.label(), self.mode),
+            None => format!("Charge limit: {} ({})", self.mode, self.vendor.label()),
+        }
+    }
+}
+
+pub fn detect_vendor() -> Vendor {
+    let manufacturer = Wmi::connect(r"root\cimv2")
+        .and_then(|wmi| wmi.query("SELECT Manufacturer FROM Win32_ComputerSystem", &["Manufacturer"]))
+        .ok()
+        .and_then(|rows| rows.into_iter().next())
+        .and_then(|row| row.into_iter().next().flatten())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    if manufacturer.contains("lenovo") {
+        Vendor::Lenovo
+    } else if manufacturer.contains("asus") {
+        Vendor::Asus
+    } else if manufacturer.contains("dell") {
+        Vendor::Dell
+    } else if manufacturer.contains("hp") || manufacturer.contains("hewlett") {
+        Vendor::Hp
+    } else {
+        Vendor::Other
+    }
+}
+
+// Returns None when no limit is configured or the vendor interface isn't available
+pub fn read_charge_limit(vendor: Vendor) -> Option<ChargeLimit> {
+    match vendor {
+        Vendor::Lenovo => read_lenovo(),
+        Vendor::Asus => read_asus(),
+        Vendor::Dell => read_dell(),
+        Vendor::Hp => read_hp(),
+        Vendor::Other => None,
+    }
+}
+
+pub fn limit_control(vendor: Vendor) -> Option<LimitControl> {
+    match vendor {
+        Vendor::Lenovo if lenovo_charge_mode().is_some() => Some(LimitControl::LenovoConservation),
+        Vendor::Asus if device_present(ASUS_ATK_DEVICE) => Some(LimitControl::AsusLimit),
+        _ => None,
+    }
+}
+
+pub fn set_lenovo_conservation(enabled: bool) -> Result<(), String> {
+    let command = if enabled { LENOVO_CONSERVATION_ON } else { LENOVO_CONSERVATION_OFF };
+    unsafe {
+        let device = open_device(LENOVO_ENERGY_DEVICE).ok_or("Lenovo Energy Management driver not found")?;
+        let mut returned = 0;
+        let result = DeviceIoControl(
+            device,
+            IOCTL_ENERGY_BATTERY_CHARGE_MODE,
+            Some(&command as *const u32 as *const _),
+            4,
+            None,
+            0,
+            Some(&mut returned),
+            None,
+        );
+        let _ = CloseHandle(device);
+        result.map_err(|e| format!("Driver rejected the request: {}", e))
+    }
+}
+
+pub fn set_asus_limit(limit: u8) -> Result<(), String> {
+    if !(40..=100).contains(&limit) {
+        return Err(format!("{}% is outside the supported 40–100% range", limit));
+    }
+
+    // Method id, argument size, then the DEVS arguments (device id, value)
+    let request: [u32; 4] = [ATK_METHOD_DEVS, 8, ATK_DEVICE_BATTERY_LIMIT, limit as u32];
+    let mut reply = [0u32; 4];
+    unsafe {
+        let device = open_device(ASUS_ATK_DEVICE).ok_or("ASUS ATK ACPI driver not found")?;
+        let mut returned = 0;
+        let result = DeviceIoControl(
+            device,
+            IOCTL_ATK_ACPI_CALL,
+            Some(request.as_ptr() as *const _),
+            std::mem::size_of_val(&request) as u32,
+            Some(reply.as_mut_ptr() as *mut _),
+            std::mem::size_of_val(&reply) as u32,
+            Some(&mut returned),
+            None,
+        );
+        let _ = CloseHandle(device);
+        result.map_err(|e| format!("Driver rejected the request: {}", e))?;
+    }
+
+    if reply[0] == 1 {
+        Ok(())
+    } else {
+        Err("The firmware does not support a charge limit on this model".to_string())
+    }
+}
+
+fn device_present(path: &str) -> bool {
+    unsafe {
+        match open_device(path) {
+            Some(device) => {
+                let _ = CloseHandle(device);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn read_lenovo() -> Option<ChargeLimit> {
+    let state = lenovo_charge_mode()?;
+    // Bit 29 of the byte-swapped reply is conservation mode; the firmware picks the level
+    let conservation = state.swap_bytes() & (1 << (31 - 29)) != 0;
+    conservation.then(|| ChargeLimit {
+        vendor: Vendor::Lenovo,
+        limit: None,
+        mode: "Conservation mode".to_string(),
+    })
+}
+
+fn lenovo_charge_mode() -> Option<u32> {
+    unsafe {
+        let device = open_device(LENOVO_ENERGY_DEVICE)?;
+        let query: u32 = 0xFF;
+        let mut reply: u32 = 0;
+        let mut returned = 0;
+        let result = DeviceIoControl(
+            device,
+            IOCTL_ENERGY_BATTERY_CHARGE_MODE,
+            Some(&query as *const u32 as *const _),
+            4,
+            Some(&mut reply as *mut u32 as *mut _),
+            4,
+            Some(&mut returned),
+            None,
+        );
+        let _ = CloseHandle(device);
+        result.ok().map(|_| reply)
+    }
+}
+
+unsafe fn open_device(path: &str) -> Option<HANDLE> {
+    let path_wide: Vec<u16> = path.encode_utf16().chain(std::iter::once(0)).collect();
+    CreateFileW(
+        PCWSTR(path_wide.as_ptr()),
+        (FILE_GENERIC_READ | FILE_GENERIC_WRITE).0,
+        FILE_SHARE_READ | FILE_SHARE_WRITE,
+        None,
+        OPEN_EXISTING,
+        FILE_ATTRIBUTE_NORMAL,
+        None,
+    )
+    .ok()
+}
+
+// MyASUS keeps the limit it applied in the registry
+fn read_asus() -> Option<ChargeLimit> {
+    let key: Vec<u16> = ASUS_OPTIMIZATION_KEY.encode_utf16().chain(std::iter::once(0)).collect();
+    let value: Vec<u16> = "ChargingRate\0".encode_utf16().collect();
+    let mut limit: u32 = 0;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            PCWSTR(key.as_ptr()),
+            PCWSTR(value.as_ptr()),
+            RRF_RT_REG_DWORD,
+            None,
+            Some(&mut limit as *mut u32 as *mut _),
+            Some(&mut size),
+        )
+        .ok()?;
+    }
+
+    (limit > 0 && limit < 100).then(|| ChargeLimit {
+        vendor: Vendor::Asus,
+        limit: Some(limit as u8),
+        mode: "Battery Health Charging".to_string(),
+    })
+}
+
+// Dell Command | Monitor BIOS attributes
+fn read_dell() -> Option<ChargeLimit> {
+    let wmi = Wmi::connect(r"root\dcim\sysman\biosattributes").ok()?;
+    let attribute = |class: &str, name: &str| {
+        wmi.query(&format!("SELECT CurrentValue FROM {} WHERE AttributeName='{}'", class, name), &["CurrentValue"])
+            .ok()
+            .and_then(|rows| rows.into_iter().next())
+            .and_then(|row| row.into_iter().next().flatten())
+    };
+
+    let mode = attribute("EnumerationAttribute", "PrimaryBattChargeCfg")?;
+    let limit = match mode.as_str() {
+        "Custom" => attribute("IntegerAttribute", "CustomChargeStop").and_then(|v| v.parse().ok()),
+        // "Primarily AC use" stops around 80% on current models
+        "PrimAcUse" => Some(80),
+        "Adaptive" => None,
+        _ => return None,
+    };
+
+    Some(ChargeLimit {
+        vendor: Vendor::Dell,
+        limit,
+        mode: match mode.as_str() {
+            "PrimAcUse" => "Primarily AC use".to_string(),
+            _ => mode,
+        },
+    })
+}
+
+// HP BIOS "Battery Health Manager"
+fn read_hp() -> Option<ChargeLimit> {
+    let wmi = Wmi::connect(r"root\HP\InstrumentedBIOS").ok()?;
+    let value = wmi
+        .query("SELECT CurrentValue FROM HP_BIOSEnumeration WHERE Name='Battery Health Manager'", &["CurrentValue"])
+        .ok()?
+        .into_iter()
+        .next()?
+        .into_iter()
+        .next()
+        .flatten()?;
+
+    if value.contains("Maximize") {
+        Some(ChargeLimit { vendor: Vendor::Hp, limit: Some(80), mode: "Maximize battery health".to_string() })
+    } else if value.contains("Let HP") {
+        Some(ChargeLimit { vendor: Vendor::Hp, limit: None, mode: "Managed by HP".to_string() })
+    } else {
+        None
+    }
+}
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use windows::Win32::Storage::FileSystem::{GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW, VS_FIXEDFILEINFO};
+use windows::Win32::System::Registry::*;
+use windows::core::PCWSTR;
+use crate::wmi::Wmi;
+
+const CURRENT_VERSION_KEY: &str = r"SOFTWARE\Microsoft\Windows NT\CurrentVersion";
+
+// Software that changes how the battery is read or drained, recorded to explain regressions
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SystemVersions {
+    pub os_build: Option<String>,
+    pub battery_driver: Option<String>,
+    pub acpi_driver: Option<String>,
+}
+
+impl SystemVersions {
+    // One line per component that differs from `previous`
+    pub fn changes_from(&self, previous: &SystemVersions) -> Vec<String> {
+        let components = [
+            ("Windows", &previous.os_build, &self.os_build),
+            ("Battery driver", &previous.battery_driver, &self.battery_driver),
+            ("ACPI driver", &previous.acpi_driver, &self.acpi_driver),
+        ];
+        components
+            .iter()
+            .filter(|(_, old, new)| new.is_some() && old != new)
+            .map(|(name, old, new)| format!(
+                "{} {} → {}",
+                name,
+                old.as_deref().unwrap_or("unknown"),
+                new.as_deref().unwrap_or("unknown"),
+            ))
+            .collect()
+    }
+}
+
+// Battesty's own version from the exe's VERSIONINFO, e.g. "1.0.0"; the compiled-in
+// Cargo.toml version when the resource wasn't linked
+pub fn app_version() -> String {
+    read_file_version().unwrap_or_else(|| env!("CARGO_PKG_VERSION").to_string())
+}
+
+fn read_file_version() -> Option<String> {
+    let exe = std::env::current_exe().ok()?;
+    let exe_wide: Vec<u16> = exe.as_os_str().to_string_lossy().encode_utf16().chain(std::iter::once(0)).collect();
+    let root: Vec<u16> = "\\\0".encode_utf16().collect();
+    unsafe {
+        let size = GetFileVersionInfoSizeW(PCWSTR(exe_wide.as_ptr()), None);
+        if size == 0 {
+            return None;
+        }
+        let mut data = vec![0u8; size as usize];
+        GetFileVersionInfoW(PCWSTR(exe_wide.as_ptr()), 0, size, data.as_mut_ptr() as *mut _).ok()?;
+
+        let mut info: *mut std::ffi::c_void = std::ptr::null_mut();
+        let mut len = 0;
+        if !VerQueryValueW(data.as_ptr() as *const _, PCWSTR(root.as_ptr()), &mut info, &mut len).as_bool() || info.is_null() {
+            return None;
+        }
+        let info = &*(info as *const VS_FIXEDFILEINFO);
+        Some(format!(
+            "{}.{}.{}",
+            info.dwFileVersionMS >> 16,
+            info.dwFileVersionMS & 0xFFFF,
+            info.dwFileVersionLS >> 16,
+        ))
+    }
+}
+
+pub fn read_versions() -> SystemVersions {
+    SystemVersions {
+        os_build: read_os_build(),
+        // Control-method battery (PNP0C0A) and the ACPI system driver (PNP0C08)
+        battery_driver: read_driver_version(r"ACPI\\PNP0C0A%"),
+        acpi_driver: read_driver_version(r"ACPI\\PNP0C08%"),
+    }
+}
+
+// e.g. "24H2 (26100.2314)"
+fn read_os_build() -> Option<String> {
+    let build = read_string("CurrentBuild")?;
+    let revision = read_dword("UBR").map(|ubr| format!("{}.{}", build, ubr)).unwrap_or(build);
+    Some(match read_string("DisplayVersion") {
+        Some(display) => format!("{} ({})", display, revision),
+        None => revision,
+    })
+}
+
+fn read_driver_version(device_id: &str) -> Option<String> {
+    let wmi = Wmi::connect(r"root\cimv2").ok()?;
+    wmi.query(
+        &format!("SELECT DriverVersion FROM Win32_PnPSignedDriver WHERE DeviceID LIKE '{}'", device_id),
+        &["DriverVersion"],
+    )
+    .ok()?
+    .into_iter()
+    .next()?
+    .into_iter()
+    .next()
+    .flatten()
+}
+
+fn read_string(name: &str) -> Option<String> {
+    let key: Vec<u16> = CURRENT_VERSION_KEY.encode_utf16().chain(std::iter::once(0)).collect();
+    let value: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+    let mut buffer = [0u16; 128];
+    let mut size = std::mem::size_of_val(&buffer) as u32;
+    unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            PCWSTR(key.as_ptr()),
+            PCWSTR(value.as_ptr()),
+            RRF_RT_REG_SZ,
+            None,
+            Some(buffer.as_mut_ptr() as *mut _),
+            Some(&mut size),
+        )
+        .ok()?;
+    }
+    let len = buffer.iter().position(|c| *c == 0).unwrap_or(buffer.len());
+    Some(String::from_utf16_lossy(&buffer[..len])).filter(|s| !s.is_empty())
+}
+
+fn read_dword(name: &str) -> Option<u32> {
+    let key: Vec<u16> = CURRENT_VERSION_KEY.encode_utf16().chain(std::iter::once(0)).collect();
+    let value: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+    let mut data: u32 = 0;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            PCWSTR(key.as_ptr()),
+            PCWSTR(value.as_ptr()),
+            RRF_RT_REG_DWORD,
+            None,
+            Some(&mut data as *mut u32 as *mut _),
+            Some(&mut size),
+        )
+        .ok()?;
+    }
+    Some(data)
+}
+
+pub fn load_versions() -> Option<SystemVersions> {
+    std::fs::read_to_string(versions_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+}
+
+pub fn save_versions(versions: &SystemVersions) {
+    if let Ok(json) = serde_json::to_string_pretty(versions) {
+        let _ = std::fs::write(versions_path(), json);
+    }
+}
+
+fn versions_path() -> std::path::PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push("battesty_versions.json");
+    path
+}
@@ -0,0 +1,189 @@
+use std::sync::Once;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use chrono::{Datelike, NaiveDate};
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Gdi::*;
+use windows::Win32::UI::WindowsAndMessaging::*;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::core::PCWSTR;
+
+use crate::rollup::DailyRollup;
+use crate::MONITOR;
+use crate::icon::app_icon;
+
+static WEAR_HWND: AtomicIsize = AtomicIsize::new(0);
+static REGISTER: Once = Once::new();
+
+const MONTHS_SHOWN: usize = 12;
+
+pub struct MonthlyWear {
+    pub month: NaiveDate,
+    // Change in health (full-charge / design capacity) in percentage points; negative is wear
+    pub change: f64,
+}
+
+impl MonthlyWear {
+    pub fn summary(&self) -> String {
+        format!("{:+.1}% in {}", self.change, self.month.format("%B %Y"))
+    }
+}
+
+// Health at the first and last snapshot of each calendar month
+pub fn monthly_wear(days: &[DailyRollup]) -> Vec<MonthlyWear> {
+    let mut months: Vec<(NaiveDate, f64, f64)> = Vec::new();
+    for day in days {
+        let Some(capacity) = day.capacity else { continue };
+        let health = capacity.health();
+        let month = day.date.with_day(1).unwrap_or(day.date);
+        match months.last_mut() {
+            Some((current, _, last)) if *current == month => *last = health,
+            _ => months.push((month, health, health)),
+        }
+    }
+
+    // Chain months together so wear across a month boundary isn't lost
+    let mut result = Vec::new();
+    let mut previous_end: Option<f64> = None;
+    for (month, first, last) in months {
+        let start = previous_end.unwrap_or(first);
+        result.push(MonthlyWear { month, change: last - start });
+        previous_end = Some(last);
+    }
+    result
+}
+
+pub fn summary(days: &[DailyRollup]) -> String {
+    let wear = monthly_wear(days);
+    if wear.is_empty() {
+        return "Monthly Wear\nNo capacity snapshots yet.\n".to_string();
+    }
+    let mut text = "Monthly Wear\n".to_string();
+    for month in wear.iter().rev().take(6) {
+        text.push_str(&format!("• {}\n", month.summary()));
+    }
+    text
+}
+
+pub fn show_wear_chart(owner: HWND) {
+    unsafe {
+        let existing = HWND(WEAR_HWND.load(Ordering::Relaxed));
+        if existing.0 != 0 && IsWindow(existing).as_bool() {
+            ShowWindow(existing, SW_SHOWNORMAL);
+            SetForegroundWindow(existing);
+            return;
+        }
+
+        let instance: HINSTANCE = GetModuleHandleW(PCWSTR::null()).unwrap().into();
+        let class_name = "BattestyWear\0".encode_utf16().collect::<Vec<u16>>();
+
+        REGISTER.call_once(|| {
+            let wc = WNDCLASSW {
+                lpfnWndProc: Some(wear_proc),
+                hInstance: instance,
+                lpszClassName: PCWSTR(class_name.as_ptr()),
+                hCursor: LoadCursorW(None, IDC_ARROW).unwrap_or_default(),
+                hIcon: app_icon(),
+                ..std::mem::zeroed()
+            };
+            RegisterClassW(&wc);
+        });
+
+        let title = "Battesty - Monthly Wear\0".encode_utf16().collect::<Vec<u16>>();
+        let hwnd = CreateWindowExW(
+            WINDOW_EX_STYLE(0),
+            PCWSTR(class_name.as_ptr()),
+            PCWSTR(title.as_ptr()),
+            WS_OVERLAPPEDWINDOW,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            560,
+            320,
+            owner,
+            None,
+            instance,
+            None,
+        );
+
+        WEAR_HWND.store(hwnd.0, Ordering::Relaxed);
+        ShowWindow(hwnd, SW_SHOWNORMAL);
+        SetForegroundWindow(hwnd);
+    }
+}
+
+unsafe extern "system" fn wear_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_PAINT => {
+            let mut ps: PAINTSTRUCT = std::mem::zeroed();
+            let hdc = BeginPaint(hwnd, &mut ps);
+            paint(hwnd, hdc);
+            EndPaint(hwnd, &ps);
+            LRESULT(0)
+        }
+        WM_SIZE => {
+            InvalidateRect(hwnd, None, TRUE);
+            LRESULT(0)
+        }
+        WM_DESTROY => {
+            WEAR_HWND.store(0, Ordering::Relaxed);
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+unsafe fn draw_text(hdc: HDC, x: i32, y: i32, text: &str) {
+    let text_wide: Vec<u16> = text.encode_utf16().collect();
+    TextOutW(hdc, x, y, &text_wide);
+}
+
+// One bar per month around a zero line; wear goes down in red, recovery up in green
+unsafe fn paint(hwnd: HWND, hdc: HDC) {
+    let mut client = RECT::default();
+    let _ = GetClientRect(hwnd, &mut client);
+    let background = CreateSolidBrush(COLORREF(0x00FFFFFF));
+    FillRect(hdc, &client, background);
+    DeleteObject(background);
+
+    let old_font = SelectObject(hdc, GetStockObject(DEFAULT_GUI_FONT));
+    SetBkMode(hdc, TRANSPARENT);
+    SetTextColor(hdc, COLORREF(0x00606060));
+
+    let wear = match MONITOR.get().and_then(|m| m.lock().ok()) {
+        Some(mon) => monthly_wear(&mon.daily),
+        None => Vec::new(),
+    };
+    let months = &wear[wear.len().saturating_sub(MONTHS_SHOWN)..];
+    if months.is_empty() {
+        draw_text(hdc, 16, 16, "No capacity snapshots yet; one is stored per day.");
+        SelectObject(hdc, old_font);
+        return;
+    }
+
+    let area = RECT { left: 16, top: 24, right: client.right - 16, bottom: client.bottom - 32 };
+    let zero = (area.top + area.bottom) / 2;
+    let scale = months.iter().map(|m| m.change.abs()).fold(0.5, f64::max);
+    let slot = (area.right - area.left) / months.len() as i32;
+
+    let pen_axis = CreatePen(PS_SOLID, 1, COLORREF(0x00C0C0C0));
+    let old_pen = SelectObject(hdc, pen_axis);
+    MoveToEx(hdc, area.left, zero, None);
+    LineTo(hdc, area.right, zero);
+    SelectObject(hdc, old_pen);
+    DeleteObject(pen_axis);
+
+    for (i, month) in months.iter().enumerate() {
+        let left = area.left + slot * i as i32 + slot / 5;
+        let right = area.left + slot * (i as i32 + 1) - slot / 5;
+        let height = ((zero - area.top) as f64 * month.change.abs() / scale).round() as i32;
+        let (top, bottom) = if month.change < 0.0 { (zero, zero + height) } else { (zero - height, zero) };
+        let brush = CreateSolidBrush(COLORREF(if month.change < 0.0 { 0x004040D0 } else { 0x0040A040 }));
+        FillRect(hdc, &RECT { left, top, right, bottom: bottom.max(top + 1) }, brush);
+        DeleteObject(brush);
+
+        draw_text(hdc, left, area.bottom + 8, &month.month.format("%b %y").to_string());
+        let label_y = if month.change < 0.0 { bottom + 2 } else { top - 16 };
+        draw_text(hdc, left, label_y, &format!("{:+.1}%", month.change));
+    }
+
+    SelectObject(hdc, old_font);
+}
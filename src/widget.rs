@@ -0,0 +1,319 @@
+use std::sync::Once;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use std::cell::RefCell;
+use chrono::{Duration, Local};
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Direct2D::Common::*;
+use windows::Win32::Graphics::Direct2D::*;
+use windows::Win32::Graphics::DirectWrite::*;
+use windows::Win32::Graphics::Dxgi::Common::DXGI_FORMAT_UNKNOWN;
+use windows::Win32::Graphics::Gdi::{BeginPaint, EndPaint, InvalidateRect, ValidateRect, PAINTSTRUCT};
+use windows::Win32::UI::HiDpi::{GetDpiForWindow, SetThreadDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2};
+use windows::Win32::UI::Input::KeyboardAndMouse::*;
+use windows::Win32::UI::WindowsAndMessaging::*;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::core::{w, PCWSTR};
+
+use crate::battery::BatteryMonitor;
+use crate::rollup;
+use crate::settings::AppSettings;
+use crate::MONITOR;
+
+static WIDGET_HWND: AtomicIsize = AtomicIsize::new(0);
+static REGISTER: Once = Once::new();
+
+pub const HOTKEY_WIDGET: i32 = 1;
+const TIMER_REFRESH: usize = 1;
+
+// Size in DIPs; the window is per-monitor DPI aware so Direct2D draws at native resolution
+const WIDTH: f32 = 320.0;
+const HEIGHT: f32 = 230.0;
+const MARGIN: f32 = 12.0;
+
+struct Summary {
+    percentage: Option<u8>,
+    status: String,
+    today: Option<String>,
+    draw: Option<String>,
+    health: Option<String>,
+    // (hours ago, percentage) over the last day
+    sparkline: Vec<(f32, u8)>,
+}
+
+struct Renderer {
+    target: ID2D1HwndRenderTarget,
+    large: IDWriteTextFormat,
+    normal: IDWriteTextFormat,
+    small: IDWriteTextFormat,
+}
+
+thread_local! {
+    // Recreated when the device is lost (EndDraw reports D2DERR_RECREATE_TARGET)
+    static RENDERER: RefCell<Option<Renderer>> = const { RefCell::new(None) };
+}
+
+pub fn toggle(owner: HWND) {
+    unsafe {
+        let existing = HWND(WIDGET_HWND.load(Ordering::Relaxed));
+        if existing.0 != 0 && IsWindow(existing).as_bool() {
+            let _ = DestroyWindow(existing);
+        } else {
+            create(owner);
+        }
+    }
+}
+
+// "Ctrl+Alt+B" style; returns false when the text doesn't parse or another app owns the combination
+pub fn register_hotkey(hwnd: HWND, settings: &AppSettings) -> bool {
+    unsafe {
+        let _ = UnregisterHotKey(hwnd, HOTKEY_WIDGET);
+        let Some((modifiers, key)) = settings.widget_hotkey.as_deref().and_then(parse_hotkey) else {
+            return false;
+        };
+        RegisterHotKey(hwnd, HOTKEY_WIDGET, modifiers | MOD_NOREPEAT, key).is_ok()
+    }
+}
+
+fn parse_hotkey(text: &str) -> Option<(HOT_KEY_MODIFIERS, u32)> {
+    let mut modifiers = HOT_KEY_MODIFIERS(0);
+    let mut key = None;
+    for part in text.split('+').map(|p| p.trim().to_ascii_uppercase()) {
+        match part.as_str() {
+            "CTRL" | "CONTROL" => modifiers |= MOD_CONTROL,
+            "ALT" => modifiers |= MOD_ALT,
+            "SHIFT" => modifiers |= MOD_SHIFT,
+            "WIN" => modifiers |= MOD_WIN,
+            single if single.len() == 1 && single.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                key = Some(single.as_bytes()[0] as u32);
+            }
+            function => {
+                let n: u32 = function.strip_prefix('F')?.parse().ok().filter(|n| (1..=24).contains(n))?;
+                key = Some(VK_F1.0 as u32 + n - 1);
+            }
+        }
+    }
+    // A bare key would swallow normal typing
+    (modifiers.0 != 0).then_some((modifiers, key?))
+}
+
+unsafe fn create(owner: HWND) {
+    let instance: HINSTANCE = GetModuleHandleW(PCWSTR::null()).unwrap().into();
+    let class_name = "BattestyWidget\0".encode_utf16().collect::<Vec<u16>>();
+
+    REGISTER.call_once(|| {
+        let wc = WNDCLASSW {
+            lpfnWndProc: Some(widget_proc),
+            hInstance: instance,
+            lpszClassName: PCWSTR(class_name.as_ptr()),
+            hCursor: LoadCursorW(None, IDC_ARROW).unwrap_or_default(),
+            ..std::mem::zeroed()
+        };
+        RegisterClassW(&wc);
+    });
+
+    // Only this window is per-monitor aware; the GDI windows keep being scaled by the system
+    let previous = SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+    let hwnd = CreateWindowExW(
+        WS_EX_LAYERED | WS_EX_TOPMOST | WS_EX_TOOLWINDOW,
+        PCWSTR(class_name.as_ptr()),
+        w!("Battesty"),
+        WS_POPUP,
+        0,
+        0,
+        0,
+        0,
+        owner,
+        None,
+        instance,
+        None,
+    );
+    SetThreadDpiAwarenessContext(previous);
+    WIDGET_HWND.store(hwnd.0, Ordering::Relaxed);
+
+    // Bottom-right, above the tray, where the Windows flyouts open
+    let scale = GetDpiForWindow(hwnd) as f32 / 96.0;
+    let mut work_area = RECT::default();
+    let _ = SystemParametersInfoW(SPI_GETWORKAREA, 0, Some(&mut work_area as *mut RECT as *mut _), SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0));
+    let (width, height, margin) = ((WIDTH * scale) as i32, (HEIGHT * scale) as i32, (MARGIN * scale) as i32);
+    let _ = SetWindowPos(hwnd, HWND_TOPMOST, work_area.right - width - margin, work_area.bottom - height - margin, width, height, SWP_NOACTIVATE);
+
+    let _ = SetLayeredWindowAttributes(hwnd, COLORREF(0), 245, LWA_ALPHA);
+    SetTimer(hwnd, TIMER_REFRESH, 30000, None);
+    ShowWindow(hwnd, SW_SHOW);
+    SetForegroundWindow(hwnd);
+}
+
+unsafe extern "system" fn widget_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_PAINT => {
+            let mut ps: PAINTSTRUCT = std::mem::zeroed();
+            BeginPaint(hwnd, &mut ps);
+            render(hwnd);
+            EndPaint(hwnd, &ps);
+            LRESULT(0)
+        }
+        WM_ERASEBKGND => LRESULT(1),
+        WM_TIMER if wparam.0 == TIMER_REFRESH => {
+            let _ = InvalidateRect(hwnd, None, false);
+            LRESULT(0)
+        }
+        // Behaves like a flyout: clicking elsewhere or Esc closes it
+        WM_ACTIVATE if (wparam.0 & 0xFFFF) as u32 == WA_INACTIVE => {
+            let _ = DestroyWindow(hwnd);
+            LRESULT(0)
+        }
+        WM_KEYDOWN if wparam.0 as u16 == VK_ESCAPE.0 => {
+            let _ = DestroyWindow(hwnd);
+            LRESULT(0)
+        }
+        WM_DPICHANGED => {
+            let suggested = &*(lparam.0 as *const RECT);
+            let _ = SetWindowPos(hwnd, None, suggested.left, suggested.top, suggested.right - suggested.left, suggested.bottom - suggested.top, SWP_NOZORDER | SWP_NOACTIVATE);
+            RENDERER.with(|r| *r.borrow_mut() = None);
+            LRESULT(0)
+        }
+        WM_DESTROY => {
+            let _ = KillTimer(hwnd, TIMER_REFRESH);
+            RENDERER.with(|r| *r.borrow_mut() = None);
+            WIDGET_HWND.store(0, Ordering::Relaxed);
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+fn summarize(mon: &BatteryMonitor) -> Summary {
+    let latest = mon.measurements.back();
+    let estimate = mon.estimate();
+    let status = match latest {
+        Some(m) if m.is_charging => "Charging".to_string(),
+        Some(_) => match estimate.eta_minutes {
+            Some(minutes) => format!("{} left{}", BatteryMonitor::format_time(minutes), mon.eta_range_text()),
+            None => "On battery".to_string(),
+        },
+        None => "No reading yet".to_string(),
+    };
+
+    let now = Local::now();
+    let sparkline = mon.measurements
+        .iter()
+        .filter(|m| now - m.timestamp <= Duration::hours(24))
+        .map(|m| ((now - m.timestamp).num_seconds() as f32 / 3600.0, m.percentage))
+        .collect();
+
+    Summary {
+        percentage: latest.map(|m| m.percentage),
+        status,
+        today: rollup::today(&mon.measurements, mon.capacity, mon.clock.now().date_naive()).map(|day| match day.discharge_wh {
+            Some(wh) => format!("{:.0}% · {:.1} Wh", day.discharge_percent, wh),
+            None => format!("{:.0}%", day.discharge_percent),
+        }),
+        draw: mon.draw_watts.map(|watts| format!("{:.1} W", watts)),
+        health: mon.capacity.map(|c| format!("{:.0}%", c.health())),
+        sparkline,
+    }
+}
+
+unsafe fn create_renderer(hwnd: HWND) -> windows::core::Result<Renderer> {
+    let factory: ID2D1Factory = D2D1CreateFactory(D2D1_FACTORY_TYPE_SINGLE_THREADED, None)?;
+    let mut client = RECT::default();
+    let _ = GetClientRect(hwnd, &mut client);
+    let dpi = GetDpiForWindow(hwnd) as f32;
+    let properties = D2D1_RENDER_TARGET_PROPERTIES {
+        pixelFormat: D2D1_PIXEL_FORMAT { format: DXGI_FORMAT_UNKNOWN, alphaMode: D2D1_ALPHA_MODE_UNKNOWN },
+        dpiX: dpi,
+        dpiY: dpi,
+        ..Default::default()
+    };
+    let hwnd_properties = D2D1_HWND_RENDER_TARGET_PROPERTIES {
+        hwnd,
+        pixelSize: D2D_SIZE_U { width: client.right as u32, height: client.bottom as u32 },
+        presentOptions: D2D1_PRESENT_OPTIONS_NONE,
+    };
+    let target = factory.CreateHwndRenderTarget(&properties, &hwnd_properties)?;
+
+    let write: IDWriteFactory = DWriteCreateFactory(DWRITE_FACTORY_TYPE_SHARED)?;
+    let format = |weight: DWRITE_FONT_WEIGHT, size: f32| {
+        write.CreateTextFormat(w!("Segoe UI"), None, weight, DWRITE_FONT_STYLE_NORMAL, DWRITE_FONT_STRETCH_NORMAL, size, w!(""))
+    };
+    Ok(Renderer {
+        target,
+        large: format(DWRITE_FONT_WEIGHT_SEMI_BOLD, 40.0)?,
+        normal: format(DWRITE_FONT_WEIGHT_SEMI_BOLD, 15.0)?,
+        small: format(DWRITE_FONT_WEIGHT_NORMAL, 12.0)?,
+    })
+}
+
+unsafe fn render(hwnd: HWND) {
+    let Some(summary) = MONITOR.get().and_then(|m| m.lock().ok()).map(|mon| summarize(&mon)) else { return };
+    RENDERER.with(|renderer| {
+        let mut renderer = renderer.borrow_mut();
+        if renderer.is_none() {
+            *renderer = create_renderer(hwnd).ok();
+        }
+        let Some(r) = renderer.as_ref() else {
+            let _ = ValidateRect(hwnd, None);
+            return;
+        };
+        if draw(r, &summary).is_err() {
+            *renderer = None;
+        }
+    });
+}
+
+unsafe fn draw(r: &Renderer, summary: &Summary) -> windows::core::Result<()> {
+    let color = |rgb: u32, a: f32| D2D1_COLOR_F {
+        r: ((rgb >> 16) & 0xFF) as f32 / 255.0,
+        g: ((rgb >> 8) & 0xFF) as f32 / 255.0,
+        b: (rgb & 0xFF) as f32 / 255.0,
+        a,
+    };
+    let text_brush = r.target.CreateSolidColorBrush(&color(0xFFFFFF, 1.0), None)?;
+    let dim_brush = r.target.CreateSolidColorBrush(&color(0xFFFFFF, 0.6), None)?;
+    let card_brush = r.target.CreateSolidColorBrush(&color(0xFFFFFF, 0.06), None)?;
+    let low = summary.percentage.is_some_and(|p| p <= 20);
+    let accent = r.target.CreateSolidColorBrush(&color(if low { 0xFF6060 } else { 0x60CDFF }, 1.0), None)?;
+
+    let text = |s: &str, format: &IDWriteTextFormat, brush: &ID2D1SolidColorBrush, left: f32, top: f32, right: f32, bottom: f32| {
+        let wide: Vec<u16> = s.encode_utf16().collect();
+        let rect = D2D_RECT_F { left, top, right, bottom };
+        r.target.DrawText(&wide, format, &rect, brush, D2D1_DRAW_TEXT_OPTIONS_NONE, DWRITE_MEASURING_MODE_NATURAL);
+    };
+
+    r.target.BeginDraw();
+    r.target.Clear(Some(&color(0x202020, 1.0)));
+
+    text("Battery", &r.small, &dim_brush, 16.0, 12.0, WIDTH - 16.0, 30.0);
+    let percentage = summary.percentage.map(|p| format!("{}%", p)).unwrap_or_else(|| "–".to_string());
+    text(&percentage, &r.large, &text_brush, 16.0, 26.0, 180.0, 80.0);
+    text(&summary.status, &r.normal, &accent, 150.0, 46.0, WIDTH - 16.0, 70.0);
+
+    // Three stat cards
+    let cards = [("Used today", &summary.today), ("Draw", &summary.draw), ("Health", &summary.health)];
+    let card_width = (WIDTH - 32.0 - 16.0) / 3.0;
+    for (i, (label, value)) in cards.iter().enumerate() {
+        let left = 16.0 + i as f32 * (card_width + 8.0);
+        let card = D2D1_ROUNDED_RECT { rect: D2D_RECT_F { left, top: 86.0, right: left + card_width, bottom: 134.0 }, radiusX: 6.0, radiusY: 6.0 };
+        r.target.FillRoundedRectangle(&card, &card_brush);
+        text(label, &r.small, &dim_brush, left + 8.0, 90.0, left + card_width - 4.0, 106.0);
+        text(value.as_deref().unwrap_or("–"), &r.normal, &text_brush, left + 8.0, 108.0, left + card_width - 4.0, 130.0);
+    }
+
+    // 24 h sparkline, oldest on the left
+    let (left, top, right, bottom) = (16.0, 150.0, WIDTH - 16.0, HEIGHT - 28.0);
+    let baseline = D2D_RECT_F { left, top: bottom, right, bottom: bottom + 1.0 };
+    r.target.FillRectangle(&baseline, &card_brush);
+    let point = |(hours_ago, percentage): (f32, u8)| D2D_POINT_2F {
+        x: right - hours_ago / 24.0 * (right - left),
+        y: bottom - percentage as f32 / 100.0 * (bottom - top),
+    };
+    for pair in summary.sparkline.windows(2) {
+        // Gaps (sleep, shutdown) aren't bridged
+        if pair[0].0 - pair[1].0 <= 0.5 {
+            r.target.DrawLine(point(pair[0]), point(pair[1]), &accent, 1.5, None);
+        }
+    }
+    text("24 h", &r.small, &dim_brush, left, bottom + 4.0, right, HEIGHT - 6.0);
+
+    r.target.EndDraw(None, None)
+}
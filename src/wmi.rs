@@ -0,0 +1,127 @@
+use windows::Win32::System::Com::*;
+use windows::Win32::System::Ole::{SafeArrayGetElement, SafeArrayGetLBound};
+use windows::Win32::System::Variant::*;
+use windows::Win32::System::Wmi::*;
+use windows::core::{BSTR, PCWSTR};
+
+// From rpcdce.h; the Rpc feature isn't worth pulling in for two constants
+const RPC_C_AUTHN_WINNT: u32 = 10;
+const RPC_C_AUTHZ_NONE: u32 = 0;
+
+pub struct Wmi {
+    services: IWbemServices,
+}
+
+impl Wmi {
+    // COM must already be initialised on the calling thread
+    pub fn connect(namespace: &str) -> Result<Self, String> {
+        unsafe {
+            let locator: IWbemLocator = CoCreateInstance(&WbemLocator, None, CLSCTX_INPROC_SERVER)
+                .map_err(|e| format!("WMI unavailable: {}", e))?;
+            let services = locator
+                .ConnectServer(&BSTR::from(namespace), &BSTR::new(), &BSTR::new(), &BSTR::new(), 0, &BSTR::new(), None)
+                .map_err(|e| format!("Cannot open {}: {}", namespace, e))?;
+            CoSetProxyBlanket(
+                &services,
+                RPC_C_AUTHN_WINNT,
+                RPC_C_AUTHZ_NONE,
+                PCWSTR::null(),
+                RPC_C_AUTHN_LEVEL_CALL,
+                RPC_C_IMP_LEVEL_IMPERSONATE,
+                None,
+                EOAC_NONE,
+            )
+            .map_err(|e| format!("Cannot secure {}: {}", namespace, e))?;
+            Ok(Self { services })
+        }
+    }
+
+    // Runs a WQL query and returns the requested properties of every row as text
+    pub fn query(&self, wql: &str, properties: &[&str]) -> Result<Vec<Vec<Option<String>>>, String> {
+        unsafe {
+            let rows = self.services
+                .ExecQuery(&BSTR::from("WQL"), &BSTR::from(wql), WBEM_FLAG_FORWARD_ONLY | WBEM_FLAG_RETURN_IMMEDIATELY, None)
+                .map_err(|e| format!("Query failed: {}", e))?;
+
+            let mut result = Vec::new();
+            loop {
+                let mut objects = [None];
+                let mut returned = 0;
+                if rows.Next(WBEM_INFINITE, &mut objects, &mut returned).is_err() || returned == 0 {
+                    break;
+                }
+                let Some(object) = objects[0].take() else { break };
+                result.push(properties.iter().map(|name| read_property(&object, name)).collect());
+            }
+            Ok(result)
+        }
+    }
+
+    // Calls `method` on every instance of `class` with integer arguments; returns how many accepted it
+    pub fn call_method(&self, class: &str, method: &str, args: &[(&str, i32)]) -> Result<usize, String> {
+        unsafe {
+            let mut definition = None;
+            self.services
+                .GetObject(&BSTR::from(class), WBEM_FLAG_RETURN_WBEM_COMPLETE, None, Some(&mut definition), None)
+                .map_err(|e| format!("Cannot open {}: {}", class, e))?;
+            let definition = definition.ok_or_else(|| format!("Cannot open {}", class))?;
+
+            let method_wide: Vec<u16> = method.encode_utf16().chain(std::iter::once(0)).collect();
+            let mut signature = None;
+            definition
+                .GetMethod(PCWSTR(method_wide.as_ptr()), 0, &mut signature, std::ptr::null_mut())
+                .map_err(|e| format!("{}.{} unavailable: {}", class, method, e))?;
+            let parameters = signature.ok_or_else(|| format!("{}.{} takes no arguments", class, method))?.SpawnInstance(0)
+                .map_err(|e| format!("Cannot prepare {}.{}: {}", class, method, e))?;
+            for (name, value) in args {
+                let name_wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+                let mut variant = VARIANT::default();
+                (*variant.Anonymous.Anonymous).vt = VT_I4;
+                (*variant.Anonymous.Anonymous).Anonymous.lVal = *value;
+                parameters
+                    .Put(PCWSTR(name_wide.as_ptr()), 0, &variant, 0)
+                    .map_err(|e| format!("Cannot set {}: {}", name, e))?;
+            }
+
+            let mut called = 0;
+            for row in self.query(&format!("SELECT __PATH FROM {}", class), &["__PATH"])? {
+                let Some(path) = row.into_iter().next().flatten() else { continue };
+                if self.services.ExecMethod(&BSTR::from(path), &BSTR::from(method), WBEM_GENERIC_FLAG_TYPE(0), None, &parameters, None, None).is_ok() {
+                    called += 1;
+                }
+            }
+            Ok(called)
+        }
+    }
+}
+
+unsafe fn read_property(object: &IWbemClassObject, name: &str) -> Option<String> {
+    let name_wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+    let mut value = VARIANT::default();
+    object.Get(PCWSTR(name_wide.as_ptr()), 0, &mut value, None, None).ok()?;
+    let text = variant_to_string(&value);
+    let _ = VariantClear(&mut value);
+    text
+}
+
+unsafe fn variant_to_string(value: &VARIANT) -> Option<String> {
+    let vt = value.Anonymous.Anonymous.vt;
+    if vt == VT_EMPTY || vt == VT_NULL {
+        return None;
+    }
+
+    // BIOS attribute providers report most values as one-element string arrays
+    if vt.0 == VT_ARRAY.0 | VT_BSTR.0 {
+        let array = value.Anonymous.Anonymous.Anonymous.parray;
+        let index = SafeArrayGetLBound(array, 1).ok()?;
+        let mut element = BSTR::new();
+        SafeArrayGetElement(array, &index, &mut element as *mut BSTR as *mut _).ok()?;
+        return Some(element.to_string());
+    }
+
+    let mut converted = VARIANT::default();
+    VariantChangeType(&mut converted, value, VAR_CHANGE_FLAGS(0), VT_BSTR).ok()?;
+    let text = (*converted.Anonymous.Anonymous.Anonymous.bstrVal).to_string();
+    let _ = VariantClear(&mut converted);
+    Some(text)
+}